@@ -25,47 +25,45 @@ impl AudioEngine {
         log::info!("Audio stream stopped");
     }
 
+    /// Start streaming `graph` to `device` with the negotiated `settings`
+    /// (see [`negotiate`]). Device buffers larger than
+    /// `settings.max_block_size` are processed in several blocks.
     pub fn start(
         mut graph: AudioGraph,
         midi_rx: Receiver<MidiEvent>,
-        device_name: Option<&str>,
-        sample_rate: u32,
-        buffer_size: u32,
+        device: &cpal::Device,
+        settings: StreamSettings,
     ) -> anyhow::Result<Self> {
-        let host = cpal::default_host();
-
-        let device = if let Some(name) = device_name {
-            host.output_devices()?
-                .find(|d| d.name().map(|n| n.contains(name)).unwrap_or(false))
-                .ok_or_else(|| anyhow::anyhow!("Audio device not found: {name}"))?
-        } else {
-            host.default_output_device()
-                .ok_or_else(|| anyhow::anyhow!("No default audio output device"))?
-        };
-
-        let dev_name = device.name().unwrap_or_else(|_| "Unknown".into());
-        log::info!("Using audio device: {dev_name}");
-
         let num_channels = graph.num_channels();
+        let max_block = settings.max_block_size as usize;
 
         let config = cpal::StreamConfig {
             channels: num_channels as u16,
-            sample_rate: cpal::SampleRate(sample_rate),
-            buffer_size: cpal::BufferSize::Fixed(buffer_size),
+            sample_rate: cpal::SampleRate(settings.sample_rate),
+            buffer_size: if settings.fixed_buffer {
+                cpal::BufferSize::Fixed(settings.max_block_size)
+            } else {
+                cpal::BufferSize::Default
+            },
         };
 
         log::info!(
-            "Audio config: {}ch, {}Hz, buffer={}",
+            "Audio config: {}ch, {}Hz, buffer={}{}",
             num_channels,
-            sample_rate,
-            buffer_size
+            settings.sample_rate,
+            settings.max_block_size,
+            if settings.fixed_buffer {
+                ""
+            } else {
+                " (max block; device default buffer)"
+            }
         );
 
         // Pre-allocate buffers that live in the closure and are reused every callback
         let mut midi_events: Vec<MidiEvent> = Vec::with_capacity(64);
-        let mut channel_bufs: Vec<Vec<f32>> = (0..num_channels)
-            .map(|_| vec![0.0f32; buffer_size as usize])
-            .collect();
+        let mut block_events: Vec<MidiEvent> = Vec::with_capacity(64);
+        let mut channel_bufs: Vec<Vec<f32>> =
+            (0..num_channels).map(|_| vec![0.0f32; max_block]).collect();
 
         let mut callback_count: u64 = 0;
 
@@ -96,23 +94,38 @@ impl AudioEngine {
 
                 let frames = data.len() / num_channels;
 
-                // Resize and zero pre-allocated per-channel buffers
-                for buf in channel_bufs.iter_mut() {
-                    buf.resize(frames, 0.0);
-                    buf.fill(0.0);
-                }
+                // Process in blocks of at most max_block frames (only more
+                // than one when the device picks its own buffer size).
+                let mut offset = 0;
+                while offset < frames {
+                    let n = (frames - offset).min(max_block);
+                    let last = offset + n == frames;
+                    block_events.clear();
+                    block_events.extend(midi_events.iter().filter_map(|&(frame, bytes)| {
+                        let frame = frame as usize;
+                        (frame >= offset && (frame < offset + n || last))
+                            .then(|| (((frame - offset).min(n - 1)) as u64, bytes))
+                    }));
 
-                if let Err(e) = graph.process(&midi_events, &mut channel_bufs) {
-                    log::error!("Audio graph process error: {e}");
-                    data.fill(0.0);
-                    return;
-                }
+                    // Resize (within capacity) and zero the per-channel buffers
+                    for buf in channel_bufs.iter_mut() {
+                        buf.resize(n, 0.0);
+                        buf.fill(0.0);
+                    }
+
+                    if let Err(e) = graph.process(&block_events, &mut channel_bufs) {
+                        log::error!("Audio graph process error: {e}");
+                        data[offset * num_channels..].fill(0.0);
+                        return;
+                    }
 
-                // Interleave back into cpal output buffer
-                for frame in 0..frames {
-                    for ch in 0..num_channels {
-                        data[frame * num_channels + ch] = channel_bufs[ch][frame];
+                    // Interleave back into cpal output buffer
+                    for frame in 0..n {
+                        for ch in 0..num_channels {
+                            data[(offset + frame) * num_channels + ch] = channel_bufs[ch][frame];
+                        }
                     }
+                    offset += n;
                 }
 
                 // Log peak level when there were MIDI events
@@ -133,3 +146,184 @@ impl AudioEngine {
         Ok(AudioEngine { stream })
     }
 }
+
+/// Find an output device by (partial) name, or the default device.
+pub fn open_device(name: Option<&str>) -> anyhow::Result<cpal::Device> {
+    let host = cpal::default_host();
+    let device = if let Some(name) = name {
+        host.output_devices()?
+            .find(|d| d.name().map(|n| n.contains(name)).unwrap_or(false))
+            .ok_or_else(|| anyhow::anyhow!("Audio device not found: {name}"))?
+    } else {
+        host.default_output_device()
+            .ok_or_else(|| anyhow::anyhow!("No default audio output device"))?
+    };
+    let dev_name = device.name().unwrap_or_else(|_| "Unknown".into());
+    log::info!("Using audio device: {dev_name}");
+    Ok(device)
+}
+
+// ---------------------------------------------------------------------------
+// Stream negotiation
+// ---------------------------------------------------------------------------
+
+/// The stream configuration chosen for a device. Plugins are loaded with
+/// this sample rate and max block size.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StreamSettings {
+    pub sample_rate: u32,
+    /// Largest block handed to the audio graph.
+    pub max_block_size: u32,
+    /// Whether the device accepts `max_block_size` as a fixed buffer size.
+    /// If not, the device picks its buffer size and callbacks are split.
+    pub fixed_buffer: bool,
+}
+
+/// One supported f32 output configuration range reported by a device.
+#[derive(Clone, Copy, Debug)]
+struct ConfigRange {
+    channels: u16,
+    min_rate: u32,
+    max_rate: u32,
+    /// Supported buffer sizes, if the device reports them.
+    buffer: Option<(u32, u32)>,
+}
+
+/// Pick the configuration closest to the requested one: nearest sample rate
+/// first, then a fixed buffer size nearest the requested one.
+fn choose(
+    ranges: &[ConfigRange],
+    channels: u16,
+    sample_rate: u32,
+    buffer_size: u32,
+) -> Option<StreamSettings> {
+    ranges
+        .iter()
+        .filter(|r| r.channels == channels)
+        .map(|r| {
+            let rate = sample_rate.clamp(r.min_rate, r.max_rate);
+            let (block, fixed) = match r.buffer {
+                Some((lo, hi)) => (buffer_size.clamp(lo, hi), true),
+                None => (buffer_size, false),
+            };
+            StreamSettings {
+                sample_rate: rate,
+                max_block_size: block,
+                fixed_buffer: fixed,
+            }
+        })
+        .min_by_key(|s| {
+            (
+                s.sample_rate.abs_diff(sample_rate),
+                !s.fixed_buffer,
+                s.max_block_size.abs_diff(buffer_size),
+            )
+        })
+}
+
+/// Query `device` for its supported output configurations and choose the
+/// closest match to the requested sample rate and buffer size, logging any
+/// change from what was asked for.
+pub fn negotiate(
+    device: &cpal::Device,
+    channels: u16,
+    sample_rate: u32,
+    buffer_size: u32,
+) -> anyhow::Result<StreamSettings> {
+    let ranges: Vec<ConfigRange> = device
+        .supported_output_configs()?
+        .filter(|c| c.sample_format() == cpal::SampleFormat::F32)
+        .map(|c| ConfigRange {
+            channels: c.channels(),
+            min_rate: c.min_sample_rate().0,
+            max_rate: c.max_sample_rate().0,
+            buffer: match *c.buffer_size() {
+                cpal::SupportedBufferSize::Range { min, max } => Some((min, max)),
+                cpal::SupportedBufferSize::Unknown => None,
+            },
+        })
+        .collect();
+    let settings = choose(&ranges, channels, sample_rate, buffer_size).ok_or_else(|| {
+        anyhow::anyhow!("Audio device has no {channels}-channel f32 output configuration")
+    })?;
+
+    if settings.sample_rate != sample_rate {
+        log::warn!(
+            "Sample rate {sample_rate}Hz not supported by the device; using {}Hz",
+            settings.sample_rate
+        );
+    }
+    if settings.fixed_buffer && settings.max_block_size != buffer_size {
+        log::warn!(
+            "Buffer size {buffer_size} not supported by the device; using {}",
+            settings.max_block_size
+        );
+    }
+    if !settings.fixed_buffer {
+        log::warn!(
+            "Device does not report buffer sizes; using its default buffer, processed in blocks of at most {}",
+            settings.max_block_size
+        );
+    }
+    log::info!(
+        "Negotiated audio config: {}Hz, max block {} frames",
+        settings.sample_rate,
+        settings.max_block_size
+    );
+    Ok(settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(channels: u16, rates: (u32, u32), buffer: Option<(u32, u32)>) -> ConfigRange {
+        ConfigRange {
+            channels,
+            min_rate: rates.0,
+            max_rate: rates.1,
+            buffer,
+        }
+    }
+
+    #[test]
+    fn choose_keeps_supported_request() {
+        let ranges = [range(2, (44100, 96000), Some((32, 4096)))];
+        let s = choose(&ranges, 2, 48000, 64).unwrap();
+        assert_eq!(
+            s,
+            StreamSettings {
+                sample_rate: 48000,
+                max_block_size: 64,
+                fixed_buffer: true
+            }
+        );
+    }
+
+    #[test]
+    fn choose_clamps_to_closest_supported_config() {
+        let ranges = [
+            range(1, (48000, 48000), Some((16, 4096))),
+            range(2, (44100, 44100), Some((256, 4096))),
+            range(2, (96000, 96000), Some((16, 4096))),
+        ];
+        let s = choose(&ranges, 2, 48000, 64).unwrap();
+        assert_eq!((s.sample_rate, s.max_block_size), (44100, 256));
+        assert!(choose(&ranges, 8, 48000, 64).is_none());
+    }
+
+    #[test]
+    fn choose_prefers_fixed_buffer_at_same_rate() {
+        let ranges = [
+            range(2, (48000, 48000), None),
+            range(2, (48000, 48000), Some((128, 1024))),
+        ];
+        let s = choose(&ranges, 2, 48000, 64).unwrap();
+        assert!(s.fixed_buffer);
+        assert_eq!(s.max_block_size, 128);
+
+        let s = choose(&ranges[..1], 2, 48000, 64).unwrap();
+        assert!(!s.fixed_buffer);
+        assert_eq!(s.max_block_size, 64);
+    }
+}
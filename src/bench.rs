@@ -0,0 +1,369 @@
+//! `tang bench`: render a fixed note workload offline through a single plugin
+//! ([`Plugin::process`]) or a whole session ([`AudioGraph::process`]) and
+//! measure how long every block takes, to judge whether it keeps up in real
+//! time at a given buffer size.
+//!
+//! The first [`WARMUP_SECONDS`] of audio are rendered but not measured, so
+//! one-off allocations on the first blocks don't dominate the worst case.
+
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::cli::OutputFormat;
+use crate::plugin::chain::AudioGraph;
+use crate::plugin::master::MasterBus;
+use crate::plugin::{self, Plugin};
+use crate::session;
+
+const WARMUP_SECONDS: f64 = 0.5;
+/// Tempo of the workload.
+const BPM: f64 = 120.0;
+/// Chord roots, one per beat, cycling.
+const CHORD_ROOTS: [u8; 4] = [48, 53, 55, 57];
+const CHORD_VOICING: [u8; 4] = [0, 4, 7, 12];
+/// Sixteenth-note melody over the chords, cycling.
+const MELODY: [u8; 5] = [72, 74, 76, 79, 81];
+/// Chords are held for this many sixteenths.
+const CHORD_LENGTH: u64 = 3;
+/// Melody notes last this fraction of a sixteenth.
+const MELODY_GATE: f64 = 0.9;
+
+// ---------------------------------------------------------------------------
+// Workload
+// ---------------------------------------------------------------------------
+
+/// Append the workload's MIDI events for frames `start..start + frames` to
+/// `out`, with frame offsets relative to `start`: a four-note chord on every
+/// beat plus a sixteenth-note melody. Note-offs sort before note-ons on the
+/// same frame.
+fn workload(start: u64, frames: usize, sample_rate: f64, out: &mut Vec<(u64, [u8; 3])>) {
+    let sixteenth = sample_rate * 60.0 / BPM / 4.0;
+    let end = start + frames as u64;
+    let at = |step: f64| (step * sixteenth).round() as u64;
+    let first = (start as f64 / sixteenth) as u64;
+    let last = (end as f64 / sixteenth) as u64 + 1;
+    let begin = out.len();
+
+    let mut push = |frame: u64, status: u8, note: u8| {
+        if (start..end).contains(&frame) {
+            let velocity = if status == 0x90 { 100 } else { 0 };
+            out.push((frame - start, [status, note, velocity]));
+        }
+    };
+    for k in first.saturating_sub(CHORD_LENGTH)..=last {
+        if k % 4 == 0 {
+            let root = CHORD_ROOTS[(k / 4) as usize % CHORD_ROOTS.len()];
+            for offset in CHORD_VOICING {
+                push(at(k as f64), 0x90, root + offset);
+                push(at((k + CHORD_LENGTH) as f64), 0x80, root + offset);
+            }
+        }
+        let note = MELODY[k as usize % MELODY.len()];
+        push(at(k as f64), 0x90, note);
+        push(at(k as f64 + MELODY_GATE), 0x80, note);
+    }
+    out[begin..].sort_by_key(|&(frame, [status, ..])| (frame, status == 0x90));
+}
+
+// ---------------------------------------------------------------------------
+// Targets
+// ---------------------------------------------------------------------------
+
+/// What is being benchmarked, set up for one buffer size.
+enum Target {
+    Plugin {
+        plugin: Box<dyn Plugin>,
+        inputs: Vec<Vec<f32>>,
+        outputs: Vec<Vec<f32>>,
+    },
+    Session {
+        graph: AudioGraph,
+        outputs: Vec<Vec<f32>>,
+    },
+}
+
+fn is_session(target: &str) -> bool {
+    target.ends_with(".toml") && Path::new(target).is_file()
+}
+
+#[cfg(feature = "lv2")]
+fn runtime(max_block_size: usize) -> plugin::Runtime {
+    plugin::Runtime::with_lv2(max_block_size)
+}
+
+#[cfg(not(feature = "lv2"))]
+fn runtime(_max_block_size: usize) -> plugin::Runtime {
+    plugin::Runtime::default()
+}
+
+impl Target {
+    fn load(target: &str, sample_rate: f32, buffer_size: usize) -> anyhow::Result<Self> {
+        let runtime = runtime(buffer_size);
+        if !is_session(target) {
+            let plugin = plugin::load(target, sample_rate, buffer_size, &runtime)?;
+            // Effects get a test tone on their inputs.
+            let tone: Vec<f32> = (0..buffer_size)
+                .map(|i| 0.25 * (i as f32 * 440.0 / sample_rate * std::f32::consts::TAU).sin())
+                .collect();
+            return Ok(Self::Plugin {
+                inputs: vec![tone; plugin.audio_input_count()],
+                outputs: vec![vec![0.0; buffer_size]; plugin.audio_output_count()],
+                plugin,
+            });
+        }
+
+        let config = session::load(target)?;
+        let session_dir = Path::new(target).parent().unwrap_or_else(|| Path::new("."));
+        let num_channels = 2;
+        let (cmd_tx, cmd_rx) = crossbeam_channel::unbounded();
+        // Nothing is swapped out during a benchmark; returned plugins are
+        // simply dropped with the channel.
+        let (return_tx, _return_rx) = crossbeam_channel::unbounded();
+        let mut graph = AudioGraph::new(num_channels, cmd_rx, return_tx);
+        // The master sanitiser and limiter are part of the signal path.
+        graph.set_master(MasterBus::new(&config.master, sample_rate, num_channels));
+        crate::load_session(
+            &config,
+            session_dir,
+            sample_rate,
+            buffer_size,
+            &runtime,
+            &cmd_tx,
+        )?;
+        graph.drain_commands();
+        Ok(Self::Session {
+            graph,
+            outputs: vec![vec![0.0; buffer_size]; num_channels],
+        })
+    }
+
+    fn process(&mut self, midi: &[(u64, [u8; 3])]) -> anyhow::Result<()> {
+        match self {
+            Self::Plugin {
+                plugin,
+                inputs,
+                outputs,
+            } => {
+                let audio_in: Vec<&[f32]> = inputs.iter().map(Vec::as_slice).collect();
+                let mut audio_out: Vec<&mut [f32]> =
+                    outputs.iter_mut().map(Vec::as_mut_slice).collect();
+                plugin.process(midi, &audio_in, &mut audio_out)
+            }
+            Self::Session { graph, outputs } => {
+                for buf in outputs.iter_mut() {
+                    buf.fill(0.0);
+                }
+                graph.process(midi, outputs)
+            }
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Measurement
+// ---------------------------------------------------------------------------
+
+#[derive(Serialize)]
+pub struct BenchResult {
+    pub buffer_size: usize,
+    pub blocks: usize,
+    /// Real time available per block.
+    pub budget_ms: f64,
+    /// Seconds of audio rendered per second of processing.
+    pub realtime_factor: f64,
+    pub mean_ms: f64,
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+    pub p99_ms: f64,
+    pub worst_ms: f64,
+    /// Blocks that took longer than the budget (would have been dropouts).
+    pub over_budget: usize,
+}
+
+fn ms(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
+
+/// The `p`th percentile (0..=100) of sorted `times`, nearest-rank.
+fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+fn summarize(buffer_size: usize, sample_rate: f32, mut times: Vec<Duration>) -> BenchResult {
+    let budget = Duration::from_secs_f64(buffer_size as f64 / sample_rate as f64);
+    let total: Duration = times.iter().sum();
+    times.sort_unstable();
+    let blocks = times.len();
+    let audio = budget.as_secs_f64() * blocks as f64;
+    BenchResult {
+        buffer_size,
+        blocks,
+        budget_ms: ms(budget),
+        realtime_factor: if total.is_zero() {
+            f64::INFINITY
+        } else {
+            audio / total.as_secs_f64()
+        },
+        mean_ms: if blocks == 0 {
+            0.0
+        } else {
+            ms(total) / blocks as f64
+        },
+        p50_ms: ms(percentile(&times, 50.0)),
+        p95_ms: ms(percentile(&times, 95.0)),
+        p99_ms: ms(percentile(&times, 99.0)),
+        worst_ms: ms(times.last().copied().unwrap_or_default()),
+        over_budget: times.iter().filter(|&&t| t > budget).count(),
+    }
+}
+
+/// Render `seconds` of the workload (after warm-up) through `target` at
+/// `buffer_size` and time every block.
+pub fn bench(
+    target: &str,
+    sample_rate: f32,
+    buffer_size: usize,
+    seconds: f64,
+) -> anyhow::Result<BenchResult> {
+    anyhow::ensure!(buffer_size > 0, "buffer size must be at least 1 frame");
+    let mut t = Target::load(target, sample_rate, buffer_size)?;
+    let blocks_for = |secs: f64| (secs * sample_rate as f64 / buffer_size as f64).ceil() as usize;
+    let warmup = blocks_for(WARMUP_SECONDS);
+    let measured = blocks_for(seconds).max(1);
+
+    let mut midi = Vec::with_capacity(256);
+    let mut times = Vec::with_capacity(measured);
+    for block in 0..warmup + measured {
+        midi.clear();
+        workload(
+            (block * buffer_size) as u64,
+            buffer_size,
+            sample_rate as f64,
+            &mut midi,
+        );
+        let started = Instant::now();
+        t.process(&midi)?;
+        let elapsed = started.elapsed();
+        if block >= warmup {
+            times.push(elapsed);
+        }
+    }
+    Ok(summarize(buffer_size, sample_rate, times))
+}
+
+/// `tang bench`: benchmark `target` at each buffer size and print a table.
+pub fn run(
+    target: &str,
+    sample_rate: u32,
+    buffer_sizes: &[usize],
+    seconds: f64,
+    format: OutputFormat,
+) -> anyhow::Result<()> {
+    let sample_rate = sample_rate as f32;
+    let kind = if is_session(target) {
+        "session"
+    } else {
+        "plugin"
+    };
+    if format == OutputFormat::Text {
+        println!(
+            "Benchmarking {kind} {target} at {sample_rate} Hz, {seconds} s of audio per buffer size"
+        );
+        println!(
+            "  {:>6}  {:>9}  {:>9}  {:>9}  {:>9}  {:>9}  {:>9}  {:>9}",
+            "frames", "budget", "realtime", "mean", "p95", "p99", "worst", "overruns"
+        );
+    }
+
+    let mut results = Vec::new();
+    for &size in buffer_sizes {
+        let r = bench(target, sample_rate, size, seconds)?;
+        if format == OutputFormat::Text {
+            println!(
+                "  {:>6}  {:>7.3}ms  {:>8.1}x  {:>7.3}ms  {:>7.3}ms  {:>7.3}ms  {:>7.3}ms  {:>9}",
+                r.buffer_size,
+                r.budget_ms,
+                r.realtime_factor,
+                r.mean_ms,
+                r.p95_ms,
+                r.p99_ms,
+                r.worst_ms,
+                r.over_budget
+            );
+        }
+        results.push(r);
+    }
+
+    if format == OutputFormat::Json {
+        println!("{}", serde_json::to_string_pretty(&results)?);
+        return Ok(());
+    }
+    for r in &results {
+        let verdict = if r.over_budget == 0 {
+            format!(
+                "OK (worst block used {:.0}% of its budget)",
+                100.0 * r.worst_ms / r.budget_ms
+            )
+        } else {
+            format!(
+                "AT RISK ({} of {} blocks over budget)",
+                r.over_budget, r.blocks
+            )
+        };
+        println!("  {} frames: {verdict}", r.buffer_size);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render_events(total: usize, block: usize) -> Vec<(u64, [u8; 3])> {
+        let mut all = Vec::new();
+        let mut midi = Vec::new();
+        for start in (0..total).step_by(block) {
+            midi.clear();
+            workload(start as u64, block.min(total - start), 48000.0, &mut midi);
+            all.extend(midi.iter().map(|&(f, data)| (f + start as u64, data)));
+        }
+        all
+    }
+
+    #[test]
+    fn workload_does_not_depend_on_block_size() {
+        let total = 48000 * 2;
+        let small = render_events(total, 64);
+        assert_eq!(small, render_events(total, 1000));
+        assert_eq!(small, render_events(total, total));
+
+        // Every note that starts also stops within two seconds.
+        let ons = small.iter().filter(|e| e.1[0] == 0x90).count();
+        let offs = small.iter().filter(|e| e.1[0] == 0x80).count();
+        assert_eq!(ons, 4 * 4 + 16);
+        assert_eq!(ons, offs);
+    }
+
+    #[test]
+    fn percentile_uses_nearest_rank() {
+        let times: Vec<Duration> = (1..=100).map(Duration::from_millis).collect();
+        assert_eq!(percentile(&times, 50.0), Duration::from_millis(50));
+        assert_eq!(percentile(&times, 99.0), Duration::from_millis(99));
+        assert_eq!(percentile(&times, 100.0), Duration::from_millis(100));
+        assert_eq!(percentile(&[], 50.0), Duration::ZERO);
+    }
+
+    #[test]
+    fn bench_builtin_plugin() {
+        let r = bench("builtin:sine", 48000.0, 64, 0.1).unwrap();
+        assert_eq!(r.blocks, 75);
+        assert!(r.worst_ms >= r.p50_ms);
+        assert!(r.realtime_factor > 0.0);
+    }
+}
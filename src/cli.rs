@@ -1,4 +1,4 @@
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 
 #[derive(Parser)]
 #[command(name = "tang", about = "Minimal CLI LV2/CLAP instrument host")]
@@ -13,15 +13,79 @@ pub struct Cli {
 #[derive(Subcommand)]
 pub enum Command {
     /// List available MIDI inputs, audio outputs, and plugins
-    #[command(subcommand)]
-    Enumerate(EnumerateTarget),
+    Enumerate {
+        /// Output format
+        #[arg(long, value_enum, default_value_t, global = true)]
+        format: OutputFormat,
+
+        #[command(subcommand)]
+        target: EnumerateTarget,
+    },
     /// Describe a plugin (parameters, presets, I/O)
     Describe {
         /// Plugin source (lv2:<URI>, clap:<ID>, or path)
         plugin: String,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t)]
+        format: OutputFormat,
+    },
+    /// Exercise a plugin with conformance checks and report pass/fail
+    Validate {
+        /// Plugin source (lv2:<URI>, clap:<ID>, or path)
+        plugin: String,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t)]
+        format: OutputFormat,
+    },
+    /// Render a fixed note workload offline and report processing times
+    Bench {
+        /// Plugin source (lv2:<URI>, clap:<ID>, or path) or session file (.toml)
+        target: String,
+
+        /// Buffer sizes in frames to test (comma-separated or repeated)
+        #[arg(long = "buffer-size", value_delimiter = ',', default_values_t = [64, 128, 256, 512])]
+        buffer_sizes: Vec<usize>,
+
+        /// Sample rate in Hz
+        #[arg(long, default_value = "48000")]
+        sample_rate: u32,
+
+        /// Seconds of audio to render per buffer size
+        #[arg(long, default_value = "10")]
+        seconds: f64,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t)]
+        format: OutputFormat,
     },
     /// Load a session and play via MIDI input with virtual piano
     Play(PlayArgs),
+    /// List or clear plugins quarantined after crashing during a scan
+    #[command(subcommand)]
+    Quarantine(QuarantineAction),
+    /// Host one plugin for a sandboxed parent process (internal)
+    #[command(hide = true)]
+    SandboxHost {
+        plugin: String,
+        #[arg(long)]
+        shm: std::path::PathBuf,
+        #[arg(long)]
+        sample_rate: f32,
+        #[arg(long)]
+        block_size: usize,
+    },
+}
+
+/// Output format of `enumerate` and `describe`.
+#[derive(Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable text
+    #[default]
+    Text,
+    /// JSON, for scripts and tooling
+    Json,
 }
 
 #[derive(Subcommand)]
@@ -30,12 +94,27 @@ pub enum EnumerateTarget {
     Midi,
     /// List available audio output devices
     Audio,
-    /// List available LV2 and CLAP plugins
-    Plugins,
+    /// List available LV2, CLAP and VST3 plugins
+    Plugins {
+        /// Ignore the scan cache and rescan every plugin bundle
+        #[arg(long)]
+        rescan: bool,
+    },
     /// List built-in plugins
     Builtins,
 }
 
+#[derive(Subcommand)]
+pub enum QuarantineAction {
+    /// List quarantined bundles and the config blocklist
+    List,
+    /// Release bundles from quarantine so they are scanned again
+    Clear {
+        /// Bundle path to release (default: all)
+        path: Option<String>,
+    },
+}
+
 #[derive(clap::Args)]
 pub struct PlayArgs {
     /// Path to session file (.toml). If omitted, creates a new session.
@@ -60,4 +139,9 @@ pub struct PlayArgs {
     /// Show the TUI instead of plain play mode
     #[arg(long)]
     pub view: bool,
+
+    /// Run each plugin in its own process, so a crashing plugin goes silent
+    /// and restarts instead of taking tang down
+    #[arg(long)]
+    pub sandbox: bool,
 }
@@ -9,6 +9,11 @@ static CONFIG: OnceLock<Config> = OnceLock::new();
 #[serde(default)]
 pub struct Config {
     pub plugin_paths: PluginPaths,
+    /// Run external plugins in child processes (same as `play --sandbox`).
+    pub sandbox: bool,
+    /// Plugin bundles (path or file name) and plugin IDs to never scan or
+    /// list.
+    pub blocklist: Vec<String>,
 }
 
 #[derive(Default, Deserialize)]
@@ -44,3 +49,11 @@ pub fn extra_lv2_paths() -> &'static [PathBuf] {
         .map(|c| c.plugin_paths.lv2.as_slice())
         .unwrap_or(&[])
 }
+
+pub fn sandbox() -> bool {
+    CONFIG.get().is_some_and(|c| c.sandbox)
+}
+
+pub fn blocklist() -> &'static [String] {
+    CONFIG.get().map(|c| c.blocklist.as_slice()).unwrap_or(&[])
+}
@@ -1,25 +1,53 @@
-use crate::plugin::builtin;
-use crate::plugin::clap;
-#[cfg(feature = "lv2")]
-use crate::plugin::lv2;
-#[cfg(feature = "vst3")]
-use crate::plugin::vst3;
-
-pub fn midi() -> anyhow::Result<()> {
-    println!("=== MIDI Input Devices ===");
+use serde::Serialize;
+
+use crate::cli::OutputFormat;
+use crate::plugin::quarantine::Quarantine;
+use crate::plugin::scan_cache::{self, Catalog};
+use crate::plugin::{self, ParameterInfo, PluginInfo, Preset, builtin};
+
+fn print_json(value: &impl Serialize) -> anyhow::Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct MidiInput {
+    name: String,
+}
+
+pub fn midi(format: OutputFormat) -> anyhow::Result<()> {
     let midi_in = midir::MidiInput::new("tang-enumerate")?;
-    let ports = midi_in.ports();
+    let ports: Vec<MidiInput> = midi_in
+        .ports()
+        .iter()
+        .map(|port| MidiInput {
+            name: midi_in.port_name(port).unwrap_or_else(|_| "Unknown".into()),
+        })
+        .collect();
+    if format == OutputFormat::Json {
+        return print_json(&ports);
+    }
+
+    println!("=== MIDI Input Devices ===");
     if ports.is_empty() {
         println!("  (none found)");
     }
     for port in &ports {
-        let name = midi_in.port_name(port).unwrap_or_else(|_| "Unknown".into());
-        println!("  {name}");
+        println!("  {}", port.name);
     }
     Ok(())
 }
 
-pub fn audio() -> anyhow::Result<()> {
+#[derive(Serialize)]
+struct AudioOutput {
+    name: String,
+    default: bool,
+    channels: u16,
+    sample_rate: u32,
+    sample_format: &'static str,
+}
+
+pub fn audio(format: OutputFormat) -> anyhow::Result<()> {
     // Suppress ALSA/JACK noise on stderr during device enumeration
     let stderr_guard = suppress_stderr();
 
@@ -37,19 +65,33 @@ pub fn audio() -> anyhow::Result<()> {
 
     let _ = stderr_guard;
 
+    let outputs: Vec<AudioOutput> = devices
+        .into_iter()
+        .map(|(name, config)| AudioOutput {
+            default: default_name.as_deref() == Some(name.as_str()),
+            name,
+            channels: config.channels(),
+            sample_rate: config.sample_rate().0,
+            sample_format: format_sample_fmt(config.sample_format()),
+        })
+        .collect();
+    if format == OutputFormat::Json {
+        return print_json(&outputs);
+    }
+
     println!("=== Audio Output Devices ===");
-    if devices.is_empty() {
+    if outputs.is_empty() {
         println!("  (none found)");
         return Ok(());
     }
-    for (name, config) in &devices {
-        let is_default = default_name.as_deref() == Some(name.as_str());
-        let marker = if is_default { " *" } else { "" };
+    for output in &outputs {
+        let marker = if output.default { " *" } else { "" };
         println!(
             "  {name}{marker}  ({ch}ch, {rate}Hz, {fmt})",
-            ch = config.channels(),
-            rate = config.sample_rate().0,
-            fmt = format_sample_fmt(config.sample_format()),
+            name = output.name,
+            ch = output.channels,
+            rate = output.sample_rate,
+            fmt = output.sample_format,
         );
     }
     Ok(())
@@ -108,9 +150,35 @@ fn suppress_stderr() -> Option<()> {
     None
 }
 
-pub fn builtins() -> anyhow::Result<()> {
-    println!("=== Built-in Plugins ===");
+/// A plugin in JSON listings: its scan info tagged with the plugin format.
+#[derive(Serialize)]
+struct PluginEntry<'a> {
+    format: &'static str,
+    #[serde(flatten)]
+    info: &'a PluginInfo,
+}
+
+fn plugin_entries<'a>(format: &'static str, plugins: &'a [PluginInfo]) -> Vec<PluginEntry<'a>> {
+    plugins
+        .iter()
+        .map(|info| PluginEntry { format, info })
+        .collect()
+}
+
+fn catalog_entries(catalog: &Catalog) -> Vec<PluginEntry<'_>> {
+    let mut entries = plugin_entries("lv2", &catalog.lv2);
+    entries.extend(plugin_entries("clap", &catalog.clap));
+    entries.extend(plugin_entries("vst3", &catalog.vst3));
+    entries
+}
+
+pub fn builtins(format: OutputFormat) -> anyhow::Result<()> {
     let plugins = builtin::enumerate_plugins();
+    if format == OutputFormat::Json {
+        return print_json(&plugin_entries("builtin", &plugins));
+    }
+
+    println!("=== Built-in Plugins ===");
     if plugins.is_empty() {
         println!("  (none)");
     }
@@ -128,15 +196,19 @@ pub fn builtins() -> anyhow::Result<()> {
     Ok(())
 }
 
-pub fn plugins() -> anyhow::Result<()> {
+pub fn plugins(rescan: bool, format: OutputFormat) -> anyhow::Result<()> {
+    let catalog = scan_cache::scan(rescan);
+    if format == OutputFormat::Json {
+        return print_json(&catalog_entries(&catalog));
+    }
+
     #[cfg(feature = "lv2")]
     {
         println!("=== LV2 Plugins ===");
-        let plugins = lv2::enumerate_plugins();
-        if plugins.is_empty() {
+        if catalog.lv2.is_empty() {
             println!("  (none found)");
         }
-        for p in &plugins {
+        for p in &catalog.lv2 {
             let kind = if p.is_instrument {
                 "instrument"
             } else {
@@ -158,11 +230,10 @@ pub fn plugins() -> anyhow::Result<()> {
     }
 
     println!("=== CLAP Plugins ===");
-    let claps = clap::enumerate_plugins();
-    if claps.is_empty() {
+    if catalog.clap.is_empty() {
         println!("  (none found)");
     }
-    for p in &claps {
+    for p in &catalog.clap {
         let kind = if p.is_instrument {
             "instrument"
         } else {
@@ -179,11 +250,10 @@ pub fn plugins() -> anyhow::Result<()> {
     #[cfg(feature = "vst3")]
     {
         println!("=== VST3 Plugins ===");
-        let vst3s = vst3::enumerate_plugins();
-        if vst3s.is_empty() {
+        if catalog.vst3.is_empty() {
             println!("  (none found)");
         }
-        for p in &vst3s {
+        for p in &catalog.vst3 {
             let kind = if p.is_instrument {
                 "instrument"
             } else {
@@ -204,3 +274,175 @@ pub fn plugins() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[derive(Serialize)]
+struct Description<'a> {
+    source: &'a str,
+    name: &'a str,
+    is_instrument: bool,
+    audio_inputs: usize,
+    audio_outputs: usize,
+    parameters: Vec<ParameterInfo>,
+    presets: Vec<Preset>,
+}
+
+pub fn describe(source: &str, format: OutputFormat) -> anyhow::Result<()> {
+    let p = plugin::load(source, 48000.0, 512, &plugin::Runtime::default())?;
+    let description = Description {
+        source,
+        name: p.name(),
+        is_instrument: p.is_instrument(),
+        audio_inputs: p.audio_input_count(),
+        audio_outputs: p.audio_output_count(),
+        parameters: p.parameters(),
+        presets: p.presets(),
+    };
+    if format == OutputFormat::Json {
+        return print_json(&description);
+    }
+
+    println!("{}", description.name);
+    println!(
+        "  Type:          {}",
+        if description.is_instrument {
+            "instrument"
+        } else {
+            "effect"
+        }
+    );
+    println!("  Audio outputs: {}", description.audio_outputs);
+    let params = &description.parameters;
+    println!("  Parameters:    {}", params.len());
+    for param in params {
+        println!(
+            "    [{}] {} (min={}, max={}, default={})",
+            param.index, param.name, param.min, param.max, param.default
+        );
+    }
+    let presets = &description.presets;
+    if presets.is_empty() {
+        println!("  Presets:       (none)");
+    } else {
+        println!("  Presets:       {}", presets.len());
+        for preset in presets {
+            println!("    {} ({})", preset.name, preset.id);
+        }
+    }
+    Ok(())
+}
+
+pub fn quarantined() -> anyhow::Result<()> {
+    let quarantine = Quarantine::open();
+    println!("=== Quarantined Bundles ===");
+    if quarantine.bundles().is_empty() {
+        println!("  (none)");
+    }
+    for bundle in quarantine.bundles() {
+        println!("  {bundle}");
+    }
+    println!();
+
+    println!("=== Blocklist (config.toml) ===");
+    let blocklist = crate::config::blocklist();
+    if blocklist.is_empty() {
+        println!("  (none)");
+    }
+    for entry in blocklist {
+        println!("  {entry}");
+    }
+    Ok(())
+}
+
+pub fn clear_quarantine(path: Option<&str>) -> anyhow::Result<()> {
+    let mut quarantine = Quarantine::open();
+    let removed = quarantine.clear(path)?;
+    match (path, removed) {
+        (Some(path), 0) => anyhow::bail!("{path} is not quarantined"),
+        _ => println!("Released {removed} bundle(s) from quarantine; they will be scanned again"),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plugin_entries_are_tagged_with_their_format() {
+        let catalog = Catalog {
+            clap: vec![PluginInfo {
+                name: "Synth".into(),
+                id: "com.example.synth".into(),
+                is_instrument: true,
+                param_count: 1,
+                preset_count: 1,
+                path: "/plugins/Synth.clap".into(),
+                audio_input_count: 0,
+                audio_output_count: 2,
+                parameters: vec![ParameterInfo {
+                    index: 0,
+                    name: "Cutoff".into(),
+                    min: 20.0,
+                    max: 20000.0,
+                    default: 1000.0,
+                }],
+                presets: vec![Preset {
+                    name: "Init".into(),
+                    id: "init".into(),
+                }],
+            }],
+            ..Catalog::default()
+        };
+        let json = serde_json::to_value(catalog_entries(&catalog)).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!([{
+                "format": "clap",
+                "name": "Synth",
+                "id": "com.example.synth",
+                "is_instrument": true,
+                "param_count": 1,
+                "preset_count": 1,
+                "path": "/plugins/Synth.clap",
+                "audio_input_count": 0,
+                "audio_output_count": 2,
+                "parameters": [{
+                    "index": 0,
+                    "name": "Cutoff",
+                    "min": 20.0,
+                    "max": 20000.0,
+                    "default": 1000.0,
+                }],
+                "presets": [{ "name": "Init", "id": "init" }],
+            }])
+        );
+    }
+
+    #[test]
+    fn builtin_entries_describe_ports_parameters_and_presets() {
+        let plugins = builtin::enumerate_plugins();
+        let json = serde_json::to_value(plugin_entries("builtin", &plugins)).unwrap();
+        let fm = json
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|e| e["id"] == "builtin:fm")
+            .unwrap();
+        assert_eq!(fm["format"], "builtin");
+        assert_eq!(fm["audio_input_count"], 0);
+        assert_eq!(fm["audio_output_count"], 2);
+        let params = fm["parameters"].as_array().unwrap();
+        assert_eq!(params.len(), fm["param_count"].as_u64().unwrap() as usize);
+        for key in ["index", "name", "min", "max", "default"] {
+            assert!(params[0].get(key).is_some(), "missing {key}");
+        }
+        assert!(!fm["presets"].as_array().unwrap().is_empty());
+        assert!(fm["presets"][0].get("id").is_some());
+
+        let reverb = plugins.iter().find(|p| p.id == "builtin:reverb").unwrap();
+        assert_eq!(
+            (reverb.audio_input_count, reverb.audio_output_count),
+            (2, 2)
+        );
+    }
+}
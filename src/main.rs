@@ -1,6 +1,7 @@
 #![allow(clippy::collapsible_if)]
 
 mod audio;
+mod bench;
 mod cli;
 mod config;
 mod enumerate;
@@ -9,13 +10,14 @@ mod piano;
 mod plugin;
 mod session;
 mod tui;
+mod validate;
 
 use std::io::Write;
 use std::path::Path;
 use std::time::{Duration, Instant, SystemTime};
 
 use clap::Parser;
-use cli::{Cli, Command, EnumerateTarget, PlayArgs};
+use cli::{Cli, Command, EnumerateTarget, PlayArgs, QuarantineAction};
 
 /// Convert a MIDI note number to a human-readable name (e.g. 60 → "C4").
 pub fn note_name(note: u8) -> String {
@@ -34,6 +36,9 @@ use crossterm::event::{
 fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
 
+    // Plugin main-thread callbacks are serviced from this thread
+    plugin::set_main_thread();
+
     // Load application config (extra plugin paths, etc.)
     if let Ok(config_dir) = dirs_config() {
         let config_path = config_dir.join("config.toml");
@@ -73,48 +78,58 @@ fn main() -> anyhow::Result<()> {
             let session = cli.session;
             todo!("TUI not yet implemented (session: {session:?})");
         }
-        Some(Command::Enumerate(target)) => {
+        Some(Command::Enumerate { format, target }) => {
             env_logger::init();
             match target {
-                EnumerateTarget::Midi => enumerate::midi(),
-                EnumerateTarget::Audio => enumerate::audio(),
-                EnumerateTarget::Plugins => enumerate::plugins(),
-                EnumerateTarget::Builtins => enumerate::builtins(),
+                EnumerateTarget::Midi => enumerate::midi(format),
+                EnumerateTarget::Audio => enumerate::audio(format),
+                EnumerateTarget::Plugins { rescan } => enumerate::plugins(rescan, format),
+                EnumerateTarget::Builtins => enumerate::builtins(format),
             }
         }
-        Some(Command::Describe { plugin: source }) => {
+        Some(Command::Describe {
+            plugin: source,
+            format,
+        }) => {
             env_logger::init();
-            let p = plugin::load(&source, 48000.0, 512, &plugin::Runtime::default())?;
-            println!("{}", p.name());
-            println!(
-                "  Type:          {}",
-                if p.is_instrument() {
-                    "instrument"
-                } else {
-                    "effect"
-                }
-            );
-            println!("  Audio outputs: {}", p.audio_output_count());
-            let params = p.parameters();
-            println!("  Parameters:    {}", params.len());
-            for param in &params {
-                println!(
-                    "    [{}] {} (min={}, max={}, default={})",
-                    param.index, param.name, param.min, param.max, param.default
-                );
-            }
-            let presets = p.presets();
-            if presets.is_empty() {
-                println!("  Presets:       (none)");
-            } else {
-                println!("  Presets:       {}", presets.len());
-                for preset in &presets {
-                    println!("    {} ({})", preset.name, preset.id);
-                }
-            }
-            Ok(())
+            enumerate::describe(&source, format)
+        }
+        Some(Command::Validate {
+            plugin: source,
+            format,
+        }) => {
+            env_logger::init();
+            validate::run(&source, format)
+        }
+        Some(Command::Bench {
+            target,
+            buffer_sizes,
+            sample_rate,
+            seconds,
+            format,
+        }) => {
+            env_logger::init();
+            bench::run(&target, sample_rate, &buffer_sizes, seconds, format)
         }
         Some(Command::Play(args)) => play(args),
+        Some(Command::Quarantine(action)) => {
+            env_logger::init();
+            match action {
+                QuarantineAction::List => enumerate::quarantined(),
+                QuarantineAction::Clear { path } => enumerate::clear_quarantine(path.as_deref()),
+            }
+        }
+        #[cfg(unix)]
+        Some(Command::SandboxHost {
+            plugin: source,
+            shm,
+            sample_rate,
+            block_size,
+        }) => plugin::sandbox::host_main(&source, &shm, sample_rate, block_size),
+        #[cfg(not(unix))]
+        Some(Command::SandboxHost { .. }) => {
+            anyhow::bail!("plugin sandboxing is not supported on this platform")
+        }
     }
 }
 
@@ -183,6 +198,7 @@ fn default_session() -> anyhow::Result<(session::SessionConfig, std::path::PathB
                 pattern: None,
             }],
         }],
+        master: Default::default(),
     };
 
     log::info!("New session (will save to {} on Ctrl+S)", path.display());
@@ -381,66 +397,16 @@ fn load_modulators(
     Ok(loaded)
 }
 
-fn play(args: PlayArgs) -> anyhow::Result<()> {
-    // Set up raw mode logger early so plugin loading messages are visible
-    log::set_logger(&RAW_MODE_LOGGER).ok();
-    log::set_max_level(
-        std::env::var("RUST_LOG")
-            .ok()
-            .and_then(|s| s.parse().ok())
-            .unwrap_or(log::LevelFilter::Info),
-    );
-
-    let sample_rate = args.sample_rate as f32;
-    let max_block_size = args.buffer_size as usize;
-
-    // Load or create session config.
-    let (config, source) = match args.session {
-        Some(s) => {
-            let config = session::load(&s)?;
-            (config, s)
-        }
-        None => {
-            let (config, path) = default_session()?;
-            (config, path.to_string_lossy().to_string())
-        }
-    };
-
-    let session_dir = Path::new(&source).parent().unwrap_or_else(|| Path::new("."));
-
-    // Create shared LV2 world (scans system plugins once, reused for all LV2 loads)
-    #[cfg(feature = "lv2")]
-    let runtime = plugin::Runtime::with_lv2(max_block_size);
-    #[cfg(not(feature = "lv2"))]
-    let runtime = plugin::Runtime::default();
-
-    // Create channels
-    let (midi_tx, midi_rx) = crossbeam_channel::bounded::<audio::MidiEvent>(1024);
-    let (cmd_tx, cmd_rx) = crossbeam_channel::bounded::<plugin::chain::GraphCommand>(64);
-    let (return_tx, return_rx) = crossbeam_channel::bounded::<Box<dyn plugin::Plugin>>(16);
-
-    // Create empty audio graph (outputs silence until instruments are added)
-    let num_channels = 2; // stereo — see CLAUDE.md design decision
-    let mut graph = plugin::chain::AudioGraph::new(num_channels, cmd_rx, return_tx);
-
-    // Pattern recording completion channel
-    let (pattern_tx, pattern_rx) = crossbeam_channel::bounded::<plugin::chain::PatternNotification>(64);
-    graph.set_pattern_tx(pattern_tx.clone());
-
-    // Start MIDI input
-    let mut midi_mgr = midi::MidiManager::new(midi_tx.clone(), args.midi_device.clone());
-    midi_mgr.open_ports()?;
-    log::info!("MIDI inputs connected: {}", midi_mgr.connection_count());
-
-    // Start audio engine (silent — no instruments yet)
-    let engine = audio::AudioEngine::start(
-        graph,
-        midi_rx,
-        args.audio_device.as_deref(),
-        args.sample_rate,
-        args.buffer_size,
-    )?;
-
+/// Load every keyboard, split, plugin, modulator and pattern of `config` into
+/// the audio graph behind `cmd_tx`, returning the matching TUI metadata.
+fn load_session(
+    config: &session::SessionConfig,
+    session_dir: &Path,
+    sample_rate: f32,
+    max_block_size: usize,
+    runtime: &plugin::Runtime,
+    cmd_tx: &crossbeam_channel::Sender<plugin::chain::GraphCommand>,
+) -> anyhow::Result<Vec<tui::LoadedKeyboard>> {
     // Build TUI metadata while loading plugins into the graph.
     let mut loaded_keyboards: Vec<tui::LoadedKeyboard> = Vec::new();
 
@@ -465,7 +431,7 @@ fn play(args: PlayArgs) -> anyhow::Result<()> {
                 let instrument_source =
                     session::resolve_plugin_path(&inst_config.plugin, session_dir);
                 let mut instrument =
-                    plugin::load(&instrument_source, sample_rate, max_block_size, &runtime)?;
+                    plugin::load(&instrument_source, sample_rate, max_block_size, runtime)?;
                 log::info!(
                     "Loaded instrument for kb={} split={}: {}",
                     kb_idx,
@@ -495,6 +461,8 @@ fn play(args: PlayArgs) -> anyhow::Result<()> {
 
                 let inst_params = instrument.parameters();
                 let inst_name = instrument.name().to_string();
+                let inst_presets = instrument.presets();
+                let inst_meters = instrument.meters();
                 let inst_buf = (0..instrument.audio_output_count())
                     .map(|_| Vec::new())
                     .collect();
@@ -556,7 +524,7 @@ fn play(args: PlayArgs) -> anyhow::Result<()> {
                     &inst_params,
                     kb_idx,
                     sp_idx,
-                    &cmd_tx,
+                    cmd_tx,
                 )?;
 
                 Some(tui::LoadedPlugin {
@@ -566,6 +534,8 @@ fn play(args: PlayArgs) -> anyhow::Result<()> {
                     params: inst_params,
                     param_values: inst_values,
                     modulators: inst_mods,
+                    presets: inst_presets,
+                    meters: inst_meters,
                 })
             } else {
                 None
@@ -577,7 +547,7 @@ fn play(args: PlayArgs) -> anyhow::Result<()> {
                 let effect_source =
                     session::resolve_plugin_path(&effect_config.plugin, session_dir);
                 let mut effect =
-                    plugin::load(&effect_source, sample_rate, max_block_size, &runtime)?;
+                    plugin::load(&effect_source, sample_rate, max_block_size, runtime)?;
                 log::info!(
                     "Loaded effect for kb={} split={} fx={}: {}",
                     kb_idx,
@@ -592,6 +562,8 @@ fn play(args: PlayArgs) -> anyhow::Result<()> {
 
                 let effect_params = effect.parameters();
                 let effect_name = effect.name().to_string();
+                let effect_presets = effect.presets();
+                let effect_meters = effect.meters();
 
                 cmd_tx
                     .send(plugin::chain::GraphCommand::InsertEffect {
@@ -641,7 +613,7 @@ fn play(args: PlayArgs) -> anyhow::Result<()> {
                     &effect_params,
                     kb_idx,
                     sp_idx,
-                    &cmd_tx,
+                    cmd_tx,
                 )?;
 
                 loaded_effects.push(tui::LoadedPlugin {
@@ -651,6 +623,8 @@ fn play(args: PlayArgs) -> anyhow::Result<()> {
                     params: effect_params,
                     param_values: fx_values,
                     modulators: fx_mods,
+                    presets: effect_presets,
+                    meters: effect_meters,
                 });
             }
 
@@ -725,10 +699,111 @@ fn play(args: PlayArgs) -> anyhow::Result<()> {
         });
     }
 
+    Ok(loaded_keyboards)
+}
+
+fn play(args: PlayArgs) -> anyhow::Result<()> {
+    // Set up raw mode logger early so plugin loading messages are visible
+    log::set_logger(&RAW_MODE_LOGGER).ok();
+    log::set_max_level(
+        std::env::var("RUST_LOG")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(log::LevelFilter::Info),
+    );
+
+    // Pick the device and the stream configuration closest to the requested
+    // one; plugins are loaded with what the device actually runs at.
+    let num_channels = 2; // stereo — see CLAUDE.md design decision
+    let device = audio::open_device(args.audio_device.as_deref())?;
+    let settings = audio::negotiate(
+        &device,
+        num_channels as u16,
+        args.sample_rate,
+        args.buffer_size,
+    )?;
+    let sample_rate = settings.sample_rate as f32;
+    let max_block_size = settings.max_block_size as usize;
+
+    // Load or create session config.
+    let (config, source) = match args.session {
+        Some(s) => {
+            let config = session::load(&s)?;
+            (config, s)
+        }
+        None => {
+            let (config, path) = default_session()?;
+            (config, path.to_string_lossy().to_string())
+        }
+    };
+
+    let session_dir = Path::new(&source).parent().unwrap_or_else(|| Path::new("."));
+
+    // Create shared LV2 world (scans system plugins once, reused for all LV2 loads)
+    // and decide whether external plugins run sandboxed in child processes
+    let sandbox = args.sandbox || config::sandbox();
+    #[cfg(feature = "lv2")]
+    let runtime = plugin::Runtime {
+        sandbox,
+        ..plugin::Runtime::with_lv2(max_block_size)
+    };
+    #[cfg(not(feature = "lv2"))]
+    let runtime = plugin::Runtime { sandbox };
+    if runtime.sandbox {
+        log::info!("Plugin sandbox enabled: external plugins run in child processes");
+    }
+
+    // Create channels
+    let (midi_tx, midi_rx) = crossbeam_channel::bounded::<audio::MidiEvent>(1024);
+    let (cmd_tx, cmd_rx) = crossbeam_channel::bounded::<plugin::chain::GraphCommand>(64);
+    let (return_tx, return_rx) = crossbeam_channel::bounded::<Box<dyn plugin::Plugin>>(16);
+
+    // Create empty audio graph (outputs silence until instruments are added)
+    let mut graph = plugin::chain::AudioGraph::new(num_channels, cmd_rx, return_tx);
+
+    // Master sanitiser/limiter on the summed output
+    let master = plugin::master::MasterBus::new(&config.master, sample_rate, num_channels);
+    let master_meters = master.meters();
+    if config.master.limiter {
+        log::info!(
+            "Master limiter enabled: ceiling {:.1} dB, {} samples look-ahead",
+            config.master.ceiling_db,
+            master.latency()
+        );
+    }
+    graph.set_master(master);
+
+    // Pattern recording completion channel
+    let (pattern_tx, pattern_rx) = crossbeam_channel::bounded::<plugin::chain::PatternNotification>(64);
+    graph.set_pattern_tx(pattern_tx.clone());
+
+    // Start MIDI input
+    let mut midi_mgr = midi::MidiManager::new(midi_tx.clone(), args.midi_device.clone());
+    midi_mgr.open_ports()?;
+    log::info!("MIDI inputs connected: {}", midi_mgr.connection_count());
+
+    // Start audio engine (silent — no instruments yet)
+    let engine = audio::AudioEngine::start(graph, midi_rx, &device, settings)?;
+
+    // Load the session's plugins into the graph, building TUI metadata.
+    let loaded_keyboards =
+        load_session(&config, session_dir, sample_rate, max_block_size, &runtime, &cmd_tx)?;
+
     // --- Branch: TUI view vs plain play mode ---
     if args.view {
         let session_path = Some(std::path::PathBuf::from(source));
-        tui::run(loaded_keyboards, cmd_tx, midi_tx, runtime, sample_rate, max_block_size, session_path, pattern_rx)?;
+        tui::run(
+            loaded_keyboards,
+            cmd_tx,
+            midi_tx,
+            runtime,
+            sample_rate,
+            max_block_size,
+            session_path,
+            pattern_rx,
+            config.master.clone(),
+            master_meters,
+        )?;
     } else {
         // --- Plain play mode (original) ---
 
@@ -785,6 +860,9 @@ fn play(args: PlayArgs) -> anyhow::Result<()> {
             // Drain returned plugins so they are dropped on the main thread
             while return_rx.try_recv().is_ok() {}
 
+            // Let plugins finish deferred main-thread work
+            plugin::pump_main_thread();
+
             // Poll for new MIDI devices every ~1s
             if last_poll.elapsed() >= Duration::from_secs(1) {
                 midi_mgr.poll_new_devices();
@@ -0,0 +1,256 @@
+//! `builtin:analyzer`: passes audio through unchanged and shows peak and
+//! RMS levels per channel plus an octave-band spectrum as meters. Insert it
+//! after any stage of a chain to see what that stage outputs.
+
+use super::Builtin;
+use super::dsp::{ParamSpec, Params, bit_reverse, fft_pass};
+use super::effect::{self, Effect, gain_to_db};
+use crate::plugin::{MeterInfo, MeterValue};
+
+pub const BUILTIN: Builtin = effect::builtin::<Analyzer>("analyzer");
+
+const SMOOTHING: usize = 0;
+
+/// FFT length; the spectrum is updated every half of it.
+const FFT_SIZE: usize = 2048;
+
+/// Samples between steps of a pending analysis. Each step is one FFT pass
+/// (or the band levels), so the transform is spread over the next hop
+/// instead of landing in one audio callback: 12 steps fit in half an FFT.
+const STEP_INTERVAL: usize = 64;
+
+/// RMS integration time in seconds.
+const RMS_TIME: f32 = 0.3;
+
+/// Peak meter fall rate in dB per second.
+const PEAK_FALL: f32 = 20.0;
+
+/// Octave bands: label and centre frequency.
+const BANDS: [(&str, f32); 10] = [
+    ("31 Hz", 31.25),
+    ("63 Hz", 62.5),
+    ("125 Hz", 125.0),
+    ("250 Hz", 250.0),
+    ("500 Hz", 500.0),
+    ("1 kHz", 1000.0),
+    ("2 kHz", 2000.0),
+    ("4 kHz", 4000.0),
+    ("8 kHz", 8000.0),
+    ("16 kHz", 16000.0),
+];
+
+pub struct Analyzer {
+    sample_rate: f32,
+    /// Per-channel peak (dB, falling) and mean square.
+    peak_db: [f32; 2],
+    mean_square: [f32; 2],
+    /// Mono input, oldest first once `filled` reaches `FFT_SIZE`.
+    history: Vec<f32>,
+    filled: usize,
+    window: Vec<f32>,
+    re: Vec<f32>,
+    im: Vec<f32>,
+    /// Length of the next FFT pass of the pending analysis; past `FFT_SIZE`
+    /// the band levels are next, and 0 means idle.
+    pass: usize,
+    /// Smoothed power per band.
+    band_power: [f32; BANDS.len()],
+    /// FFT bin range per band.
+    band_bins: [(usize, usize); BANDS.len()],
+    peak_meters: [MeterValue; 2],
+    rms_meters: [MeterValue; 2],
+    band_meters: [MeterValue; BANDS.len()],
+}
+
+impl Analyzer {
+    /// Takes a windowed copy of the history and begins transforming it; the
+    /// rest happens in [`Self::step`].
+    fn start_analysis(&mut self) {
+        for (i, (re, im)) in self.re.iter_mut().zip(self.im.iter_mut()).enumerate() {
+            *re = self.history[i] * self.window[i];
+            *im = 0.0;
+        }
+        bit_reverse(&mut self.re, &mut self.im);
+        self.pass = 2;
+    }
+
+    /// Runs the next step of a pending analysis, if any.
+    fn step(&mut self, smoothing: f32) {
+        if self.pass == 0 {
+            return;
+        }
+        if self.pass <= FFT_SIZE {
+            fft_pass(&mut self.re, &mut self.im, self.pass);
+            self.pass <<= 1;
+        } else {
+            self.update_bands(smoothing);
+            self.pass = 0;
+        }
+    }
+
+    fn update_bands(&mut self, smoothing: f32) {
+        // Scaled so a full-scale sine reads 0 dB: a Hann-windowed sine of
+        // amplitude A puts A² · 3N²/32 in the positive-frequency bins.
+        let scale = 32.0 / (3.0 * (FFT_SIZE * FFT_SIZE) as f32);
+        for (band, &(lo, hi)) in self.band_bins.iter().enumerate() {
+            let power: f32 = (lo..hi)
+                .map(|k| self.re[k] * self.re[k] + self.im[k] * self.im[k])
+                .sum::<f32>()
+                * scale;
+            let smoothed = power + (self.band_power[band] - power) * smoothing;
+            self.band_power[band] = smoothed;
+            self.band_meters[band].set(10.0 * smoothed.max(1e-12).log10());
+        }
+    }
+}
+
+impl Effect for Analyzer {
+    const NAME: &'static str = "Analyzer";
+    const PARAMS: &'static [ParamSpec] = &[ParamSpec::new("Smoothing", 0.0, 0.95, 0.5)];
+
+    fn new(sample_rate: f32) -> Self {
+        let bin_width = sample_rate / FFT_SIZE as f32;
+        let band_bins = BANDS.map(|(_, centre)| {
+            let lo = (centre / std::f32::consts::SQRT_2 / bin_width).ceil() as usize;
+            let hi = (centre * std::f32::consts::SQRT_2 / bin_width).ceil() as usize;
+            (lo.max(1), hi.min(FFT_SIZE / 2))
+        });
+        Self {
+            sample_rate,
+            peak_db: [-120.0; 2],
+            mean_square: [0.0; 2],
+            history: vec![0.0; FFT_SIZE],
+            filled: 0,
+            window: (0..FFT_SIZE)
+                .map(|i| 0.5 - 0.5 * (std::f32::consts::TAU * i as f32 / FFT_SIZE as f32).cos())
+                .collect(),
+            re: vec![0.0; FFT_SIZE],
+            im: vec![0.0; FFT_SIZE],
+            pass: 0,
+            band_power: [0.0; BANDS.len()],
+            band_bins,
+            peak_meters: Default::default(),
+            rms_meters: Default::default(),
+            band_meters: Default::default(),
+        }
+    }
+
+    fn process(&mut self, params: &Params, left: &mut [f32], right: &mut [f32]) {
+        let frames = left.len();
+        let rms_coef = (-1.0 / (RMS_TIME * self.sample_rate)).exp();
+        let fall = PEAK_FALL * frames as f32 / self.sample_rate;
+
+        for (ch, samples) in [&*left, &*right].into_iter().enumerate() {
+            let mut peak = 0.0_f32;
+            let mut ms = self.mean_square[ch];
+            for &s in samples {
+                peak = peak.max(s.abs());
+                ms = s * s + (ms - s * s) * rms_coef;
+            }
+            self.mean_square[ch] = ms;
+            self.peak_db[ch] = gain_to_db(peak).max(self.peak_db[ch] - fall);
+            self.peak_meters[ch].set(self.peak_db[ch]);
+            self.rms_meters[ch].set(gain_to_db(ms.sqrt()));
+        }
+
+        // Slide mono input through the history, analysing every half FFT.
+        let smoothing = params.get(SMOOTHING);
+        for (l, r) in left.iter().zip(right.iter()) {
+            self.history[self.filled] = 0.5 * (l + r);
+            self.filled += 1;
+            if self.filled == FFT_SIZE {
+                // A hop has room for every step, so this only runs if the
+                // interval and FFT size stop agreeing.
+                while self.pass != 0 {
+                    self.step(smoothing);
+                }
+                self.start_analysis();
+                self.history.copy_within(FFT_SIZE / 2.., 0);
+                self.filled = FFT_SIZE / 2;
+            } else if self.filled.is_multiple_of(STEP_INTERVAL) {
+                self.step(smoothing);
+            }
+        }
+    }
+
+    fn meters(&self) -> Vec<MeterInfo> {
+        let level = |name: &str, value: &MeterValue| MeterInfo {
+            name: name.into(),
+            min: -60.0,
+            max: 0.0,
+            value: value.clone(),
+        };
+        let mut meters = vec![
+            level("Peak L", &self.peak_meters[0]),
+            level("Peak R", &self.peak_meters[1]),
+            level("RMS L", &self.rms_meters[0]),
+            level("RMS R", &self.rms_meters[1]),
+        ];
+        meters.extend(
+            BANDS
+                .iter()
+                .zip(&self.band_meters)
+                .map(|(&(name, _), value)| MeterInfo {
+                    name: name.into(),
+                    min: -90.0,
+                    max: 0.0,
+                    value: value.clone(),
+                }),
+        );
+        meters
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin::builtin::effect::{self, run, sine};
+
+    const SR: f32 = 48000.0;
+
+    fn meter(plugin: &dyn crate::plugin::Plugin, name: &str) -> f32 {
+        plugin
+            .meters()
+            .into_iter()
+            .find(|m| m.name == name)
+            .unwrap()
+            .value
+            .get()
+    }
+
+    #[test]
+    fn levels_and_spectrum_of_sine() {
+        let mut analyzer = effect::load::<Analyzer>(SR);
+        analyzer.set_parameter(SMOOTHING as u32, 0.0).unwrap();
+        let input = sine(1000.0, 0.5, SR, 96000);
+        let quiet = vec![0.0; 96000];
+        let (out_l, out_r) = run(analyzer.as_mut(), &input, &quiet);
+        // Audio passes through untouched.
+        assert_eq!(out_l, input);
+        assert_eq!(out_r, quiet);
+
+        // 0.5 amplitude: -6 dB peak, -9 dB RMS.
+        assert!((meter(analyzer.as_ref(), "Peak L") + 6.02).abs() < 0.1);
+        assert!((meter(analyzer.as_ref(), "RMS L") + 9.03).abs() < 0.2);
+        assert!(meter(analyzer.as_ref(), "Peak R") <= -60.0);
+
+        // The mono sum is a 0.25 sine: -12 dB in the 1 kHz band only.
+        let band = meter(analyzer.as_ref(), "1 kHz");
+        assert!((band + 12.04).abs() < 0.5, "{band}");
+        assert!(meter(analyzer.as_ref(), "125 Hz") < -60.0);
+        assert!(meter(analyzer.as_ref(), "8 kHz") < -60.0);
+    }
+
+    #[test]
+    fn peak_falls_back_after_signal_stops() {
+        let mut analyzer = effect::load::<Analyzer>(SR);
+        let input = sine(1000.0, 1.0, SR, 4800);
+        run(analyzer.as_mut(), &input, &input);
+        let loud = meter(analyzer.as_ref(), "Peak L");
+        let silence = vec![0.0; 24000];
+        run(analyzer.as_mut(), &silence, &silence);
+        let after = meter(analyzer.as_ref(), "Peak L");
+        // Half a second at 20 dB/s.
+        assert!((loud - after - 10.0).abs() < 0.1, "{loud} -> {after}");
+    }
+}
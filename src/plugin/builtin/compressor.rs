@@ -0,0 +1,138 @@
+//! `builtin:compressor`: a stereo-linked feed-forward compressor with a
+//! soft knee. Gain reduction is shown as a meter.
+
+use super::Builtin;
+use super::dsp::{ParamSpec, Params};
+use super::effect::{self, Effect, db_to_gain};
+use crate::plugin::{MeterInfo, MeterValue};
+
+pub const BUILTIN: Builtin = effect::builtin::<Compressor>("compressor");
+
+const THRESHOLD: usize = 0;
+const RATIO: usize = 1;
+const ATTACK: usize = 2;
+const RELEASE: usize = 3;
+const KNEE: usize = 4;
+const MAKEUP: usize = 5;
+
+/// Static gain curve: the gain change in dB for an input level in dB.
+fn gain_computer(level: f32, threshold: f32, ratio: f32, knee: f32) -> f32 {
+    let over = level - threshold;
+    let out = if 2.0 * over < -knee || (knee <= 0.0 && over <= 0.0) {
+        level
+    } else if knee > 0.0 && 2.0 * over.abs() <= knee {
+        level + (1.0 / ratio - 1.0) * (over + knee / 2.0).powi(2) / (2.0 * knee)
+    } else {
+        threshold + over / ratio
+    };
+    out - level
+}
+
+/// Per-sample smoothing coefficient for a time constant in milliseconds.
+fn coef(ms: f32, sample_rate: f32) -> f32 {
+    (-1000.0 / (ms * sample_rate)).exp()
+}
+
+pub struct Compressor {
+    sample_rate: f32,
+    /// Smoothed gain change in dB (0 or negative).
+    reduction: f32,
+    meter: MeterValue,
+}
+
+impl Effect for Compressor {
+    const NAME: &'static str = "Compressor";
+    const PARAMS: &'static [ParamSpec] = &[
+        ParamSpec::new("Threshold", -60.0, 0.0, -18.0),
+        ParamSpec::new("Ratio", 1.0, 20.0, 4.0),
+        ParamSpec::new("Attack", 0.1, 200.0, 10.0),
+        ParamSpec::new("Release", 5.0, 2000.0, 150.0),
+        ParamSpec::new("Knee", 0.0, 24.0, 6.0),
+        ParamSpec::new("Makeup", -12.0, 24.0, 0.0),
+    ];
+
+    fn new(sample_rate: f32) -> Self {
+        Self {
+            sample_rate,
+            reduction: 0.0,
+            meter: MeterValue::default(),
+        }
+    }
+
+    fn process(&mut self, params: &Params, left: &mut [f32], right: &mut [f32]) {
+        let threshold = params.get(THRESHOLD);
+        let ratio = params.get(RATIO);
+        let knee = params.get(KNEE);
+        let attack = coef(params.get(ATTACK), self.sample_rate);
+        let release = coef(params.get(RELEASE), self.sample_rate);
+        let makeup = params.get(MAKEUP);
+        let mut peak_reduction: f32 = 0.0;
+
+        for (l, r) in left.iter_mut().zip(right.iter_mut()) {
+            let level = 20.0 * l.abs().max(r.abs()).max(1e-6).log10();
+            let target = gain_computer(level, threshold, ratio, knee);
+            let c = if target < self.reduction {
+                attack
+            } else {
+                release
+            };
+            self.reduction = target + (self.reduction - target) * c;
+            peak_reduction = peak_reduction.min(self.reduction);
+            let gain = db_to_gain(self.reduction + makeup);
+            *l *= gain;
+            *r *= gain;
+        }
+        self.meter.set(peak_reduction);
+    }
+
+    fn meters(&self) -> Vec<MeterInfo> {
+        vec![MeterInfo {
+            name: "Gain Reduction".into(),
+            min: -40.0,
+            max: 0.0,
+            value: self.meter.clone(),
+        }]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin::builtin::effect::{self, rms, run, sine};
+
+    const SR: f32 = 48000.0;
+
+    #[test]
+    fn gain_curve() {
+        // Below the knee: untouched; well above: threshold + over / ratio.
+        assert_eq!(gain_computer(-40.0, -20.0, 4.0, 6.0), 0.0);
+        assert!((gain_computer(0.0, -20.0, 4.0, 6.0) + 15.0).abs() < 1e-4);
+        // Inside the knee the reduction is partial.
+        let knee = gain_computer(-20.0, -20.0, 4.0, 6.0);
+        assert!(knee < 0.0 && knee > -1.5, "{knee}");
+        // A hard knee at exactly the threshold is the corner itself.
+        assert_eq!(gain_computer(-20.0, -20.0, 4.0, 0.0), 0.0);
+        assert!((gain_computer(0.0, -20.0, 4.0, 0.0) + 15.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn compresses_loud_signals_only() {
+        let mut comp = effect::load::<Compressor>(SR);
+        comp.set_parameter(KNEE as u32, 0.0).unwrap();
+        let meter = comp.meters()[0].value.clone();
+
+        let quiet = sine(1000.0, 0.05, SR, 24000);
+        let (out, _) = run(comp.as_mut(), &quiet, &quiet);
+        assert!((rms(&out, 12000) - rms(&quiet, 12000)).abs() < 1e-4);
+        assert_eq!(meter.get(), 0.0);
+
+        // A 0 dBFS peak is 18 dB over threshold: 4:1 leaves 4.5 dB, so the
+        // gain settles around -13.5 dB (the detector follows peaks, so a
+        // little more).
+        let loud = sine(1000.0, 1.0, SR, 48000);
+        let (out, _) = run(comp.as_mut(), &loud, &loud);
+        let change = 20.0 * (rms(&out, 24000) / rms(&loud, 24000)).log10();
+        assert!(change < -10.0 && change > -14.5, "{change}");
+        assert!(meter.get() < -10.0);
+    }
+}
@@ -0,0 +1,139 @@
+//! `builtin:delay`: a stereo echo with feedback tone control, optional
+//! ping-pong and tempo sync to the global BPM.
+
+use super::Builtin;
+use super::dsp::{DelayLine, OnePole, ParamSpec, Params, flush_denormal};
+use super::effect::{self, Effect, mix};
+
+pub const BUILTIN: Builtin = effect::builtin::<Delay>("delay");
+
+/// Longest delay, in seconds.
+const MAX_DELAY: f32 = 4.0;
+
+/// Tempo-synced note lengths selectable by the Sync parameter, in beats.
+/// Index 0 (Off) uses the Time parameter instead.
+const SYNC_BEATS: [f32; 7] = [0.0, 2.0, 1.0, 0.75, 0.5, 1.0 / 3.0, 0.25];
+
+const TIME: usize = 0;
+const SYNC: usize = 1;
+const FEEDBACK: usize = 2;
+const TONE: usize = 3;
+const PING_PONG: usize = 4;
+const MIX: usize = 5;
+
+pub struct Delay {
+    sample_rate: f32,
+    bpm: f32,
+    lines: [DelayLine; 2],
+    tone: [OnePole; 2],
+    /// Current delay in samples, gliding towards the target.
+    delay: f32,
+}
+
+impl Delay {
+    /// Target delay in samples for the current parameters and tempo.
+    fn target_delay(&self, params: &Params) -> f32 {
+        let beats = SYNC_BEATS[params.choice(SYNC).min(SYNC_BEATS.len() - 1)];
+        let seconds = if beats > 0.0 {
+            beats * 60.0 / self.bpm
+        } else {
+            params.get(TIME) / 1000.0
+        };
+        seconds.min(MAX_DELAY) * self.sample_rate
+    }
+}
+
+impl Effect for Delay {
+    const NAME: &'static str = "Delay";
+    /// Sync: 0 off, 1 half note, 2 quarter, 3 dotted eighth, 4 eighth,
+    /// 5 eighth triplet, 6 sixteenth.
+    const PARAMS: &'static [ParamSpec] = &[
+        ParamSpec::new("Time", 1.0, MAX_DELAY * 1000.0, 375.0),
+        ParamSpec::stepped("Sync", 0.0, (SYNC_BEATS.len() - 1) as f32, 0.0),
+        ParamSpec::new("Feedback", 0.0, 0.95, 0.4),
+        ParamSpec::new("Tone", 500.0, 20000.0, 6000.0),
+        ParamSpec::stepped("Ping Pong", 0.0, 1.0, 0.0),
+        ParamSpec::new("Mix", 0.0, 1.0, 0.3),
+    ];
+
+    fn new(sample_rate: f32) -> Self {
+        let max = (MAX_DELAY * sample_rate) as usize + 1;
+        Self {
+            sample_rate,
+            bpm: 120.0,
+            lines: [DelayLine::new(max), DelayLine::new(max)],
+            tone: [OnePole::default(), OnePole::default()],
+            delay: 0.375 * sample_rate,
+        }
+    }
+
+    fn process(&mut self, params: &Params, left: &mut [f32], right: &mut [f32]) {
+        let target = self.target_delay(params);
+        let feedback = params.get(FEEDBACK);
+        let tone = params.get(TONE);
+        let ping_pong = params.choice(PING_PONG) == 1;
+        let amount = params.get(MIX);
+        let sr = self.sample_rate;
+
+        for (l, r) in left.iter_mut().zip(right.iter_mut()) {
+            // Glide to new delay times instead of jumping, like tape.
+            self.delay += (target - self.delay) * 0.0005;
+            let wet_l = self.tone[0].process(self.lines[0].read(self.delay), tone, sr);
+            let wet_r = self.tone[1].process(self.lines[1].read(self.delay), tone, sr);
+            if ping_pong {
+                self.lines[0].write(flush_denormal((*l + *r) * 0.5 + feedback * wet_r));
+                self.lines[1].write(flush_denormal(feedback * wet_l));
+            } else {
+                self.lines[0].write(flush_denormal(*l + feedback * wet_l));
+                self.lines[1].write(flush_denormal(*r + feedback * wet_r));
+            }
+            *l = mix(*l, wet_l, amount);
+            *r = mix(*r, wet_r, amount);
+        }
+    }
+
+    fn set_tempo(&mut self, bpm: f32) {
+        if bpm > 0.0 {
+            self.bpm = bpm;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin::builtin::effect::{self, run};
+
+    const SR: f32 = 48000.0;
+
+    /// Frame of the first echo of an impulse.
+    fn echo_frame(delay: &mut dyn crate::plugin::Plugin) -> usize {
+        let mut impulse = vec![0.0; 48000];
+        impulse[0] = 1.0;
+        let (out, _) = run(delay, &impulse, &impulse);
+        1 + out[1..].iter().position(|s| s.abs() > 0.05).unwrap()
+    }
+
+    #[test]
+    fn echoes_after_delay_time() {
+        let mut delay = effect::load::<Delay>(SR);
+        delay.set_parameter(MIX as u32, 1.0).unwrap();
+        delay.set_parameter(TONE as u32, 20000.0).unwrap();
+        let frame = echo_frame(delay.as_mut());
+        assert!((frame as i64 - 18000).abs() < 10, "{frame}");
+    }
+
+    #[test]
+    fn syncs_to_tempo() {
+        let mut delay = effect::load::<Delay>(SR);
+        delay.set_parameter(MIX as u32, 1.0).unwrap();
+        delay.set_parameter(TONE as u32, 20000.0).unwrap();
+        // An eighth note at 160 BPM is 187.5 ms.
+        delay.set_parameter(SYNC as u32, 4.0).unwrap();
+        delay.set_tempo(160.0);
+        // Let the delay time settle.
+        run(delay.as_mut(), &[0.0; 48000], &[0.0; 48000]);
+        let frame = echo_frame(delay.as_mut());
+        assert!((frame as i64 - 9000).abs() < 10, "{frame}");
+    }
+}
@@ -0,0 +1,608 @@
+//! `builtin:drums`: a drum machine that plays one-shot samples mapped to
+//! notes ("pads").
+//!
+//! Without a file it plays a small synthesized kit on the General MIDI drum
+//! notes. `builtin:drums:<kit.toml>` loads a kit of WAV/FLAC samples:
+//!
+//! ```toml
+//! [[pad]]
+//! name = "Kick"
+//! note = "C2"          # or a MIDI note number
+//! sample = "kick.wav"  # relative to the kit file
+//! volume = 0.0         # dB
+//! pan = 0.0            # -1 (left) to 1 (right)
+//! pitch = 0.0          # semitones
+//! choke = 1            # pads in the same choke group cut each other off
+//! output = 0           # output pair; 0 is the main mix
+//! ```
+//!
+//! Each pad's volume, pan and pitch are plugin parameters. Pads on output
+//! pairs other than 0 are left out of the main pair when the host routes
+//! that pair (e.g. to a multichannel audio device), and mixed into the main
+//! pair otherwise.
+
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serde::Deserialize;
+
+use super::dsp::{FilterMode, Noise, ParamSpec, Params, Svf};
+use super::instrument::{Instrument, InstrumentPlugin};
+use super::sample::Sample;
+use super::{Builtin, FileArg};
+use crate::plugin::Plugin;
+
+pub const BUILTIN: Builtin = Builtin {
+    name: "drums",
+    display_name: DrumMachine::NAME,
+    is_instrument: true,
+    file: FileArg::Optional("kit.toml"),
+    params: || DrumMachine::new(48000.0).parameters(),
+    presets: Vec::new,
+    create: |sample_rate, file| match file {
+        Some(file) => Ok(Box::new(DrumMachine::load(file, sample_rate)?)),
+        None => Ok(Box::new(DrumMachine::new(sample_rate))),
+    },
+};
+
+const MAX_VOICES: usize = 32;
+/// Fade-out time for voices cut off by a choke group, in seconds.
+const CHOKE_FADE: f32 = 0.005;
+/// Most output pairs a kit can use.
+const MAX_OUTPUTS: usize = 8;
+/// Parameters per pad: volume, pan, pitch.
+const PAD_PARAMS: usize = 3;
+
+// ---------------------------------------------------------------------------
+// Kit
+// ---------------------------------------------------------------------------
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum NoteRef {
+    Number(u8),
+    Name(String),
+}
+
+impl NoteRef {
+    fn resolve(&self) -> anyhow::Result<u8> {
+        match self {
+            Self::Number(n) if *n <= 127 => Ok(*n),
+            Self::Number(n) => anyhow::bail!("note {n} is out of MIDI range (0-127)"),
+            Self::Name(name) => crate::session::parse_note_name(name),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PadConfig {
+    name: Option<String>,
+    note: NoteRef,
+    sample: String,
+    #[serde(default)]
+    volume: f32,
+    #[serde(default)]
+    pan: f32,
+    #[serde(default)]
+    pitch: f32,
+    #[serde(default)]
+    choke: u32,
+    #[serde(default)]
+    output: usize,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KitConfig {
+    #[serde(default)]
+    pad: Vec<PadConfig>,
+}
+
+struct Pad {
+    name: String,
+    note: u8,
+    sample: Arc<Sample>,
+    volume: f32,
+    pan: f32,
+    pitch: f32,
+    choke: u32,
+    output: usize,
+}
+
+/// Load a kit file and its samples.
+fn load_kit(path: &Path) -> anyhow::Result<Vec<Pad>> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Failed to read {}: {e}", path.display()))?;
+    let config: KitConfig =
+        toml::from_str(&text).map_err(|e| anyhow::anyhow!("{}: {e}", path.display()))?;
+    if config.pad.is_empty() {
+        anyhow::bail!("{} has no pads", path.display());
+    }
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let mut samples: HashMap<PathBuf, Arc<Sample>> = HashMap::new();
+    let mut pads = Vec::with_capacity(config.pad.len());
+    for pad in config.pad {
+        let note = pad
+            .note
+            .resolve()
+            .map_err(|e| anyhow::anyhow!("{}: {e}", path.display()))?;
+        if pad.output >= MAX_OUTPUTS {
+            anyhow::bail!(
+                "{}: pad output {} is out of range (0-{})",
+                path.display(),
+                pad.output,
+                MAX_OUTPUTS - 1
+            );
+        }
+        let sample_path = dir.join(&pad.sample);
+        let sample = match samples.get(&sample_path) {
+            Some(sample) => sample.clone(),
+            None => {
+                let sample = Arc::new(Sample::load(&sample_path)?);
+                samples.insert(sample_path, sample.clone());
+                sample
+            }
+        };
+        let name = pad.name.unwrap_or_else(|| {
+            Path::new(&pad.sample)
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_else(|| format!("Pad {note}"))
+        });
+        pads.push(Pad {
+            name,
+            note,
+            sample,
+            volume: pad.volume,
+            pan: pad.pan,
+            pitch: pad.pitch,
+            choke: pad.choke,
+            output: pad.output,
+        });
+    }
+    Ok(pads)
+}
+
+// ---------------------------------------------------------------------------
+// Synthesized default kit
+// ---------------------------------------------------------------------------
+
+/// Render `seconds` of audio from `f(t, noise)`.
+fn synth(sample_rate: f32, seconds: f32, mut f: impl FnMut(f32, &mut Noise) -> f32) -> Vec<f32> {
+    let mut noise = Noise::new(0x1234_5678);
+    (0..(seconds * sample_rate) as usize)
+        .map(|i| f(i as f32 / sample_rate, &mut noise))
+        .collect()
+}
+
+/// Sine with an exponential pitch drop, for kicks and toms.
+fn swept_sine(sample_rate: f32, seconds: f32, from: f32, to: f32, decay: f32) -> Vec<f32> {
+    let mut phase = 0.0_f32;
+    synth(sample_rate, seconds, |t, _| {
+        let freq = to + (from - to) * (-t / 0.04).exp();
+        phase = (phase + freq / sample_rate).fract();
+        (phase * std::f32::consts::TAU).sin() * (-t / decay).exp()
+    })
+}
+
+/// Filtered noise with an exponential decay.
+fn filtered_noise(
+    sample_rate: f32,
+    seconds: f32,
+    cutoff: f32,
+    mode: FilterMode,
+    decay: f32,
+) -> Vec<f32> {
+    let mut filter = Svf::default();
+    synth(sample_rate, seconds, |t, noise| {
+        filter.process(noise.next(), cutoff, 0.3, mode, sample_rate) * (-t / decay).exp()
+    })
+}
+
+fn default_kit(sample_rate: f32) -> Vec<Pad> {
+    let sr = sample_rate;
+    let snare = {
+        let tone = swept_sine(sr, 0.3, 260.0, 185.0, 0.06);
+        let rattle = filtered_noise(sr, 0.3, 1800.0, FilterMode::Highpass, 0.09);
+        tone.iter()
+            .zip(&rattle)
+            .map(|(a, b)| 0.6 * a + 0.8 * b)
+            .collect()
+    };
+    let clap = {
+        let mut filter = Svf::default();
+        synth(sr, 0.35, |t, noise| {
+            // Three quick bursts, then a tail.
+            let burst = t % 0.011;
+            let env = if t < 0.033 {
+                (-burst / 0.004).exp()
+            } else {
+                (-(t - 0.033) / 0.09).exp()
+            };
+            2.0 * filter.process(noise.next(), 1200.0, 0.4, FilterMode::Bandpass, sr) * env
+        })
+    };
+    let pads: [(&str, u8, Vec<f32>, f32, u32); 7] = [
+        ("Kick", 36, swept_sine(sr, 0.6, 160.0, 48.0, 0.2), 0.0, 0),
+        ("Snare", 38, snare, 0.0, 0),
+        ("Clap", 39, clap, 0.1, 0),
+        (
+            "Low Tom",
+            45,
+            swept_sine(sr, 0.5, 150.0, 95.0, 0.2),
+            -0.3,
+            0,
+        ),
+        (
+            "High Tom",
+            50,
+            swept_sine(sr, 0.4, 240.0, 160.0, 0.15),
+            0.3,
+            0,
+        ),
+        (
+            "Closed Hat",
+            42,
+            filtered_noise(sr, 0.1, 7500.0, FilterMode::Highpass, 0.025),
+            -0.2,
+            1,
+        ),
+        (
+            "Open Hat",
+            46,
+            filtered_noise(sr, 0.7, 7000.0, FilterMode::Highpass, 0.22),
+            -0.2,
+            1,
+        ),
+    ];
+    pads.into_iter()
+        .map(|(name, note, data, pan, choke)| Pad {
+            name: name.into(),
+            note,
+            sample: Arc::new(Sample::mono(data, sr)),
+            volume: -3.0,
+            pan,
+            pitch: 0.0,
+            choke,
+            output: 0,
+        })
+        .collect()
+}
+
+// ---------------------------------------------------------------------------
+// Player
+// ---------------------------------------------------------------------------
+
+#[derive(Clone, Default)]
+struct Voice {
+    active: bool,
+    pad: usize,
+    velocity: f32,
+    /// Position in the sample, in sample frames.
+    position: f64,
+    /// Remaining choke fade, as a gain stepping down to 0.
+    fade: Option<f32>,
+    /// Note-on order, for stealing the oldest voice.
+    started: u64,
+}
+
+pub struct DrumMachine {
+    sample_rate: f32,
+    pads: Vec<Pad>,
+    /// Pad index per MIDI note.
+    note_map: [Option<usize>; 128],
+    outputs: usize,
+    /// Output pairs the host routes; pads on other pairs play on pair 0.
+    routed_pairs: usize,
+    voices: Vec<Voice>,
+    note_counter: u64,
+    /// Per-pad left/right gains and playback rates, refreshed each render.
+    gains: Vec<[f32; 2]>,
+    rates: Vec<f64>,
+}
+
+impl DrumMachine {
+    /// The synthesized default kit.
+    pub fn new(sample_rate: f32) -> InstrumentPlugin<Self> {
+        Self::with_pads(default_kit(sample_rate), sample_rate)
+    }
+
+    /// Load the kit file at `path`.
+    pub fn load(path: &Path, sample_rate: f32) -> anyhow::Result<InstrumentPlugin<Self>> {
+        let pads = load_kit(path)?;
+        log::info!("Loaded drum kit {} ({} pads)", path.display(), pads.len());
+        Ok(Self::with_pads(pads, sample_rate))
+    }
+
+    fn with_pads(pads: Vec<Pad>, sample_rate: f32) -> InstrumentPlugin<Self> {
+        let mut note_map = [None; 128];
+        for (i, pad) in pads.iter().enumerate() {
+            if let Some(previous) = note_map[pad.note as usize].replace(i) {
+                log::warn!(
+                    "Drum pads {:?} and {:?} share note {}; using {:?}",
+                    pads[previous].name,
+                    pad.name,
+                    pad.note,
+                    pad.name
+                );
+            }
+        }
+        let specs: Vec<ParamSpec> = std::iter::once(ParamSpec::new("Volume", 0.0, 1.0, 0.8))
+            .chain(pads.iter().flat_map(|pad| {
+                [
+                    ParamSpec::named(format!("{} Volume", pad.name), -60.0, 12.0, pad.volume),
+                    ParamSpec::named(format!("{} Pan", pad.name), -1.0, 1.0, pad.pan),
+                    ParamSpec::named(format!("{} Pitch", pad.name), -24.0, 24.0, pad.pitch),
+                ]
+            }))
+            .collect();
+        let outputs = pads.iter().map(|p| p.output).max().unwrap_or(0) + 1;
+        let drums = Self {
+            sample_rate,
+            note_map,
+            outputs,
+            routed_pairs: outputs,
+            voices: vec![Voice::default(); MAX_VOICES],
+            note_counter: 0,
+            gains: vec![[0.0; 2]; pads.len()],
+            rates: vec![0.0; pads.len()],
+            pads,
+        };
+        InstrumentPlugin::new(drums, &specs, sample_rate)
+    }
+
+    /// Index of pad `pad`'s first parameter (volume, then pan and pitch).
+    fn pad_param(pad: usize) -> usize {
+        1 + pad * PAD_PARAMS
+    }
+
+    fn trigger(&mut self, note: u8, velocity: u8) {
+        let Some(pad) = self.note_map[note as usize] else {
+            return;
+        };
+        let choke = self.pads[pad].choke;
+        if choke != 0 {
+            for voice in self.voices.iter_mut().filter(|v| v.active) {
+                if self.pads[voice.pad].choke == choke && voice.fade.is_none() {
+                    voice.fade = Some(1.0);
+                }
+            }
+        }
+        let slot = self
+            .voices
+            .iter()
+            .position(|v| !v.active)
+            .unwrap_or_else(|| {
+                self.voices
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|(_, v)| v.started)
+                    .map(|(i, _)| i)
+                    .unwrap_or(0)
+            });
+        self.note_counter += 1;
+        self.voices[slot] = Voice {
+            active: true,
+            pad,
+            velocity: velocity as f32 / 127.0,
+            position: 0.0,
+            fade: None,
+            started: self.note_counter,
+        };
+    }
+}
+
+impl Instrument for DrumMachine {
+    const NAME: &'static str = "Drum Machine";
+
+    fn handle_midi(&mut self, _params: &Params, [status, data1, data2]: [u8; 3]) {
+        match status & 0xF0 {
+            0x90 if data2 > 0 => self.trigger(data1 & 0x7F, data2),
+            0xB0 if data1 == 120 || data1 == 123 => {
+                for voice in self.voices.iter_mut().filter(|v| v.active) {
+                    voice.fade.get_or_insert(1.0);
+                }
+            }
+            // One-shots ignore note-offs.
+            _ => {}
+        }
+    }
+
+    fn render(&mut self, params: &Params, out: &mut [&mut [f32]], frames: Range<usize>) {
+        let sr = self.sample_rate;
+        let fade_step = 1.0 / (CHOKE_FADE * sr);
+
+        // Per-pad gains and playback rates, read once per call.
+        let volume = params.get(0);
+        for (i, pad) in self.pads.iter().enumerate() {
+            let base = Self::pad_param(i);
+            let gain = volume * 10.0_f32.powf(params.get(base) / 20.0);
+            let pan = params.get(base + 1);
+            self.gains[i] = [gain * (1.0 - pan).min(1.0), gain * (1.0 + pan).min(1.0)];
+            let pitch = params.get(base + 2);
+            self.rates[i] =
+                2.0_f64.powf(pitch as f64 / 12.0) * pad.sample.sample_rate as f64 / sr as f64;
+        }
+
+        // Pads on pairs the host doesn't route (or didn't provide) fall back
+        // to the main pair.
+        let routed = self.routed_pairs.min(out.len() / 2);
+
+        for frame in frames {
+            for voice in self.voices.iter_mut().filter(|v| v.active) {
+                let pad = &self.pads[voice.pad];
+                let mut gain = voice.velocity;
+                if let Some(fade) = &mut voice.fade {
+                    *fade -= fade_step;
+                    if *fade <= 0.0 {
+                        voice.active = false;
+                        continue;
+                    }
+                    gain *= *fade;
+                }
+                let pair = if pad.output < routed { pad.output } else { 0 };
+                for (c, pad_gain) in self.gains[voice.pad].iter().enumerate() {
+                    let s = pad.sample.read(c, voice.position) * gain * pad_gain;
+                    if let Some(ch) = out.get_mut(pair * 2 + c) {
+                        ch[frame] += s;
+                    }
+                }
+                voice.position += self.rates[voice.pad];
+                if voice.position >= pad.sample.len() as f64 {
+                    voice.active = false;
+                }
+            }
+        }
+    }
+
+    fn audio_output_count(&self) -> usize {
+        self.outputs * 2
+    }
+
+    fn set_routed_outputs(&mut self, channels: usize) {
+        self.routed_pairs = (channels / 2).max(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin::builtin::instrument::{peak, render_outputs as render};
+
+    const SR: f32 = 48000.0;
+
+    #[test]
+    fn default_kit_plays_mapped_notes() {
+        let mut drums = DrumMachine::new(SR);
+        assert_eq!(drums.audio_output_count(), 2);
+        assert_eq!(drums.parameters().len(), 1 + 7 * PAD_PARAMS);
+        assert_eq!(drums.parameters()[1].name, "Kick Volume");
+        for note in [36, 38, 39, 42, 45, 46, 50] {
+            let out = render(&mut drums, &[(0, [0x99, note, 127])], 2048);
+            assert!(out[0].iter().all(|s| s.is_finite()));
+            assert!(peak(&out[0]) > 0.01, "note {note} is silent");
+            render(&mut drums, &[(0, [0xB9, 120, 0])], 1024);
+        }
+        let out = render(&mut drums, &[(0, [0x99, 60, 127])], 512);
+        assert_eq!(peak(&out[0]), 0.0);
+    }
+
+    #[test]
+    fn choke_group_cuts_open_hat() {
+        let mut drums = DrumMachine::new(SR);
+        render(&mut drums, &[(0, [0x99, 46, 127])], 256);
+        render(&mut drums, &[(0, [0x99, 42, 127])], 512);
+        let playing: Vec<_> = drums
+            .instrument
+            .voices
+            .iter()
+            .filter(|v| v.active)
+            .map(|v| v.pad)
+            .collect();
+        assert_eq!(playing, [5]);
+    }
+
+    /// A kit with a kick on the main pair and a pitched-up "Perc" on pair 1.
+    fn two_pair_kit(dir: &Path) -> PathBuf {
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: SR as u32,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(dir.join("hit.wav"), spec).unwrap();
+        for _ in 0..1000 {
+            writer.write_sample(16384_i16).unwrap();
+        }
+        writer.finalize().unwrap();
+        let kit = dir.join("kit.toml");
+        std::fs::write(
+            &kit,
+            "[[pad]]\nnote = \"C2\"\nsample = \"hit.wav\"\n\n\
+             [[pad]]\nname = \"Perc\"\nnote = 40\nsample = \"hit.wav\"\noutput = 1\npitch = 12.0\n",
+        )
+        .unwrap();
+        kit
+    }
+
+    #[test]
+    fn kit_file_routes_pads_to_output_pairs() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut drums = DrumMachine::load(&two_pair_kit(dir.path()), SR).unwrap();
+        assert_eq!(drums.audio_output_count(), 4);
+        assert_eq!(drums.parameters()[1].name, "hit Volume");
+        assert_eq!(drums.parameters()[4].name, "Perc Volume");
+
+        let out = render(&mut drums, &[(0, [0x90, 40, 127])], 256);
+        assert_eq!(peak(&out[0]), 0.0);
+        assert!(peak(&out[2]) > 0.1);
+        // An octave up, the sample ends twice as fast.
+        render(&mut drums, &[], 256);
+        assert!(drums.instrument.voices.iter().all(|v| !v.active));
+
+        let out = render(&mut drums, &[(0, [0x90, 36, 127])], 256);
+        assert!(peak(&out[0]) > 0.1);
+        assert_eq!(peak(&out[2]), 0.0);
+    }
+
+    /// Output of a graph with `channels` channels playing `drums` for one
+    /// block, with `midi`.
+    fn render_in_graph(kit: &Path, channels: usize, midi: &[(u64, [u8; 3])]) -> Vec<Vec<f32>> {
+        use crate::plugin::chain::{AudioGraph, GraphCommand};
+
+        use crate::plugin::Preset;
+        use crate::plugin::library::LibraryPresets;
+
+        // Loaded the way sessions load it, wrapped as it is once the kit has
+        // library presets.
+        let source = format!("builtin:drums:{}", kit.display());
+        let drums = crate::plugin::load(&source, SR, 256, &Default::default()).unwrap();
+        let library = vec![Preset {
+            name: "Saved".into(),
+            id: "tang:saved".into(),
+        }];
+        let drums = LibraryPresets::with_presets(drums, library);
+        let (cmd_tx, cmd_rx) = crossbeam_channel::bounded(8);
+        let (return_tx, _return_rx) = crossbeam_channel::bounded(8);
+        let mut graph = AudioGraph::new(channels, cmd_rx, return_tx);
+        let inst_buf = (0..drums.audio_output_count())
+            .map(|_| Vec::new())
+            .collect();
+        for cmd in [
+            GraphCommand::AddKeyboard,
+            GraphCommand::AddSplit { kb: 0, range: None },
+            GraphCommand::SwapInstrument {
+                kb: 0,
+                split: 0,
+                instrument: drums,
+                inst_buf,
+                remapper: None,
+            },
+        ] {
+            cmd_tx.send(cmd).unwrap();
+        }
+        let mut out = vec![vec![0.0; 256]; channels];
+        graph.process(midi, &mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn unrouted_pairs_play_in_the_main_mix() {
+        let dir = tempfile::tempdir().unwrap();
+        let kit = two_pair_kit(dir.path());
+        let perc = [(0, [0x90, 40, 127])];
+
+        // A stereo graph has nowhere to send pair 1, so Perc joins the mix.
+        let out = render_in_graph(&kit, 2, &perc);
+        assert!(peak(&out[0]) > 0.1);
+        assert!(peak(&out[1]) > 0.1);
+
+        // With four channels, pair 1 keeps its own outputs.
+        let out = render_in_graph(&kit, 4, &perc);
+        assert_eq!(peak(&out[0]), 0.0);
+        assert!(peak(&out[2]) > 0.1);
+    }
+}
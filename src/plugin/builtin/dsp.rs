@@ -0,0 +1,656 @@
+//! Building blocks shared by the built-in plugins: parameter tables with
+//! modulation offsets and factory presets, band-limited oscillators,
+//! envelopes and filters.
+
+use std::borrow::Cow;
+
+use crate::plugin::{ParameterInfo, Preset};
+
+// ---------------------------------------------------------------------------
+// Parameters and presets
+// ---------------------------------------------------------------------------
+
+/// Description of one parameter. Parameter indices are positions in the
+/// plugin's spec table.
+#[derive(Clone)]
+pub struct ParamSpec {
+    pub name: Cow<'static, str>,
+    pub min: f32,
+    pub max: f32,
+    pub default: f32,
+    /// Whether the parameter selects between discrete options (waveforms,
+    /// modes). Stepped parameters are rounded and not modulatable.
+    pub stepped: bool,
+}
+
+impl ParamSpec {
+    pub const fn new(name: &'static str, min: f32, max: f32, default: f32) -> Self {
+        Self {
+            name: Cow::Borrowed(name),
+            min,
+            max,
+            default,
+            stepped: false,
+        }
+    }
+
+    pub const fn stepped(name: &'static str, min: f32, max: f32, default: f32) -> Self {
+        Self {
+            name: Cow::Borrowed(name),
+            min,
+            max,
+            default,
+            stepped: true,
+        }
+    }
+
+    /// A continuous parameter with a name built at runtime (e.g. per pad).
+    pub fn named(name: String, min: f32, max: f32, default: f32) -> Self {
+        Self {
+            name: Cow::Owned(name),
+            min,
+            max,
+            default,
+            stepped: false,
+        }
+    }
+}
+
+/// A named set of parameter values; parameters not listed keep their
+/// defaults.
+pub struct FactoryPreset {
+    pub name: &'static str,
+    pub values: &'static [(&'static str, f32)],
+}
+
+/// Current parameter values plus per-block modulation offsets.
+pub struct Params {
+    specs: Vec<ParamSpec>,
+    values: Vec<f32>,
+    offsets: Vec<f32>,
+}
+
+impl Params {
+    pub fn new(specs: &[ParamSpec]) -> Self {
+        Self {
+            specs: specs.to_vec(),
+            values: specs.iter().map(|s| s.default).collect(),
+            offsets: vec![0.0; specs.len()],
+        }
+    }
+
+    /// Effective value: base value plus modulation offset, clamped to range.
+    pub fn get(&self, index: usize) -> f32 {
+        let spec = &self.specs[index];
+        let v = (self.values[index] + self.offsets[index]).clamp(spec.min, spec.max);
+        if spec.stepped { v.round() } else { v }
+    }
+
+    /// Effective value of a stepped parameter as an index.
+    pub fn choice(&self, index: usize) -> usize {
+        self.get(index).max(0.0) as usize
+    }
+
+    /// Base value (without modulation).
+    pub fn base(&self, index: u32) -> Option<f32> {
+        self.values.get(index as usize).copied()
+    }
+
+    pub fn set(&mut self, index: u32, value: f32) -> anyhow::Result<()> {
+        let Some(spec) = self.specs.get(index as usize) else {
+            anyhow::bail!("no parameter with index {index}");
+        };
+        let value = value.clamp(spec.min, spec.max);
+        self.values[index as usize] = if spec.stepped { value.round() } else { value };
+        Ok(())
+    }
+
+    pub fn supports_modulation(&self, index: u32) -> bool {
+        self.specs.get(index as usize).is_some_and(|s| !s.stepped)
+    }
+
+    pub fn set_offset(&mut self, index: u32, offset: f32) -> anyhow::Result<()> {
+        if !self.supports_modulation(index) {
+            anyhow::bail!("parameter {index} does not support modulation");
+        }
+        self.offsets[index as usize] = offset;
+        Ok(())
+    }
+
+    /// Drop modulation offsets; call after each block, since modulators
+    /// re-send them every block.
+    pub fn clear_offsets(&mut self) {
+        self.offsets.fill(0.0);
+    }
+
+    pub fn infos(&self) -> Vec<ParameterInfo> {
+        self.specs
+            .iter()
+            .enumerate()
+            .map(|(i, s)| ParameterInfo {
+                index: i as u32,
+                name: s.name.to_string(),
+                min: s.min,
+                max: s.max,
+                default: s.default,
+            })
+            .collect()
+    }
+
+    /// Reset to defaults, then apply `preset`.
+    pub fn apply(&mut self, preset: &FactoryPreset) {
+        for (value, spec) in self.values.iter_mut().zip(&self.specs) {
+            *value = spec.default;
+        }
+        for &(name, value) in preset.values {
+            if let Some(i) = self.specs.iter().position(|s| s.name == name) {
+                let _ = self.set(i as u32, value);
+            }
+        }
+    }
+}
+
+pub fn preset_list(presets: &[FactoryPreset]) -> Vec<Preset> {
+    presets
+        .iter()
+        .map(|p| Preset {
+            name: p.name.to_string(),
+            id: p.name.to_string(),
+        })
+        .collect()
+}
+
+pub fn find_preset<'a>(
+    presets: &'a [FactoryPreset],
+    id: &str,
+) -> anyhow::Result<&'a FactoryPreset> {
+    presets
+        .iter()
+        .find(|p| p.name == id)
+        .ok_or_else(|| anyhow::anyhow!("no preset with id {id:?}"))
+}
+
+// ---------------------------------------------------------------------------
+// Oscillators
+// ---------------------------------------------------------------------------
+
+pub fn note_to_freq(note: f32) -> f32 {
+    440.0 * 2.0_f32.powf((note - 69.0) / 12.0)
+}
+
+/// Polynomial band-limited step, for hard edges (saw, square).
+fn poly_blep(t: f32, dt: f32) -> f32 {
+    if t < dt {
+        let t = t / dt;
+        2.0 * t - t * t - 1.0
+    } else if t > 1.0 - dt {
+        let t = (t - 1.0) / dt;
+        t * t + 2.0 * t + 1.0
+    } else {
+        0.0
+    }
+}
+
+/// Polynomial band-limited ramp, for corners (triangle).
+fn poly_blamp(t: f32, dt: f32) -> f32 {
+    if t < dt {
+        let t = t / dt - 1.0;
+        -t * t * t / 3.0
+    } else if t > 1.0 - dt {
+        let t = (t - 1.0) / dt + 1.0;
+        t * t * t / 3.0
+    } else {
+        0.0
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Waveform {
+    Sine,
+    Saw,
+    Square,
+    Triangle,
+    Noise,
+}
+
+impl Waveform {
+    /// The subtractive synth's waveform order (no sine).
+    pub fn from_index(i: usize) -> Self {
+        match i {
+            0 => Self::Saw,
+            1 => Self::Square,
+            2 => Self::Triangle,
+            _ => Self::Noise,
+        }
+    }
+}
+
+/// xorshift32 white noise in -1..1.
+#[derive(Clone)]
+pub struct Noise(u32);
+
+impl Noise {
+    pub fn new(seed: u32) -> Self {
+        Self(seed.max(1))
+    }
+
+    pub fn next(&mut self) -> f32 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.0 = x;
+        (x as f32 / u32::MAX as f32) * 2.0 - 1.0
+    }
+}
+
+/// Phase-accumulating oscillator with PolyBLEP/PolyBLAMP anti-aliasing.
+#[derive(Clone, Default)]
+pub struct Oscillator {
+    phase: f32,
+}
+
+impl Oscillator {
+    pub fn reset(&mut self) {
+        self.phase = 0.0;
+    }
+
+    /// Next sample for frequency `freq / sample_rate` = `dt` (cycles per
+    /// sample).
+    pub fn next(&mut self, wave: Waveform, dt: f32, noise: &mut Noise) -> f32 {
+        let t = self.phase;
+        let dt = dt.clamp(0.0, 0.5);
+        let out = match wave {
+            Waveform::Sine => (t * std::f32::consts::TAU).sin(),
+            Waveform::Saw => 2.0 * t - 1.0 - poly_blep(t, dt),
+            Waveform::Square => {
+                let naive = if t < 0.5 { 1.0 } else { -1.0 };
+                naive + poly_blep(t, dt) - poly_blep((t + 0.5).fract(), dt)
+            }
+            Waveform::Triangle => {
+                let naive = 1.0 - 4.0 * (t - 0.5).abs();
+                naive + 4.0 * dt * (poly_blamp(t, dt) - poly_blamp((t + 0.5).fract(), dt))
+            }
+            Waveform::Noise => noise.next(),
+        };
+        self.phase += dt;
+        if self.phase >= 1.0 {
+            self.phase -= 1.0;
+        }
+        out
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Envelope
+// ---------------------------------------------------------------------------
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Stage {
+    Idle,
+    Attack,
+    Decay,
+    Release,
+}
+
+/// ADSR envelope: linear attack, exponential decay and release. Times are
+/// roughly how long each stage takes to settle, in seconds.
+#[derive(Clone)]
+pub struct Adsr {
+    stage: Stage,
+    level: f32,
+}
+
+impl Default for Adsr {
+    fn default() -> Self {
+        Self {
+            stage: Stage::Idle,
+            level: 0.0,
+        }
+    }
+}
+
+/// Per-sample coefficient for an exponential segment reaching ~99% of its
+/// target after `seconds`.
+fn exp_coef(seconds: f32, sample_rate: f32) -> f32 {
+    1.0 - (-4.6 / (seconds.max(1e-4) * sample_rate)).exp()
+}
+
+impl Adsr {
+    /// Start (or restart, from the current level) the attack.
+    pub fn gate_on(&mut self) {
+        self.stage = Stage::Attack;
+    }
+
+    pub fn gate_off(&mut self) {
+        if self.stage != Stage::Idle {
+            self.stage = Stage::Release;
+        }
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.stage == Stage::Idle
+    }
+
+    pub fn is_released(&self) -> bool {
+        matches!(self.stage, Stage::Release | Stage::Idle)
+    }
+
+    pub fn level(&self) -> f32 {
+        self.level
+    }
+
+    pub fn next(&mut self, a: f32, d: f32, s: f32, r: f32, sample_rate: f32) -> f32 {
+        match self.stage {
+            Stage::Idle => {}
+            Stage::Attack => {
+                self.level += 1.0 / (a.max(1e-4) * sample_rate);
+                if self.level >= 1.0 {
+                    self.level = 1.0;
+                    self.stage = Stage::Decay;
+                }
+            }
+            Stage::Decay => self.level += (s - self.level) * exp_coef(d, sample_rate),
+            Stage::Release => {
+                self.level -= self.level * exp_coef(r, sample_rate);
+                if self.level < 1e-4 {
+                    self.level = 0.0;
+                    self.stage = Stage::Idle;
+                }
+            }
+        }
+        self.level
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Filter
+// ---------------------------------------------------------------------------
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FilterMode {
+    Lowpass,
+    Bandpass,
+    Highpass,
+}
+
+impl FilterMode {
+    pub fn from_index(i: usize) -> Self {
+        match i {
+            0 => Self::Lowpass,
+            1 => Self::Bandpass,
+            _ => Self::Highpass,
+        }
+    }
+}
+
+/// Zero values too small to hear, so decaying filter state never reaches
+/// the (slow) denormal range.
+pub(super) fn flush_denormal(x: f32) -> f32 {
+    if x.abs() < 1e-15 { 0.0 } else { x }
+}
+
+/// Zero-delay-feedback state-variable filter (Zavalishin's TPT SVF); stays
+/// stable under fast cutoff modulation.
+#[derive(Clone, Default)]
+pub struct Svf {
+    ic1: f32,
+    ic2: f32,
+}
+
+impl Svf {
+    pub fn reset(&mut self) {
+        self.ic1 = 0.0;
+        self.ic2 = 0.0;
+    }
+
+    /// Filter one sample. `resonance` is 0..1 (self-oscillation is avoided).
+    pub fn process(
+        &mut self,
+        input: f32,
+        cutoff: f32,
+        resonance: f32,
+        mode: FilterMode,
+        sample_rate: f32,
+    ) -> f32 {
+        let cutoff = cutoff.clamp(10.0, sample_rate * 0.49);
+        let g = (std::f32::consts::PI * cutoff / sample_rate).tan();
+        let k = 2.0 - 1.96 * resonance.clamp(0.0, 1.0);
+        let a1 = 1.0 / (1.0 + g * (g + k));
+        let a2 = g * a1;
+        let a3 = g * a2;
+        let v3 = input - self.ic2;
+        let v1 = a1 * self.ic1 + a2 * v3;
+        let v2 = self.ic2 + a2 * self.ic1 + a3 * v3;
+        self.ic1 = flush_denormal(2.0 * v1 - self.ic1);
+        self.ic2 = flush_denormal(2.0 * v2 - self.ic2);
+        match mode {
+            FilterMode::Lowpass => v2,
+            FilterMode::Bandpass => v1,
+            FilterMode::Highpass => input - k * v1 - v2,
+        }
+    }
+}
+
+/// One-pole lowpass, for tone controls and smoothing.
+#[derive(Clone, Default)]
+pub struct OnePole {
+    z: f32,
+}
+
+impl OnePole {
+    pub fn process(&mut self, input: f32, cutoff: f32, sample_rate: f32) -> f32 {
+        let a = 1.0 - (-std::f32::consts::TAU * cutoff / sample_rate).exp();
+        self.z = flush_denormal(self.z + a * (input - self.z));
+        self.z
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Delay line
+// ---------------------------------------------------------------------------
+
+/// Circular buffer read at fractional delays with linear interpolation.
+#[derive(Clone)]
+pub struct DelayLine {
+    buffer: Vec<f32>,
+    write: usize,
+}
+
+impl DelayLine {
+    pub fn new(max_delay: usize) -> Self {
+        Self {
+            buffer: vec![0.0; max_delay + 2],
+            write: 0,
+        }
+    }
+
+    /// The value written `delay` samples ago (at least 1, at most the
+    /// maximum delay).
+    pub fn read(&self, delay: f32) -> f32 {
+        let len = self.buffer.len();
+        let delay = delay.clamp(1.0, (len - 2) as f32);
+        let whole = delay as usize;
+        let frac = delay - whole as f32;
+        let a = self.buffer[(self.write + len - whole) % len];
+        let b = self.buffer[(self.write + len - whole - 1) % len];
+        a + (b - a) * frac
+    }
+
+    pub fn write(&mut self, value: f32) {
+        self.buffer[self.write] = value;
+        self.write = (self.write + 1) % self.buffer.len();
+    }
+}
+
+// ---------------------------------------------------------------------------
+// FFT
+// ---------------------------------------------------------------------------
+
+/// In-place iterative radix-2 FFT. `re` and `im` must have the same
+/// power-of-two length.
+#[cfg(test)]
+pub fn fft(re: &mut [f32], im: &mut [f32]) {
+    debug_assert!(re.len().is_power_of_two() && im.len() == re.len());
+    bit_reverse(re, im);
+    let mut len = 2;
+    while len <= re.len() {
+        fft_pass(re, im, len);
+        len <<= 1;
+    }
+}
+
+/// The bit-reversal permutation that starts [`fft`]. With [`fft_pass`]
+/// this lets a transform be spread over several audio blocks.
+pub fn bit_reverse(re: &mut [f32], im: &mut [f32]) {
+    let n = re.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+}
+
+/// One butterfly pass of [`fft`], combining sub-transforms of `len / 2`
+/// into transforms of `len`. Passes run for `len` = 2, 4, ... up to the
+/// full length.
+pub fn fft_pass(re: &mut [f32], im: &mut [f32], len: usize) {
+    let angle = -std::f32::consts::TAU / len as f32;
+    for start in (0..re.len()).step_by(len) {
+        for k in 0..len / 2 {
+            let (w_im, w_re) = (angle * k as f32).sin_cos();
+            let a = start + k;
+            let b = a + len / 2;
+            let t_re = re[b] * w_re - im[b] * w_im;
+            let t_im = re[b] * w_im + im[b] * w_re;
+            re[b] = re[a] - t_re;
+            im[b] = im[a] - t_im;
+            re[a] += t_re;
+            im[a] += t_im;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn oscillators_stay_in_range() {
+        let mut noise = Noise::new(1);
+        for wave in [
+            Waveform::Sine,
+            Waveform::Saw,
+            Waveform::Square,
+            Waveform::Triangle,
+            Waveform::Noise,
+        ] {
+            let mut osc = Oscillator::default();
+            for _ in 0..48000 {
+                let s = osc.next(wave, 1234.5 / 48000.0, &mut noise);
+                assert!(s.abs() <= 1.01, "{wave:?} produced {s}");
+            }
+        }
+    }
+
+    #[test]
+    fn adsr_runs_through_stages() {
+        let mut env = Adsr::default();
+        let sr = 1000.0;
+        env.gate_on();
+        for _ in 0..10 {
+            env.next(0.01, 0.1, 0.5, 0.1, sr);
+        }
+        assert_eq!(env.level(), 1.0);
+        for _ in 0..200 {
+            env.next(0.01, 0.1, 0.5, 0.1, sr);
+        }
+        assert!((env.level() - 0.5).abs() < 0.01);
+        env.gate_off();
+        for _ in 0..500 {
+            env.next(0.01, 0.1, 0.5, 0.1, sr);
+        }
+        assert!(env.is_idle());
+    }
+
+    #[test]
+    fn lowpass_attenuates_above_cutoff() {
+        let sr = 48000.0;
+        let rms = |freq: f32| {
+            let mut f = Svf::default();
+            let mut sum = 0.0;
+            for i in 0..4800 {
+                let x = (i as f32 * freq / sr * std::f32::consts::TAU).sin();
+                let y = f.process(x, 500.0, 0.0, FilterMode::Lowpass, sr);
+                if i >= 2400 {
+                    sum += y * y;
+                }
+            }
+            (sum / 2400.0).sqrt()
+        };
+        assert!(rms(100.0) > 0.6);
+        assert!(rms(8000.0) < 0.01);
+    }
+
+    #[test]
+    fn delay_line_reads_fractional_delays() {
+        let mut line = DelayLine::new(8);
+        for x in [1.0, 2.0, 3.0, 4.0] {
+            line.write(x);
+        }
+        assert_eq!(line.read(1.0), 4.0);
+        assert_eq!(line.read(3.0), 2.0);
+        assert_eq!(line.read(2.5), 2.5);
+        // Clamped to the buffer length.
+        assert_eq!(line.read(100.0), line.read(8.0));
+    }
+
+    #[test]
+    fn fft_finds_sine_bin() {
+        let n = 256;
+        let mut re: Vec<f32> = (0..n)
+            .map(|i| (i as f32 * 10.0 / n as f32 * std::f32::consts::TAU).cos())
+            .collect();
+        let mut im = vec![0.0; n];
+        fft(&mut re, &mut im);
+        let mags: Vec<f32> = re.iter().zip(&im).map(|(r, i)| r.hypot(*i)).collect();
+        // A cosine of amplitude 1 puts n/2 in its bin and its mirror.
+        assert!((mags[10] - n as f32 / 2.0).abs() < 1e-2);
+        assert!((mags[n - 10] - n as f32 / 2.0).abs() < 1e-2);
+        assert!(
+            mags[..n / 2]
+                .iter()
+                .enumerate()
+                .all(|(k, m)| k == 10 || *m < 1e-2)
+        );
+    }
+
+    #[test]
+    fn params_clamp_round_and_offset() {
+        static SPECS: [ParamSpec; 2] = [
+            ParamSpec::new("cutoff", 20.0, 20000.0, 1000.0),
+            ParamSpec::stepped("wave", 0.0, 3.0, 0.0),
+        ];
+        let mut p = Params::new(&SPECS);
+        p.set(0, 50000.0).unwrap();
+        assert_eq!(p.get(0), 20000.0);
+        p.set(1, 1.6).unwrap();
+        assert_eq!(p.choice(1), 2);
+        p.set(0, 1000.0).unwrap();
+        p.set_offset(0, 500.0).unwrap();
+        assert_eq!(p.get(0), 1500.0);
+        assert_eq!(p.base(0), Some(1000.0));
+        assert!(p.set_offset(1, 1.0).is_err());
+        p.clear_offsets();
+        assert_eq!(p.get(0), 1000.0);
+        assert!(p.set(2, 0.0).is_err());
+    }
+}
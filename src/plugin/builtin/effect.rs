@@ -0,0 +1,189 @@
+//! Shared plumbing for the built-in stereo effects: an [`Effect`] only
+//! processes audio, and [`EffectPlugin`] adapts it to [`Plugin`].
+
+use std::path::Path;
+
+use super::dsp::{ParamSpec, Params};
+use super::{Builtin, FileArg};
+use crate::plugin::{MeterInfo, ParameterInfo, Plugin, Preset};
+
+pub trait Effect: Send + 'static {
+    const NAME: &'static str;
+    const PARAMS: &'static [ParamSpec];
+
+    fn new(sample_rate: f32) -> Self;
+
+    /// Process one block in place. `params` includes this block's
+    /// modulation.
+    fn process(&mut self, params: &Params, left: &mut [f32], right: &mut [f32]);
+
+    fn set_tempo(&mut self, _bpm: f32) {}
+
+    fn meters(&self) -> Vec<MeterInfo> {
+        Vec::new()
+    }
+}
+
+pub struct EffectPlugin<E> {
+    effect: E,
+    params: Params,
+    sample_rate: f32,
+}
+
+/// Create a built-in effect plugin.
+pub fn load<E: Effect>(sample_rate: f32) -> Box<dyn Plugin> {
+    Box::new(EffectPlugin {
+        effect: E::new(sample_rate),
+        params: Params::new(E::PARAMS),
+        sample_rate,
+    })
+}
+
+/// Registry entry for a built-in effect, loaded as `builtin:<name>`.
+pub const fn builtin<E: Effect>(name: &'static str) -> Builtin {
+    Builtin {
+        name,
+        display_name: E::NAME,
+        is_instrument: false,
+        file: FileArg::None,
+        params: params::<E>,
+        presets: Vec::new,
+        create: create::<E>,
+    }
+}
+
+fn params<E: Effect>() -> Vec<ParameterInfo> {
+    Params::new(E::PARAMS).infos()
+}
+
+fn create<E: Effect>(sample_rate: f32, _file: Option<&Path>) -> anyhow::Result<Box<dyn Plugin>> {
+    Ok(load::<E>(sample_rate))
+}
+
+impl<E: Effect> Plugin for EffectPlugin<E> {
+    fn name(&self) -> &str {
+        E::NAME
+    }
+
+    fn is_instrument(&self) -> bool {
+        false
+    }
+
+    fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+
+    fn audio_output_count(&self) -> usize {
+        2
+    }
+
+    fn audio_input_count(&self) -> usize {
+        2
+    }
+
+    fn process(
+        &mut self,
+        _midi_events: &[(u64, [u8; 3])],
+        audio_in: &[&[f32]],
+        audio_out: &mut [&mut [f32]],
+    ) -> anyhow::Result<()> {
+        let [left, right, ..] = audio_out else {
+            anyhow::bail!("{} needs two output channels", E::NAME);
+        };
+        // Mono input feeds both channels.
+        match audio_in {
+            [] => {
+                left.fill(0.0);
+                right.fill(0.0);
+            }
+            [mono] => {
+                left.copy_from_slice(mono);
+                right.copy_from_slice(mono);
+            }
+            [l, r, ..] => {
+                left.copy_from_slice(l);
+                right.copy_from_slice(r);
+            }
+        }
+        self.effect.process(&self.params, left, right);
+        self.params.clear_offsets();
+        Ok(())
+    }
+
+    fn parameters(&self) -> Vec<ParameterInfo> {
+        self.params.infos()
+    }
+
+    fn get_parameter(&mut self, index: u32) -> Option<f32> {
+        self.params.base(index)
+    }
+
+    fn set_parameter(&mut self, index: u32, value: f32) -> anyhow::Result<()> {
+        self.params.set(index, value)
+    }
+
+    fn presets(&self) -> Vec<Preset> {
+        Vec::new()
+    }
+
+    fn load_preset(&mut self, id: &str) -> anyhow::Result<()> {
+        anyhow::bail!("no preset with id {id:?}")
+    }
+
+    fn meters(&self) -> Vec<MeterInfo> {
+        self.effect.meters()
+    }
+
+    fn supports_modulation(&self, index: u32) -> bool {
+        self.params.supports_modulation(index)
+    }
+
+    fn set_modulation(&mut self, index: u32, offset: f32) -> anyhow::Result<()> {
+        self.params.set_offset(index, offset)
+    }
+
+    fn set_tempo(&mut self, bpm: f32) {
+        self.effect.set_tempo(bpm);
+    }
+}
+
+/// Mix dry and wet signals.
+pub fn mix(dry: f32, wet: f32, amount: f32) -> f32 {
+    dry + (wet - dry) * amount
+}
+
+/// Decibels to linear gain.
+pub fn db_to_gain(db: f32) -> f32 {
+    10.0_f32.powf(db / 20.0)
+}
+
+/// Linear gain to decibels, floored at -120 dB for silence.
+pub fn gain_to_db(gain: f32) -> f32 {
+    20.0 * gain.max(1e-6).log10()
+}
+
+/// Run `plugin` over a stereo input and return its output.
+#[cfg(test)]
+pub fn run(plugin: &mut dyn Plugin, left: &[f32], right: &[f32]) -> (Vec<f32>, Vec<f32>) {
+    let mut out_l = vec![0.0; left.len()];
+    let mut out_r = vec![0.0; right.len()];
+    plugin
+        .process(&[], &[left, right], &mut [&mut out_l, &mut out_r])
+        .unwrap();
+    (out_l, out_r)
+}
+
+/// RMS of `samples` after skipping the first `skip`.
+#[cfg(test)]
+pub fn rms(samples: &[f32], skip: usize) -> f32 {
+    let tail = &samples[skip..];
+    (tail.iter().map(|s| s * s).sum::<f32>() / tail.len() as f32).sqrt()
+}
+
+/// A sine at `freq` Hz.
+#[cfg(test)]
+pub fn sine(freq: f32, amplitude: f32, sample_rate: f32, frames: usize) -> Vec<f32> {
+    (0..frames)
+        .map(|i| amplitude * (i as f32 * freq / sample_rate * std::f32::consts::TAU).sin())
+        .collect()
+}
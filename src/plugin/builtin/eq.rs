@@ -0,0 +1,222 @@
+//! `builtin:eq`: a four-band parametric EQ (low shelf, two peaking bands,
+//! high shelf) using the RBJ cookbook biquads.
+
+use std::f32::consts::PI;
+
+use super::Builtin;
+use super::dsp::{ParamSpec, Params, flush_denormal};
+use super::effect::{self, Effect, db_to_gain};
+
+pub const BUILTIN: Builtin = effect::builtin::<Eq>("eq");
+
+const LOW_FREQ: usize = 0;
+const LOW_GAIN: usize = 1;
+const MID1_FREQ: usize = 2;
+const MID1_GAIN: usize = 3;
+const MID1_Q: usize = 4;
+const MID2_FREQ: usize = 5;
+const MID2_GAIN: usize = 6;
+const MID2_Q: usize = 7;
+const HIGH_FREQ: usize = 8;
+const HIGH_GAIN: usize = 9;
+const OUTPUT: usize = 10;
+
+/// Shelf slope (RBJ `S`); 1 is the steepest without overshoot.
+const SHELF_SLOPE: f32 = 1.0;
+
+#[derive(Clone, Copy)]
+enum Shape {
+    LowShelf,
+    Peak,
+    HighShelf,
+}
+
+/// Normalised biquad coefficients.
+#[derive(Clone, Copy, Default)]
+struct Coefs {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+}
+
+impl Coefs {
+    /// `q` only applies to peaking bands; shelves use [`SHELF_SLOPE`].
+    fn new(shape: Shape, freq: f32, gain_db: f32, q: f32, sample_rate: f32) -> Self {
+        let a = 10.0_f32.powf(gain_db / 40.0);
+        let w0 = 2.0 * PI * freq.min(sample_rate * 0.49) / sample_rate;
+        let (sin, cos) = w0.sin_cos();
+        let (b0, b1, b2, a0, a1, a2) = match shape {
+            Shape::Peak => {
+                let alpha = sin / (2.0 * q);
+                (
+                    1.0 + alpha * a,
+                    -2.0 * cos,
+                    1.0 - alpha * a,
+                    1.0 + alpha / a,
+                    -2.0 * cos,
+                    1.0 - alpha / a,
+                )
+            }
+            Shape::LowShelf | Shape::HighShelf => {
+                let alpha = sin / 2.0 * ((a + 1.0 / a) * (1.0 / SHELF_SLOPE - 1.0) + 2.0).sqrt();
+                let k = 2.0 * a.sqrt() * alpha;
+                // The high shelf is the low shelf with the sign of cos flipped.
+                let s = if matches!(shape, Shape::LowShelf) {
+                    1.0
+                } else {
+                    -1.0
+                };
+                (
+                    a * ((a + 1.0) - s * (a - 1.0) * cos + k),
+                    s * 2.0 * a * ((a - 1.0) - s * (a + 1.0) * cos),
+                    a * ((a + 1.0) - s * (a - 1.0) * cos - k),
+                    (a + 1.0) + s * (a - 1.0) * cos + k,
+                    -s * 2.0 * ((a - 1.0) + s * (a + 1.0) * cos),
+                    (a + 1.0) + s * (a - 1.0) * cos - k,
+                )
+            }
+        };
+        Self {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: a1 / a0,
+            a2: a2 / a0,
+        }
+    }
+}
+
+/// Transposed direct form II state.
+#[derive(Clone, Copy, Default)]
+struct Biquad {
+    z1: f32,
+    z2: f32,
+}
+
+impl Biquad {
+    fn process(&mut self, c: &Coefs, x: f32) -> f32 {
+        let y = c.b0 * x + self.z1;
+        self.z1 = flush_denormal(c.b1 * x - c.a1 * y + self.z2);
+        self.z2 = flush_denormal(c.b2 * x - c.a2 * y);
+        y
+    }
+}
+
+pub struct Eq {
+    sample_rate: f32,
+    /// Filter state per band and channel.
+    state: [[Biquad; 2]; 4],
+}
+
+impl Eq {
+    fn coefs(&self, params: &Params) -> [Coefs; 4] {
+        let sr = self.sample_rate;
+        [
+            Coefs::new(
+                Shape::LowShelf,
+                params.get(LOW_FREQ),
+                params.get(LOW_GAIN),
+                1.0,
+                sr,
+            ),
+            Coefs::new(
+                Shape::Peak,
+                params.get(MID1_FREQ),
+                params.get(MID1_GAIN),
+                params.get(MID1_Q),
+                sr,
+            ),
+            Coefs::new(
+                Shape::Peak,
+                params.get(MID2_FREQ),
+                params.get(MID2_GAIN),
+                params.get(MID2_Q),
+                sr,
+            ),
+            Coefs::new(
+                Shape::HighShelf,
+                params.get(HIGH_FREQ),
+                params.get(HIGH_GAIN),
+                1.0,
+                sr,
+            ),
+        ]
+    }
+}
+
+impl Effect for Eq {
+    const NAME: &'static str = "Parametric EQ";
+    const PARAMS: &'static [ParamSpec] = &[
+        ParamSpec::new("Low Freq", 20.0, 1000.0, 100.0),
+        ParamSpec::new("Low Gain", -18.0, 18.0, 0.0),
+        ParamSpec::new("Mid 1 Freq", 40.0, 10000.0, 500.0),
+        ParamSpec::new("Mid 1 Gain", -18.0, 18.0, 0.0),
+        ParamSpec::new("Mid 1 Q", 0.1, 10.0, 1.0),
+        ParamSpec::new("Mid 2 Freq", 200.0, 18000.0, 2500.0),
+        ParamSpec::new("Mid 2 Gain", -18.0, 18.0, 0.0),
+        ParamSpec::new("Mid 2 Q", 0.1, 10.0, 1.0),
+        ParamSpec::new("High Freq", 1000.0, 20000.0, 8000.0),
+        ParamSpec::new("High Gain", -18.0, 18.0, 0.0),
+        ParamSpec::new("Output", -18.0, 18.0, 0.0),
+    ];
+
+    fn new(sample_rate: f32) -> Self {
+        Self {
+            sample_rate,
+            state: Default::default(),
+        }
+    }
+
+    fn process(&mut self, params: &Params, left: &mut [f32], right: &mut [f32]) {
+        // Coefficients follow parameter changes and modulation per block.
+        let coefs = self.coefs(params);
+        let output = db_to_gain(params.get(OUTPUT));
+        for (c, channel) in [left, right].into_iter().enumerate() {
+            for x in channel.iter_mut() {
+                let mut y = *x;
+                for (band, coefs) in coefs.iter().enumerate() {
+                    y = self.state[band][c].process(coefs, y);
+                }
+                *x = y * output;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin::builtin::effect::{self, rms, run, sine};
+
+    const SR: f32 = 48000.0;
+
+    /// Gain in dB the EQ applies to a sine at `freq`.
+    fn response(eq: &mut dyn crate::plugin::Plugin, freq: f32) -> f32 {
+        let input = sine(freq, 0.25, SR, 24000);
+        let (out, _) = run(eq, &input, &input);
+        20.0 * (rms(&out, 12000) / rms(&input, 12000)).log10()
+    }
+
+    #[test]
+    fn flat_by_default() {
+        let mut eq = effect::load::<Eq>(SR);
+        for freq in [50.0, 500.0, 5000.0, 15000.0] {
+            assert!(response(eq.as_mut(), freq).abs() < 0.1, "{freq} Hz");
+        }
+    }
+
+    #[test]
+    fn bands_boost_and_cut() {
+        let mut eq = effect::load::<Eq>(SR);
+        eq.set_parameter(MID1_GAIN as u32, 12.0).unwrap();
+        eq.set_parameter(HIGH_GAIN as u32, -12.0).unwrap();
+        let mid = response(eq.as_mut(), 500.0);
+        assert!((mid - 12.0).abs() < 0.5, "{mid}");
+        let high = response(eq.as_mut(), 18000.0);
+        assert!((high + 12.0).abs() < 1.0, "{high}");
+        let low = response(eq.as_mut(), 40.0);
+        assert!(low.abs() < 1.0, "{low}");
+    }
+}
@@ -0,0 +1,414 @@
+//! `builtin:fm`: a polyphonic four-operator FM (phase modulation) synth with
+//! a factory preset bank.
+//!
+//! Each operator is a sine with its own frequency ratio, level and ADSR.
+//! The algorithm decides which operators modulate which and which are heard:
+//!
+//! - `0` stack: 4 → 3 → 2 → 1
+//! - `1` branch: 4 → 3 → 1, 2 → 1
+//! - `2` two pairs: 2 → 1, 4 → 3
+//! - `3` one to three: 4 → 1, 2, 3
+//! - `4` additive: all four heard
+//!
+//! Operator 4 can also modulate itself (Feedback).
+
+use std::ops::Range;
+
+use super::Builtin;
+use super::dsp::{Adsr, FactoryPreset, ParamSpec, Params, note_to_freq};
+use super::instrument::{self, Instrument, Synth, Voice as _, Voices};
+
+const MAX_VOICES: usize = 16;
+const OPERATORS: usize = 4;
+/// Per-voice gain, leaving headroom for chords.
+const VOICE_GAIN: f32 = 0.25;
+/// Phase deviation, in cycles, of a modulator at full level.
+const MOD_DEPTH: f32 = 2.0;
+/// Phase deviation, in cycles, of operator 4's feedback at full amount.
+const FEEDBACK_DEPTH: f32 = 0.25;
+
+/// Parameters per operator, starting at `op * OP_PARAMS`.
+const OP_PARAMS: usize = 7;
+const RATIO: usize = 0;
+const DETUNE: usize = 1;
+const LEVEL: usize = 2;
+const ATTACK: usize = 3;
+
+const ALGORITHM: usize = 28;
+const FEEDBACK: usize = 29;
+const VELOCITY: usize = 30;
+const VOICES: usize = 31;
+const VOLUME: usize = 32;
+
+/// Per algorithm: for each operator, a bitmask of the operators modulating
+/// it (always higher-numbered), and a bitmask of the operators heard.
+const ALGORITHMS: [([u8; OPERATORS], u8); 5] = [
+    ([0b0010, 0b0100, 0b1000, 0], 0b0001),
+    ([0b0110, 0, 0b1000, 0], 0b0001),
+    ([0b0010, 0, 0b1000, 0], 0b0101),
+    ([0b1000, 0b1000, 0b1000, 0], 0b0111),
+    ([0, 0, 0, 0], 0b1111),
+];
+
+pub const BUILTIN: Builtin = instrument::builtin::<FmSynth>("fm");
+
+pub const PARAMS: [ParamSpec; 33] = [
+    ParamSpec::new("Op 1 Ratio", 0.5, 16.0, 1.0),
+    ParamSpec::new("Op 1 Detune", -50.0, 50.0, 0.0),
+    ParamSpec::new("Op 1 Level", 0.0, 1.0, 1.0),
+    ParamSpec::new("Op 1 Attack", 0.001, 5.0, 0.005),
+    ParamSpec::new("Op 1 Decay", 0.001, 5.0, 0.3),
+    ParamSpec::new("Op 1 Sustain", 0.0, 1.0, 0.8),
+    ParamSpec::new("Op 1 Release", 0.001, 5.0, 0.2),
+    ParamSpec::new("Op 2 Ratio", 0.5, 16.0, 1.0),
+    ParamSpec::new("Op 2 Detune", -50.0, 50.0, 0.0),
+    ParamSpec::new("Op 2 Level", 0.0, 1.0, 0.0),
+    ParamSpec::new("Op 2 Attack", 0.001, 5.0, 0.005),
+    ParamSpec::new("Op 2 Decay", 0.001, 5.0, 0.3),
+    ParamSpec::new("Op 2 Sustain", 0.0, 1.0, 0.8),
+    ParamSpec::new("Op 2 Release", 0.001, 5.0, 0.2),
+    ParamSpec::new("Op 3 Ratio", 0.5, 16.0, 1.0),
+    ParamSpec::new("Op 3 Detune", -50.0, 50.0, 0.0),
+    ParamSpec::new("Op 3 Level", 0.0, 1.0, 0.0),
+    ParamSpec::new("Op 3 Attack", 0.001, 5.0, 0.005),
+    ParamSpec::new("Op 3 Decay", 0.001, 5.0, 0.3),
+    ParamSpec::new("Op 3 Sustain", 0.0, 1.0, 0.8),
+    ParamSpec::new("Op 3 Release", 0.001, 5.0, 0.2),
+    ParamSpec::new("Op 4 Ratio", 0.5, 16.0, 1.0),
+    ParamSpec::new("Op 4 Detune", -50.0, 50.0, 0.0),
+    ParamSpec::new("Op 4 Level", 0.0, 1.0, 0.0),
+    ParamSpec::new("Op 4 Attack", 0.001, 5.0, 0.005),
+    ParamSpec::new("Op 4 Decay", 0.001, 5.0, 0.3),
+    ParamSpec::new("Op 4 Sustain", 0.0, 1.0, 0.8),
+    ParamSpec::new("Op 4 Release", 0.001, 5.0, 0.2),
+    ParamSpec::stepped("Algorithm", 0.0, (ALGORITHMS.len() - 1) as f32, 0.0),
+    ParamSpec::new("Feedback", 0.0, 1.0, 0.0),
+    ParamSpec::new("Velocity", 0.0, 1.0, 0.5),
+    ParamSpec::stepped("Voices", 1.0, MAX_VOICES as f32, 8.0),
+    ParamSpec::new("Volume", 0.0, 1.0, 0.7),
+];
+
+pub const PRESETS: [FactoryPreset; 6] = [
+    FactoryPreset {
+        name: "Init",
+        values: &[],
+    },
+    FactoryPreset {
+        name: "E. Piano",
+        values: &[
+            ("Algorithm", 2.0),
+            ("Op 1 Decay", 1.5),
+            ("Op 1 Sustain", 0.2),
+            ("Op 1 Release", 0.4),
+            ("Op 2 Ratio", 14.0),
+            ("Op 2 Level", 0.15),
+            ("Op 2 Decay", 0.3),
+            ("Op 2 Sustain", 0.0),
+            ("Op 3 Detune", 3.0),
+            ("Op 3 Level", 0.8),
+            ("Op 3 Decay", 2.0),
+            ("Op 3 Sustain", 0.3),
+            ("Op 3 Release", 0.4),
+            ("Op 4 Level", 0.35),
+            ("Op 4 Decay", 0.8),
+            ("Op 4 Sustain", 0.1),
+            ("Velocity", 0.8),
+        ],
+    },
+    FactoryPreset {
+        name: "Bell",
+        values: &[
+            ("Algorithm", 2.0),
+            ("Op 1 Decay", 4.0),
+            ("Op 1 Sustain", 0.0),
+            ("Op 1 Release", 2.0),
+            ("Op 2 Ratio", 3.5),
+            ("Op 2 Level", 0.5),
+            ("Op 2 Decay", 3.0),
+            ("Op 2 Sustain", 0.0),
+            ("Op 2 Release", 2.0),
+            ("Op 3 Ratio", 2.0),
+            ("Op 3 Level", 0.6),
+            ("Op 3 Decay", 3.0),
+            ("Op 3 Sustain", 0.0),
+            ("Op 3 Release", 2.0),
+            ("Op 4 Ratio", 7.1),
+            ("Op 4 Level", 0.3),
+            ("Op 4 Decay", 2.0),
+            ("Op 4 Sustain", 0.0),
+            ("Op 4 Release", 2.0),
+        ],
+    },
+    FactoryPreset {
+        name: "Bass",
+        values: &[
+            ("Op 1 Decay", 0.5),
+            ("Op 1 Sustain", 0.6),
+            ("Op 1 Release", 0.1),
+            ("Op 2 Level", 0.6),
+            ("Op 2 Decay", 0.25),
+            ("Op 2 Sustain", 0.2),
+            ("Op 2 Release", 0.1),
+            ("Op 3 Ratio", 2.0),
+            ("Op 3 Level", 0.2),
+            ("Op 3 Decay", 0.2),
+            ("Op 3 Sustain", 0.0),
+            ("Op 4 Level", 0.3),
+            ("Feedback", 0.3),
+            ("Voices", 1.0),
+        ],
+    },
+    FactoryPreset {
+        name: "Brass",
+        values: &[
+            ("Algorithm", 1.0),
+            ("Op 1 Attack", 0.05),
+            ("Op 1 Sustain", 0.9),
+            ("Op 2 Level", 0.5),
+            ("Op 2 Attack", 0.08),
+            ("Op 2 Sustain", 0.6),
+            ("Op 3 Level", 0.4),
+            ("Op 3 Attack", 0.1),
+            ("Op 3 Sustain", 0.7),
+            ("Op 4 Level", 0.2),
+            ("Feedback", 0.4),
+            ("Voices", 6.0),
+        ],
+    },
+    FactoryPreset {
+        name: "Organ",
+        values: &[
+            ("Algorithm", 4.0),
+            ("Op 1 Ratio", 0.5),
+            ("Op 1 Level", 0.7),
+            ("Op 1 Sustain", 1.0),
+            ("Op 1 Release", 0.05),
+            ("Op 2 Level", 0.7),
+            ("Op 2 Sustain", 1.0),
+            ("Op 2 Release", 0.05),
+            ("Op 3 Ratio", 2.0),
+            ("Op 3 Level", 0.5),
+            ("Op 3 Sustain", 1.0),
+            ("Op 3 Release", 0.05),
+            ("Op 4 Ratio", 3.0),
+            ("Op 4 Level", 0.4),
+            ("Op 4 Sustain", 1.0),
+            ("Op 4 Release", 0.05),
+            ("Feedback", 0.1),
+            ("Velocity", 0.0),
+        ],
+    },
+];
+
+#[derive(Clone, Default)]
+struct Voice {
+    key: u8,
+    velocity: f32,
+    /// Operators heard with the current algorithm, which decide when the
+    /// voice is idle.
+    carriers: u8,
+    /// Operator phases in cycles.
+    phases: [f32; OPERATORS],
+    envs: [Adsr; OPERATORS],
+    /// Operator 4's last two outputs, averaged for feedback.
+    feedback: [f32; 2],
+}
+
+impl instrument::Voice for Voice {
+    fn key(&self) -> u8 {
+        self.key
+    }
+
+    /// Silent once every heard operator has finished its release.
+    fn is_idle(&self) -> bool {
+        (0..OPERATORS).all(|op| self.carriers & (1 << op) == 0 || self.envs[op].is_idle())
+    }
+
+    fn released_level(&self) -> Option<f32> {
+        self.envs[0].is_released().then(|| self.envs[0].level())
+    }
+
+    fn note_on(&mut self, key: u8, velocity: f32) {
+        if self.is_idle() {
+            self.phases = [0.0; OPERATORS];
+            self.feedback = [0.0; 2];
+        }
+        self.key = key;
+        self.velocity = velocity;
+        for env in &mut self.envs {
+            env.gate_on();
+        }
+    }
+
+    fn note_off(&mut self) {
+        for env in &mut self.envs {
+            env.gate_off();
+        }
+    }
+}
+
+pub struct FmSynth {
+    sample_rate: f32,
+    voices: Voices<Voice>,
+}
+
+fn voice_limit(params: &Params) -> usize {
+    params.choice(VOICES).clamp(1, MAX_VOICES)
+}
+
+fn algorithm(params: &Params) -> ([u8; OPERATORS], u8) {
+    ALGORITHMS[params.choice(ALGORITHM).min(ALGORITHMS.len() - 1)]
+}
+
+impl FmSynth {
+    /// Tell the voices which operators the current algorithm hears.
+    fn update_carriers(&mut self, params: &Params) {
+        let (_, carriers) = algorithm(params);
+        for voice in self.voices.iter_mut() {
+            voice.carriers = carriers;
+        }
+    }
+}
+
+impl Instrument for FmSynth {
+    const NAME: &'static str = "FM Synth";
+    const PRESETS: &'static [FactoryPreset] = &PRESETS;
+
+    fn handle_midi(&mut self, params: &Params, message: [u8; 3]) {
+        self.update_carriers(params);
+        self.voices.handle_midi(message, voice_limit(params));
+    }
+
+    fn render(&mut self, params: &Params, out: &mut [&mut [f32]], frames: Range<usize>) {
+        let sr = self.sample_rate;
+        self.update_carriers(params);
+        self.voices.release_above(voice_limit(params));
+
+        // Parameters are read once per block, including modulation.
+        let p = params;
+        let (modulators, carriers) = algorithm(params);
+        let ops = [0, 1, 2, 3].map(|op| {
+            let base = op * OP_PARAMS;
+            (
+                p.get(base + RATIO) * 2.0_f32.powf(p.get(base + DETUNE) / 1200.0),
+                p.get(base + LEVEL),
+                [0, 1, 2, 3].map(|i| p.get(base + ATTACK + i)),
+            )
+        });
+        let feedback = p.get(FEEDBACK) * FEEDBACK_DEPTH;
+        let velocity_sens = p.get(VELOCITY);
+        let gain = p.get(VOLUME) * VOICE_GAIN / carriers.count_ones() as f32;
+
+        let bend = self.voices.bend();
+        for frame in frames {
+            let mut sample = 0.0;
+            for voice in self.voices.iter_mut() {
+                if voice.is_idle() {
+                    continue;
+                }
+                let base_freq = note_to_freq(voice.key as f32 + bend);
+                let velocity_gain = 1.0 - velocity_sens * (1.0 - voice.velocity);
+
+                // Higher operators only modulate lower ones, so run 4 → 1.
+                let mut out = [0.0; OPERATORS];
+                for op in (0..OPERATORS).rev() {
+                    let (ratio, level, [a, d, s, r]) = ops[op];
+                    let env = voice.envs[op].next(a, d, s, r, sr);
+                    let mut pm: f32 = (0..OPERATORS)
+                        .filter(|m| modulators[op] & (1 << m) != 0)
+                        .map(|m| out[m] * MOD_DEPTH)
+                        .sum();
+                    if op == OPERATORS - 1 {
+                        pm += (voice.feedback[0] + voice.feedback[1]) * 0.5 * feedback;
+                    }
+                    let phase = voice.phases[op] + pm;
+                    out[op] = (phase * std::f32::consts::TAU).sin() * level * env * velocity_gain;
+
+                    voice.phases[op] = (voice.phases[op] + base_freq * ratio / sr).fract();
+                }
+                voice.feedback = [out[OPERATORS - 1], voice.feedback[0]];
+
+                sample += (0..OPERATORS)
+                    .filter(|op| carriers & (1 << op) != 0)
+                    .map(|op| out[op])
+                    .sum::<f32>();
+            }
+
+            let sample = sample * gain;
+            for ch in out.iter_mut() {
+                ch[frame] = sample;
+            }
+        }
+    }
+}
+
+impl Synth for FmSynth {
+    const PARAMS: &'static [ParamSpec] = &PARAMS;
+
+    fn new(sample_rate: f32) -> Self {
+        Self {
+            sample_rate,
+            voices: Voices::new(MAX_VOICES),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin::Plugin;
+    use crate::plugin::builtin::dsp::fft;
+    use crate::plugin::builtin::instrument::render;
+
+    const SR: f32 = 48000.0;
+
+    /// Share of spectral energy above twice the fundamental of A4.
+    fn overtone_share(samples: &[f32]) -> f32 {
+        let n = 4096;
+        let mut re: Vec<f32> = samples[samples.len() - n..]
+            .iter()
+            .enumerate()
+            .map(|(i, s)| s * (0.5 - 0.5 * (std::f32::consts::TAU * i as f32 / n as f32).cos()))
+            .collect();
+        let mut im = vec![0.0; n];
+        fft(&mut re, &mut im);
+        let power: Vec<f32> = re.iter().zip(&im).map(|(r, i)| r * r + i * i).collect();
+        let split = (2.0 * 440.0 / SR * n as f32) as usize;
+        let total: f32 = power[1..n / 2].iter().sum();
+        power[split..n / 2].iter().sum::<f32>() / total
+    }
+
+    #[test]
+    fn every_preset_sounds_and_releases() {
+        instrument::assert_presets_sound::<FmSynth>();
+    }
+
+    #[test]
+    fn modulator_adds_overtones() {
+        let mut synth = instrument::load::<FmSynth>(SR);
+        let sine = render(&mut synth, &[(0, [0x90, 69, 127])], 9600);
+        assert!(overtone_share(&sine) < 0.01);
+
+        let mut synth = instrument::load::<FmSynth>(SR);
+        synth
+            .set_parameter((OP_PARAMS + LEVEL) as u32, 0.5)
+            .unwrap();
+        let fm = render(&mut synth, &[(0, [0x90, 69, 127])], 9600);
+        assert!(overtone_share(&fm) > 0.1);
+    }
+
+    #[test]
+    fn presets_are_listed_and_loaded() {
+        let mut synth = instrument::load::<FmSynth>(SR);
+        let names: Vec<_> = synth.presets().into_iter().map(|p| p.name).collect();
+        assert_eq!(names.len(), PRESETS.len());
+        assert!(names.iter().any(|n| n == "E. Piano"));
+
+        synth.load_preset("Organ").unwrap();
+        assert_eq!(synth.get_parameter(ALGORITHM as u32), Some(4.0));
+        // Loading another preset resets what it doesn't mention.
+        synth.load_preset("Init").unwrap();
+        assert_eq!(synth.get_parameter(ALGORITHM as u32), Some(0.0));
+        assert!(synth.load_preset("Nope").is_err());
+    }
+}
@@ -0,0 +1,432 @@
+//! Shared plumbing for the built-in instruments: an [`Instrument`] renders
+//! audio between MIDI messages, [`InstrumentPlugin`] adapts it to
+//! [`Plugin`], and [`Voices`] allocates polyphonic voices and handles the
+//! MIDI messages the synths have in common.
+
+use std::ops::{Deref, DerefMut, Range};
+use std::path::Path;
+
+use super::dsp::{FactoryPreset, ParamSpec, Params, find_preset, preset_list};
+use super::{Builtin, FileArg};
+use crate::plugin::{ParameterInfo, Plugin, Preset};
+
+/// Pitch bend range in semitones.
+const BEND_RANGE: f32 = 2.0;
+
+pub trait Instrument: Send + 'static {
+    const NAME: &'static str;
+    const PRESETS: &'static [FactoryPreset] = &[];
+
+    /// Display name; defaults to [`Self::NAME`].
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    /// Apply one MIDI message at its frame.
+    fn handle_midi(&mut self, params: &Params, message: [u8; 3]);
+
+    /// Add `frames` of output to `out`, which starts the block silent.
+    /// `params` includes this block's modulation.
+    fn render(&mut self, params: &Params, out: &mut [&mut [f32]], frames: Range<usize>);
+
+    fn audio_output_count(&self) -> usize {
+        2
+    }
+
+    fn set_routed_outputs(&mut self, _channels: usize) {}
+}
+
+/// An instrument with a fixed parameter table that needs nothing but the
+/// sample rate, registered with [`builtin`].
+pub trait Synth: Instrument {
+    const PARAMS: &'static [ParamSpec];
+
+    fn new(sample_rate: f32) -> Self;
+}
+
+pub struct InstrumentPlugin<I> {
+    pub instrument: I,
+    pub params: Params,
+    sample_rate: f32,
+}
+
+impl<I: Instrument> InstrumentPlugin<I> {
+    pub fn new(instrument: I, specs: &[ParamSpec], sample_rate: f32) -> Self {
+        Self {
+            instrument,
+            params: Params::new(specs),
+            sample_rate,
+        }
+    }
+}
+
+/// Create a built-in synth.
+pub fn load<S: Synth>(sample_rate: f32) -> InstrumentPlugin<S> {
+    InstrumentPlugin::new(S::new(sample_rate), S::PARAMS, sample_rate)
+}
+
+/// Registry entry for a built-in synth, loaded as `builtin:<name>`.
+pub const fn builtin<S: Synth>(name: &'static str) -> Builtin {
+    Builtin {
+        name,
+        display_name: S::NAME,
+        is_instrument: true,
+        file: FileArg::None,
+        params: params::<S>,
+        presets: presets::<S>,
+        create: create::<S>,
+    }
+}
+
+fn params<S: Synth>() -> Vec<ParameterInfo> {
+    Params::new(S::PARAMS).infos()
+}
+
+fn presets<S: Synth>() -> Vec<Preset> {
+    preset_list(S::PRESETS)
+}
+
+fn create<S: Synth>(sample_rate: f32, _file: Option<&Path>) -> anyhow::Result<Box<dyn Plugin>> {
+    Ok(Box::new(load::<S>(sample_rate)))
+}
+
+impl<I: Instrument> Plugin for InstrumentPlugin<I> {
+    fn name(&self) -> &str {
+        self.instrument.name()
+    }
+
+    fn is_instrument(&self) -> bool {
+        true
+    }
+
+    fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+
+    fn audio_output_count(&self) -> usize {
+        self.instrument.audio_output_count()
+    }
+
+    fn audio_input_count(&self) -> usize {
+        0
+    }
+
+    fn set_routed_outputs(&mut self, channels: usize) {
+        self.instrument.set_routed_outputs(channels);
+    }
+
+    fn process(
+        &mut self,
+        midi_events: &[(u64, [u8; 3])],
+        _audio_in: &[&[f32]],
+        audio_out: &mut [&mut [f32]],
+    ) -> anyhow::Result<()> {
+        let block_size = audio_out.first().map_or(0, |ch| ch.len());
+        for ch in audio_out.iter_mut() {
+            ch.fill(0.0);
+        }
+
+        // Render up to each message, then apply it.
+        let mut start = 0;
+        for &(frame, message) in midi_events {
+            let frame = (frame as usize).clamp(start, block_size);
+            if frame > start {
+                self.instrument
+                    .render(&self.params, audio_out, start..frame);
+                start = frame;
+            }
+            self.instrument.handle_midi(&self.params, message);
+        }
+        if start < block_size {
+            self.instrument
+                .render(&self.params, audio_out, start..block_size);
+        }
+
+        self.params.clear_offsets();
+        Ok(())
+    }
+
+    fn parameters(&self) -> Vec<ParameterInfo> {
+        self.params.infos()
+    }
+
+    fn get_parameter(&mut self, index: u32) -> Option<f32> {
+        self.params.base(index)
+    }
+
+    fn set_parameter(&mut self, index: u32, value: f32) -> anyhow::Result<()> {
+        self.params.set(index, value)
+    }
+
+    fn presets(&self) -> Vec<Preset> {
+        preset_list(I::PRESETS)
+    }
+
+    fn load_preset(&mut self, id: &str) -> anyhow::Result<()> {
+        let preset = find_preset(I::PRESETS, id)?;
+        self.params.apply(preset);
+        Ok(())
+    }
+
+    fn supports_modulation(&self, index: u32) -> bool {
+        self.params.supports_modulation(index)
+    }
+
+    fn set_modulation(&mut self, index: u32, offset: f32) -> anyhow::Result<()> {
+        self.params.set_offset(index, offset)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Voices
+// ---------------------------------------------------------------------------
+
+/// One synth voice, as far as [`Voices`] needs to know.
+pub trait Voice: Clone + Default {
+    /// The key playing, or last played.
+    fn key(&self) -> u8;
+
+    fn is_idle(&self) -> bool;
+
+    /// Level of a released voice, so the quietest is stolen first; `None`
+    /// while the key is held.
+    fn released_level(&self) -> Option<f32>;
+
+    /// Start (or retrigger) `key`. Idle voices should reset their
+    /// oscillators and filters first.
+    fn note_on(&mut self, key: u8, velocity: f32);
+
+    fn note_off(&mut self);
+}
+
+/// A fixed set of voices plus pitch bend, driven by MIDI.
+pub struct Voices<V> {
+    voices: Vec<V>,
+    /// Note-on order per voice, for stealing the oldest.
+    started: Vec<u64>,
+    note_counter: u64,
+    /// Pitch bend in semitones.
+    bend: f32,
+}
+
+impl<V: Voice> Voices<V> {
+    pub fn new(count: usize) -> Self {
+        Self {
+            voices: vec![V::default(); count],
+            started: vec![0; count],
+            note_counter: 0,
+            bend: 0.0,
+        }
+    }
+
+    pub fn bend(&self) -> f32 {
+        self.bend
+    }
+
+    /// Handle notes, all-sound-off, all-notes-off and pitch bend. New notes
+    /// use the first `limit` voices.
+    pub fn handle_midi(&mut self, [status, data1, data2]: [u8; 3], limit: usize) {
+        match status & 0xF0 {
+            0x90 if data2 > 0 => self.note_on(data1, data2, limit),
+            0x80 | 0x90 => {
+                for voice in self.voices.iter_mut().filter(|v| v.key() == data1) {
+                    voice.note_off();
+                }
+            }
+            0xB0 if data1 == 120 => {
+                // All sound off.
+                self.voices.fill(V::default());
+            }
+            0xB0 if data1 == 123 => {
+                // All notes off.
+                for voice in &mut self.voices {
+                    voice.note_off();
+                }
+            }
+            0xE0 => {
+                let value = ((data2 as i32) << 7 | data1 as i32) - 8192;
+                self.bend = value as f32 / 8192.0 * BEND_RANGE;
+            }
+            _ => {}
+        }
+    }
+
+    /// Release voices above a lowered voice limit, so they fade out.
+    pub fn release_above(&mut self, limit: usize) {
+        for voice in self.voices.iter_mut().skip(limit) {
+            voice.note_off();
+        }
+    }
+
+    fn note_on(&mut self, key: u8, velocity: u8, limit: usize) {
+        let i = self.allocate(key, limit);
+        self.note_counter += 1;
+        self.started[i] = self.note_counter;
+        self.voices[i].note_on(key, velocity as f32 / 127.0);
+    }
+
+    /// Pick the voice for a new note: the voice already playing `key`, else
+    /// a free voice, else the quietest released voice, else the oldest.
+    fn allocate(&self, key: u8, limit: usize) -> usize {
+        let voices = &self.voices[..limit.clamp(1, self.voices.len())];
+        if let Some(i) = voices.iter().position(|v| !v.is_idle() && v.key() == key) {
+            return i;
+        }
+        if let Some(i) = voices.iter().position(|v| v.is_idle()) {
+            return i;
+        }
+        let released = voices
+            .iter()
+            .enumerate()
+            .filter_map(|(i, v)| Some((i, v.released_level()?)))
+            .min_by(|(_, a), (_, b)| a.total_cmp(b));
+        if let Some((i, _)) = released {
+            return i;
+        }
+        (0..voices.len())
+            .min_by_key(|&i| self.started[i])
+            .unwrap_or(0)
+    }
+
+    /// Number of voices currently sounding.
+    #[cfg(test)]
+    pub fn active(&self) -> usize {
+        self.voices.iter().filter(|v| !v.is_idle()).count()
+    }
+}
+
+impl<V> Deref for Voices<V> {
+    type Target = [V];
+
+    fn deref(&self) -> &[V] {
+        &self.voices
+    }
+}
+
+impl<V> DerefMut for Voices<V> {
+    fn deref_mut(&mut self) -> &mut [V] {
+        &mut self.voices
+    }
+}
+
+/// Run `plugin` for `frames` with `midi` and return every output channel.
+#[cfg(test)]
+pub fn render_outputs(
+    plugin: &mut dyn Plugin,
+    midi: &[(u64, [u8; 3])],
+    frames: usize,
+) -> Vec<Vec<f32>> {
+    let mut out = vec![vec![0.0; frames]; plugin.audio_output_count()];
+    let mut refs: Vec<&mut [f32]> = out.iter_mut().map(Vec::as_mut_slice).collect();
+    plugin.process(midi, &[], &mut refs).unwrap();
+    out
+}
+
+/// Render a stereo instrument whose channels match and return one of them.
+#[cfg(test)]
+pub fn render(plugin: &mut dyn Plugin, midi: &[(u64, [u8; 3])], frames: usize) -> Vec<f32> {
+    let mut out = render_outputs(plugin, midi, frames);
+    assert_eq!(out.len(), 2);
+    assert_eq!(out[0], out[1]);
+    out.swap_remove(0)
+}
+
+#[cfg(test)]
+pub fn peak(samples: &[f32]) -> f32 {
+    samples.iter().fold(0.0, |m, s| m.max(s.abs()))
+}
+
+/// Check that every factory preset of `S` sounds without clipping and
+/// releases to silence.
+#[cfg(test)]
+pub fn assert_presets_sound<S: Synth>() {
+    for preset in S::PRESETS.iter().map(|p| p.name) {
+        let mut synth = load::<S>(48000.0);
+        synth.load_preset(preset).unwrap();
+        let on = render(&mut synth, &[(0, [0x90, 60, 100])], 4800);
+        assert!(on.iter().all(|s| s.is_finite()));
+        assert!(peak(&on) > 0.01, "{preset} is silent");
+        assert!(peak(&on) <= 1.0, "{preset} clips");
+
+        render(&mut synth, &[(0, [0x80, 60, 0])], 48000 * 4);
+        let tail = render(&mut synth, &[], 512);
+        assert_eq!(peak(&tail), 0.0, "{preset} did not release");
+    }
+}
+
+/// Check that a soft note plays well below a loud one.
+#[cfg(test)]
+pub fn assert_velocity_scales_level<S: Synth>() {
+    let loud = render(&mut load::<S>(48000.0), &[(0, [0x90, 60, 127])], 4800);
+    let soft = render(&mut load::<S>(48000.0), &[(0, [0x90, 60, 20])], 4800);
+    assert!(peak(&soft) < peak(&loud) * 0.6);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct TestVoice {
+        key: u8,
+        held: bool,
+        level: f32,
+    }
+
+    impl Voice for TestVoice {
+        fn key(&self) -> u8 {
+            self.key
+        }
+        fn is_idle(&self) -> bool {
+            self.level == 0.0
+        }
+        fn released_level(&self) -> Option<f32> {
+            (!self.held).then_some(self.level)
+        }
+        fn note_on(&mut self, key: u8, velocity: f32) {
+            *self = TestVoice {
+                key,
+                held: true,
+                level: velocity,
+            };
+        }
+        fn note_off(&mut self) {
+            self.held = false;
+        }
+    }
+
+    fn keys(voices: &Voices<TestVoice>) -> Vec<u8> {
+        voices.iter().map(|v| v.key).collect()
+    }
+
+    #[test]
+    fn voice_limit_steals_oldest() {
+        let mut voices = Voices::<TestVoice>::new(4);
+        for key in 60..64 {
+            voices.handle_midi([0x90, key, 100], 2);
+        }
+        assert_eq!(voices.active(), 2);
+        assert_eq!(keys(&voices)[..2], [62, 63]);
+
+        // Retriggering a playing key reuses its voice.
+        voices.handle_midi([0x90, 63, 100], 2);
+        assert_eq!(voices.active(), 2);
+        assert_eq!(keys(&voices)[..2], [62, 63]);
+    }
+
+    #[test]
+    fn quietest_released_voice_is_stolen_first() {
+        let mut voices = Voices::<TestVoice>::new(2);
+        voices.handle_midi([0x90, 60, 100], 2);
+        voices.handle_midi([0x90, 61, 100], 2);
+        voices.handle_midi([0x80, 61, 0], 2);
+        // The released voice goes before the older, held one.
+        voices.handle_midi([0x90, 62, 20], 2);
+        assert_eq!(keys(&voices), [60, 62]);
+
+        // Once both are released, the quieter goes before the older.
+        voices.handle_midi([0xB0, 123, 0], 2);
+        voices.handle_midi([0x90, 63, 100], 2);
+        assert_eq!(keys(&voices), [60, 63]);
+    }
+}
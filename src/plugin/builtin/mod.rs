@@ -0,0 +1,165 @@
+//! Plugins built into tang, usable with no external plugins installed.
+
+mod dsp;
+mod effect;
+mod instrument;
+mod sample;
+
+use std::path::Path;
+
+use super::{ParameterInfo, Plugin, PluginInfo, Preset};
+
+/// Declares the built-in plugin modules, each of which exports a `BUILTIN`
+/// registry entry, and collects those entries in order.
+macro_rules! builtins {
+    ($($module:ident),* $(,)?) => {
+        $(mod $module;)*
+
+        /// Every built-in plugin. Adding one means adding its module here.
+        pub static BUILTINS: &[Builtin] = &[$($module::BUILTIN),*];
+    };
+}
+
+builtins!(
+    sine, subsynth, fm, drums, sfz, delay, reverb, chorus, eq, compressor, utility, tuner,
+    analyzer,
+);
+
+/// Whether a built-in takes a file after a second colon
+/// (`builtin:<name>:<file>`). The string is shown in usage messages.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum FileArg {
+    None,
+    Optional(&'static str),
+    Required(&'static str),
+}
+
+/// Creates a built-in instance, given the file after `builtin:<name>:` if any.
+pub type Factory = fn(sample_rate: f32, file: Option<&Path>) -> anyhow::Result<Box<dyn Plugin>>;
+
+/// Registry entry describing a built-in plugin.
+pub struct Builtin {
+    /// Name after `builtin:`.
+    pub name: &'static str,
+    /// Display name, as returned by [`Plugin::name`].
+    pub display_name: &'static str,
+    pub is_instrument: bool,
+    pub file: FileArg,
+    /// Parameters of a freshly created instance (without a file).
+    pub params: fn() -> Vec<ParameterInfo>,
+    /// Factory presets.
+    pub presets: fn() -> Vec<Preset>,
+    pub create: Factory,
+}
+
+impl Builtin {
+    pub fn id(&self) -> String {
+        format!("builtin:{}", self.name)
+    }
+
+    fn info(&self) -> PluginInfo {
+        let path = match self.file {
+            FileArg::Required(file) => format!("(built-in, use {}:<{file}>)", self.id()),
+            _ => "(built-in)".into(),
+        };
+        let parameters = (self.params)();
+        let presets = (self.presets)();
+        // Built-in instruments are stereo out; effects stereo in and out.
+        PluginInfo {
+            name: self.display_name.into(),
+            id: self.id(),
+            is_instrument: self.is_instrument,
+            param_count: parameters.len(),
+            preset_count: presets.len(),
+            path,
+            audio_input_count: if self.is_instrument { 0 } else { 2 },
+            audio_output_count: 2,
+            parameters,
+            presets,
+        }
+    }
+
+    /// How the built-in is written in a session, e.g. `drums[:<kit.toml>]`.
+    fn usage(&self) -> String {
+        match self.file {
+            FileArg::None => self.name.into(),
+            FileArg::Optional(file) => format!("{}[:<{file}>]", self.name),
+            FileArg::Required(file) => format!("{}:<{file}>", self.name),
+        }
+    }
+}
+
+/// Look up a built-in by its name after `builtin:`.
+pub fn find(name: &str) -> Option<&'static Builtin> {
+    BUILTINS.iter().find(|b| b.name == name)
+}
+
+/// Load a built-in plugin by source string (e.g. `"builtin:sine"`). Built-ins
+/// that need a file take it after a second colon (`"builtin:sfz:piano.sfz"`).
+pub fn load(
+    source: &str,
+    sample_rate: f32,
+    _max_block_size: usize,
+) -> anyhow::Result<Box<dyn Plugin>> {
+    let name = source.strip_prefix("builtin:").unwrap_or(source);
+    let (name, file) = match name.split_once(':') {
+        Some((name, file)) => (name, Some(Path::new(file))),
+        None => (name, None),
+    };
+    let Some(builtin) = find(name) else {
+        let available: Vec<String> = BUILTINS.iter().map(Builtin::usage).collect();
+        anyhow::bail!(
+            "Unknown built-in plugin: {source:?}\n\
+             Available built-ins: {}",
+            available.join(", ")
+        );
+    };
+    match (builtin.file, file) {
+        (FileArg::None, Some(_)) => {
+            anyhow::bail!("builtin:{name} does not take a file\nUsage: builtin:{name}")
+        }
+        (FileArg::Required(hint), None) => anyhow::bail!(
+            "builtin:{name} needs a file\n\
+             Usage: builtin:{name}:path/to/{hint}"
+        ),
+        _ => (builtin.create)(sample_rate, file),
+    }
+}
+
+/// Return enumeration info for all built-in plugins.
+pub fn enumerate_plugins() -> Vec<PluginInfo> {
+    BUILTINS.iter().map(Builtin::info).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registry_matches_loaded_plugins() {
+        for builtin in BUILTINS {
+            assert_eq!(find(builtin.name).unwrap().name, builtin.name);
+            if let FileArg::Required(_) = builtin.file {
+                assert!(load(&builtin.id(), 48000.0, 512).is_err());
+                assert!((builtin.create)(48000.0, None).is_err());
+                continue;
+            }
+            let plugin = load(&builtin.id(), 48000.0, 512).unwrap();
+            assert_eq!(plugin.name(), builtin.display_name);
+            assert_eq!(plugin.is_instrument(), builtin.is_instrument);
+            assert_eq!(plugin.parameters().len(), (builtin.params)().len());
+            assert_eq!(plugin.presets().len(), (builtin.presets)().len());
+        }
+    }
+
+    #[test]
+    fn unknown_builtin_lists_available() {
+        let err = load("builtin:nope", 48000.0, 512)
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(err.contains("drums[:<kit.toml>]"), "{err}");
+        assert!(err.contains("sfz:<instrument.sfz>"), "{err}");
+        assert!(load("builtin:sine:extra.txt", 48000.0, 512).is_err());
+    }
+}
@@ -0,0 +1,176 @@
+//! `builtin:reverb`: a Freeverb-style stereo reverb (parallel damped comb
+//! filters into series allpasses) with pre-delay.
+
+use super::Builtin;
+use super::dsp::{DelayLine, ParamSpec, Params, flush_denormal};
+use super::effect::{self, Effect, mix};
+
+pub const BUILTIN: Builtin = effect::builtin::<Reverb>("reverb");
+
+/// Comb and allpass lengths in samples at 44.1 kHz, from Freeverb.
+const COMB_TUNING: [usize; 8] = [1116, 1188, 1277, 1356, 1422, 1491, 1557, 1617];
+const ALLPASS_TUNING: [usize; 4] = [556, 441, 341, 225];
+/// Extra length of the right channel's filters, for stereo decorrelation.
+const STEREO_SPREAD: usize = 23;
+const INPUT_GAIN: f32 = 0.015;
+const WET_GAIN: f32 = 3.0;
+const MAX_PREDELAY_MS: f32 = 200.0;
+
+const SIZE: usize = 0;
+const DAMPING: usize = 1;
+const WIDTH: usize = 2;
+const PREDELAY: usize = 3;
+const MIX: usize = 4;
+
+struct Comb {
+    buffer: Vec<f32>,
+    pos: usize,
+    filter: f32,
+}
+
+impl Comb {
+    fn new(len: usize) -> Self {
+        Self {
+            buffer: vec![0.0; len.max(1)],
+            pos: 0,
+            filter: 0.0,
+        }
+    }
+
+    fn process(&mut self, input: f32, feedback: f32, damp: f32) -> f32 {
+        let out = self.buffer[self.pos];
+        self.filter = flush_denormal(out * (1.0 - damp) + self.filter * damp);
+        self.buffer[self.pos] = flush_denormal(input + self.filter * feedback);
+        self.pos = (self.pos + 1) % self.buffer.len();
+        out
+    }
+}
+
+struct Allpass {
+    buffer: Vec<f32>,
+    pos: usize,
+}
+
+impl Allpass {
+    fn new(len: usize) -> Self {
+        Self {
+            buffer: vec![0.0; len.max(1)],
+            pos: 0,
+        }
+    }
+
+    fn process(&mut self, input: f32) -> f32 {
+        let delayed = self.buffer[self.pos];
+        self.buffer[self.pos] = flush_denormal(input + delayed * 0.5);
+        self.pos = (self.pos + 1) % self.buffer.len();
+        delayed - input
+    }
+}
+
+/// One channel's filter network.
+struct Tank {
+    combs: Vec<Comb>,
+    allpasses: Vec<Allpass>,
+}
+
+impl Tank {
+    fn new(sample_rate: f32, spread: usize) -> Self {
+        let scale = |len: usize| ((len + spread) as f32 * sample_rate / 44100.0) as usize;
+        Self {
+            combs: COMB_TUNING.iter().map(|&l| Comb::new(scale(l))).collect(),
+            allpasses: ALLPASS_TUNING
+                .iter()
+                .map(|&l| Allpass::new(scale(l)))
+                .collect(),
+        }
+    }
+
+    fn process(&mut self, input: f32, feedback: f32, damp: f32) -> f32 {
+        let mut out: f32 = self
+            .combs
+            .iter_mut()
+            .map(|c| c.process(input, feedback, damp))
+            .sum();
+        for allpass in &mut self.allpasses {
+            out = allpass.process(out);
+        }
+        out
+    }
+}
+
+pub struct Reverb {
+    sample_rate: f32,
+    predelay: DelayLine,
+    tanks: [Tank; 2],
+}
+
+impl Effect for Reverb {
+    const NAME: &'static str = "Reverb";
+    const PARAMS: &'static [ParamSpec] = &[
+        ParamSpec::new("Size", 0.0, 1.0, 0.6),
+        ParamSpec::new("Damping", 0.0, 1.0, 0.4),
+        ParamSpec::new("Width", 0.0, 1.0, 1.0),
+        ParamSpec::new("Pre-delay", 0.0, MAX_PREDELAY_MS, 10.0),
+        ParamSpec::new("Mix", 0.0, 1.0, 0.25),
+    ];
+
+    fn new(sample_rate: f32) -> Self {
+        Self {
+            sample_rate,
+            predelay: DelayLine::new((MAX_PREDELAY_MS / 1000.0 * sample_rate) as usize + 1),
+            tanks: [
+                Tank::new(sample_rate, 0),
+                Tank::new(sample_rate, STEREO_SPREAD),
+            ],
+        }
+    }
+
+    fn process(&mut self, params: &Params, left: &mut [f32], right: &mut [f32]) {
+        let feedback = 0.7 + params.get(SIZE) * 0.28;
+        let damp = params.get(DAMPING) * 0.4;
+        let width = params.get(WIDTH);
+        let wet1 = (width / 2.0 + 0.5) * WET_GAIN;
+        let wet2 = (1.0 - width) / 2.0 * WET_GAIN;
+        let predelay = params.get(PREDELAY) / 1000.0 * self.sample_rate;
+        let amount = params.get(MIX);
+
+        for (l, r) in left.iter_mut().zip(right.iter_mut()) {
+            self.predelay.write((*l + *r) * INPUT_GAIN);
+            let input = if predelay < 1.0 {
+                (*l + *r) * INPUT_GAIN
+            } else {
+                self.predelay.read(predelay)
+            };
+            let out_l = self.tanks[0].process(input, feedback, damp);
+            let out_r = self.tanks[1].process(input, feedback, damp);
+            let wet_l = out_l * wet1 + out_r * wet2;
+            let wet_r = out_r * wet1 + out_l * wet2;
+            *l = mix(*l, wet_l, amount);
+            *r = mix(*r, wet_r, amount);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin::builtin::effect::{self, rms, run};
+
+    const SR: f32 = 48000.0;
+
+    #[test]
+    fn tail_rings_then_decays() {
+        let mut reverb = effect::load::<Reverb>(SR);
+        reverb.set_parameter(MIX as u32, 1.0).unwrap();
+        let mut impulse = vec![0.0; 48000];
+        impulse[0] = 1.0;
+        let (l, r) = run(reverb.as_mut(), &impulse, &impulse);
+        assert!(l.iter().chain(&r).all(|s| s.is_finite()));
+        assert!(rms(&l[..12000], 0) > 1e-3);
+        assert_ne!(l, r);
+
+        let silence = vec![0.0; 48000 * 8];
+        let (l, _) = run(reverb.as_mut(), &silence, &silence);
+        assert!(rms(&l, 48000 * 7) < 1e-4);
+    }
+}
@@ -0,0 +1,92 @@
+//! Sample files for the built-in samplers, decoded into memory.
+
+use std::path::Path;
+
+/// Decoded sample data, one buffer per channel (mono or stereo).
+pub struct Sample {
+    pub channels: Vec<Vec<f32>>,
+    pub sample_rate: f32,
+}
+
+impl Sample {
+    pub fn len(&self) -> usize {
+        self.channels[0].len()
+    }
+
+    /// A mono sample from generated data.
+    pub fn mono(data: Vec<f32>, sample_rate: f32) -> Self {
+        Self {
+            channels: vec![data],
+            sample_rate,
+        }
+    }
+
+    /// Linearly interpolated value of `channel` (the last channel if out of
+    /// range) at fractional frame `position`, or silence past the end.
+    pub fn read(&self, channel: usize, position: f64) -> f32 {
+        let data = &self.channels[channel.min(self.channels.len() - 1)];
+        let index = position as usize;
+        let Some(&a) = data.get(index) else {
+            return 0.0;
+        };
+        let b = data.get(index + 1).copied().unwrap_or(a);
+        a + (b - a) * (position - index as f64) as f32
+    }
+
+    fn from_interleaved(data: Vec<f32>, channels: usize, sample_rate: u32) -> anyhow::Result<Self> {
+        if channels == 0 || data.is_empty() {
+            anyhow::bail!("sample is empty");
+        }
+        let used = channels.min(2);
+        let channels = (0..used)
+            .map(|c| data.iter().skip(c).step_by(channels).copied().collect())
+            .collect();
+        Ok(Self {
+            channels,
+            sample_rate: sample_rate as f32,
+        })
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let ext = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        let result = match ext.as_str() {
+            "wav" => Self::load_wav(path),
+            "flac" => Self::load_flac(path),
+            _ => anyhow::bail!(
+                "unsupported sample format: {} (use WAV or FLAC)",
+                path.display()
+            ),
+        };
+        result.map_err(|e| anyhow::anyhow!("Failed to load sample {}: {e}", path.display()))
+    }
+
+    fn load_wav(path: &Path) -> anyhow::Result<Self> {
+        let mut reader = hound::WavReader::open(path)?;
+        let spec = reader.spec();
+        let data = match spec.sample_format {
+            hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<Vec<_>, _>>()?,
+            hound::SampleFormat::Int => {
+                let scale = 1.0 / (1u64 << (spec.bits_per_sample - 1)) as f32;
+                reader
+                    .samples::<i32>()
+                    .map(|s| s.map(|s| s as f32 * scale))
+                    .collect::<Result<Vec<_>, _>>()?
+            }
+        };
+        Self::from_interleaved(data, spec.channels as usize, spec.sample_rate)
+    }
+
+    fn load_flac(path: &Path) -> anyhow::Result<Self> {
+        let mut reader = claxon::FlacReader::open(path)?;
+        let info = reader.streaminfo();
+        let scale = 1.0 / (1u64 << (info.bits_per_sample - 1)) as f32;
+        let data = reader
+            .samples()
+            .map(|s| s.map(|s| s as f32 * scale))
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::from_interleaved(data, info.channels as usize, info.sample_rate)
+    }
+}
@@ -0,0 +1,781 @@
+//! `builtin:sfz:<file>`: a sample player for SFZ instruments.
+//!
+//! Supports the common subset of SFZ: `<control>`, `<global>`, `<master>`,
+//! `<group>` and `<region>` headers; key and velocity ranges; pitch, tuning
+//! and key tracking; volume, pan and velocity tracking; sample offset and
+//! loop points; the amp envelope; round robins (`seq_length`/`seq_position`
+//! and `lorand`/`hirand`); release triggers and choke groups (`group` and
+//! `off_by`). Samples can be WAV or FLAC and are loaded into memory when the
+//! instrument loads. Unsupported opcodes are ignored.
+
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use super::dsp::{Adsr, Noise, ParamSpec, Params};
+use super::instrument::{Instrument, InstrumentPlugin};
+use super::sample::Sample;
+use super::{Builtin, FileArg};
+
+pub const BUILTIN: Builtin = Builtin {
+    name: "sfz",
+    display_name: SfzPlayer::NAME,
+    is_instrument: true,
+    file: FileArg::Required("instrument.sfz"),
+    params: || Params::new(&PARAMS).infos(),
+    presets: Vec::new,
+    create: |sample_rate, file| {
+        let Some(file) = file else {
+            anyhow::bail!("builtin:sfz needs a file");
+        };
+        Ok(Box::new(SfzPlayer::load(file, sample_rate)?))
+    },
+};
+
+const MAX_VOICES: usize = 64;
+/// Pitch bend range in semitones.
+const BEND_RANGE: f32 = 2.0;
+/// Release time for voices cut off by a choke group, in seconds.
+const CHOKE_RELEASE: f32 = 0.006;
+
+const VOLUME: usize = 0;
+const TRANSPOSE: usize = 1;
+
+pub const PARAMS: [ParamSpec; 2] = [
+    ParamSpec::new("Volume", 0.0, 1.0, 0.8),
+    ParamSpec::stepped("Transpose", -24.0, 24.0, 0.0),
+];
+
+// ---------------------------------------------------------------------------
+// Parsing
+// ---------------------------------------------------------------------------
+
+/// One `<header>` and the opcodes that follow it, in file order.
+struct Section {
+    header: String,
+    opcodes: Vec<(String, String)>,
+}
+
+/// Split SFZ text into sections. Opcode values run to the next opcode or
+/// header, so sample names may contain spaces.
+fn parse_sections(text: &str) -> anyhow::Result<Vec<Section>> {
+    let mut sections = vec![Section {
+        header: String::new(),
+        opcodes: Vec::new(),
+    }];
+    for (line_no, line) in text.lines().enumerate() {
+        let line = line.split("//").next().unwrap_or_default();
+        let mut rest = line.trim();
+        if rest.starts_with('#') {
+            log::warn!("SFZ line {}: unsupported directive {rest:?}", line_no + 1);
+            continue;
+        }
+        while !rest.is_empty() {
+            if let Some(after) = rest.strip_prefix('<') {
+                let Some(end) = after.find('>') else {
+                    anyhow::bail!("SFZ line {}: unterminated header", line_no + 1);
+                };
+                sections.push(Section {
+                    header: after[..end].trim().to_string(),
+                    opcodes: Vec::new(),
+                });
+                rest = after[end + 1..].trim_start();
+                continue;
+            }
+            let Some(eq) = rest.find('=') else {
+                anyhow::bail!(
+                    "SFZ line {}: expected opcode=value, got {rest:?}",
+                    line_no + 1
+                );
+            };
+            let name = rest[..eq].trim().to_string();
+            let after = &rest[eq + 1..];
+            let end = value_end(after);
+            let value = after[..end].trim().to_string();
+            sections.last_mut().unwrap().opcodes.push((name, value));
+            rest = after[end..].trim_start();
+        }
+    }
+    Ok(sections)
+}
+
+/// Byte offset where an opcode value ends: the start of the first later
+/// token that is an opcode or header.
+fn value_end(s: &str) -> usize {
+    let mut rest = s.trim_start();
+    let mut first = true;
+    loop {
+        let start = s.len() - rest.len();
+        let Some(token) = rest.split_whitespace().next() else {
+            return s.len();
+        };
+        if !first && (token.starts_with('<') || token.contains('=')) {
+            return start;
+        }
+        first = false;
+        rest = rest[token.len()..].trim_start();
+    }
+}
+
+/// Parse a key number or SFZ note name (`c4` = 60, `f#3`, `eb5`).
+fn parse_key(value: &str) -> anyhow::Result<u8> {
+    if let Ok(n) = value.parse::<u8>() {
+        if n <= 127 {
+            return Ok(n);
+        }
+    }
+    crate::session::parse_note_name(value).map_err(|_| anyhow::anyhow!("invalid key {value:?}"))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum LoopMode {
+    NoLoop,
+    OneShot,
+    Continuous,
+    Sustain,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Trigger {
+    Attack,
+    Release,
+}
+
+/// A region as read from the file, before its sample is loaded.
+#[derive(Clone, Debug)]
+struct RegionSpec {
+    sample: String,
+    lokey: u8,
+    hikey: u8,
+    lovel: u8,
+    hivel: u8,
+    pitch_keycenter: u8,
+    /// Cents per key.
+    pitch_keytrack: f32,
+    tune: f32,
+    transpose: f32,
+    volume: f32,
+    pan: f32,
+    amp_veltrack: f32,
+    offset: usize,
+    loop_mode: Option<LoopMode>,
+    loop_start: Option<usize>,
+    loop_end: Option<usize>,
+    attack: f32,
+    decay: f32,
+    sustain: f32,
+    release: f32,
+    trigger: Trigger,
+    seq_length: u32,
+    seq_position: u32,
+    lorand: f32,
+    hirand: f32,
+    group: u32,
+    off_by: Option<u32>,
+}
+
+impl Default for RegionSpec {
+    fn default() -> Self {
+        Self {
+            sample: String::new(),
+            lokey: 0,
+            hikey: 127,
+            lovel: 1,
+            hivel: 127,
+            pitch_keycenter: 60,
+            pitch_keytrack: 100.0,
+            tune: 0.0,
+            transpose: 0.0,
+            volume: 0.0,
+            pan: 0.0,
+            amp_veltrack: 100.0,
+            offset: 0,
+            loop_mode: None,
+            loop_start: None,
+            loop_end: None,
+            attack: 0.0,
+            decay: 0.0,
+            sustain: 100.0,
+            release: 0.001,
+            trigger: Trigger::Attack,
+            seq_length: 1,
+            seq_position: 1,
+            lorand: 0.0,
+            hirand: 1.0,
+            group: 0,
+            off_by: None,
+        }
+    }
+}
+
+impl RegionSpec {
+    fn apply(&mut self, name: &str, value: &str) -> anyhow::Result<()> {
+        fn num<T: std::str::FromStr>(name: &str, value: &str) -> anyhow::Result<T> {
+            value
+                .parse()
+                .map_err(|_| anyhow::anyhow!("invalid value for {name}: {value:?}"))
+        }
+        match name {
+            "sample" => self.sample = value.replace('\\', "/"),
+            "lokey" => self.lokey = parse_key(value)?,
+            "hikey" => self.hikey = parse_key(value)?,
+            "key" => {
+                let key = parse_key(value)?;
+                self.lokey = key;
+                self.hikey = key;
+                self.pitch_keycenter = key;
+            }
+            "lovel" => self.lovel = num(name, value)?,
+            "hivel" => self.hivel = num(name, value)?,
+            "pitch_keycenter" => self.pitch_keycenter = parse_key(value)?,
+            "pitch_keytrack" => self.pitch_keytrack = num(name, value)?,
+            "tune" | "pitch" => self.tune = num(name, value)?,
+            "transpose" => self.transpose = num(name, value)?,
+            "volume" | "gain" => self.volume = num(name, value)?,
+            "pan" => self.pan = num(name, value)?,
+            "amp_veltrack" => self.amp_veltrack = num(name, value)?,
+            "offset" => self.offset = num(name, value)?,
+            "loop_mode" | "loopmode" => {
+                self.loop_mode = Some(match value {
+                    "no_loop" => LoopMode::NoLoop,
+                    "one_shot" => LoopMode::OneShot,
+                    "loop_continuous" => LoopMode::Continuous,
+                    "loop_sustain" => LoopMode::Sustain,
+                    _ => anyhow::bail!("invalid loop_mode {value:?}"),
+                })
+            }
+            "loop_start" | "loopstart" => self.loop_start = Some(num(name, value)?),
+            "loop_end" | "loopend" => self.loop_end = Some(num(name, value)?),
+            "ampeg_attack" => self.attack = num(name, value)?,
+            "ampeg_decay" => self.decay = num(name, value)?,
+            "ampeg_sustain" => self.sustain = num(name, value)?,
+            "ampeg_release" => self.release = num(name, value)?,
+            "trigger" => {
+                self.trigger = match value {
+                    "attack" => Trigger::Attack,
+                    "release" => Trigger::Release,
+                    _ => anyhow::bail!("unsupported trigger {value:?}"),
+                }
+            }
+            "seq_length" => self.seq_length = num::<u32>(name, value)?.max(1),
+            "seq_position" => self.seq_position = num::<u32>(name, value)?.max(1),
+            "lorand" => self.lorand = num(name, value)?,
+            "hirand" => self.hirand = num(name, value)?,
+            "group" => self.group = num(name, value)?,
+            "off_by" => self.off_by = Some(num(name, value)?),
+            _ => log::debug!("Ignoring unsupported SFZ opcode {name}"),
+        }
+        Ok(())
+    }
+
+    fn loop_mode(&self) -> LoopMode {
+        match self.loop_mode {
+            Some(mode) => mode,
+            None if self.loop_end.is_some() => LoopMode::Continuous,
+            None => LoopMode::NoLoop,
+        }
+    }
+}
+
+/// Parse SFZ text into regions plus the `<control>` `default_path`.
+fn parse(text: &str) -> anyhow::Result<(Vec<RegionSpec>, String)> {
+    let mut default_path = String::new();
+    let mut global: Vec<(String, String)> = Vec::new();
+    let mut master: Vec<(String, String)> = Vec::new();
+    let mut group: Vec<(String, String)> = Vec::new();
+    let mut regions = Vec::new();
+    for section in parse_sections(text)? {
+        match section.header.as_str() {
+            "control" => {
+                for (name, value) in &section.opcodes {
+                    if name == "default_path" {
+                        default_path = value.replace('\\', "/");
+                    }
+                }
+            }
+            "" | "global" => {
+                global = section.opcodes;
+                master.clear();
+                group.clear();
+            }
+            "master" => {
+                master = section.opcodes;
+                group.clear();
+            }
+            "group" => group = section.opcodes,
+            "region" => {
+                let mut region = RegionSpec::default();
+                for (name, value) in global
+                    .iter()
+                    .chain(&master)
+                    .chain(&group)
+                    .chain(&section.opcodes)
+                {
+                    region.apply(name, value)?;
+                }
+                if region.sample.is_empty() {
+                    anyhow::bail!("SFZ region without a sample");
+                }
+                regions.push(region);
+            }
+            other => log::debug!("Ignoring unsupported SFZ header <{other}>"),
+        }
+    }
+    Ok((regions, default_path))
+}
+
+/// A region ready to play.
+struct Region {
+    spec: RegionSpec,
+    sample: Arc<Sample>,
+    loop_mode: LoopMode,
+    /// Loop start and end (inclusive) frames, if the region loops.
+    loop_range: Option<(usize, usize)>,
+    /// Linear gain from `volume`.
+    gain: f32,
+}
+
+impl Region {
+    fn new(spec: RegionSpec, sample: Arc<Sample>) -> Self {
+        let last = sample.len() - 1;
+        let loop_mode = spec.loop_mode();
+        let loop_range = match loop_mode {
+            LoopMode::Continuous | LoopMode::Sustain => {
+                let start = spec.loop_start.unwrap_or(0).min(last);
+                let end = spec.loop_end.unwrap_or(last).min(last);
+                (end > start).then_some((start, end))
+            }
+            LoopMode::NoLoop | LoopMode::OneShot => None,
+        };
+        let gain = 10.0_f32.powf(spec.volume / 20.0);
+        Self {
+            spec,
+            sample,
+            loop_mode,
+            loop_range,
+            gain,
+        }
+    }
+
+    fn matches(&self, key: u8, velocity: u8, trigger: Trigger) -> bool {
+        self.spec.trigger == trigger
+            && (self.spec.lokey..=self.spec.hikey).contains(&key)
+            && (self.spec.lovel..=self.spec.hivel).contains(&velocity)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Player
+// ---------------------------------------------------------------------------
+
+#[derive(Clone, Default)]
+struct Voice {
+    active: bool,
+    region: usize,
+    key: u8,
+    /// Position in the sample, in sample frames.
+    position: f64,
+    /// Playback rate relative to the sample's own rate, before pitch bend.
+    ratio: f64,
+    gain: [f32; 2],
+    env: Adsr,
+    release: f32,
+    /// Key released, but held by the sustain pedal.
+    pedal_held: bool,
+    /// Note-on order, for stealing the oldest voice.
+    started: u64,
+}
+
+pub struct SfzPlayer {
+    name: String,
+    sample_rate: f32,
+    regions: Vec<Region>,
+    /// Round-robin counter per region.
+    sequence: Vec<u32>,
+    voices: Vec<Voice>,
+    note_counter: u64,
+    /// Velocity of the last note-on per key, for release triggers.
+    velocities: [u8; 128],
+    sustain_pedal: bool,
+    /// Pitch bend in semitones.
+    bend: f32,
+    random: Noise,
+}
+
+impl SfzPlayer {
+    /// Load the instrument at `path` and all of its samples.
+    pub fn load(path: &Path, sample_rate: f32) -> anyhow::Result<InstrumentPlugin<Self>> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read {}: {e}", path.display()))?;
+        let (specs, default_path) =
+            parse(&text).map_err(|e| anyhow::anyhow!("{}: {e}", path.display()))?;
+        if specs.is_empty() {
+            anyhow::bail!("{} has no regions", path.display());
+        }
+        let base = path.parent().unwrap_or(Path::new(".")).join(default_path);
+        let mut samples: HashMap<PathBuf, Arc<Sample>> = HashMap::new();
+        let mut regions = Vec::with_capacity(specs.len());
+        for spec in specs {
+            let sample_path = base.join(&spec.sample);
+            let sample = match samples.get(&sample_path) {
+                Some(sample) => sample.clone(),
+                None => {
+                    let sample = Arc::new(Sample::load(&sample_path)?);
+                    samples.insert(sample_path, sample.clone());
+                    sample
+                }
+            };
+            regions.push(Region::new(spec, sample));
+        }
+        log::info!(
+            "Loaded {} ({} regions, {} samples)",
+            path.display(),
+            regions.len(),
+            samples.len()
+        );
+        let name = path
+            .file_stem()
+            .map(|s| format!("SFZ: {}", s.to_string_lossy()))
+            .unwrap_or_else(|| "SFZ Sampler".into());
+        let player = Self {
+            name,
+            sample_rate,
+            sequence: vec![0; regions.len()],
+            regions,
+            voices: vec![Voice::default(); MAX_VOICES],
+            note_counter: 0,
+            velocities: [0; 128],
+            sustain_pedal: false,
+            bend: 0.0,
+            random: Noise::new(0x9e37_79b9),
+        };
+        Ok(InstrumentPlugin::new(player, &PARAMS, sample_rate))
+    }
+
+    /// Start every region matching the key, velocity and trigger.
+    fn trigger(&mut self, params: &Params, key: u8, velocity: u8, trigger: Trigger) {
+        let random = self.random.next() * 0.5 + 0.5;
+        let transpose = params.get(TRANSPOSE);
+        for i in 0..self.regions.len() {
+            let region = &self.regions[i];
+            if !region.matches(key, velocity, trigger) {
+                continue;
+            }
+            let spec = &region.spec;
+            let step = self.sequence[i];
+            self.sequence[i] = step.wrapping_add(1);
+            if step % spec.seq_length != spec.seq_position - 1 {
+                continue;
+            }
+            if random < spec.lorand || random >= spec.hirand {
+                continue;
+            }
+
+            if spec.group != 0 {
+                let group = spec.group;
+                for voice in self.voices.iter_mut().filter(|v| v.active) {
+                    if self.regions[voice.region].spec.off_by == Some(group) {
+                        voice.release = CHOKE_RELEASE;
+                        voice.env.gate_off();
+                    }
+                }
+            }
+
+            let semitones = (key as f32 - spec.pitch_keycenter as f32) * spec.pitch_keytrack
+                / 100.0
+                + spec.tune / 100.0
+                + spec.transpose
+                + transpose;
+            let ratio = 2.0_f64.powf(semitones as f64 / 12.0) * region.sample.sample_rate as f64
+                / self.sample_rate as f64;
+            let v = velocity as f32 / 127.0;
+            let velocity_gain = 1.0 - spec.amp_veltrack / 100.0 * (1.0 - v * v);
+            let gain = region.gain * velocity_gain;
+            let pan = (spec.pan / 100.0).clamp(-1.0, 1.0);
+            let offset = spec.offset.min(region.sample.len() - 1) as f64;
+
+            let slot = self.free_voice();
+            self.note_counter += 1;
+            let voice = &mut self.voices[slot];
+            *voice = Voice {
+                active: true,
+                region: i,
+                key,
+                position: offset,
+                ratio,
+                gain: [gain * (1.0 - pan).min(1.0), gain * (1.0 + pan).min(1.0)],
+                env: Adsr::default(),
+                release: spec.release,
+                pedal_held: false,
+                started: self.note_counter,
+            };
+            voice.env.gate_on();
+        }
+    }
+
+    /// A free voice, or the oldest one if all are busy.
+    fn free_voice(&self) -> usize {
+        self.voices
+            .iter()
+            .position(|v| !v.active)
+            .unwrap_or_else(|| {
+                self.voices
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|(_, v)| v.started)
+                    .map(|(i, _)| i)
+                    .unwrap_or(0)
+            })
+    }
+
+    fn release_voice(voice: &mut Voice, region: &Region) {
+        if region.loop_mode != LoopMode::OneShot {
+            voice.env.gate_off();
+        }
+    }
+
+    fn note_off(&mut self, params: &Params, key: u8) {
+        for voice in self.voices.iter_mut().filter(|v| v.active && v.key == key) {
+            let region = &self.regions[voice.region];
+            if region.spec.trigger == Trigger::Release {
+                continue;
+            }
+            if self.sustain_pedal {
+                voice.pedal_held = true;
+            } else {
+                Self::release_voice(voice, region);
+            }
+        }
+        let velocity = self.velocities[key as usize];
+        self.trigger(params, key, velocity, Trigger::Release);
+    }
+
+    #[cfg(test)]
+    fn active_voices(&self) -> usize {
+        self.voices.iter().filter(|v| v.active).count()
+    }
+}
+
+impl Instrument for SfzPlayer {
+    const NAME: &'static str = "SFZ Sampler";
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn handle_midi(&mut self, params: &Params, [status, data1, data2]: [u8; 3]) {
+        match status & 0xF0 {
+            0x90 if data2 > 0 => {
+                self.velocities[data1 as usize & 0x7F] = data2;
+                self.trigger(params, data1, data2, Trigger::Attack);
+            }
+            0x80 | 0x90 => self.note_off(params, data1 & 0x7F),
+            0xB0 if data1 == 64 => {
+                self.sustain_pedal = data2 >= 64;
+                if !self.sustain_pedal {
+                    for voice in self.voices.iter_mut().filter(|v| v.pedal_held) {
+                        voice.pedal_held = false;
+                        Self::release_voice(voice, &self.regions[voice.region]);
+                    }
+                }
+            }
+            0xB0 if data1 == 120 => {
+                for voice in &mut self.voices {
+                    voice.active = false;
+                }
+            }
+            0xB0 if data1 == 123 => {
+                for voice in self.voices.iter_mut().filter(|v| v.active) {
+                    voice.pedal_held = false;
+                    voice.env.gate_off();
+                }
+            }
+            0xE0 => {
+                let value = ((data2 as i32) << 7 | data1 as i32) - 8192;
+                self.bend = value as f32 / 8192.0 * BEND_RANGE;
+            }
+            _ => {}
+        }
+    }
+
+    fn render(&mut self, params: &Params, out: &mut [&mut [f32]], frames: Range<usize>) {
+        let sr = self.sample_rate;
+        let volume = params.get(VOLUME);
+        let bend = 2.0_f64.powf(self.bend as f64 / 12.0);
+
+        for frame in frames {
+            let mut sum = [0.0_f32; 2];
+            for voice in self.voices.iter_mut().filter(|v| v.active) {
+                let region = &self.regions[voice.region];
+                let spec = &region.spec;
+                let sample = &region.sample;
+                let looping = match region.loop_mode {
+                    LoopMode::Continuous => region.loop_range,
+                    LoopMode::Sustain if !voice.env.is_released() => region.loop_range,
+                    _ => None,
+                };
+
+                let index = voice.position as usize;
+                let frac = (voice.position - index as f64) as f32;
+                let next = match looping {
+                    Some((start, end)) if index >= end => start,
+                    _ => (index + 1).min(sample.len() - 1),
+                };
+                let env = voice.env.next(
+                    spec.attack,
+                    spec.decay,
+                    spec.sustain / 100.0,
+                    voice.release,
+                    sr,
+                );
+                for (c, sum) in sum.iter_mut().enumerate() {
+                    let data = &sample.channels[c.min(sample.channels.len() - 1)];
+                    let s = data[index] + (data[next] - data[index]) * frac;
+                    *sum += s * env * voice.gain[c];
+                }
+
+                voice.position += voice.ratio * bend;
+                if let Some((start, end)) = looping {
+                    let length = (end - start + 1) as f64;
+                    while voice.position >= end as f64 + 1.0 {
+                        voice.position -= length;
+                    }
+                }
+                if voice.position >= sample.len() as f64 || voice.env.is_idle() {
+                    voice.active = false;
+                }
+            }
+
+            let channels = out.len();
+            for (c, ch) in out.iter_mut().enumerate() {
+                ch[frame] = if channels == 1 {
+                    (sum[0] + sum[1]) * 0.5 * volume
+                } else {
+                    sum[c.min(1)] * volume
+                };
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin::Plugin;
+    use crate::plugin::builtin::instrument::render;
+
+    const SR: f32 = 48000.0;
+
+    /// Write a mono 16-bit WAV holding a constant `level` for `frames`.
+    fn write_wav(path: &Path, level: f32, frames: usize) {
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: SR as u32,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(path, spec).unwrap();
+        for _ in 0..frames {
+            writer.write_sample((level * 32767.0) as i16).unwrap();
+        }
+        writer.finalize().unwrap();
+    }
+
+    #[test]
+    fn parse_sections_and_inheritance() {
+        let (regions, default_path) = parse(
+            "<control> default_path=samples\\\n\
+             <global> ampeg_release=0.5 // comment\n\
+             <group> lovel=64 hivel=127\n\
+             <region> sample=Grand Piano C4.wav key=c4\n\
+             <region>sample=b.flac lokey=61 hikey=f#4 pitch_keycenter=62 ampeg_release=1\n\
+             <group> seq_length=2\n\
+             <region> sample=rr1.wav seq_position=1 <region> sample=rr2.wav seq_position=2\n",
+        )
+        .unwrap();
+        assert_eq!(default_path, "samples/");
+        assert_eq!(regions.len(), 4);
+        assert_eq!(regions[0].sample, "Grand Piano C4.wav");
+        assert_eq!((regions[0].lokey, regions[0].hikey), (60, 60));
+        assert_eq!((regions[0].lovel, regions[0].release), (64, 0.5));
+        assert_eq!((regions[1].lokey, regions[1].hikey), (61, 66));
+        assert_eq!(regions[1].release, 1.0);
+        // A new group drops the previous group's opcodes.
+        assert_eq!((regions[2].lovel, regions[2].seq_length), (1, 2));
+        assert_eq!(regions[3].sample, "rr2.wav");
+        assert_eq!(regions[3].seq_position, 2);
+    }
+
+    #[test]
+    fn plays_regions_by_key_and_round_robin() {
+        let dir = tempfile::tempdir().unwrap();
+        write_wav(&dir.path().join("low.wav"), 0.25, 4800);
+        write_wav(&dir.path().join("rr1.wav"), 0.5, 4800);
+        write_wav(&dir.path().join("rr2.wav"), -0.5, 4800);
+        let sfz = dir.path().join("kit.sfz");
+        std::fs::write(
+            &sfz,
+            "<region> sample=low.wav hikey=59 amp_veltrack=0\n\
+             <group> lokey=60 hikey=60 amp_veltrack=0 seq_length=2\n\
+             <region> sample=rr1.wav seq_position=1\n\
+             <region> sample=rr2.wav seq_position=2\n",
+        )
+        .unwrap();
+        let mut player = SfzPlayer::load(&sfz, SR).unwrap();
+        player.set_parameter(VOLUME as u32, 1.0).unwrap();
+
+        let out = render(&mut player, &[(0, [0x90, 48, 10])], 64);
+        assert!((out[32] - 0.25).abs() < 0.01, "{}", out[32]);
+        render(&mut player, &[(0, [0x80, 48, 0])], 64);
+
+        let first = render(&mut player, &[(0, [0x90, 60, 100])], 64);
+        let second = render(&mut player, &[(0, [0x80, 60, 0]), (0, [0x90, 60, 100])], 64);
+        assert!(first[8] > 0.4, "{}", first[8]);
+        assert!(second[32] < -0.4, "{}", second[32]);
+
+        // Without a loop, voices end with their sample.
+        render(&mut player, &[], 9600);
+        assert_eq!(player.instrument.active_voices(), 0);
+    }
+
+    #[test]
+    fn loops_sustain_until_release_and_chokes() {
+        let dir = tempfile::tempdir().unwrap();
+        write_wav(&dir.path().join("a.wav"), 0.5, 100);
+        let sfz = dir.path().join("loop.sfz");
+        std::fs::write(
+            &sfz,
+            "<region> sample=a.wav key=60 loop_mode=loop_sustain loop_start=10 loop_end=89 group=1\n\
+             <region> sample=a.wav key=62 off_by=1\n\
+             <region> sample=a.wav key=64 group=1\n",
+        )
+        .unwrap();
+        let mut player = SfzPlayer::load(&sfz, SR).unwrap();
+        let out = render(&mut player, &[(0, [0x90, 60, 127])], 4800);
+        assert!(out[4799].abs() > 0.1);
+        assert_eq!(player.instrument.active_voices(), 1);
+        render(&mut player, &[(0, [0x80, 60, 0])], 4800);
+        assert_eq!(player.instrument.active_voices(), 0);
+
+        // Note 64 (group 1) chokes the voice started by note 62.
+        render(&mut player, &[(0, [0x90, 62, 127])], 8);
+        render(&mut player, &[(0, [0x90, 64, 127])], 8);
+        let choked = player
+            .instrument
+            .voices
+            .iter()
+            .find(|v| v.active && v.key == 62)
+            .unwrap();
+        assert!(choked.env.is_released());
+    }
+
+    #[test]
+    fn missing_sample_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let sfz = dir.path().join("broken.sfz");
+        std::fs::write(&sfz, "<region> sample=nope.wav\n").unwrap();
+        let err = SfzPlayer::load(&sfz, SR).err().unwrap().to_string();
+        assert!(err.contains("nope.wav"), "{err}");
+    }
+}
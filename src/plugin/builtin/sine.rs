@@ -0,0 +1,208 @@
+//! `builtin:sine`: a simple polyphonic oscillator, useful for testing
+//! audio/MIDI without external plugins. Despite the name it also does
+//! triangle, saw and square, with an attack/release envelope and velocity.
+//!
+//! Waveform is `0` sine, `1` triangle, `2` saw, `3` square.
+
+use std::ops::Range;
+
+use super::Builtin;
+use super::dsp::{
+    Adsr, FactoryPreset, Noise, Oscillator, ParamSpec, Params, Waveform, note_to_freq,
+};
+use super::instrument::{self, Instrument, Synth, Voices};
+
+const MAX_VOICES: usize = 32;
+
+const WAVEFORM: usize = 0;
+const GAIN: usize = 1;
+const ATTACK: usize = 2;
+const RELEASE: usize = 3;
+const DETUNE: usize = 4;
+const VELOCITY: usize = 5;
+
+pub const BUILTIN: Builtin = instrument::builtin::<SineOscillator>("sine");
+
+pub const PARAMS: [ParamSpec; 6] = [
+    ParamSpec::stepped("Waveform", 0.0, 3.0, 0.0),
+    ParamSpec::new("Gain", 0.0, 1.0, 0.5),
+    ParamSpec::new("Attack", 0.001, 5.0, 0.005),
+    ParamSpec::new("Release", 0.001, 5.0, 0.05),
+    ParamSpec::new("Detune", -100.0, 100.0, 0.0),
+    ParamSpec::new("Velocity", 0.0, 1.0, 0.7),
+];
+
+pub const PRESETS: [FactoryPreset; 4] = [
+    FactoryPreset {
+        name: "Init",
+        values: &[],
+    },
+    FactoryPreset {
+        name: "Soft",
+        values: &[
+            ("Waveform", 1.0),
+            ("Attack", 0.08),
+            ("Release", 0.6),
+            ("Velocity", 0.3),
+        ],
+    },
+    FactoryPreset {
+        name: "Square Lead",
+        values: &[("Waveform", 3.0), ("Gain", 0.3), ("Release", 0.1)],
+    },
+    FactoryPreset {
+        name: "Saw",
+        values: &[("Waveform", 2.0), ("Gain", 0.35), ("Release", 0.2)],
+    },
+];
+
+fn waveform(index: usize) -> Waveform {
+    match index {
+        0 => Waveform::Sine,
+        1 => Waveform::Triangle,
+        2 => Waveform::Saw,
+        _ => Waveform::Square,
+    }
+}
+
+#[derive(Clone, Default)]
+struct Voice {
+    key: u8,
+    velocity: f32,
+    osc: Oscillator,
+    env: Adsr,
+}
+
+impl instrument::Voice for Voice {
+    fn key(&self) -> u8 {
+        self.key
+    }
+
+    fn is_idle(&self) -> bool {
+        self.env.is_idle()
+    }
+
+    fn released_level(&self) -> Option<f32> {
+        self.env.is_released().then(|| self.env.level())
+    }
+
+    fn note_on(&mut self, key: u8, velocity: f32) {
+        if self.env.is_idle() {
+            self.osc.reset();
+        }
+        self.key = key;
+        self.velocity = velocity;
+        self.env.gate_on();
+    }
+
+    fn note_off(&mut self) {
+        self.env.gate_off();
+    }
+}
+
+pub struct SineOscillator {
+    sample_rate: f32,
+    voices: Voices<Voice>,
+    /// Unused by the waveforms offered here, but required by `Oscillator`.
+    noise: Noise,
+}
+
+impl Instrument for SineOscillator {
+    const NAME: &'static str = "Sine Oscillator";
+    const PRESETS: &'static [FactoryPreset] = &PRESETS;
+
+    fn handle_midi(&mut self, _params: &Params, message: [u8; 3]) {
+        self.voices.handle_midi(message, MAX_VOICES);
+    }
+
+    fn render(&mut self, params: &Params, out: &mut [&mut [f32]], frames: Range<usize>) {
+        let sr = self.sample_rate;
+        let wave = waveform(params.choice(WAVEFORM));
+        let gain = params.get(GAIN);
+        let attack = params.get(ATTACK);
+        let release = params.get(RELEASE);
+        let detune = params.get(DETUNE) / 100.0;
+        let velocity_sens = params.get(VELOCITY);
+
+        for frame in frames {
+            let mut sample = 0.0;
+            for voice in self.voices.iter_mut() {
+                if voice.env.is_idle() {
+                    continue;
+                }
+                let dt = note_to_freq(voice.key as f32 + detune) / sr;
+                let osc = voice.osc.next(wave, dt, &mut self.noise);
+                let env = voice.env.next(attack, 0.001, 1.0, release, sr);
+                let velocity_gain = 1.0 - velocity_sens * (1.0 - voice.velocity);
+                sample += osc * env * velocity_gain;
+            }
+
+            // Clamp to avoid blowup with many voices
+            let sample = (sample * gain).clamp(-1.0, 1.0);
+            for ch in out.iter_mut() {
+                ch[frame] = sample;
+            }
+        }
+    }
+}
+
+impl Synth for SineOscillator {
+    const PARAMS: &'static [ParamSpec] = &PARAMS;
+
+    fn new(sample_rate: f32) -> Self {
+        Self {
+            sample_rate,
+            voices: Voices::new(MAX_VOICES),
+            noise: Noise::new(1),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin::Plugin;
+    use crate::plugin::builtin::instrument::{peak, render};
+
+    const SR: f32 = 48000.0;
+
+    #[test]
+    fn envelope_fades_in_and_out() {
+        let mut synth = instrument::load::<SineOscillator>(SR);
+        synth.set_parameter(ATTACK as u32, 0.01).unwrap();
+        synth.set_parameter(RELEASE as u32, 0.1).unwrap();
+        let on = render(&mut synth, &[(0, [0x90, 69, 127])], 4800);
+        // No click: the first millisecond stays well below full level.
+        assert!(peak(&on[..48]) < 0.1 * peak(&on));
+        assert!((peak(&on[2400..]) - 0.5).abs() < 0.01);
+
+        // Still sounding just after note off, silent once released.
+        let off = render(&mut synth, &[(0, [0x80, 69, 0])], 480);
+        assert!(peak(&off) > 0.1);
+        render(&mut synth, &[], 48000);
+        assert_eq!(peak(&render(&mut synth, &[], 512)), 0.0);
+    }
+
+    #[test]
+    fn velocity_scales_level() {
+        instrument::assert_velocity_scales_level::<SineOscillator>();
+    }
+
+    #[test]
+    fn detune_shifts_pitch() {
+        let crossings = |detune: f32| {
+            let mut synth = instrument::load::<SineOscillator>(SR);
+            synth.set_parameter(DETUNE as u32, detune).unwrap();
+            let out = render(&mut synth, &[(0, [0x90, 69, 127])], 48000);
+            out.windows(2).filter(|w| w[0] < 0.0 && w[1] >= 0.0).count()
+        };
+        assert!((crossings(0.0) as i32 - 440).abs() <= 1);
+        // +100 cents is a semitone up.
+        assert!((crossings(100.0) as i32 - 466).abs() <= 1);
+    }
+
+    #[test]
+    fn every_preset_sounds() {
+        instrument::assert_presets_sound::<SineOscillator>();
+    }
+}
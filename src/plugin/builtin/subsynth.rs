@@ -0,0 +1,291 @@
+//! `builtin:subsynth`: a polyphonic subtractive synthesizer. Two
+//! band-limited oscillators (saw, square, triangle or noise) feed a resonant
+//! state-variable filter with its own ADSR, followed by an amp ADSR.
+//!
+//! Waveform parameters are `0` saw, `1` square, `2` triangle, `3` noise;
+//! filter type is `0` lowpass, `1` bandpass, `2` highpass.
+
+use std::ops::Range;
+
+use super::Builtin;
+use super::dsp::{
+    Adsr, FactoryPreset, FilterMode, Noise, Oscillator, ParamSpec, Params, Svf, Waveform,
+    note_to_freq,
+};
+use super::instrument::{self, Instrument, Synth, Voices};
+
+const MAX_VOICES: usize = 16;
+/// Per-voice gain, leaving headroom for chords.
+const VOICE_GAIN: f32 = 0.25;
+
+const OSC1_WAVE: usize = 0;
+const OSC1_LEVEL: usize = 1;
+const OSC2_WAVE: usize = 2;
+const OSC2_LEVEL: usize = 3;
+const OSC2_SEMITONES: usize = 4;
+const OSC2_DETUNE: usize = 5;
+const CUTOFF: usize = 6;
+const RESONANCE: usize = 7;
+const FILTER_TYPE: usize = 8;
+const FILTER_ENV: usize = 9;
+const KEY_TRACKING: usize = 10;
+const FILTER_ATTACK: usize = 11;
+const AMP_ATTACK: usize = 15;
+const VELOCITY_AMP: usize = 19;
+const VELOCITY_FILTER: usize = 20;
+const VOICES: usize = 21;
+const VOLUME: usize = 22;
+
+pub const BUILTIN: Builtin = instrument::builtin::<SubSynth>("subsynth");
+
+pub const PARAMS: [ParamSpec; 23] = [
+    ParamSpec::stepped("Osc 1 Wave", 0.0, 3.0, 0.0),
+    ParamSpec::new("Osc 1 Level", 0.0, 1.0, 0.8),
+    ParamSpec::stepped("Osc 2 Wave", 0.0, 3.0, 1.0),
+    ParamSpec::new("Osc 2 Level", 0.0, 1.0, 0.0),
+    ParamSpec::stepped("Osc 2 Semitones", -24.0, 24.0, 0.0),
+    ParamSpec::new("Osc 2 Detune", -50.0, 50.0, 7.0),
+    ParamSpec::new("Cutoff", 20.0, 20000.0, 2000.0),
+    ParamSpec::new("Resonance", 0.0, 1.0, 0.2),
+    ParamSpec::stepped("Filter Type", 0.0, 2.0, 0.0),
+    ParamSpec::new("Filter Env Amount", -6.0, 6.0, 2.0),
+    ParamSpec::new("Key Tracking", 0.0, 1.0, 0.5),
+    ParamSpec::new("Filter Attack", 0.001, 5.0, 0.005),
+    ParamSpec::new("Filter Decay", 0.001, 5.0, 0.3),
+    ParamSpec::new("Filter Sustain", 0.0, 1.0, 0.2),
+    ParamSpec::new("Filter Release", 0.001, 5.0, 0.3),
+    ParamSpec::new("Amp Attack", 0.001, 5.0, 0.005),
+    ParamSpec::new("Amp Decay", 0.001, 5.0, 0.3),
+    ParamSpec::new("Amp Sustain", 0.0, 1.0, 0.7),
+    ParamSpec::new("Amp Release", 0.001, 5.0, 0.2),
+    ParamSpec::new("Velocity > Amp", 0.0, 1.0, 0.7),
+    ParamSpec::new("Velocity > Filter", 0.0, 1.0, 0.3),
+    ParamSpec::stepped("Voices", 1.0, MAX_VOICES as f32, 8.0),
+    ParamSpec::new("Volume", 0.0, 1.0, 0.7),
+];
+
+pub const PRESETS: [FactoryPreset; 4] = [
+    FactoryPreset {
+        name: "Init",
+        values: &[],
+    },
+    FactoryPreset {
+        name: "Bass",
+        values: &[
+            ("Osc 2 Wave", 1.0),
+            ("Osc 2 Level", 0.6),
+            ("Osc 2 Semitones", -12.0),
+            ("Osc 2 Detune", 0.0),
+            ("Cutoff", 300.0),
+            ("Resonance", 0.4),
+            ("Filter Env Amount", 3.0),
+            ("Filter Decay", 0.2),
+            ("Filter Sustain", 0.0),
+            ("Amp Sustain", 0.9),
+            ("Amp Release", 0.08),
+            ("Voices", 1.0),
+        ],
+    },
+    FactoryPreset {
+        name: "Pad",
+        values: &[
+            ("Osc 2 Wave", 0.0),
+            ("Osc 2 Level", 0.7),
+            ("Osc 2 Detune", 12.0),
+            ("Cutoff", 1200.0),
+            ("Resonance", 0.1),
+            ("Filter Env Amount", 1.0),
+            ("Filter Attack", 1.5),
+            ("Filter Decay", 2.0),
+            ("Filter Sustain", 0.5),
+            ("Filter Release", 1.5),
+            ("Amp Attack", 0.8),
+            ("Amp Decay", 1.0),
+            ("Amp Sustain", 0.8),
+            ("Amp Release", 1.5),
+            ("Velocity > Amp", 0.3),
+        ],
+    },
+    FactoryPreset {
+        name: "Lead",
+        values: &[
+            ("Osc 1 Wave", 1.0),
+            ("Osc 2 Wave", 0.0),
+            ("Osc 2 Level", 0.5),
+            ("Osc 2 Semitones", 12.0),
+            ("Osc 2 Detune", 5.0),
+            ("Cutoff", 2500.0),
+            ("Resonance", 0.5),
+            ("Filter Env Amount", 1.5),
+            ("Filter Sustain", 0.4),
+            ("Amp Sustain", 0.8),
+            ("Voices", 4.0),
+        ],
+    },
+];
+
+#[derive(Clone, Default)]
+struct Voice {
+    key: u8,
+    velocity: f32,
+    osc1: Oscillator,
+    osc2: Oscillator,
+    amp_env: Adsr,
+    filter_env: Adsr,
+    filter: Svf,
+}
+
+impl instrument::Voice for Voice {
+    fn key(&self) -> u8 {
+        self.key
+    }
+
+    fn is_idle(&self) -> bool {
+        self.amp_env.is_idle()
+    }
+
+    fn released_level(&self) -> Option<f32> {
+        self.amp_env.is_released().then(|| self.amp_env.level())
+    }
+
+    fn note_on(&mut self, key: u8, velocity: f32) {
+        if self.amp_env.is_idle() {
+            self.osc1.reset();
+            self.osc2.reset();
+            self.filter.reset();
+        }
+        self.key = key;
+        self.velocity = velocity;
+        self.amp_env.gate_on();
+        self.filter_env.gate_on();
+    }
+
+    fn note_off(&mut self) {
+        self.amp_env.gate_off();
+        self.filter_env.gate_off();
+    }
+}
+
+pub struct SubSynth {
+    sample_rate: f32,
+    voices: Voices<Voice>,
+    noise: Noise,
+}
+
+fn voice_limit(params: &Params) -> usize {
+    params.choice(VOICES).clamp(1, MAX_VOICES)
+}
+
+impl Instrument for SubSynth {
+    const NAME: &'static str = "Subtractive Synth";
+    const PRESETS: &'static [FactoryPreset] = &PRESETS;
+
+    fn handle_midi(&mut self, params: &Params, message: [u8; 3]) {
+        self.voices.handle_midi(message, voice_limit(params));
+    }
+
+    fn render(&mut self, params: &Params, out: &mut [&mut [f32]], frames: Range<usize>) {
+        let sr = self.sample_rate;
+        self.voices.release_above(voice_limit(params));
+
+        // Parameters are read once per block, including modulation.
+        let p = params;
+        let wave1 = Waveform::from_index(p.choice(OSC1_WAVE));
+        let wave2 = Waveform::from_index(p.choice(OSC2_WAVE));
+        let level1 = p.get(OSC1_LEVEL);
+        let level2 = p.get(OSC2_LEVEL);
+        let osc2_offset = p.get(OSC2_SEMITONES) + p.get(OSC2_DETUNE) / 100.0;
+        let cutoff = p.get(CUTOFF);
+        let resonance = p.get(RESONANCE);
+        let mode = FilterMode::from_index(p.choice(FILTER_TYPE));
+        let filter_env_amount = p.get(FILTER_ENV);
+        let key_tracking = p.get(KEY_TRACKING);
+        let filter_adsr = [0, 1, 2, 3].map(|i| p.get(FILTER_ATTACK + i));
+        let amp_adsr = [0, 1, 2, 3].map(|i| p.get(AMP_ATTACK + i));
+        let velocity_amp = p.get(VELOCITY_AMP);
+        let velocity_filter = p.get(VELOCITY_FILTER);
+        let gain = p.get(VOLUME) * VOICE_GAIN;
+
+        let bend = self.voices.bend();
+        for frame in frames {
+            let mut sample = 0.0;
+            for voice in self.voices.iter_mut() {
+                if voice.amp_env.is_idle() {
+                    continue;
+                }
+                let note = voice.key as f32 + bend;
+                let dt1 = note_to_freq(note) / sr;
+                let dt2 = note_to_freq(note + osc2_offset) / sr;
+                let mut osc = 0.0;
+                if level1 > 0.0 {
+                    osc += level1 * voice.osc1.next(wave1, dt1, &mut self.noise);
+                }
+                if level2 > 0.0 {
+                    osc += level2 * voice.osc2.next(wave2, dt2, &mut self.noise);
+                }
+
+                let [a, d, s, r] = filter_adsr;
+                let filter_env = voice.filter_env.next(a, d, s, r, sr);
+                let velocity = voice.velocity;
+                let env_octaves =
+                    filter_env_amount * filter_env * (1.0 - velocity_filter * (1.0 - velocity));
+                let track_octaves = key_tracking * (note - 60.0) / 12.0;
+                let voice_cutoff = cutoff * 2.0_f32.powf(env_octaves + track_octaves);
+                let filtered = voice.filter.process(osc, voice_cutoff, resonance, mode, sr);
+
+                let [a, d, s, r] = amp_adsr;
+                let amp = voice.amp_env.next(a, d, s, r, sr);
+                let velocity_gain = 1.0 - velocity_amp * (1.0 - velocity);
+                sample += filtered * amp * velocity_gain;
+            }
+
+            let sample = sample * gain;
+            for ch in out.iter_mut() {
+                ch[frame] = sample;
+            }
+        }
+    }
+}
+
+impl Synth for SubSynth {
+    const PARAMS: &'static [ParamSpec] = &PARAMS;
+
+    fn new(sample_rate: f32) -> Self {
+        Self {
+            sample_rate,
+            voices: Voices::new(MAX_VOICES),
+            noise: Noise::new(0x2545_f491),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin::Plugin;
+    use crate::plugin::builtin::instrument::render;
+
+    const SR: f32 = 48000.0;
+
+    #[test]
+    fn every_preset_sounds_and_releases() {
+        instrument::assert_presets_sound::<SubSynth>();
+    }
+
+    #[test]
+    fn velocity_scales_level() {
+        instrument::assert_velocity_scales_level::<SubSynth>();
+    }
+
+    #[test]
+    fn modulation_offsets_last_one_block() {
+        let mut synth = instrument::load::<SubSynth>(SR);
+        assert!(synth.supports_modulation(CUTOFF as u32));
+        assert!(!synth.supports_modulation(OSC1_WAVE as u32));
+        synth.set_modulation(CUTOFF as u32, 1000.0).unwrap();
+        assert_eq!(synth.params.get(CUTOFF), 3000.0);
+        render(&mut synth, &[], 64);
+        assert_eq!(synth.params.get(CUTOFF), 2000.0);
+        assert_eq!(synth.get_parameter(CUTOFF as u32), Some(2000.0));
+    }
+}
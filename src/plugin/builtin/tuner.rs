@@ -0,0 +1,265 @@
+//! `builtin:tuner`: passes audio through unchanged and shows the detected
+//! pitch, the nearest note and its deviation in cents as meters. Useful
+//! after a remapped or detuned instrument to check where notes really land.
+//!
+//! Pitch is detected with the YIN difference function over blocks of
+//! mono-summed input. The difference for one lag is computed per incoming
+//! sample, so analysing a block is spread over the first half of the next
+//! one rather than done in a single audio callback.
+
+use super::Builtin;
+use super::dsp::{ParamSpec, Params};
+use super::effect::{self, Effect};
+use crate::plugin::{MeterInfo, MeterValue};
+
+pub const BUILTIN: Builtin = effect::builtin::<Tuner>("tuner");
+
+const REFERENCE: usize = 0;
+
+/// Lowest and highest detectable pitch in Hz.
+const MIN_FREQ: f32 = 40.0;
+const MAX_FREQ: f32 = 2000.0;
+
+/// YIN threshold on the normalised difference; lower is stricter.
+const THRESHOLD: f32 = 0.15;
+
+/// Blocks quieter than this RMS (-60 dBFS) show no pitch.
+const SILENCE: f32 = 1e-3;
+
+pub struct Tuner {
+    sample_rate: f32,
+    /// Input being collected: twice the longest period.
+    buffer: Vec<f32>,
+    filled: usize,
+    /// The previous full buffer, being analysed.
+    analysis: Vec<f32>,
+    /// Normalised difference per lag, reused between blocks.
+    diff: Vec<f32>,
+    /// Next lag to compute for `analysis`; 0 when idle.
+    lag: usize,
+    /// Sum of the differences up to `lag`.
+    running: f32,
+    frequency: MeterValue,
+    note: MeterValue,
+    cents: MeterValue,
+}
+
+impl Tuner {
+    /// Swaps in a full buffer for analysis, publishing straight away if
+    /// it is silent.
+    fn start_analysis(&mut self, reference: f32) {
+        std::mem::swap(&mut self.buffer, &mut self.analysis);
+        let len = self.analysis.len() as f32;
+        let rms = (self.analysis.iter().map(|s| s * s).sum::<f32>() / len).sqrt();
+        if rms < SILENCE {
+            self.publish(None, reference);
+            return;
+        }
+        self.diff[0] = 1.0;
+        self.running = 0.0;
+        self.lag = 1;
+    }
+
+    /// Computes the cumulative mean normalised difference for the next lag,
+    /// publishing the pitch after the last one.
+    fn step(&mut self, reference: f32) {
+        if self.lag == 0 {
+            return;
+        }
+        let max_lag = self.diff.len() - 1;
+        let lag = self.lag;
+        let d: f32 = self.analysis[..max_lag]
+            .iter()
+            .zip(&self.analysis[lag..lag + max_lag])
+            .map(|(a, b)| (a - b) * (a - b))
+            .sum();
+        self.running += d;
+        self.diff[lag] = if self.running > 0.0 {
+            d * lag as f32 / self.running
+        } else {
+            1.0
+        };
+
+        if lag == max_lag {
+            self.lag = 0;
+            let frequency = self.pitch();
+            self.publish(frequency, reference);
+        } else {
+            self.lag += 1;
+        }
+    }
+
+    /// Frequency in Hz from a complete difference function, if it has a
+    /// clear pitch.
+    fn pitch(&self) -> Option<f32> {
+        let max_lag = self.diff.len() - 1;
+        let min_lag = ((self.sample_rate / MAX_FREQ) as usize).max(2);
+
+        // First dip under the threshold, followed down to its minimum.
+        let mut lag = (min_lag..max_lag).find(|&l| self.diff[l] < THRESHOLD)?;
+        while lag + 1 < max_lag && self.diff[lag + 1] < self.diff[lag] {
+            lag += 1;
+        }
+
+        // Parabolic interpolation between neighbouring lags.
+        let (a, b, c) = (self.diff[lag - 1], self.diff[lag], self.diff[lag + 1]);
+        let denom = a - 2.0 * b + c;
+        let offset = if denom.abs() > f32::EPSILON {
+            (0.5 * (a - c) / denom).clamp(-0.5, 0.5)
+        } else {
+            0.0
+        };
+        Some(self.sample_rate / (lag as f32 + offset))
+    }
+
+    fn publish(&self, frequency: Option<f32>, reference: f32) {
+        match frequency {
+            Some(freq) => {
+                let note = 69.0 + 12.0 * (freq / reference).log2();
+                let nearest = note.round();
+                self.frequency.set(freq);
+                self.note.set(nearest);
+                self.cents.set((note - nearest) * 100.0);
+            }
+            None => {
+                self.frequency.set(0.0);
+                self.note.set(0.0);
+                self.cents.set(0.0);
+            }
+        }
+    }
+}
+
+impl Effect for Tuner {
+    const NAME: &'static str = "Tuner";
+    const PARAMS: &'static [ParamSpec] = &[ParamSpec::new("Reference", 415.0, 466.0, 440.0)];
+
+    fn new(sample_rate: f32) -> Self {
+        let max_lag = (sample_rate / MIN_FREQ) as usize;
+        Self {
+            sample_rate,
+            buffer: vec![0.0; 2 * max_lag],
+            filled: 0,
+            analysis: vec![0.0; 2 * max_lag],
+            diff: vec![0.0; max_lag + 1],
+            lag: 0,
+            running: 0.0,
+            frequency: MeterValue::default(),
+            note: MeterValue::default(),
+            cents: MeterValue::default(),
+        }
+    }
+
+    fn process(&mut self, params: &Params, left: &mut [f32], right: &mut [f32]) {
+        let reference = params.get(REFERENCE);
+        for (l, r) in left.iter().zip(right.iter()) {
+            self.buffer[self.filled] = 0.5 * (l + r);
+            self.filled += 1;
+            if self.filled == self.buffer.len() {
+                // One lag per sample finishes halfway through the next
+                // buffer, so the previous analysis is already done.
+                while self.lag != 0 {
+                    self.step(reference);
+                }
+                self.start_analysis(reference);
+                self.filled = 0;
+            } else {
+                self.step(reference);
+            }
+        }
+    }
+
+    fn meters(&self) -> Vec<MeterInfo> {
+        vec![
+            MeterInfo {
+                name: "Frequency".into(),
+                min: 0.0,
+                max: MAX_FREQ,
+                value: self.frequency.clone(),
+            },
+            MeterInfo {
+                name: "Note".into(),
+                min: 0.0,
+                max: 127.0,
+                value: self.note.clone(),
+            },
+            MeterInfo {
+                name: "Cents".into(),
+                min: -50.0,
+                max: 50.0,
+                value: self.cents.clone(),
+            },
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin::builtin::effect::{self, run, sine};
+
+    const SR: f32 = 48000.0;
+
+    fn readings(plugin: &dyn crate::plugin::Plugin) -> (f32, f32, f32) {
+        let meters = plugin.meters();
+        (
+            meters[0].value.get(),
+            meters[1].value.get(),
+            meters[2].value.get(),
+        )
+    }
+
+    #[test]
+    fn detects_pitch_and_cents() {
+        let mut tuner = effect::load::<Tuner>(SR);
+        let input = sine(440.0, 0.5, SR, 9600);
+        let (out_l, _) = run(tuner.as_mut(), &input, &input);
+        // Audio passes through untouched.
+        assert_eq!(out_l, input);
+        let (freq, note, cents) = readings(tuner.as_ref());
+        assert!((freq - 440.0).abs() < 0.5, "{freq}");
+        assert_eq!(note, 69.0);
+        assert!(cents.abs() < 2.0, "{cents}");
+
+        // A low E 20 cents sharp.
+        let freq = 82.4069 * 2.0_f32.powf(20.0 / 1200.0);
+        let input = sine(freq, 0.5, SR, 9600);
+        run(tuner.as_mut(), &input, &input);
+        let (_, note, cents) = readings(tuner.as_ref());
+        assert_eq!(note, 40.0);
+        assert!((cents - 20.0).abs() < 2.0, "{cents}");
+    }
+
+    #[test]
+    fn detects_pitch_over_small_blocks() {
+        let mut tuner = effect::load::<Tuner>(SR);
+        let input = sine(440.0, 0.5, SR, 9600);
+        for block in input.chunks(64) {
+            run(tuner.as_mut(), block, block);
+        }
+        let (freq, note, _) = readings(tuner.as_ref());
+        assert!((freq - 440.0).abs() < 0.5, "{freq}");
+        assert_eq!(note, 69.0);
+    }
+
+    #[test]
+    fn reference_pitch_shifts_reading() {
+        let mut tuner = effect::load::<Tuner>(SR);
+        tuner.set_parameter(REFERENCE as u32, 442.0).unwrap();
+        let input = sine(442.0, 0.5, SR, 9600);
+        run(tuner.as_mut(), &input, &input);
+        let (_, note, cents) = readings(tuner.as_ref());
+        assert_eq!(note, 69.0);
+        assert!(cents.abs() < 2.0, "{cents}");
+    }
+
+    #[test]
+    fn silence_shows_no_pitch() {
+        let mut tuner = effect::load::<Tuner>(SR);
+        let input = sine(440.0, 0.5, SR, 9600);
+        run(tuner.as_mut(), &input, &input);
+        let silence = vec![0.0; 9600];
+        run(tuner.as_mut(), &silence, &silence);
+        assert_eq!(readings(tuner.as_ref()), (0.0, 0.0, 0.0));
+    }
+}
@@ -0,0 +1,131 @@
+//! `builtin:utility`: gain in dB, constant-power pan, stereo width,
+//! polarity flip and mono summing, for balancing splits.
+
+use std::f32::consts::FRAC_PI_4;
+
+use super::Builtin;
+use super::dsp::{ParamSpec, Params};
+use super::effect::{self, Effect, db_to_gain};
+
+pub const BUILTIN: Builtin = effect::builtin::<Utility>("utility");
+
+const GAIN: usize = 0;
+const PAN: usize = 1;
+const WIDTH: usize = 2;
+const INVERT_LEFT: usize = 3;
+const INVERT_RIGHT: usize = 4;
+const MONO: usize = 5;
+
+pub struct Utility {
+    /// Channel gains applied at the end of the previous block; gains ramp
+    /// from these over each block so changes don't click.
+    gains: [f32; 2],
+}
+
+impl Utility {
+    /// Per-channel gain from gain, pan and polarity.
+    fn target_gains(params: &Params) -> [f32; 2] {
+        let gain = db_to_gain(params.get(GAIN));
+        // Constant-power pan law, normalised to unity at the centre.
+        let angle = (params.get(PAN) + 1.0) * FRAC_PI_4;
+        let (sin, cos) = angle.sin_cos();
+        let polarity = |index| if params.choice(index) == 1 { -1.0 } else { 1.0 };
+        [
+            gain * cos * std::f32::consts::SQRT_2 * polarity(INVERT_LEFT),
+            gain * sin * std::f32::consts::SQRT_2 * polarity(INVERT_RIGHT),
+        ]
+    }
+}
+
+impl Effect for Utility {
+    const NAME: &'static str = "Utility";
+    const PARAMS: &'static [ParamSpec] = &[
+        ParamSpec::new("Gain", -60.0, 24.0, 0.0),
+        ParamSpec::new("Pan", -1.0, 1.0, 0.0),
+        ParamSpec::new("Width", 0.0, 2.0, 1.0),
+        ParamSpec::stepped("Invert Left", 0.0, 1.0, 0.0),
+        ParamSpec::stepped("Invert Right", 0.0, 1.0, 0.0),
+        ParamSpec::stepped("Mono", 0.0, 1.0, 0.0),
+    ];
+
+    fn new(_sample_rate: f32) -> Self {
+        Self { gains: [1.0, 1.0] }
+    }
+
+    fn process(&mut self, params: &Params, left: &mut [f32], right: &mut [f32]) {
+        let width = if params.choice(MONO) == 1 {
+            0.0
+        } else {
+            params.get(WIDTH)
+        };
+        let target = Self::target_gains(params);
+        let start = self.gains;
+        let frames = left.len().max(1) as f32;
+
+        for (i, (l, r)) in left.iter_mut().zip(right.iter_mut()).enumerate() {
+            let mid = (*l + *r) * 0.5;
+            let side = (*l - *r) * 0.5 * width;
+            let t = (i + 1) as f32 / frames;
+            *l = (mid + side) * (start[0] + (target[0] - start[0]) * t);
+            *r = (mid - side) * (start[1] + (target[1] - start[1]) * t);
+        }
+        self.gains = target;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin::builtin::effect::{self, run};
+
+    const SR: f32 = 48000.0;
+
+    /// Output for a constant stereo input, after gains have settled.
+    fn settle(utility: &mut dyn crate::plugin::Plugin, l: f32, r: f32) -> (f32, f32) {
+        run(utility, &[l; 64], &[r; 64]);
+        let (out_l, out_r) = run(utility, &[l; 64], &[r; 64]);
+        (out_l[63], out_r[63])
+    }
+
+    fn close(a: (f32, f32), b: (f32, f32)) -> bool {
+        (a.0 - b.0).abs() < 1e-4 && (a.1 - b.1).abs() < 1e-4
+    }
+
+    #[test]
+    fn neutral_by_default() {
+        let mut utility = effect::load::<Utility>(SR);
+        assert!(close(settle(utility.as_mut(), 0.3, -0.2), (0.3, -0.2)));
+    }
+
+    #[test]
+    fn gain_pan_width_polarity_mono() {
+        let mut utility = effect::load::<Utility>(SR);
+        utility.set_parameter(GAIN as u32, -6.0206).unwrap();
+        assert!(close(settle(utility.as_mut(), 0.5, 0.5), (0.25, 0.25)));
+
+        utility.set_parameter(GAIN as u32, 0.0).unwrap();
+        utility.set_parameter(PAN as u32, 1.0).unwrap();
+        let (l, r) = settle(utility.as_mut(), 0.5, 0.5);
+        assert!(l.abs() < 1e-4 && (r - 0.5 * 2.0_f32.sqrt()).abs() < 1e-4);
+
+        utility.set_parameter(PAN as u32, 0.0).unwrap();
+        utility.set_parameter(WIDTH as u32, 0.0).unwrap();
+        assert!(close(settle(utility.as_mut(), 1.0, 0.0), (0.5, 0.5)));
+        utility.set_parameter(WIDTH as u32, 2.0).unwrap();
+        assert!(close(settle(utility.as_mut(), 1.0, 0.0), (1.5, -0.5)));
+
+        utility.set_parameter(MONO as u32, 1.0).unwrap();
+        utility.set_parameter(INVERT_RIGHT as u32, 1.0).unwrap();
+        assert!(close(settle(utility.as_mut(), 1.0, 0.0), (0.5, -0.5)));
+    }
+
+    #[test]
+    fn gain_changes_ramp() {
+        let mut utility = effect::load::<Utility>(SR);
+        utility.set_parameter(GAIN as u32, -60.0).unwrap();
+        let (out, _) = run(utility.as_mut(), &[1.0; 100], &[1.0; 100]);
+        assert!(out[0] > 0.9);
+        assert!(out.windows(2).all(|w| w[1] <= w[0]));
+        assert!(out[99] < 0.01);
+    }
+}
@@ -3,7 +3,8 @@ use std::mem::MaybeUninit;
 
 use crossbeam_channel::{Receiver, Sender};
 
-use super::Plugin;
+use super::master::MasterBus;
+use super::{ParameterInfo, Plugin, Preset};
 use crate::session::{self, RemapTarget};
 
 /// Maximum number of audio channels supported (for stack-allocated reference arrays).
@@ -342,6 +343,19 @@ pub enum ModSource {
     },
 }
 
+/// Per-voice envelope state, used when the target plugin supports
+/// per-voice modulation.
+#[derive(Debug, Clone)]
+struct VoiceEnvelope {
+    channel: u8,
+    key: u8,
+    state: EnvState,
+    level: f32,
+}
+
+/// Maximum number of per-voice envelopes tracked by one modulator.
+const MAX_ENVELOPE_VOICES: usize = 64;
+
 /// A block-rate modulator with a source (LFO or Envelope) and targets.
 #[derive(Debug, Clone)]
 pub struct Modulator {
@@ -350,6 +364,50 @@ pub struct Modulator {
     pub targets: Vec<ModTarget>,
     /// Last computed output value (bipolar -1..1 for LFO, unipolar 0..1 for envelope).
     pub last_output: f32,
+    /// Envelope sources also run one envelope per held note, for plugins
+    /// that accept per-voice modulation.
+    voices: Vec<VoiceEnvelope>,
+}
+
+/// Advance an ADSR envelope by `dt` seconds.
+fn advance_envelope(
+    state: &mut EnvState,
+    level: &mut f32,
+    (attack, decay, sustain, release): (f32, f32, f32, f32),
+    dt: f32,
+) {
+    match *state {
+        EnvState::Idle => {
+            *level = 0.0;
+        }
+        EnvState::Attack => {
+            let rate = if attack > 0.0 { dt / attack } else { 1.0 };
+            *level += rate;
+            if *level >= 1.0 {
+                *level = 1.0;
+                *state = EnvState::Decay;
+            }
+        }
+        EnvState::Decay => {
+            let rate = if decay > 0.0 { dt / decay } else { 1.0 };
+            *level -= rate * (1.0 - sustain);
+            if *level <= sustain {
+                *level = sustain;
+                *state = EnvState::Sustain;
+            }
+        }
+        EnvState::Sustain => {
+            *level = sustain;
+        }
+        EnvState::Release => {
+            let rate = if release > 0.0 { dt / release } else { 1.0 };
+            *level -= rate * (*level).max(0.001);
+            if *level <= 0.001 {
+                *level = 0.0;
+                *state = EnvState::Idle;
+            }
+        }
+    }
 }
 
 impl Modulator {
@@ -359,6 +417,7 @@ impl Modulator {
             sample_rate,
             targets: Vec::new(),
             last_output: 0.0,
+            voices: Vec::with_capacity(MAX_ENVELOPE_VOICES),
         }
     }
 
@@ -371,14 +430,31 @@ impl Modulator {
                 self.last_output = waveform.eval(*phase);
             }
             ModSource::Envelope { attack, decay, sustain, release, state, level, notes_held } => {
+                // Voices that went idle last buffer have had their final zero
+                // offset applied; forget them now.
+                self.voices.retain(|v| v.state != EnvState::Idle);
+
                 // Process MIDI events for note-on/off.
                 for &(_frame, bytes) in midi_events {
                     let status_type = bytes[0] & 0xF0;
+                    let (channel, key) = (bytes[0] & 0x0F, bytes[1]);
                     match status_type {
                         0x90 if bytes[2] > 0 => {
                             // Note-on: retrigger from Attack.
                             *notes_held = notes_held.saturating_add(1);
                             *state = EnvState::Attack;
+                            // Per-voice: restart this key's envelope, stealing
+                            // the oldest voice when full.
+                            self.voices.retain(|v| v.channel != channel || v.key != key);
+                            if self.voices.len() == MAX_ENVELOPE_VOICES {
+                                self.voices.remove(0);
+                            }
+                            self.voices.push(VoiceEnvelope {
+                                channel,
+                                key,
+                                state: EnvState::Attack,
+                                level: 0.0,
+                            });
                         }
                         0x80 | 0x90 => {
                             // Note-off.
@@ -386,44 +462,22 @@ impl Modulator {
                             if *notes_held == 0 {
                                 *state = EnvState::Release;
                             }
+                            for v in self.voices.iter_mut() {
+                                if v.channel == channel && v.key == key {
+                                    v.state = EnvState::Release;
+                                }
+                            }
                         }
                         _ => {}
                     }
                 }
 
-                // Advance envelope state machine.
+                // Advance envelope state machines.
                 let dt = buffer_size as f32 / self.sample_rate;
-                match *state {
-                    EnvState::Idle => {
-                        *level = 0.0;
-                    }
-                    EnvState::Attack => {
-                        let rate = if *attack > 0.0 { dt / *attack } else { 1.0 };
-                        *level += rate;
-                        if *level >= 1.0 {
-                            *level = 1.0;
-                            *state = EnvState::Decay;
-                        }
-                    }
-                    EnvState::Decay => {
-                        let rate = if *decay > 0.0 { dt / *decay } else { 1.0 };
-                        *level -= rate * (1.0 - *sustain);
-                        if *level <= *sustain {
-                            *level = *sustain;
-                            *state = EnvState::Sustain;
-                        }
-                    }
-                    EnvState::Sustain => {
-                        *level = *sustain;
-                    }
-                    EnvState::Release => {
-                        let rate = if *release > 0.0 { dt / *release } else { 1.0 };
-                        *level -= rate * (*level).max(0.001);
-                        if *level <= 0.001 {
-                            *level = 0.0;
-                            *state = EnvState::Idle;
-                        }
-                    }
+                let adsr = (*attack, *decay, *sustain, *release);
+                advance_envelope(state, level, adsr, dt);
+                for v in self.voices.iter_mut() {
+                    advance_envelope(&mut v.state, &mut v.level, adsr, dt);
                 }
                 self.last_output = *level;
             }
@@ -517,7 +571,13 @@ enum CrossModField {
 /// modulators target the same parameter. Each parameter gets:
 ///   base_value + sum(depth_i * output_i * range)
 /// This prevents the last-modulator-wins overwrite bug.
-fn apply_modulators_to_plugin(modulators: &[Modulator], plugin: &mut dyn Plugin) {
+///
+/// Plugins that support modulation offsets receive the summed offset instead,
+/// so the parameter's own value (and the plugin's UI/state) keeps the base.
+/// When `per_voice` is set (instruments) and the plugin supports per-voice
+/// modulation of a parameter, envelope modulators drive each voice with its
+/// own envelope instead of the shared one.
+fn apply_modulators_to_plugin(modulators: &[Modulator], plugin: &mut dyn Plugin, per_voice: bool) {
     // Collect (param_index, base_value, min, max, total_offset).
     // We use a small vec since most plugins have few modulated params.
     let mut accum: Vec<(u32, f32, f32, f32, f32)> = Vec::new();
@@ -526,6 +586,16 @@ fn apply_modulators_to_plugin(modulators: &[Modulator], plugin: &mut dyn Plugin)
         for target in &m.targets {
             if let ModTargetKind::PluginParam { param_index } = target.kind {
                 let range = target.param_max - target.param_min;
+                if per_voice
+                    && matches!(m.source, ModSource::Envelope { .. })
+                    && plugin.supports_voice_modulation(param_index)
+                {
+                    for v in &m.voices {
+                        let offset = v.level * target.depth * range;
+                        let _ = plugin.set_voice_modulation(param_index, v.channel, v.key, offset);
+                    }
+                    continue;
+                }
                 let offset = m.last_output * target.depth * range;
                 if let Some(entry) = accum.iter_mut().find(|e| e.0 == param_index) {
                     // Accumulate offset; base_value/min/max are the same for all
@@ -540,7 +610,11 @@ fn apply_modulators_to_plugin(modulators: &[Modulator], plugin: &mut dyn Plugin)
 
     for (param_index, base_value, min, max, total_offset) in accum {
         let modulated = (base_value + total_offset).clamp(min, max);
-        let _ = plugin.set_parameter(param_index, modulated);
+        if plugin.supports_modulation(param_index) {
+            let _ = plugin.set_modulation(param_index, modulated - base_value);
+        } else {
+            let _ = plugin.set_parameter(param_index, modulated);
+        }
     }
 }
 
@@ -810,7 +884,8 @@ pub enum GraphCommand {
         split_a: usize,
         split_b: usize,
     },
-    /// Set the global BPM (applied to all pattern players).
+    /// Set the global BPM (applied to all pattern players and passed to
+    /// plugins for tempo sync).
     SetGlobalBpm {
         bpm: f32,
     },
@@ -832,6 +907,84 @@ pub enum GraphCommand {
         split: usize,
         semitones: i8,
     },
+    /// Put `plugin` in a slot (0 = instrument, 1..N = effects) and send the
+    /// plugin it replaces back on `reply`, or on the return channel if
+    /// `reply` is `None`. Preset and state IO must not run on the audio
+    /// thread, so the main thread swaps a [`Placeholder`] in, works on the
+    /// real plugin and swaps it back. If the slot is gone, `plugin` goes
+    /// back on the return channel and `reply` is dropped.
+    ExchangePlugin {
+        kb: usize,
+        split: usize,
+        slot: usize,
+        plugin: Box<dyn Plugin>,
+        reply: Option<Sender<Box<dyn Plugin>>>,
+    },
+}
+
+/// Stands in for a plugin while it is away on the main thread: passes audio
+/// straight through, or outputs silence in an instrument slot.
+pub struct Placeholder {
+    name: String,
+    is_instrument: bool,
+    sample_rate: f32,
+}
+
+impl Placeholder {
+    pub fn new(name: &str, is_instrument: bool, sample_rate: f32) -> Self {
+        Placeholder {
+            name: name.to_string(),
+            is_instrument,
+            sample_rate,
+        }
+    }
+}
+
+impl Plugin for Placeholder {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn is_instrument(&self) -> bool {
+        self.is_instrument
+    }
+    fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+    fn audio_output_count(&self) -> usize {
+        2
+    }
+    fn audio_input_count(&self) -> usize {
+        if self.is_instrument { 0 } else { 2 }
+    }
+    fn process(
+        &mut self,
+        _midi_events: &[(u64, [u8; 3])],
+        audio_in: &[&[f32]],
+        audio_out: &mut [&mut [f32]],
+    ) -> anyhow::Result<()> {
+        for (ch, out) in audio_out.iter_mut().enumerate() {
+            match audio_in.get(ch) {
+                Some(input) => out.copy_from_slice(input),
+                None => out.fill(0.0),
+            }
+        }
+        Ok(())
+    }
+    fn parameters(&self) -> Vec<ParameterInfo> {
+        Vec::new()
+    }
+    fn get_parameter(&mut self, _index: u32) -> Option<f32> {
+        None
+    }
+    fn set_parameter(&mut self, _index: u32, _value: f32) -> anyhow::Result<()> {
+        Ok(())
+    }
+    fn presets(&self) -> Vec<Preset> {
+        Vec::new()
+    }
+    fn load_preset(&mut self, id: &str) -> anyhow::Result<()> {
+        anyhow::bail!("{} is busy, cannot load preset {id}", self.name)
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -1361,6 +1514,10 @@ struct SplitLane {
     pattern: PatternPlayer,
     /// Transpose in semitones applied to note events.
     transpose: i8,
+    /// Send All Notes Off to the instrument before its next block: it was
+    /// just swapped in and missed the note-offs sent to the one it replaced.
+    notes_off_pending: bool,
+    notes_off_events: Vec<(u64, [u8; 3])>,
 }
 
 impl SplitLane {
@@ -1382,6 +1539,8 @@ impl SplitLane {
             effect_modulators: Vec::new(),
             pattern: PatternPlayer::new(48000.0),
             transpose: 0,
+            notes_off_pending: false,
+            notes_off_events: Vec::with_capacity(128 + 16),
         }
     }
 
@@ -1477,6 +1636,16 @@ impl SplitLane {
             effective_events
         };
 
+        let effective_events = if std::mem::take(&mut self.notes_off_pending) {
+            self.notes_off_events.clear();
+            self.notes_off_events
+                .extend((0..16).map(|ch| (0, [0xB0 | ch, 123, 0])));
+            self.notes_off_events.extend_from_slice(effective_events);
+            self.notes_off_events.as_slice()
+        } else {
+            effective_events
+        };
+
         // Apply modulators (block-rate: once per buffer, before instrument processing).
         // Three-pass: tick all → apply cross-mod → apply plugin targets.
         let buffer_size = split_out.first().map(|b| b.len()).unwrap_or(0);
@@ -1490,7 +1659,7 @@ impl SplitLane {
                 // Pass 2: cross-mod.
                 apply_cross_mod(&mut self.inst_modulators);
                 // Pass 3: apply plugin-param targets (summing all modulators).
-                apply_modulators_to_plugin(&self.inst_modulators, inst.as_mut());
+                apply_modulators_to_plugin(&self.inst_modulators, inst.as_mut(), true);
             }
             // Effect modulators.
             for (fx, mods) in self.effects.iter_mut().zip(self.effect_modulators.iter_mut()) {
@@ -1498,7 +1667,7 @@ impl SplitLane {
                     m.tick(buffer_size, effective_events);
                 }
                 apply_cross_mod(mods);
-                apply_modulators_to_plugin(mods, fx.as_mut());
+                apply_modulators_to_plugin(mods, fx.as_mut(), false);
             }
         }
 
@@ -1664,6 +1833,10 @@ pub struct AudioGraph {
     return_tx: Sender<Box<dyn Plugin>>,
     /// Notification channel for pattern recording completion.
     pattern_tx: Option<Sender<PatternNotification>>,
+    /// Global tempo, passed on to plugins via [`Plugin::set_tempo`].
+    bpm: f32,
+    /// Sanitiser and limiter applied to the summed output.
+    master: Option<MasterBus>,
 }
 
 impl AudioGraph {
@@ -1681,9 +1854,16 @@ impl AudioGraph {
             command_rx,
             return_tx,
             pattern_tx: None,
+            bpm: 120.0,
+            master: None,
         }
     }
 
+    /// Process the summed output through `master` before it reaches the device.
+    pub fn set_master(&mut self, master: MasterBus) {
+        self.master = Some(master);
+    }
+
     /// Set the notification channel for pattern recording completion.
     pub fn set_pattern_tx(&mut self, tx: Sender<PatternNotification>) {
         self.pattern_tx = Some(tx.clone());
@@ -1708,10 +1888,12 @@ impl AudioGraph {
                 GraphCommand::SwapInstrument {
                     kb,
                     split,
-                    instrument: new_inst,
+                    instrument: mut new_inst,
                     inst_buf,
                     remapper,
                 } => {
+                    new_inst.set_tempo(self.bpm);
+                    new_inst.set_routed_outputs(self.num_channels);
                     if let Some(lane) = self.get_split_mut(kb, split) {
                         lane.inst_buf = inst_buf;
                         lane.remapper = remapper;
@@ -1724,9 +1906,10 @@ impl AudioGraph {
                     kb,
                     split,
                     index,
-                    effect,
+                    mut effect,
                     mix,
                 } => {
+                    effect.set_tempo(self.bpm);
                     let num_channels = self.num_channels;
                     if let Some(lane) = self.get_split_mut(kb, split) {
                         if effect.audio_output_count() != num_channels {
@@ -2163,9 +2346,16 @@ impl AudioGraph {
                     }
                 }
                 GraphCommand::SetGlobalBpm { bpm } => {
+                    self.bpm = bpm;
                     for kb in &mut self.keyboards {
                         for sp in &mut kb.splits {
                             sp.pattern.bpm = bpm;
+                            if let Some(inst) = &mut sp.instrument {
+                                inst.set_tempo(bpm);
+                            }
+                            for effect in &mut sp.effects {
+                                effect.set_tempo(bpm);
+                            }
                         }
                     }
                 }
@@ -2184,6 +2374,30 @@ impl AudioGraph {
                         lane.transpose = semitones;
                     }
                 }
+                GraphCommand::ExchangePlugin { kb, split, slot, mut plugin, reply } => {
+                    plugin.set_tempo(self.bpm);
+                    if slot == 0 {
+                        plugin.set_routed_outputs(self.num_channels);
+                        if let Some(lane) = self.get_split_mut(kb, split) {
+                            lane.notes_off_pending = true;
+                        }
+                    }
+                    match self.get_plugin_mut(kb, split, slot) {
+                        Some(current) => {
+                            let old = std::mem::replace(current, plugin);
+                            let old = match reply {
+                                Some(reply) => reply.try_send(old).err().map(|e| e.into_inner()),
+                                None => Some(old),
+                            };
+                            if let Some(old) = old {
+                                let _ = self.return_tx.try_send(old);
+                            }
+                        }
+                        None => {
+                            let _ = self.return_tx.try_send(plugin);
+                        }
+                    }
+                }
             }
         }
     }
@@ -2194,6 +2408,16 @@ impl AudioGraph {
             .and_then(|k| k.splits.get_mut(split))
     }
 
+    /// Plugin in a split's slot: 0 = instrument, 1..N = effects.
+    fn get_plugin_mut(&mut self, kb: usize, split: usize, slot: usize) -> Option<&mut Box<dyn Plugin>> {
+        let lane = self.get_split_mut(kb, split)?;
+        if slot == 0 {
+            lane.instrument.as_mut()
+        } else {
+            lane.effects.get_mut(slot - 1)
+        }
+    }
+
     /// Process audio: drain commands, run all keyboards/splits, sum to output.
     /// Outputs silence if no instruments are loaded.
     pub fn process(
@@ -2235,6 +2459,10 @@ impl AudioGraph {
             }
         }
 
+        if let Some(master) = &mut self.master {
+            master.process(&mut self.mix_buf, frames);
+        }
+
         // Copy mix_buf to audio_out
         for (ch, out) in audio_out.iter_mut().enumerate() {
             if ch < self.mix_buf.len() {
@@ -2321,10 +2549,11 @@ mod tests {
             _audio_in: &[&[f32]],
             audio_out: &mut [&mut [f32]],
         ) -> anyhow::Result<()> {
-            for &(_, [status, _, velocity]) in midi_events {
+            for &(_, [status, data1, data2]) in midi_events {
                 match status & 0xF0 {
-                    0x90 if velocity > 0 => self.has_note = true,
+                    0x90 if data2 > 0 => self.has_note = true,
                     0x80 | 0x90 => self.has_note = false,
+                    0xB0 if data1 == 123 => self.has_note = false,
                     _ => {}
                 }
             }
@@ -2637,6 +2866,80 @@ mod tests {
         assert_eq!(old.unwrap().name(), "ConstInstrument");
     }
 
+    #[test]
+    fn exchange_plugin_lends_effect_to_main_thread() {
+        let (mut graph, cmd_tx, return_rx) = make_graph(2);
+        swap_instrument(&cmd_tx, ConstInstrument::new(1.0));
+        insert_effect(&cmd_tx, 0, Box::new(ScaleEffect(0.5)), 1.0);
+
+        // While the effect is away, its placeholder passes audio through.
+        let (reply, lent_rx) = crossbeam_channel::bounded(1);
+        cmd_tx
+            .send(GraphCommand::ExchangePlugin {
+                kb: 0,
+                split: 0,
+                slot: 1,
+                plugin: Box::new(Placeholder::new("placeholder", false, 48000.0)),
+                reply: Some(reply),
+            })
+            .unwrap();
+        let mut out = make_output();
+        graph.process(&[note_on(60)], &mut out).unwrap();
+        assert!(out[0].iter().all(|&s| s == 1.0));
+        let effect = lent_rx.try_recv().unwrap();
+
+        // Swapping it back sends the placeholder to the return channel.
+        cmd_tx
+            .send(GraphCommand::ExchangePlugin { kb: 0, split: 0, slot: 1, plugin: effect, reply: None })
+            .unwrap();
+        graph.process(&[], &mut out).unwrap();
+        assert!(out[0].iter().all(|&s| (s - 0.5).abs() < 1e-6));
+        assert_eq!(return_rx.try_recv().unwrap().name(), "placeholder");
+
+        // A slot that no longer exists hands the plugin straight back.
+        let (reply, lent_rx) = crossbeam_channel::bounded(1);
+        cmd_tx
+            .send(GraphCommand::ExchangePlugin {
+                kb: 0,
+                split: 0,
+                slot: 5,
+                plugin: ConstInstrument::new(0.0),
+                reply: Some(reply),
+            })
+            .unwrap();
+        graph.process(&[], &mut out).unwrap();
+        assert!(lent_rx.try_recv().is_err());
+        assert_eq!(return_rx.try_recv().unwrap().name(), "ConstInstrument");
+    }
+
+    #[test]
+    fn returned_instrument_gets_all_notes_off() {
+        let (mut graph, cmd_tx, _return_rx) = make_graph(2);
+        swap_instrument(&cmd_tx, ConstInstrument::new(1.0));
+        let mut out = make_output();
+        graph.process(&[note_on(60)], &mut out).unwrap();
+
+        // The note-off arrives while the instrument is lent out.
+        let (reply, lent_rx) = crossbeam_channel::bounded(1);
+        cmd_tx
+            .send(GraphCommand::ExchangePlugin {
+                kb: 0,
+                split: 0,
+                slot: 0,
+                plugin: Box::new(Placeholder::new("placeholder", true, 48000.0)),
+                reply: Some(reply),
+            })
+            .unwrap();
+        graph.process(&[note_off(60)], &mut out).unwrap();
+        let instrument = lent_rx.try_recv().unwrap();
+
+        cmd_tx
+            .send(GraphCommand::ExchangePlugin { kb: 0, split: 0, slot: 0, plugin: instrument, reply: None })
+            .unwrap();
+        graph.process(&[], &mut out).unwrap();
+        assert!(out[0].iter().all(|&s| s == 0.0));
+    }
+
     #[test]
     fn remove_effect() {
         let (mut graph, cmd_tx, _) = make_graph(2);
@@ -2976,6 +3279,46 @@ mod tests {
         drop(return_rx);
     }
 
+    #[test]
+    fn master_limits_summed_splits() {
+        let (mut graph, cmd_tx, _return_rx) = make_graph(2);
+        graph.keyboards[0].splits.push(SplitLane::new(2));
+        let config = crate::session::MasterConfig {
+            sanitise: false,
+            limiter: true,
+            ceiling_db: -6.0,
+            lookahead_ms: 0.1,
+            ..Default::default()
+        };
+        let master = MasterBus::new(&config, 48000.0, 2);
+        let meters = master.meters();
+        graph.set_master(master);
+
+        for split in 0..2 {
+            let inst = ConstInstrument::new(0.8);
+            let inst_buf = (0..inst.audio_output_count()).map(|_| Vec::new()).collect();
+            cmd_tx
+                .send(GraphCommand::SwapInstrument {
+                    kb: 0,
+                    split,
+                    instrument: inst,
+                    inst_buf,
+                    remapper: None,
+                })
+                .unwrap();
+        }
+
+        // 0.8 + 0.8 would clip; the limiter holds it at -6 dBFS.
+        let ceiling = 10.0_f32.powf(-6.0 / 20.0);
+        let mut out = make_output();
+        for _ in 0..4 {
+            graph.process(&[note_on(60)], &mut out).unwrap();
+        }
+        assert!(out.iter().flatten().all(|&s| s <= ceiling + 1e-6));
+        assert!((out[0][FRAMES - 1] - ceiling).abs() < 1e-4);
+        assert!(meters.gain_reduction_db.get() > 6.0);
+    }
+
     #[test]
     fn range_filtering() {
         let (cmd_tx, cmd_rx) = crossbeam_channel::bounded(64);
@@ -3429,4 +3772,173 @@ mod tests {
         graph.process(&[note_on(60)], &mut out).unwrap();
         assert!(out[0].iter().all(|&s| s.is_finite()));
     }
+
+    /// Instrument that records modulation offsets (global and per-voice) on
+    /// parameter 0, and counts destructive `set_parameter` calls.
+    #[derive(Default)]
+    struct ModRecordingInstrument {
+        offsets: std::sync::Arc<std::sync::Mutex<Vec<f32>>>,
+        voice_mods: std::sync::Arc<std::sync::Mutex<Vec<(u8, u8, f32)>>>,
+        param_sets: std::sync::Arc<std::sync::Mutex<usize>>,
+    }
+
+    impl Plugin for ModRecordingInstrument {
+        fn name(&self) -> &str {
+            "ModRecording"
+        }
+        fn is_instrument(&self) -> bool {
+            true
+        }
+        fn audio_output_count(&self) -> usize {
+            2
+        }
+        fn audio_input_count(&self) -> usize {
+            0
+        }
+        fn process(
+            &mut self,
+            _midi_events: &[(u64, [u8; 3])],
+            _audio_in: &[&[f32]],
+            _audio_out: &mut [&mut [f32]],
+        ) -> anyhow::Result<()> {
+            Ok(())
+        }
+        fn sample_rate(&self) -> f32 {
+            48000.0
+        }
+        fn parameters(&self) -> Vec<ParameterInfo> {
+            Vec::new()
+        }
+        fn get_parameter(&mut self, _: u32) -> Option<f32> {
+            None
+        }
+        fn set_parameter(&mut self, _: u32, _: f32) -> anyhow::Result<()> {
+            *self.param_sets.lock().unwrap() += 1;
+            Ok(())
+        }
+        fn presets(&self) -> Vec<Preset> {
+            Vec::new()
+        }
+        fn load_preset(&mut self, id: &str) -> anyhow::Result<()> {
+            anyhow::bail!("no preset {id}")
+        }
+        fn supports_modulation(&self, index: u32) -> bool {
+            index == 0
+        }
+        fn set_modulation(&mut self, _: u32, offset: f32) -> anyhow::Result<()> {
+            self.offsets.lock().unwrap().push(offset);
+            Ok(())
+        }
+        fn supports_voice_modulation(&self, index: u32) -> bool {
+            index == 0
+        }
+        fn set_voice_modulation(&mut self, _: u32, channel: u8, key: u8, offset: f32) -> anyhow::Result<()> {
+            self.voice_mods.lock().unwrap().push((channel, key, offset));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn envelope_modulates_each_voice_when_supported() {
+        let (mut graph, cmd_tx, _return_rx) = make_graph(2);
+
+        let recorder = ModRecordingInstrument::default();
+        let voice_mods = recorder.voice_mods.clone();
+        let param_sets = recorder.param_sets.clone();
+        swap_instrument(&cmd_tx, Box::new(recorder));
+        cmd_tx
+            .send(GraphCommand::InsertModulator {
+                kb: 0,
+                split: 0,
+                parent_slot: 0,
+                index: 0,
+                source: ModSource::Envelope {
+                    attack: 0.0,
+                    decay: 0.0,
+                    sustain: 1.0,
+                    release: 0.0,
+                    state: EnvState::Idle,
+                    level: 0.0,
+                    notes_held: 0,
+                },
+            })
+            .unwrap();
+        cmd_tx
+            .send(GraphCommand::AddModTarget {
+                kb: 0,
+                split: 0,
+                parent_slot: 0,
+                mod_index: 0,
+                target: ModTarget {
+                    kind: ModTargetKind::PluginParam { param_index: 0 },
+                    depth: 0.5,
+                    base_value: 0.0,
+                    param_min: 0.0,
+                    param_max: 1.0,
+                },
+            })
+            .unwrap();
+
+        let mut out = make_output();
+        graph.process(&[note_on(60), note_on(64)], &mut out).unwrap();
+
+        // Both voices got their own fully-open envelope; the shared
+        // parameter value was never overwritten.
+        let mods = voice_mods.lock().unwrap().clone();
+        assert_eq!(mods, vec![(0, 60, 0.5), (0, 64, 0.5)]);
+        assert_eq!(*param_sets.lock().unwrap(), 0);
+
+        // Releasing one note resets that voice's offset once, then stops
+        // modulating it.
+        voice_mods.lock().unwrap().clear();
+        graph.process(&[note_off(60)], &mut out).unwrap();
+        graph.process(&[], &mut out).unwrap();
+        let mods = voice_mods.lock().unwrap().clone();
+        assert_eq!(mods, vec![(0, 60, 0.0), (0, 64, 0.5), (0, 64, 0.5)]);
+    }
+
+    #[test]
+    fn lfo_sends_modulation_offset_when_supported() {
+        let (mut graph, cmd_tx, _return_rx) = make_graph(2);
+
+        let recorder = ModRecordingInstrument::default();
+        let offsets = recorder.offsets.clone();
+        let param_sets = recorder.param_sets.clone();
+        swap_instrument(&cmd_tx, Box::new(recorder));
+        cmd_tx
+            .send(GraphCommand::InsertModulator {
+                kb: 0,
+                split: 0,
+                parent_slot: 0,
+                index: 0,
+                // Square at phase 0 evaluates to +1 for the first buffers.
+                source: ModSource::Lfo { waveform: LfoWaveform::Square, rate: 1.0, phase: 0.0 },
+            })
+            .unwrap();
+        cmd_tx
+            .send(GraphCommand::AddModTarget {
+                kb: 0,
+                split: 0,
+                parent_slot: 0,
+                mod_index: 0,
+                target: ModTarget {
+                    kind: ModTargetKind::PluginParam { param_index: 0 },
+                    depth: 0.5,
+                    base_value: 0.8,
+                    param_min: 0.0,
+                    param_max: 1.0,
+                },
+            })
+            .unwrap();
+
+        let mut out = make_output();
+        graph.process(&[], &mut out).unwrap();
+
+        // Offset is clamped to the parameter range (0.8 + 0.5 → 1.0), and the
+        // base value is never overwritten.
+        let offsets = offsets.lock().unwrap().clone();
+        assert_eq!(offsets.len(), 1);
+        assert!((offsets[0] - 0.2).abs() < 1e-6, "offset = {}", offsets[0]);
+        assert_eq!(*param_sets.lock().unwrap(), 0);
+    }
 }
@@ -1,19 +1,36 @@
+use std::cell::Cell;
 use std::ffi::CStr;
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, Weak};
+use std::thread::ThreadId;
+use std::time::{Duration, Instant};
 
 use clack_extensions::audio_ports::{
     AudioPortInfoBuffer, HostAudioPorts, HostAudioPortsImpl, PluginAudioPorts, RescanType,
 };
+use clack_extensions::log::{HostLog, HostLogImpl, LogSeverity};
+use clack_extensions::note_ports::{
+    HostNotePorts, HostNotePortsImpl, NoteDialects, NotePortInfoBuffer, NoteRescanFlags,
+    PluginNotePorts,
+};
 use clack_extensions::params::{
     HostParams, HostParamsImplMainThread, HostParamsImplShared, ParamClearFlags, ParamInfoBuffer,
-    ParamRescanFlags, PluginParams,
+    ParamInfoFlags, ParamRescanFlags, PluginParams,
 };
 use clack_extensions::preset_discovery::HostPresetLoadImpl;
 use clack_extensions::preset_discovery::prelude::{
     FileType, Flags, HostPresetLoad, IndexerImpl, Location, LocationInfo, MetadataReceiverImpl,
     PluginPresetLoad, PresetDiscoveryFactory, Provider, Soundpack, Timestamp, UniversalPluginId,
 };
-use clack_host::events::event_types::ParamValueEvent;
+use clack_extensions::state::PluginState;
+use clack_extensions::thread_check::{HostThreadCheck, HostThreadCheckImpl};
+use clack_extensions::timer::{HostTimer, HostTimerImpl, PluginTimer, TimerId};
+use clack_host::events::Match;
+use clack_host::events::event_types::{
+    NoteExpressionEvent, NoteExpressionType, NoteOffEvent, NoteOnEvent, ParamModEvent,
+    ParamValueEvent,
+};
 use clack_host::prelude::*;
 use clack_host::process::StartedPluginAudioProcessor;
 use clack_host::utils::Cookie;
@@ -21,12 +38,52 @@ use clack_host::utils::Cookie;
 use super::{ParameterInfo, Plugin, PluginInfo, Preset};
 
 // ---------------------------------------------------------------------------
-// Host handler types (minimal, no-op callbacks)
+// Host handler types
 // ---------------------------------------------------------------------------
 
 struct TangHost;
-struct TangHostShared;
-struct TangHostMainThread;
+
+struct TangHostShared {
+    requests: Arc<MainThreadRequests>,
+}
+
+struct TangHostMainThread {
+    requests: Arc<MainThreadRequests>,
+}
+
+/// Main-thread work a plugin has asked the host for. Written from any thread
+/// by the host callbacks, consumed by [`pump_main_thread`].
+#[derive(Default)]
+struct MainThreadRequests {
+    callback_requested: AtomicBool,
+    timers: Mutex<Vec<HostTimerEntry>>,
+    next_timer_id: AtomicU32,
+    /// Per-voice modulation offsets dropped on the audio thread because a
+    /// block had more than [`MAX_VOICE_MODS`]; reported by the pump.
+    dropped_voice_mods: AtomicU32,
+}
+
+struct HostTimerEntry {
+    id: u32,
+    period: Duration,
+    next_fire: Instant,
+}
+
+impl MainThreadRequests {
+    /// Return the ids of all timers that are due, rescheduling each one.
+    fn due_timers(&self, now: Instant) -> Vec<u32> {
+        let mut timers = self.timers.lock().unwrap_or_else(|e| e.into_inner());
+        let mut due = Vec::new();
+        for timer in timers.iter_mut() {
+            if now >= timer.next_fire {
+                due.push(timer.id);
+                // Skip missed periods instead of firing a burst to catch up.
+                timer.next_fire = now + timer.period;
+            }
+        }
+        due
+    }
+}
 
 impl HostHandlers for TangHost {
     type Shared<'a> = TangHostShared;
@@ -35,8 +92,12 @@ impl HostHandlers for TangHost {
 
     fn declare_extensions(builder: &mut HostExtensions<Self>, _shared: &Self::Shared<'_>) {
         builder.register::<HostAudioPorts>();
+        builder.register::<HostLog>();
+        builder.register::<HostNotePorts>();
         builder.register::<HostParams>();
         builder.register::<HostPresetLoad>();
+        builder.register::<HostThreadCheck>();
+        builder.register::<HostTimer>();
     }
 }
 
@@ -48,12 +109,65 @@ impl<'a> SharedHandler<'a> for TangHostShared {
         log::debug!("CLAP plugin requested process (ignored)");
     }
     fn request_callback(&self) {
-        log::debug!("CLAP plugin requested callback (ignored)");
+        self.requests.callback_requested.store(true, Ordering::Release);
     }
 }
 
 impl<'a> MainThreadHandler<'a> for TangHostMainThread {}
 
+impl HostLogImpl for TangHostShared {
+    fn log(&self, severity: LogSeverity, message: &str) {
+        match severity {
+            LogSeverity::Debug => log::debug!("CLAP plugin: {message}"),
+            LogSeverity::Info => log::info!("CLAP plugin: {message}"),
+            LogSeverity::Warning => log::warn!("CLAP plugin: {message}"),
+            LogSeverity::Error | LogSeverity::Fatal => log::error!("CLAP plugin: {message}"),
+            LogSeverity::HostMisbehaving => log::error!("CLAP plugin reports host misbehaving: {message}"),
+            LogSeverity::PluginMisbehaving => log::warn!("CLAP plugin misbehaving: {message}"),
+        }
+    }
+}
+
+impl HostThreadCheckImpl for TangHostShared {
+    fn is_main_thread(&self) -> bool {
+        is_main_thread()
+    }
+    fn is_audio_thread(&self) -> bool {
+        IS_AUDIO_THREAD.with(|flag| flag.get())
+    }
+}
+
+impl HostTimerImpl for TangHostMainThread {
+    fn register_timer(&mut self, period_ms: u32) -> Result<TimerId, HostError> {
+        let id = self.requests.next_timer_id.fetch_add(1, Ordering::Relaxed) + 1;
+        // Timers are serviced by the UI loop, so very short periods are
+        // effectively rounded up to its tick rate.
+        let period = Duration::from_millis(period_ms.max(1) as u64);
+        self.requests
+            .timers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(HostTimerEntry {
+                id,
+                period,
+                next_fire: Instant::now() + period,
+            });
+        log::debug!("CLAP timer {id} registered ({period_ms}ms)");
+        Ok(TimerId(id))
+    }
+
+    fn unregister_timer(&mut self, timer_id: TimerId) -> Result<(), HostError> {
+        let mut timers = self.requests.timers.lock().unwrap_or_else(|e| e.into_inner());
+        let before = timers.len();
+        timers.retain(|t| t.id != timer_id.0);
+        if timers.len() == before {
+            return Err(HostError::Message("Unknown timer ID"));
+        }
+        log::debug!("CLAP timer {} unregistered", timer_id.0);
+        Ok(())
+    }
+}
+
 impl HostParamsImplShared for TangHostShared {
     fn request_flush(&self) {
         log::debug!("CLAP params: request_flush (ignored)");
@@ -84,6 +198,15 @@ impl HostPresetLoadImpl for TangHostMainThread {
     }
 }
 
+impl HostNotePortsImpl for TangHostMainThread {
+    fn supported_dialects(&self) -> NoteDialects {
+        NoteDialects::CLAP | NoteDialects::MIDI
+    }
+    fn rescan(&mut self, _flags: NoteRescanFlags) {
+        log::debug!("CLAP note_ports: rescan (ignored)");
+    }
+}
+
 impl HostAudioPortsImpl for TangHostMainThread {
     fn is_rescan_flag_supported(&self, _flag: RescanType) -> bool {
         false
@@ -93,6 +216,108 @@ impl HostAudioPortsImpl for TangHostMainThread {
     }
 }
 
+// ---------------------------------------------------------------------------
+// Main-thread pump
+// ---------------------------------------------------------------------------
+
+/// The thread that runs [`pump_main_thread`], recorded by [`set_main_thread`]
+/// at startup.
+static MAIN_THREAD: OnceLock<ThreadId> = OnceLock::new();
+
+/// Loaded plugins whose main-thread callbacks are serviced by
+/// [`pump_main_thread`], whichever thread created them.
+static MAIN_THREAD_PORTS: Mutex<Vec<MainThreadPort>> = Mutex::new(Vec::new());
+
+thread_local! {
+    /// Set on any thread that has run `ClapPlugin::process`.
+    static IS_AUDIO_THREAD: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Record the calling thread as the CLAP main thread. Call once, early in
+/// `main` (or the sandbox child), before any plugin is loaded.
+pub fn set_main_thread() {
+    if MAIN_THREAD.set(std::thread::current().id()).is_err() {
+        log::warn!("CLAP main thread set twice; keeping the first");
+    }
+}
+
+fn is_main_thread() -> bool {
+    MAIN_THREAD
+        .get()
+        .is_some_and(|id| *id == std::thread::current().id())
+}
+
+/// Main-thread side of a loaded plugin. Holds only a weak reference so a
+/// plugin removed from the graph is released as soon as it is dropped.
+struct MainThreadPort {
+    instance: Weak<Mutex<PluginInstance<TangHost>>>,
+    requests: Arc<MainThreadRequests>,
+    timer_ext: Option<PluginTimer>,
+}
+
+// Safety: the port is only registered (a plain push) and dropped from other
+// threads; the instance behind the weak reference is only upgraded and used
+// by `pump_main_thread`, which does nothing off the main thread.
+unsafe impl Send for MainThreadPort {}
+
+/// Service `request_callback` and timer-support for every loaded CLAP plugin.
+/// Must be called regularly from the main thread; does nothing elsewhere.
+pub fn pump_main_thread() {
+    if !is_main_thread() {
+        return;
+    }
+    let mut ports = MAIN_THREAD_PORTS.lock().unwrap_or_else(|e| e.into_inner());
+    ports.retain(|port| port.instance.strong_count() > 0);
+    let now = Instant::now();
+    for port in ports.iter() {
+        let Some(instance) = port.instance.upgrade() else {
+            continue;
+        };
+        let mut instance = instance.lock().unwrap_or_else(|e| e.into_inner());
+        if port.requests.callback_requested.swap(false, Ordering::AcqRel) {
+            instance.call_on_main_thread_callback();
+        }
+        if let Some(timer_ext) = port.timer_ext {
+            for id in port.requests.due_timers(now) {
+                timer_ext.on_timer(&mut instance.plugin_handle(), TimerId(id));
+            }
+        }
+        let dropped = port.requests.dropped_voice_mods.swap(0, Ordering::Relaxed);
+        if dropped > 0 {
+            log::warn!(
+                "CLAP: dropped {dropped} per-voice modulation offsets (more than {MAX_VOICE_MODS} in a block)"
+            );
+        }
+    }
+}
+
+/// Instantiate a plugin with fresh host handlers, servicing any callback it
+/// requests during `init` straight away.
+fn instantiate(
+    bundle: &PluginBundle,
+    plugin_id: &CStr,
+    host_info: &HostInfo,
+) -> anyhow::Result<(PluginInstance<TangHost>, Arc<MainThreadRequests>)> {
+    let requests = Arc::new(MainThreadRequests::default());
+    let shared_requests = requests.clone();
+    let mut instance = PluginInstance::<TangHost>::new(
+        move |_| TangHostShared {
+            requests: shared_requests,
+        },
+        |shared| TangHostMainThread {
+            requests: shared.requests.clone(),
+        },
+        bundle,
+        plugin_id,
+        host_info,
+    )
+    .map_err(|e| anyhow::anyhow!("Failed to instantiate CLAP plugin: {e}"))?;
+    if requests.callback_requested.swap(false, Ordering::AcqRel) {
+        instance.call_on_main_thread_callback();
+    }
+    Ok((instance, requests))
+}
+
 // ---------------------------------------------------------------------------
 // Preset discovery helpers
 // ---------------------------------------------------------------------------
@@ -326,6 +551,9 @@ fn discover_presets(bundle: &PluginBundle, host_info: &HostInfo) -> Vec<(Preset,
 // ClapPlugin
 // ---------------------------------------------------------------------------
 
+/// Most per-voice modulation offsets queued for one block.
+const MAX_VOICE_MODS: usize = 256;
+
 pub struct ClapPlugin {
     name: String,
     is_instrument: bool,
@@ -337,12 +565,36 @@ pub struct ClapPlugin {
     params_ext: Option<PluginParams>,
     params_cache: Vec<ParameterInfo>,
     param_ids: Vec<ClapId>,
+    /// Per parameter: accepts `ParamModEvent` modulation.
+    param_modulatable: Vec<bool>,
+    /// Per parameter: accepts per-voice modulation (per note ID or per key).
+    param_voice_modulatable: Vec<bool>,
     pending_param_changes: Vec<(ClapId, f64)>,
+    /// Per parameter: modulation amount requested for the next block.
+    pending_mod_amounts: Vec<Option<f64>>,
+    /// Per parameter: modulation amount currently applied inside the plugin.
+    mod_amounts: Vec<f64>,
+    /// Per-voice modulation offsets for the next block: (param, channel, key,
+    /// amount). Never grows past [`MAX_VOICE_MODS`].
+    pending_voice_mods: Vec<(ClapId, u8, u8, f64)>,
+    /// Shared with the main-thread pump, which reports dropped voice offsets.
+    requests: Arc<MainThreadRequests>,
+    /// Send notes as native CLAP note events (with note IDs) instead of MIDI.
+    clap_note_dialect: bool,
+    /// Forward remaining MIDI messages (CC, pitch bend, ...) as raw MIDI.
+    midi_note_dialect: bool,
+    /// Note ID of the most recent note on each (channel, key), indexed
+    /// `channel * 128 + key`.
+    note_ids: Vec<Option<u32>>,
+    next_note_id: u32,
     preset_cache: Vec<Preset>,
     preset_data: Vec<ClapPresetData>,
     preset_load_ext: Option<PluginPresetLoad>,
+    state_ext: Option<PluginState>,
     _bundle: PluginBundle,
-    instance: PluginInstance<TangHost>,
+    /// Shared with the main-thread pump, which needs it for `on_main_thread`
+    /// and timer callbacks while the plugin itself lives on the audio thread.
+    instance: Arc<Mutex<PluginInstance<TangHost>>>,
     audio_processor: Option<StartedPluginAudioProcessor<TangHost>>,
     // Pre-allocated buffers
     output_ports: AudioPorts,
@@ -355,19 +607,25 @@ pub struct ClapPlugin {
 }
 
 // Safety: PluginInstance is !Send because CLAP enforces main-thread affinity for
-// certain operations (e.g. init, activate, deactivate, destroy). We satisfy this:
-// the plugin is created, activated, and preset-loaded on the main thread, then
-// moved (by value) into the PluginChain which is moved into the audio callback
-// closure — single owner, no concurrent access. On the audio thread only
-// process() is called, via the StartedPluginAudioProcessor handle. On shutdown
-// the plugin is sent back to the main thread for deactivation and drop.
+// certain operations (e.g. init, activate, deactivate, destroy). The plugin is
+// created and activated on the thread that loads it (the main thread in tang
+// and in the sandbox child), then moved (by value) into the PluginChain which
+// is moved into the audio callback closure — single owner. On the audio thread
+// only process() is called, via the StartedPluginAudioProcessor handle. The
+// instance itself is shared through a mutex with `pump_main_thread`, which
+// runs `on_main_thread` and timers on the main thread while the plugin lives
+// on the audio thread. On shutdown the plugin is sent back to the main thread
+// for deactivation and drop.
 unsafe impl Send for ClapPlugin {}
 
 impl Drop for ClapPlugin {
     fn drop(&mut self) {
         if let Some(processor) = self.audio_processor.take() {
             let stopped = processor.stop_processing();
-            self.instance.deactivate(stopped);
+            self.instance
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .deactivate(stopped);
         }
     }
 }
@@ -376,29 +634,12 @@ impl Drop for ClapPlugin {
 // Enumeration (unchanged)
 // ---------------------------------------------------------------------------
 
-/// Enumerate all CLAP plugins found on the system.
-pub fn enumerate_plugins() -> Vec<PluginInfo> {
-    let mut plugins = Vec::new();
-
-    for bundle_path in clack_finder::ClapFinder::from_standard_paths() {
-        match scan_bundle(&bundle_path) {
-            Some(found) => plugins.extend(found),
-            None => {
-                log::warn!("Failed to scan CLAP bundle: {}", bundle_path.display());
-            }
-        }
-    }
-
-    for bundle_path in extra_clap_bundles() {
-        match scan_bundle(&bundle_path) {
-            Some(found) => plugins.extend(found),
-            None => {
-                log::warn!("Failed to scan CLAP bundle: {}", bundle_path.display());
-            }
-        }
-    }
-
-    plugins
+/// All CLAP bundles on the standard search paths and configured extra paths.
+pub fn bundle_paths() -> Vec<std::path::PathBuf> {
+    clack_finder::ClapFinder::from_standard_paths()
+        .into_iter()
+        .chain(extra_clap_bundles())
+        .collect()
 }
 
 /// Resolve extra CLAP paths from config. Paths pointing to `.clap` files are
@@ -429,7 +670,9 @@ fn extra_clap_bundles() -> Vec<std::path::PathBuf> {
     files
 }
 
-fn scan_bundle(path: &Path) -> Option<Vec<PluginInfo>> {
+/// Scan one bundle for its plugins, briefly loading each one to record its
+/// audio ports, parameters and presets.
+pub fn scan_bundle(path: &Path) -> Option<Vec<PluginInfo>> {
     use clack_host::plugin::features::INSTRUMENT;
 
     // Safety: loading external dynamic libraries is inherently unsafe
@@ -447,34 +690,31 @@ fn scan_bundle(path: &Path) -> Option<Vec<PluginInfo>> {
             .map(|s| s.to_string_lossy().to_string())
             .unwrap_or_else(|| id.clone());
         let is_instrument = descriptor.features().any(|f| f == INSTRUMENT);
+        let path = path.to_string_lossy().to_string();
 
-        // Briefly instantiate to query param count
-        let plugin_id = std::ffi::CString::new(id.as_str()).ok()?;
-        let param_count = PluginInstance::<TangHost>::new(
-            |_| TangHostShared,
-            |_| TangHostMainThread,
-            &bundle,
-            &plugin_id,
-            &host_info,
-        )
-        .ok()
-        .and_then(|mut inst| {
-            let ext: PluginParams = inst.plugin_shared_handle().get_extension()?;
-            let mut handle = inst.plugin_handle();
-            Some(ext.count(&mut handle) as usize)
-        })
-        .unwrap_or(0);
-
-        let preset_count = discover_presets(&bundle, &host_info).len();
-
-        found.push(PluginInfo {
-            name,
-            id,
+        let loaded = load_found(
+            bundle.clone(),
+            id.clone(),
+            name.clone(),
             is_instrument,
-            param_count,
-            preset_count,
-            path: path.to_string_lossy().to_string(),
-        });
+            48000.0,
+            512,
+        );
+        let info = match loaded {
+            Ok(plugin) => PluginInfo::of(plugin.as_ref(), id, path),
+            Err(e) => {
+                log::warn!("CLAP plugin {id} failed to load during scan: {e}");
+                PluginInfo {
+                    name,
+                    id,
+                    is_instrument,
+                    preset_count: discover_presets(&bundle, &host_info).len(),
+                    path,
+                    ..PluginInfo::default()
+                }
+            }
+        };
+        found.push(info);
     }
 
     Some(found)
@@ -489,23 +729,27 @@ pub fn load(
     sample_rate: f32,
     max_block_size: usize,
 ) -> anyhow::Result<Box<dyn Plugin>> {
-    let host_info = HostInfo::new("tang", "akerud", "https://github.com/akerud/tang", "0.1.0")?;
-
     // Resolve plugin ID and bundle
     let (bundle, plugin_id_string, name, is_instrument) = find_plugin(source)?;
+    load_found(bundle, plugin_id_string, name, is_instrument, sample_rate, max_block_size)
+}
+
+/// Load plugin `plugin_id_string` from an already opened bundle.
+fn load_found(
+    bundle: PluginBundle,
+    plugin_id_string: String,
+    name: String,
+    is_instrument: bool,
+    sample_rate: f32,
+    max_block_size: usize,
+) -> anyhow::Result<Box<dyn Plugin>> {
+    let host_info = HostInfo::new("tang", "akerud", "https://github.com/akerud/tang", "0.1.0")?;
 
     let plugin_id =
         std::ffi::CString::new(plugin_id_string.as_str()).expect("plugin ID contains NUL");
 
     // Instantiate
-    let mut instance = PluginInstance::<TangHost>::new(
-        |_| TangHostShared,
-        |_shared| TangHostMainThread,
-        &bundle,
-        &plugin_id,
-        &host_info,
-    )
-    .map_err(|e| anyhow::anyhow!("Failed to instantiate CLAP plugin: {e}"))?;
+    let (mut instance, requests) = instantiate(&bundle, &plugin_id, &host_info)?;
 
     // Query audio output ports
     let (audio_out_channel_count, output_port_channel_counts) = {
@@ -573,18 +817,49 @@ pub fn load(
         }
     };
 
+    // Query the main note input port. Plugins without note ports get raw MIDI.
+    let (clap_note_dialect, midi_note_dialect) = {
+        let note_ports_ext: Option<PluginNotePorts> =
+            instance.plugin_shared_handle().get_extension();
+        let dialects = note_ports_ext.and_then(|ext| {
+            let mut handle = instance.plugin_handle();
+            let mut buf = NotePortInfoBuffer::new();
+            if ext.count(&mut handle, true) == 0 {
+                return None;
+            }
+            ext.get(&mut handle, 0, true, &mut buf)
+                .map(|info| info.supported_dialects)
+        });
+        match dialects {
+            Some(d) => {
+                log::info!("CLAP note input port dialects: {d:?}");
+                (d.contains(NoteDialects::CLAP), d.contains(NoteDialects::MIDI))
+            }
+            None => (false, true),
+        }
+    };
+
     // Query parameters
     let params_ext: Option<PluginParams> = instance.plugin_shared_handle().get_extension();
-    let (params_cache, param_ids) = match params_ext {
+    let (params_cache, param_ids, param_modulatable, param_voice_modulatable) = match params_ext {
         Some(ext) => {
             let mut handle = instance.plugin_handle();
             let mut info_buf = ParamInfoBuffer::new();
             let count = ext.count(&mut handle);
             let mut params = Vec::with_capacity(count as usize);
             let mut ids = Vec::with_capacity(count as usize);
+            let mut modulatable = Vec::with_capacity(count as usize);
+            let mut voice_modulatable = Vec::with_capacity(count as usize);
             for i in 0..count {
                 if let Some(info) = ext.get_info(&mut handle, i, &mut info_buf) {
                     ids.push(info.id);
+                    modulatable.push(info.flags.contains(ParamInfoFlags::IS_MODULATABLE));
+                    voice_modulatable.push(
+                        info.flags.contains(ParamInfoFlags::IS_MODULATABLE)
+                            && (info.flags.contains(ParamInfoFlags::IS_MODULATABLE_PER_KEY)
+                                || (clap_note_dialect
+                                    && info.flags.contains(ParamInfoFlags::IS_MODULATABLE_PER_NOTE_ID))),
+                    );
                     params.push(ParameterInfo {
                         index: i,
                         name: String::from_utf8_lossy(info.name).to_string(),
@@ -595,11 +870,11 @@ pub fn load(
                 }
             }
             log::info!("CLAP plugin has {} parameters", params.len());
-            (params, ids)
+            (params, ids, modulatable, voice_modulatable)
         }
         None => {
             log::info!("CLAP plugin does not support params extension");
-            (Vec::new(), Vec::new())
+            (Vec::new(), Vec::new(), Vec::new(), Vec::new())
         }
     };
 
@@ -607,8 +882,10 @@ pub fn load(
     let (preset_cache, preset_data): (Vec<Preset>, Vec<ClapPresetData>) =
         discover_presets(&bundle, &host_info).into_iter().unzip();
 
-    // Query preset load extension
+    // Query preset load, state and timer extensions
     let preset_load_ext: Option<PluginPresetLoad> = instance.plugin_shared_handle().get_extension();
+    let state_ext: Option<PluginState> = instance.plugin_shared_handle().get_extension();
+    let timer_ext: Option<PluginTimer> = instance.plugin_shared_handle().get_extension();
 
     log::info!(
         "Loaded CLAP plugin: {name} (instrument={is_instrument}, output_channels={audio_out_channel_count}, params={}, presets={})",
@@ -644,6 +921,17 @@ pub fn load(
 
     let event_buffer = EventBuffer::new();
 
+    // Hand the instance to the main-thread pump.
+    let instance = Arc::new(Mutex::new(instance));
+    MAIN_THREAD_PORTS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .push(MainThreadPort {
+            instance: Arc::downgrade(&instance),
+            requests: requests.clone(),
+            timer_ext,
+        });
+
     Ok(Box::new(ClapPlugin {
         name,
         is_instrument,
@@ -652,11 +940,22 @@ pub fn load(
         audio_out_channel_count,
         params_ext,
         params_cache,
+        pending_mod_amounts: vec![None; param_ids.len()],
+        mod_amounts: vec![0.0; param_ids.len()],
         param_ids,
+        param_modulatable,
+        param_voice_modulatable,
         pending_param_changes: Vec::new(),
+        pending_voice_mods: Vec::with_capacity(MAX_VOICE_MODS),
+        requests,
+        clap_note_dialect,
+        midi_note_dialect,
+        note_ids: vec![None; 16 * 128],
+        next_note_id: 0,
         preset_cache,
         preset_data,
         preset_load_ext,
+        state_ext,
         _bundle: bundle,
         instance,
         audio_processor: Some(started),
@@ -678,11 +977,7 @@ fn find_plugin(source: &str) -> anyhow::Result<(PluginBundle, String, String, bo
     // Try stripping "clap:" prefix for ID-based lookup
     if let Some(plugin_id) = source.strip_prefix("clap:") {
         // Search installed bundles for this ID (standard paths + extra paths)
-        let all_paths = clack_finder::ClapFinder::from_standard_paths()
-            .into_iter()
-            .chain(extra_clap_bundles());
-
-        for bundle_path in all_paths {
+        for bundle_path in bundle_paths() {
             let bundle = match unsafe { PluginBundle::load(&bundle_path) } {
                 Ok(b) => b,
                 Err(_) => continue,
@@ -739,6 +1034,16 @@ fn find_plugin(source: &str) -> anyhow::Result<(PluginBundle, String, String, bo
     Ok((bundle, id, name, is_instrument))
 }
 
+/// Address a single voice on note port 0: by note ID when one is known for
+/// the key, otherwise by channel and key alone.
+fn note_pckn(note_ids: &[Option<u32>], channel: u8, key: u8) -> Pckn {
+    let note_id = match note_ids[channel as usize * 128 + key as usize] {
+        Some(id) => Match::Specific(id),
+        None => Match::All,
+    };
+    Pckn::new(0u16, channel as u16, key as u16, note_id)
+}
+
 // ---------------------------------------------------------------------------
 // Plugin trait implementation
 // ---------------------------------------------------------------------------
@@ -775,6 +1080,8 @@ impl Plugin for ClapPlugin {
             .as_mut()
             .ok_or_else(|| anyhow::anyhow!("CLAP audio processor not active"))?;
 
+        IS_AUDIO_THREAD.with(|flag| flag.set(true));
+
         let frames = audio_out.first().map(|b| b.len()).unwrap_or(0);
         if frames == 0 {
             return Ok(());
@@ -788,13 +1095,86 @@ impl Plugin for ClapPlugin {
             self.event_buffer.push(&event);
         }
 
-        // Convert MIDI events to clack MidiEvent and push to event buffer
+        // Modulation offsets: send changed amounts, and reset parameters that
+        // are no longer being modulated back to their base value.
+        for (i, pending) in self.pending_mod_amounts.iter_mut().enumerate() {
+            let amount = pending.take().unwrap_or(0.0);
+            if amount != self.mod_amounts[i] {
+                self.mod_amounts[i] = amount;
+                let event = ParamModEvent::new(
+                    0,
+                    self.param_ids[i],
+                    Pckn::match_all(),
+                    amount,
+                    Cookie::empty(),
+                );
+                self.event_buffer.push(&event);
+            }
+        }
+
+        // Per-voice modulation applies from the start of the block, except for
+        // notes starting in this block, which get theirs right after note-on.
+        let starts_in_block = |channel: u8, key: u8| {
+            midi_events.iter().any(|(_, b)| {
+                b[0] & 0xF0 == 0x90 && b[2] > 0 && b[0] & 0x0F == channel && b[1] == key
+            })
+        };
+        for &(param_id, channel, key, amount) in &self.pending_voice_mods {
+            if !starts_in_block(channel, key) {
+                let pckn = note_pckn(&self.note_ids, channel, key);
+                let event = ParamModEvent::new(0, param_id, pckn, amount, Cookie::empty());
+                self.event_buffer.push(&event);
+            }
+        }
+
+        // Convert MIDI to native CLAP note events where the plugin supports
+        // them, and to clack MidiEvent otherwise.
         for (timestamp, bytes) in midi_events {
-            let midi =
-                clack_host::events::event_types::MidiEvent::new(*timestamp as u32, 0, *bytes);
-            self.event_buffer.push(&midi);
-            log::debug!("CLAP: pushed MIDI event t={timestamp} data={bytes:02x?}",);
+            let time = *timestamp as u32;
+            let (status, channel, key) = (bytes[0] & 0xF0, bytes[0] & 0x0F, bytes[1] & 0x7F);
+            match status {
+                0x90 if self.clap_note_dialect && bytes[2] > 0 => {
+                    let note_id = self.next_note_id;
+                    self.next_note_id = self.next_note_id.wrapping_add(1);
+                    self.note_ids[channel as usize * 128 + key as usize] = Some(note_id);
+                    let pckn = note_pckn(&self.note_ids, channel, key);
+                    let velocity = bytes[2] as f64 / 127.0;
+                    self.event_buffer.push(&NoteOnEvent::new(time, pckn, velocity));
+                }
+                0x80 | 0x90 if self.clap_note_dialect => {
+                    let pckn = note_pckn(&self.note_ids, channel, key);
+                    let velocity = bytes[2] as f64 / 127.0;
+                    self.event_buffer.push(&NoteOffEvent::new(time, pckn, velocity));
+                }
+                0xA0 if self.clap_note_dialect => {
+                    let pckn = note_pckn(&self.note_ids, channel, key);
+                    let pressure = bytes[2] as f64 / 127.0;
+                    self.event_buffer.push(&NoteExpressionEvent::new(
+                        time,
+                        pckn,
+                        NoteExpressionType::Pressure,
+                        pressure,
+                    ));
+                }
+                _ if self.midi_note_dialect => {
+                    let midi = clack_host::events::event_types::MidiEvent::new(time, 0, *bytes);
+                    self.event_buffer.push(&midi);
+                }
+                _ => {}
+            }
+            log::debug!("CLAP: pushed note/MIDI event t={timestamp} data={bytes:02x?}",);
+
+            if status == 0x90 && bytes[2] > 0 {
+                for &(param_id, ch, k, amount) in &self.pending_voice_mods {
+                    if ch == channel && k == key {
+                        let pckn = note_pckn(&self.note_ids, channel, key);
+                        let event = ParamModEvent::new(time, param_id, pckn, amount, Cookie::empty());
+                        self.event_buffer.push(&event);
+                    }
+                }
+            }
         }
+        self.pending_voice_mods.clear();
 
         // Resize per-channel output buffers
         for buf in &mut self.output_channel_bufs {
@@ -910,8 +1290,8 @@ impl Plugin for ClapPlugin {
     fn get_parameter(&mut self, index: u32) -> Option<f32> {
         let param_id = *self.param_ids.get(index as usize)?;
         let ext = self.params_ext?;
-        let mut handle = self.instance.plugin_handle();
-        ext.get_value(&mut handle, param_id).map(|v| v as f32)
+        let mut instance = self.instance.lock().unwrap_or_else(|e| e.into_inner());
+        ext.get_value(&mut instance.plugin_handle(), param_id).map(|v| v as f32)
     }
 
     fn set_parameter(&mut self, index: u32, value: f32) -> anyhow::Result<()> {
@@ -927,6 +1307,61 @@ impl Plugin for ClapPlugin {
         self.preset_cache.clone()
     }
 
+    fn supports_modulation(&self, index: u32) -> bool {
+        self.param_modulatable
+            .get(index as usize)
+            .copied()
+            .unwrap_or(false)
+    }
+
+    fn set_modulation(&mut self, index: u32, offset: f32) -> anyhow::Result<()> {
+        let pending = self
+            .pending_mod_amounts
+            .get_mut(index as usize)
+            .ok_or_else(|| anyhow::anyhow!("Parameter index out of range: {index}"))?;
+        *pending = Some(offset as f64);
+        Ok(())
+    }
+
+    fn supports_voice_modulation(&self, index: u32) -> bool {
+        self.param_voice_modulatable
+            .get(index as usize)
+            .copied()
+            .unwrap_or(false)
+    }
+
+    fn set_voice_modulation(
+        &mut self,
+        index: u32,
+        channel: u8,
+        key: u8,
+        offset: f32,
+    ) -> anyhow::Result<()> {
+        let param_id = *self
+            .param_ids
+            .get(index as usize)
+            .ok_or_else(|| anyhow::anyhow!("Parameter index out of range: {index}"))?;
+        let (channel, key, amount) = (channel & 0x0F, key & 0x7F, offset as f64);
+        // A later offset for the same voice and parameter replaces the earlier
+        // one; past capacity offsets are dropped rather than reallocating on
+        // the audio thread.
+        if let Some(pending) = self
+            .pending_voice_mods
+            .iter_mut()
+            .find(|m| m.0 == param_id && m.1 == channel && m.2 == key)
+        {
+            pending.3 = amount;
+        } else if self.pending_voice_mods.len() < MAX_VOICE_MODS {
+            self.pending_voice_mods
+                .push((param_id, channel, key, amount));
+        } else {
+            self.requests
+                .dropped_voice_mods
+                .fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+
     fn load_preset(&mut self, id: &str) -> anyhow::Result<()> {
         let index: usize = id
             .parse()
@@ -964,11 +1399,35 @@ impl Plugin for ClapPlugin {
             }
         };
 
+        let mut instance = self.instance.lock().unwrap_or_else(|e| e.into_inner());
         preset_load
-            .load_from_location(&mut self.instance.plugin_handle(), location, load_key)
+            .load_from_location(&mut instance.plugin_handle(), location, load_key)
             .map_err(|e| anyhow::anyhow!("Failed to load preset: {e}"))?;
 
         log::info!("CLAP: loaded preset {id}");
         Ok(())
     }
+
+    fn save_state(&mut self) -> Option<Vec<u8>> {
+        let state_ext = self.state_ext?;
+        let mut data = Vec::new();
+        let mut instance = self.instance.lock().unwrap_or_else(|e| e.into_inner());
+        match state_ext.save(&mut instance.plugin_handle(), &mut data) {
+            Ok(()) => Some(data),
+            Err(e) => {
+                log::warn!("CLAP: failed to save state: {e}");
+                None
+            }
+        }
+    }
+
+    fn load_state(&mut self, data: &[u8]) -> anyhow::Result<()> {
+        let state_ext = self
+            .state_ext
+            .ok_or_else(|| anyhow::anyhow!("Plugin does not support the state extension"))?;
+        let mut instance = self.instance.lock().unwrap_or_else(|e| e.into_inner());
+        state_ext
+            .load(&mut instance.plugin_handle(), &mut &data[..])
+            .map_err(|e| anyhow::anyhow!("Failed to load state: {e}"))
+    }
 }
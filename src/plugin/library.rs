@@ -0,0 +1,417 @@
+//! Tang's own host-neutral preset format and the user preset library.
+//!
+//! A tang preset is a TOML file recording the plugin it belongs to, parameter
+//! values by name, an optional opaque state blob and the modulators attached
+//! to the slot. Presets live under `<config dir>/presets/<plugin>/`, and are
+//! listed next to a plugin's native presets with a `tang:` ID prefix.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use super::{MeterInfo, ParameterInfo, Plugin, Preset};
+use crate::session::{ModulatorConfig, ModulatorOut, SaveModulator};
+
+/// Preset ID prefix for library presets; the rest of the ID is the file path.
+pub const PRESET_ID_PREFIX: &str = "tang:";
+
+/// A parsed tang preset file.
+pub struct TangPreset {
+    pub name: String,
+    /// Plugin source ID (e.g. `clap:org.surge-synth-team.surge-xt`).
+    pub plugin: String,
+    pub params: Vec<(String, f32)>,
+    pub state: Option<Vec<u8>>,
+    pub modulators: Vec<ModulatorConfig>,
+}
+
+#[derive(Deserialize)]
+struct TangPresetRaw {
+    name: String,
+    plugin: String,
+    #[serde(default)]
+    state: Option<String>,
+    #[serde(default)]
+    params: BTreeMap<String, f64>,
+    #[serde(default, rename = "modulator")]
+    modulators: Vec<ModulatorConfig>,
+}
+
+#[derive(Serialize)]
+struct TangPresetOut {
+    name: String,
+    plugin: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    state: Option<String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    params: BTreeMap<String, f64>,
+    #[serde(skip_serializing_if = "Vec::is_empty", rename = "modulator")]
+    modulators: Vec<ModulatorOut>,
+}
+
+/// Read a tang preset file.
+pub fn load(path: &Path) -> anyhow::Result<TangPreset> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Failed to read {}: {e}", path.display()))?;
+    let raw: TangPresetRaw = toml::from_str(&content)
+        .map_err(|e| anyhow::anyhow!("Invalid tang preset {}: {e}", path.display()))?;
+    let state = raw.state.as_deref().map(decode_hex).transpose()?;
+    Ok(TangPreset {
+        name: raw.name,
+        plugin: raw.plugin,
+        params: raw
+            .params
+            .into_iter()
+            .map(|(k, v)| (k, v as f32))
+            .collect(),
+        state,
+        modulators: raw.modulators,
+    })
+}
+
+/// Write a tang preset file, creating parent directories as needed.
+pub fn save(
+    path: &Path,
+    name: &str,
+    plugin: &str,
+    params: &[(String, f32)],
+    state: Option<&[u8]>,
+    modulators: &[SaveModulator],
+) -> anyhow::Result<()> {
+    let out = TangPresetOut {
+        name: name.to_string(),
+        plugin: plugin.to_string(),
+        state: state.map(encode_hex),
+        params: params
+            .iter()
+            .map(|(k, v)| (k.clone(), *v as f64))
+            .collect(),
+        modulators: crate::session::modulators_to_out(modulators),
+    };
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, toml::to_string_pretty(&out)?)
+        .map_err(|e| anyhow::anyhow!("Failed to write {}: {e}", path.display()))
+}
+
+fn encode_hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn decode_hex(s: &str) -> anyhow::Result<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        anyhow::bail!("state has an odd number of hex digits");
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| {
+            s.get(i..i + 2)
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                .ok_or_else(|| anyhow::anyhow!("invalid hex in state at offset {i}"))
+        })
+        .collect()
+}
+
+// ---------------------------------------------------------------------------
+// Library
+// ---------------------------------------------------------------------------
+
+/// Root of the user preset library.
+fn library_root() -> Option<PathBuf> {
+    crate::dirs_config().ok().map(|dir| dir.join("presets"))
+}
+
+/// Make a plugin ID or preset name safe to use as a single path component.
+fn file_component(s: &str) -> String {
+    let cleaned: String = s
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || matches!(c, ' ' | '-' | '_' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let trimmed = cleaned.trim().trim_matches('.');
+    if trimmed.is_empty() {
+        "_".into()
+    } else {
+        trimmed.to_string()
+    }
+}
+
+/// Path a preset called `name` for `plugin` is saved to.
+pub fn preset_path(plugin: &str, name: &str) -> anyhow::Result<PathBuf> {
+    let root =
+        library_root().ok_or_else(|| anyhow::anyhow!("could not determine config directory"))?;
+    Ok(preset_path_in(&root, plugin, name))
+}
+
+fn preset_path_in(root: &Path, plugin: &str, name: &str) -> PathBuf {
+    root.join(file_component(plugin))
+        .join(format!("{}.toml", file_component(name)))
+}
+
+/// List the library presets for `plugin`, sorted by name.
+pub fn presets_for(plugin: &str) -> Vec<Preset> {
+    library_root()
+        .map(|root| presets_in(&root, plugin))
+        .unwrap_or_default()
+}
+
+fn presets_in(root: &Path, plugin: &str) -> Vec<Preset> {
+    let dir = root.join(file_component(plugin));
+    let entries = match std::fs::read_dir(&dir) {
+        Ok(e) => e,
+        Err(_) => return Vec::new(),
+    };
+    let mut presets: Vec<Preset> = entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|ext| ext == "toml"))
+        .filter_map(|path| match load(&path) {
+            Ok(preset) if preset.plugin == plugin => Some(Preset {
+                name: preset.name,
+                id: format!("{PRESET_ID_PREFIX}{}", path.display()),
+            }),
+            Ok(_) => None,
+            Err(e) => {
+                log::warn!("Skipping preset: {e}");
+                None
+            }
+        })
+        .collect();
+    presets.sort_by_key(|p| p.name.to_lowercase());
+    presets
+}
+
+/// Apply a tang preset's parameter values and state to a plugin. Modulators
+/// are host-side and are applied by the caller.
+pub fn apply(plugin: &mut dyn Plugin, preset: &TangPreset) -> anyhow::Result<()> {
+    if let Some(state) = &preset.state {
+        plugin.load_state(state)?;
+    }
+    let params: Vec<ParameterInfo> = plugin.parameters();
+    for (name, value) in &preset.params {
+        match params.iter().find(|p| p.name == *name) {
+            Some(info) => plugin.set_parameter(info.index, *value)?,
+            None => log::warn!("Preset '{}': unknown parameter '{name}'", preset.name),
+        }
+    }
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// Plugin wrapper
+// ---------------------------------------------------------------------------
+
+/// Wraps a loaded plugin so `presets()` also lists its library presets and
+/// `load_preset()` accepts their `tang:` IDs.
+pub struct LibraryPresets {
+    inner: Box<dyn Plugin>,
+    library: Vec<Preset>,
+}
+
+impl LibraryPresets {
+    pub fn wrap(inner: Box<dyn Plugin>, plugin_id: &str) -> Box<dyn Plugin> {
+        let library = presets_for(plugin_id);
+        if !library.is_empty() {
+            log::info!("{} library presets for {plugin_id}", library.len());
+        }
+        Self::with_presets(inner, library)
+    }
+
+    /// Wrap `inner` with the given library presets; returns it unchanged
+    /// when there are none.
+    pub fn with_presets(inner: Box<dyn Plugin>, library: Vec<Preset>) -> Box<dyn Plugin> {
+        if library.is_empty() {
+            return inner;
+        }
+        Box::new(LibraryPresets { inner, library })
+    }
+}
+
+impl Plugin for LibraryPresets {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn is_instrument(&self) -> bool {
+        self.inner.is_instrument()
+    }
+
+    fn sample_rate(&self) -> f32 {
+        self.inner.sample_rate()
+    }
+
+    fn audio_output_count(&self) -> usize {
+        self.inner.audio_output_count()
+    }
+
+    fn audio_input_count(&self) -> usize {
+        self.inner.audio_input_count()
+    }
+
+    fn process(
+        &mut self,
+        midi_events: &[(u64, [u8; 3])],
+        audio_in: &[&[f32]],
+        audio_out: &mut [&mut [f32]],
+    ) -> anyhow::Result<()> {
+        self.inner.process(midi_events, audio_in, audio_out)
+    }
+
+    fn parameters(&self) -> Vec<ParameterInfo> {
+        self.inner.parameters()
+    }
+
+    fn get_parameter(&mut self, index: u32) -> Option<f32> {
+        self.inner.get_parameter(index)
+    }
+
+    fn set_parameter(&mut self, index: u32, value: f32) -> anyhow::Result<()> {
+        self.inner.set_parameter(index, value)
+    }
+
+    fn presets(&self) -> Vec<Preset> {
+        let mut presets = self.inner.presets();
+        presets.extend(self.library.iter().cloned());
+        presets
+    }
+
+    fn load_preset(&mut self, id: &str) -> anyhow::Result<()> {
+        match id.strip_prefix(PRESET_ID_PREFIX) {
+            Some(path) => {
+                let preset = load(Path::new(path))?;
+                apply(self.inner.as_mut(), &preset)?;
+                log::info!("Loaded tang preset '{}'", preset.name);
+                Ok(())
+            }
+            None => self.inner.load_preset(id),
+        }
+    }
+
+    fn save_preset(&mut self, name: &str) -> anyhow::Result<Preset> {
+        self.inner.save_preset(name)
+    }
+
+    fn save_state(&mut self) -> Option<Vec<u8>> {
+        self.inner.save_state()
+    }
+
+    fn load_state(&mut self, data: &[u8]) -> anyhow::Result<()> {
+        self.inner.load_state(data)
+    }
+
+    fn meters(&self) -> Vec<MeterInfo> {
+        self.inner.meters()
+    }
+
+    fn supports_modulation(&self, index: u32) -> bool {
+        self.inner.supports_modulation(index)
+    }
+
+    fn set_modulation(&mut self, index: u32, offset: f32) -> anyhow::Result<()> {
+        self.inner.set_modulation(index, offset)
+    }
+
+    fn supports_voice_modulation(&self, index: u32) -> bool {
+        self.inner.supports_voice_modulation(index)
+    }
+
+    fn set_voice_modulation(
+        &mut self,
+        index: u32,
+        channel: u8,
+        key: u8,
+        offset: f32,
+    ) -> anyhow::Result<()> {
+        self.inner.set_voice_modulation(index, channel, key, offset)
+    }
+
+    fn set_tempo(&mut self, bpm: f32) {
+        self.inner.set_tempo(bpm)
+    }
+
+    fn set_routed_outputs(&mut self, channels: usize) {
+        self.inner.set_routed_outputs(channels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin::chain::ModTargetKind;
+    use crate::session::{SaveModSource, SaveModTarget};
+
+    #[test]
+    fn preset_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = preset_path_in(dir.path(), "clap:com.example.synth", "Warm Pad");
+        let modulators = vec![SaveModulator {
+            source: SaveModSource::Lfo {
+                waveform: "triangle".into(),
+                rate: 0.25,
+            },
+            targets: vec![SaveModTarget {
+                kind: ModTargetKind::PluginParam { param_index: 3 },
+                label: "Cutoff".into(),
+                depth: 0.4,
+            }],
+        }];
+        save(
+            &path,
+            "Warm Pad",
+            "clap:com.example.synth",
+            &[("Cutoff".into(), 0.5), ("Resonance".into(), 0.25)],
+            Some(&[0x00, 0xab, 0xff]),
+            &modulators,
+        )
+        .unwrap();
+
+        let preset = load(&path).unwrap();
+        assert_eq!(preset.name, "Warm Pad");
+        assert_eq!(preset.plugin, "clap:com.example.synth");
+        assert_eq!(
+            preset.params,
+            vec![("Cutoff".to_string(), 0.5), ("Resonance".to_string(), 0.25)]
+        );
+        assert_eq!(preset.state, Some(vec![0x00, 0xab, 0xff]));
+        assert_eq!(preset.modulators.len(), 1);
+        assert_eq!(preset.modulators[0].waveform, "triangle");
+        assert_eq!(preset.modulators[0].targets[0].param.as_deref(), Some("Cutoff"));
+    }
+
+    #[test]
+    fn library_lists_only_matching_plugin() {
+        let dir = tempfile::tempdir().unwrap();
+        let plugin = "vst3:Some Synth";
+        save(&preset_path_in(dir.path(), plugin, "b"), "b", plugin, &[], None, &[]).unwrap();
+        save(&preset_path_in(dir.path(), plugin, "A"), "A", plugin, &[], None, &[]).unwrap();
+        // A stray file for another plugin in the same folder is ignored.
+        let other = dir.path().join(file_component(plugin)).join("other.toml");
+        save(&other, "other", "vst3:Other", &[], None, &[]).unwrap();
+
+        let presets = presets_in(dir.path(), plugin);
+        let names: Vec<&str> = presets.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["A", "b"]);
+        assert!(presets[0].id.starts_with(PRESET_ID_PREFIX));
+        assert!(presets_in(dir.path(), "vst3:Missing").is_empty());
+    }
+
+    #[test]
+    fn hex_state() {
+        assert_eq!(decode_hex(&encode_hex(&[1, 2, 254])).unwrap(), vec![1, 2, 254]);
+        assert!(decode_hex("abc").is_err());
+        assert!(decode_hex("zz").is_err());
+    }
+
+    #[test]
+    fn file_components_are_safe() {
+        assert_eq!(file_component("lv2:http://example.org/synth"), "lv2_http___example.org_synth");
+        assert_eq!(file_component(".."), "_");
+    }
+}
@@ -1,35 +1,144 @@
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
 
-use super::{ParameterInfo, Plugin, PluginInfo, Preset};
+use super::{MeterInfo, MeterValue, ParameterInfo, Plugin, PluginInfo, Preset};
 
 /// Shared LV2 runtime: one World + Features, created once and reused for all URI-based loads.
 /// Avoids re-scanning the entire LV2 plugin directory for each plugin.
 pub struct Lv2Runtime {
     world: Arc<livi::World>,
     features: Arc<livi::Features>,
+    worker: Arc<WorkerThread>,
 }
 
 impl Lv2Runtime {
     pub fn new(max_block_size: usize) -> Self {
         let world = livi::World::new();
-        let features = world.build_features(livi::FeaturesBuilder {
-            min_block_length: 1,
-            max_block_length: max_block_size,
-        });
+        let features = build_features(&world, max_block_size);
+        let worker = Arc::new(WorkerThread::spawn(&features));
         Lv2Runtime {
             world: Arc::new(world),
             features,
+            worker,
+        }
+    }
+}
+
+/// Build the host feature set for a world.
+///
+/// livi provides URID map/unmap, `bufsz:boundedBlockLength`, `opts:options`
+/// (min/max block length) and `work:schedule`; workers scheduled by plugins
+/// are serviced by a [`WorkerThread`] attached to the features.
+///
+/// `state:mapPath`/`state:makePath` are not offered yet (backlog item
+/// user-032-paths): livi's feature set is fixed and it has no state interface
+/// to pass them to. Until then, plugins that require them are refused by
+/// [`check_path_features`] with an explicit error, and plugins that merely
+/// support them load without file references in state.
+fn build_features(world: &livi::World, max_block_size: usize) -> Arc<livi::Features> {
+    world.build_features(livi::FeaturesBuilder {
+        min_block_length: 1,
+        max_block_length: max_block_size,
+    })
+}
+
+/// Path-mapping features tang doesn't provide yet; see [`build_features`].
+const PATH_FEATURES: [&str; 2] = [
+    "http://lv2plug.in/ns/ext/state#mapPath",
+    "http://lv2plug.in/ns/ext/state#makePath",
+];
+
+/// Fail clearly when a plugin requires `state:mapPath`/`state:makePath`, and
+/// warn when it only supports them, instead of a generic instantiate error.
+fn check_path_features(world: &livi::World, uri: &str, name: &str) -> anyhow::Result<()> {
+    let lilv_world = world.raw();
+    let plugin_node = lilv_world.new_uri(uri);
+    let required_pred = lilv_world.new_uri("http://lv2plug.in/ns/lv2core#requiredFeature");
+    let optional_pred = lilv_world.new_uri("http://lv2plug.in/ns/lv2core#optionalFeature");
+    let declares = |pred, feature: &str| {
+        let feature = lilv_world.new_uri(feature);
+        lilv_world
+            .find_nodes(Some(&plugin_node), pred, Some(&feature))
+            .into_iter()
+            .next()
+            .is_some()
+    };
+    let required: Vec<&str> = PATH_FEATURES
+        .into_iter()
+        .filter(|f| declares(&required_pred, *f))
+        .collect();
+    if !required.is_empty() {
+        anyhow::bail!(
+            "LV2 plugin {name} requires {}, which tang can't provide (the livi host \
+             library has no state path mapping); load its CLAP or VST3 version instead",
+            required.join(" and ")
+        );
+    }
+    if PATH_FEATURES.into_iter().any(|f| declares(&optional_pred, f)) {
+        log::warn!(
+            "{name}: state:mapPath/makePath are unsupported, files referenced by its state \
+             (samples, impulse responses) won't be restored"
+        );
+    }
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// Worker thread
+// ---------------------------------------------------------------------------
+
+/// How often pending `work:schedule` requests are serviced.
+const WORKER_INTERVAL: Duration = Duration::from_millis(5);
+
+/// Background thread that runs LV2 worker jobs (sample loading, IR
+/// convolution setup, ...) off the audio thread. Responses are delivered back
+/// to the plugin by livi at the end of its next `run()`.
+struct WorkerThread {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl WorkerThread {
+    fn spawn(features: &livi::Features) -> Self {
+        let manager = features.worker_manager().clone();
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        let handle = std::thread::Builder::new()
+            .name("lv2-worker".into())
+            .spawn(move || {
+                while !thread_stop.load(Ordering::Relaxed) {
+                    manager.run_workers();
+                    std::thread::sleep(WORKER_INTERVAL);
+                }
+            })
+            .map_err(|e| log::error!("Failed to spawn LV2 worker thread: {e}"))
+            .ok();
+        WorkerThread { stop, handle }
+    }
+}
+
+impl Drop for WorkerThread {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
         }
     }
 }
 
-/// Cached port values for a single LV2 preset.
+/// Cached port and patch parameter values for a single LV2 preset.
 struct Lv2PresetData {
     port_values: Vec<(livi::PortIndex, f32)>,
+    /// Values from the preset's `state:state`, by position in `patch_params`.
+    patch_values: Vec<(usize, f32)>,
 }
 
 pub struct Lv2Plugin {
     name: String,
+    uri: String,
     is_instrument: bool,
     sample_rate: f32,
     #[expect(dead_code)]
@@ -39,10 +148,22 @@ pub struct Lv2Plugin {
     instance: livi::Instance,
     /// Keep the World alive so the Instance's LV2 cleanup can access plugin data on drop.
     _world: Arc<livi::World>,
+    /// Services this instance's worker requests; shared with the runtime
+    /// for URI loads. Declared after `instance` so it outlives it on drop.
+    _worker: Arc<WorkerThread>,
     midi_urid: u32,
     event_buf: livi::event::LV2AtomSequence,
     atom_seq_outputs: Vec<livi::event::LV2AtomSequence>,
     control_input_ports: Vec<livi::Port>,
+    control_output_ports: Vec<livi::Port>,
+    /// Meter handles for `control_output_ports`, published after each run.
+    meters: Vec<MeterInfo>,
+    patch_urids: PatchUrids,
+    patch_params: Vec<PatchParam>,
+    /// Ask the plugin to report its patch parameters on the next run.
+    patch_get_pending: bool,
+    /// The plugin has `state:interface`, so its presets can't be saved.
+    has_state: bool,
     /// Pre-allocated silence buffers for padding audio inputs (e.g. unconnected sidechains)
     silence_bufs: Vec<Vec<f32>>,
     preset_cache: Vec<Preset>,
@@ -54,6 +175,7 @@ fn discover_presets(
     world: &livi::World,
     uri: &str,
     control_input_ports: &[livi::Port],
+    patch_params: &[PatchParam],
 ) -> (Vec<Preset>, Vec<Lv2PresetData>) {
     let lilv_world = world.raw();
     let uri_node = lilv_world.new_uri(uri);
@@ -67,6 +189,11 @@ fn discover_presets(
     let port_pred = lilv_world.new_uri("http://lv2plug.in/ns/lv2core#port");
     let symbol_pred = lilv_world.new_uri("http://lv2plug.in/ns/lv2core#symbol");
     let value_pred = lilv_world.new_uri("http://lv2plug.in/ns/ext/presets#value");
+    let state_pred = lilv_world.new_uri("http://lv2plug.in/ns/ext/state#state");
+    let patch_preds: Vec<_> = patch_params
+        .iter()
+        .map(|p| lilv_world.new_uri(&p.uri))
+        .collect();
 
     let preset_nodes = match lilv_plugin.related(Some(&preset_class)) {
         Some(nodes) => nodes,
@@ -115,36 +242,445 @@ fn discover_presets(
             }
         }
 
+        // Patch parameters are stored as properties of the preset's state.
+        let mut patch_values = Vec::new();
+        for state_node in lilv_world.find_nodes(Some(&preset_node), &state_pred, None) {
+            for (i, pred) in patch_preds.iter().enumerate() {
+                let value = lilv_world
+                    .find_nodes(Some(&state_node), pred, None)
+                    .into_iter()
+                    .next()
+                    .and_then(|n| {
+                        n.as_float().or_else(|| match n.as_str()? {
+                            "true" => Some(1.0),
+                            "false" => Some(0.0),
+                            _ => None,
+                        })
+                    });
+                if let Some(value) = value {
+                    patch_values.push((i, value));
+                }
+            }
+        }
+
         presets.push(Preset { name, id });
-        data.push(Lv2PresetData { port_values });
+        data.push(Lv2PresetData {
+            port_values,
+            patch_values,
+        });
     }
 
     (presets, data)
 }
 
+// ---------------------------------------------------------------------------
+// Patch parameters
+// ---------------------------------------------------------------------------
+
+/// Offset added to a patch parameter's position to form its parameter index,
+/// keeping it clear of control port indices.
+const PATCH_INDEX_BASE: u32 = 1 << 24;
+
+/// Upper bound on an encoded patch message body.
+const PATCH_MSG_SIZE: usize = 64;
+
+/// Atom type of a `patch:writable` parameter's value.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum PatchValueType {
+    Float,
+    Double,
+    Int,
+    Long,
+    Bool,
+}
+
+/// A parameter exposed as a `patch:writable` atom property rather than a
+/// control port.
+struct PatchParam {
+    urid: u32,
+    uri: String,
+    name: String,
+    value_type: PatchValueType,
+    min: f32,
+    max: f32,
+    default: f32,
+    /// Last value sent to or reported by the plugin.
+    value: f32,
+    /// A `patch:Set` for this parameter is waiting for the next process call.
+    pending: bool,
+}
+
+/// URIDs used to build and parse `patch:Set`/`patch:Get` messages.
+#[derive(Clone, Copy)]
+struct PatchUrids {
+    object: u32,
+    urid: u32,
+    float: u32,
+    double: u32,
+    int: u32,
+    long: u32,
+    bool: u32,
+    set: u32,
+    get: u32,
+    property: u32,
+    value: u32,
+}
+
+impl PatchUrids {
+    fn map(features: &livi::Features) -> Self {
+        PatchUrids {
+            object: features.urid(c"http://lv2plug.in/ns/ext/atom#Object"),
+            urid: features.urid(c"http://lv2plug.in/ns/ext/atom#URID"),
+            float: features.urid(c"http://lv2plug.in/ns/ext/atom#Float"),
+            double: features.urid(c"http://lv2plug.in/ns/ext/atom#Double"),
+            int: features.urid(c"http://lv2plug.in/ns/ext/atom#Int"),
+            long: features.urid(c"http://lv2plug.in/ns/ext/atom#Long"),
+            bool: features.urid(c"http://lv2plug.in/ns/ext/atom#Bool"),
+            set: features.urid(c"http://lv2plug.in/ns/ext/patch#Set"),
+            get: features.urid(c"http://lv2plug.in/ns/ext/patch#Get"),
+            property: features.urid(c"http://lv2plug.in/ns/ext/patch#property"),
+            value: features.urid(c"http://lv2plug.in/ns/ext/patch#value"),
+        }
+    }
+
+    fn type_urid(&self, ty: PatchValueType) -> u32 {
+        match ty {
+            PatchValueType::Float => self.float,
+            PatchValueType::Double => self.double,
+            PatchValueType::Int => self.int,
+            PatchValueType::Long => self.long,
+            PatchValueType::Bool => self.bool,
+        }
+    }
+
+    fn value_type(&self, urid: u32) -> Option<PatchValueType> {
+        [
+            PatchValueType::Float,
+            PatchValueType::Double,
+            PatchValueType::Int,
+            PatchValueType::Long,
+            PatchValueType::Bool,
+        ]
+        .into_iter()
+        .find(|&ty| self.type_urid(ty) == urid)
+    }
+}
+
+fn write_u32(out: &mut [u8], pos: usize, value: u32) {
+    out[pos..pos + 4].copy_from_slice(&value.to_ne_bytes());
+}
+
+fn read_u32(data: &[u8], pos: usize) -> Option<u32> {
+    let bytes = data.get(pos..pos + 4)?;
+    Some(u32::from_ne_bytes(bytes.try_into().ok()?))
+}
+
+/// Write one object property (key, context, value atom) at `pos`, padded to
+/// 64 bits as atom containers require. Returns the position after it.
+fn write_property(out: &mut [u8], pos: usize, key: u32, value_type: u32, body: &[u8]) -> usize {
+    write_u32(out, pos, key);
+    write_u32(out, pos + 4, 0);
+    write_u32(out, pos + 8, body.len() as u32);
+    write_u32(out, pos + 12, value_type);
+    out[pos + 16..pos + 16 + body.len()].copy_from_slice(body);
+    let end = pos + 16 + body.len().next_multiple_of(8);
+    out[pos + 16 + body.len()..end].fill(0);
+    end
+}
+
+/// Encode the body of a `patch:Set` object setting `property` to `value`.
+/// Returns the number of bytes written.
+fn encode_patch_set(
+    urids: &PatchUrids,
+    property: u32,
+    value_type: PatchValueType,
+    value: f32,
+    out: &mut [u8; PATCH_MSG_SIZE],
+) -> usize {
+    write_u32(out, 0, 0); // id: blank node
+    write_u32(out, 4, urids.set);
+    let pos = write_property(out, 8, urids.property, urids.urid, &property.to_ne_bytes());
+    let mut body = [0u8; 8];
+    let len = match value_type {
+        PatchValueType::Float => {
+            body[..4].copy_from_slice(&value.to_ne_bytes());
+            4
+        }
+        PatchValueType::Double => {
+            body.copy_from_slice(&(value as f64).to_ne_bytes());
+            8
+        }
+        PatchValueType::Int => {
+            body[..4].copy_from_slice(&(value.round() as i32).to_ne_bytes());
+            4
+        }
+        PatchValueType::Long => {
+            body.copy_from_slice(&(value.round() as i64).to_ne_bytes());
+            8
+        }
+        PatchValueType::Bool => {
+            body[..4].copy_from_slice(&i32::from(value >= 0.5).to_ne_bytes());
+            4
+        }
+    };
+    write_property(out, pos, urids.value, urids.type_urid(value_type), &body[..len])
+}
+
+/// Encode the body of a `patch:Get` object with no property, which asks the
+/// plugin to report all of its parameters.
+fn encode_patch_get(urids: &PatchUrids, out: &mut [u8; PATCH_MSG_SIZE]) -> usize {
+    write_u32(out, 0, 0);
+    write_u32(out, 4, urids.get);
+    8
+}
+
+/// Parse a `patch:Set` object body into its property URID and numeric value.
+/// Other messages and non-numeric values yield `None`.
+fn parse_patch_set(urids: &PatchUrids, body: &[u8]) -> Option<(u32, f32)> {
+    if read_u32(body, 4)? != urids.set {
+        return None;
+    }
+    let mut property = None;
+    let mut value = None;
+    let mut pos = 8;
+    while pos + 16 <= body.len() {
+        let key = read_u32(body, pos)?;
+        let size = read_u32(body, pos + 8)? as usize;
+        let value_type = read_u32(body, pos + 12)?;
+        let data = body.get(pos + 16..pos + 16 + size)?;
+        if key == urids.property && value_type == urids.urid {
+            property = read_u32(data, 0);
+        } else if key == urids.value {
+            value = match urids.value_type(value_type)? {
+                PatchValueType::Float => Some(f32::from_ne_bytes(data.get(..4)?.try_into().ok()?)),
+                PatchValueType::Double => {
+                    Some(f64::from_ne_bytes(data.get(..8)?.try_into().ok()?) as f32)
+                }
+                PatchValueType::Int | PatchValueType::Bool => {
+                    Some(i32::from_ne_bytes(data.get(..4)?.try_into().ok()?) as f32)
+                }
+                PatchValueType::Long => {
+                    Some(i64::from_ne_bytes(data.get(..8)?.try_into().ok()?) as f32)
+                }
+            };
+        }
+        pos += 16 + size.next_multiple_of(8);
+    }
+    Some((property?, value?))
+}
+
+/// Whether the plugin keeps state beyond its parameters (`state:interface`),
+/// which tang has no way to read through livi.
+fn has_state_interface(world: &livi::World, uri: &str) -> bool {
+    let lilv_world = world.raw();
+    let plugin_node = lilv_world.new_uri(uri);
+    let extension_pred = lilv_world.new_uri("http://lv2plug.in/ns/lv2core#extensionData");
+    let state_interface = lilv_world.new_uri("http://lv2plug.in/ns/ext/state#interface");
+    lilv_world
+        .find_nodes(Some(&plugin_node), &extension_pred, Some(&state_interface))
+        .into_iter()
+        .next()
+        .is_some()
+}
+
+/// Discover the plugin's numeric `patch:writable` parameters. Path and
+/// string parameters (sample/IR file choosers) are skipped.
+fn discover_patch_params(
+    world: &livi::World,
+    uri: &str,
+    features: &livi::Features,
+) -> Vec<PatchParam> {
+    let lilv_world = world.raw();
+    let plugin_node = lilv_world.new_uri(uri);
+    let writable_pred = lilv_world.new_uri("http://lv2plug.in/ns/ext/patch#writable");
+    let label_pred = lilv_world.new_uri("http://www.w3.org/2000/01/rdf-schema#label");
+    let range_pred = lilv_world.new_uri("http://www.w3.org/2000/01/rdf-schema#range");
+    let min_pred = lilv_world.new_uri("http://lv2plug.in/ns/lv2core#minimum");
+    let max_pred = lilv_world.new_uri("http://lv2plug.in/ns/lv2core#maximum");
+    let default_pred = lilv_world.new_uri("http://lv2plug.in/ns/lv2core#default");
+
+    let first_float = |subject: &_, pred: &_| {
+        lilv_world
+            .find_nodes(Some(subject), pred, None)
+            .into_iter()
+            .next()
+            .and_then(|n| n.as_float())
+    };
+
+    let mut params = Vec::new();
+    for param_node in lilv_world.find_nodes(Some(&plugin_node), &writable_pred, None) {
+        let Some(param_uri) = param_node.as_uri().map(String::from) else { continue };
+        let range = lilv_world
+            .find_nodes(Some(&param_node), &range_pred, None)
+            .into_iter()
+            .next()
+            .and_then(|n| n.as_uri().map(String::from))
+            .unwrap_or_default();
+        let value_type = match range.rsplit('#').next() {
+            Some("Float") => PatchValueType::Float,
+            Some("Double") => PatchValueType::Double,
+            Some("Int") => PatchValueType::Int,
+            Some("Long") => PatchValueType::Long,
+            Some("Bool") => PatchValueType::Bool,
+            _ => {
+                log::debug!("LV2: skipping patch parameter {param_uri} (range {range})");
+                continue;
+            }
+        };
+        let name = lilv_world
+            .find_nodes(Some(&param_node), &label_pred, None)
+            .into_iter()
+            .next()
+            .and_then(|n| n.as_str().map(String::from))
+            .unwrap_or_else(|| param_uri.rsplit(['#', '/']).next().unwrap_or(&param_uri).to_string());
+        let (min, max) = if value_type == PatchValueType::Bool {
+            (0.0, 1.0)
+        } else {
+            (
+                first_float(&param_node, &min_pred).unwrap_or(0.0),
+                first_float(&param_node, &max_pred).unwrap_or(1.0),
+            )
+        };
+        let default = first_float(&param_node, &default_pred).unwrap_or(min);
+        let Ok(c_uri) = std::ffi::CString::new(param_uri.clone()) else { continue };
+        params.push(PatchParam {
+            urid: features.urid(&c_uri),
+            uri: param_uri,
+            name,
+            value_type,
+            min,
+            max,
+            default,
+            value: default,
+            pending: false,
+        });
+    }
+    params
+}
+
+// ---------------------------------------------------------------------------
+// Preset saving
+// ---------------------------------------------------------------------------
+
+/// Directory where LV2 hosts look for user bundles.
+fn user_bundle_dir() -> Option<PathBuf> {
+    #[cfg(target_os = "macos")]
+    {
+        std::env::var_os("HOME").map(|h| PathBuf::from(h).join("Library/Audio/Plug-Ins/LV2"))
+    }
+    #[cfg(target_os = "windows")]
+    {
+        std::env::var_os("APPDATA").map(|a| PathBuf::from(a).join("LV2"))
+    }
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    {
+        std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".lv2"))
+    }
+}
+
+/// Reduce a label to a file-name-safe symbol, as jalv does for preset bundles.
+fn symbolify(label: &str) -> String {
+    let symbol: String = label
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if symbol.is_empty() {
+        "preset".into()
+    } else {
+        symbol
+    }
+}
+
+fn escape_turtle_string(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Turtle literal for a patch parameter value of type `ty`.
+fn patch_literal(ty: PatchValueType, value: f32) -> String {
+    match ty {
+        PatchValueType::Float => format!("\"{value:?}\"^^xsd:float"),
+        PatchValueType::Double => format!("\"{value:?}\"^^xsd:double"),
+        PatchValueType::Int => format!("\"{}\"^^xsd:int", value.round() as i32),
+        PatchValueType::Long => format!("\"{}\"^^xsd:long", value.round() as i64),
+        PatchValueType::Bool => (value >= 0.5).to_string(),
+    }
+}
+
+/// Turtle for a preset bundle: `(manifest.ttl, <file_name>)` contents.
+/// `properties` are `(property URI, Turtle literal)` pairs written as the
+/// preset's `state:state`.
+fn preset_bundle_ttl(
+    plugin_uri: &str,
+    label: &str,
+    file_name: &str,
+    port_values: &[(String, f32)],
+    properties: &[(String, String)],
+) -> (String, String) {
+    const PREFIXES: &str = "@prefix lv2: <http://lv2plug.in/ns/lv2core#> .\n\
+                            @prefix pset: <http://lv2plug.in/ns/ext/presets#> .\n\
+                            @prefix rdfs: <http://www.w3.org/2000/01/rdf-schema#> .\n\
+                            @prefix state: <http://lv2plug.in/ns/ext/state#> .\n\
+                            @prefix xsd: <http://www.w3.org/2001/XMLSchema#> .\n\n";
+
+    let manifest = format!(
+        "{PREFIXES}<{file_name}>\n    a pset:Preset ;\n    lv2:appliesTo <{plugin_uri}> ;\n    \
+         rdfs:seeAlso <{file_name}> .\n"
+    );
+
+    let mut preset = format!(
+        "{PREFIXES}<>\n    a pset:Preset ;\n    lv2:appliesTo <{plugin_uri}> ;\n    \
+         rdfs:label \"{}\"",
+        escape_turtle_string(label)
+    );
+    let ports: Vec<String> = port_values
+        .iter()
+        .filter(|(_, value)| value.is_finite())
+        .map(|(symbol, value)| {
+            format!(
+                "[\n        lv2:symbol \"{}\" ;\n        pset:value {value:?}\n    ]",
+                escape_turtle_string(symbol)
+            )
+        })
+        .collect();
+    if !ports.is_empty() {
+        preset.push_str(" ;\n    lv2:port ");
+        preset.push_str(&ports.join(" , "));
+    }
+    if !properties.is_empty() {
+        let props: Vec<String> = properties
+            .iter()
+            .map(|(uri, literal)| format!("        <{uri}> {literal}"))
+            .collect();
+        preset.push_str(" ;\n    state:state [\n");
+        preset.push_str(&props.join(" ;\n"));
+        preset.push_str("\n    ]");
+    }
+    preset.push_str(" .\n");
+
+    (manifest, preset)
+}
+
 pub fn load(
     source: &str,
     sample_rate: f32,
     max_block_size: usize,
     runtime: Option<&Lv2Runtime>,
 ) -> anyhow::Result<Box<dyn Plugin>> {
-    let (world, features, lv2_plugin) = if let Some(uri) = source.strip_prefix("lv2:") {
+    let (world, features, worker, lv2_plugin) = if let Some(uri) = source.strip_prefix("lv2:") {
         // Load by URI — reuse shared runtime if available
-        let (world, features) = match runtime {
-            Some(rt) => (rt.world.clone(), rt.features.clone()),
+        let (world, features, worker) = match runtime {
+            Some(rt) => (rt.world.clone(), rt.features.clone(), rt.worker.clone()),
             None => {
                 let w = livi::World::new();
-                let f = w.build_features(livi::FeaturesBuilder {
-                    min_block_length: 1,
-                    max_block_length: max_block_size,
-                });
-                (Arc::new(w), f)
+                let f = build_features(&w, max_block_size);
+                let worker = Arc::new(WorkerThread::spawn(&f));
+                (Arc::new(w), f, worker)
             }
         };
         let plugin = world
             .plugin_by_uri(uri)
             .ok_or_else(|| anyhow::anyhow!("LV2 plugin not found for URI: {uri}\nRun `tang enumerate` to list available plugins."))?;
-        (world, features, plugin)
+        (world, features, worker, plugin)
     } else {
         // Load by bundle path — lightweight, only scans one bundle
         let bundle_uri = if source.starts_with("file://") {
@@ -156,15 +692,13 @@ pub fn load(
             format!("file://{}/", abs.display())
         };
         let w = livi::World::with_load_bundle(&bundle_uri);
-        let f = w.build_features(livi::FeaturesBuilder {
-            min_block_length: 1,
-            max_block_length: max_block_size,
-        });
+        let f = build_features(&w, max_block_size);
+        let worker = Arc::new(WorkerThread::spawn(&f));
         let plugin = w
             .iter_plugins()
             .next()
             .ok_or_else(|| anyhow::anyhow!("No plugin found in bundle: {bundle_uri}"))?;
-        (Arc::new(w), f, plugin)
+        (Arc::new(w), f, worker, plugin)
     };
 
     let name = lv2_plugin.name();
@@ -183,6 +717,8 @@ pub fn load(
         port_counts.control_outputs,
     );
 
+    check_path_features(&world, &uri, &name)?;
+
     let instance = unsafe {
         lv2_plugin
             .instantiate(features.clone(), sample_rate as f64)
@@ -192,6 +728,18 @@ pub fn load(
     let control_input_ports: Vec<livi::Port> = lv2_plugin
         .ports_with_type(livi::PortType::ControlInput)
         .collect();
+    let control_output_ports: Vec<livi::Port> = lv2_plugin
+        .ports_with_type(livi::PortType::ControlOutput)
+        .collect();
+    let meters = control_output_ports
+        .iter()
+        .map(|port| MeterInfo {
+            name: port.name.clone(),
+            min: port.min_value.unwrap_or(0.0),
+            max: port.max_value.unwrap_or(1.0),
+            value: MeterValue::default(),
+        })
+        .collect();
 
     let midi_urid = features.midi_urid();
     let event_buf = livi::event::LV2AtomSequence::new(&features, 4096);
@@ -199,8 +747,21 @@ pub fn load(
         .map(|_| livi::event::LV2AtomSequence::new(&features, 4096))
         .collect();
 
+    // Patch parameters are sent over the atom input, so need one.
+    let patch_urids = PatchUrids::map(&features);
+    let patch_params = if atom_seq_in_count > 0 {
+        discover_patch_params(&world, &uri, &features)
+    } else {
+        Vec::new()
+    };
+    if !patch_params.is_empty() {
+        log::info!("Found {} patch parameters for {name}", patch_params.len());
+    }
+    let has_state = has_state_interface(&world, &uri);
+
     // Eagerly cache presets (avoids needing World on the audio thread)
-    let (preset_cache, preset_data) = discover_presets(&world, &uri, &control_input_ports);
+    let (preset_cache, preset_data) =
+        discover_presets(&world, &uri, &control_input_ports, &patch_params);
     log::info!("Cached {} presets for {name}", preset_cache.len());
 
     // Pre-allocate silence buffers for any audio inputs (resized in process())
@@ -208,6 +769,7 @@ pub fn load(
 
     Ok(Box::new(Lv2Plugin {
         name,
+        uri,
         is_instrument,
         sample_rate,
         audio_in_count,
@@ -215,30 +777,86 @@ pub fn load(
         atom_seq_in_count,
         instance,
         _world: world,
+        _worker: worker,
         midi_urid,
         event_buf,
         atom_seq_outputs,
         control_input_ports,
+        control_output_ports,
+        meters,
+        patch_urids,
+        patch_get_pending: !patch_params.is_empty(),
+        patch_params,
+        has_state,
         silence_bufs,
         preset_cache,
         preset_data,
     }))
 }
 
-/// Enumerate all LV2 plugins found on the system.
+/// LV2 search directories: `LV2_PATH` if set, otherwise lilv's defaults.
+fn search_paths() -> Vec<PathBuf> {
+    if let Some(path) = std::env::var_os("LV2_PATH") {
+        return std::env::split_paths(&path).collect();
+    }
+
+    let mut paths = Vec::new();
+    #[cfg(not(target_os = "windows"))]
+    {
+        if let Some(home) = std::env::var_os("HOME") {
+            paths.push(PathBuf::from(&home).join(".lv2"));
+            #[cfg(target_os = "macos")]
+            paths.push(PathBuf::from(&home).join("Library/Audio/Plug-Ins/LV2"));
+        }
+        paths.push(PathBuf::from("/usr/local/lib/lv2"));
+        paths.push(PathBuf::from("/usr/lib/lv2"));
+        #[cfg(target_os = "macos")]
+        paths.push(PathBuf::from("/Library/Audio/Plug-Ins/LV2"));
+    }
+    #[cfg(target_os = "windows")]
+    {
+        if let Some(appdata) = std::env::var_os("APPDATA") {
+            paths.push(PathBuf::from(appdata).join("LV2"));
+        }
+        if let Some(common) = std::env::var_os("COMMONPROGRAMFILES") {
+            paths.push(PathBuf::from(common).join("LV2"));
+        }
+    }
+    paths
+}
+
+/// All `.lv2` bundles in the search directories.
+pub fn bundle_paths() -> Vec<PathBuf> {
+    let mut bundles: Vec<PathBuf> = search_paths()
+        .iter()
+        .filter_map(|dir| std::fs::read_dir(dir).ok())
+        .flat_map(|entries| entries.flatten().map(|e| e.path()))
+        .filter(|p| p.is_dir() && p.extension().is_some_and(|ext| ext == "lv2"))
+        .collect();
+    bundles.sort();
+    bundles
+}
+
+/// Enumerate all LV2 plugins found on the system. Ports, parameters and
+/// presets come from the plugins' Turtle data; nothing is instantiated.
 pub fn enumerate_plugins() -> Vec<PluginInfo> {
     let world = livi::World::new();
-    let lilv_world = world.raw();
-    let preset_class = lilv_world.new_uri("http://lv2plug.in/ns/ext/presets#Preset");
+    let features = build_features(&world, 512);
 
     world
         .iter_plugins()
         .map(|p| {
-            let preset_count = p
-                .raw()
-                .related(Some(&preset_class))
-                .map(|nodes| nodes.count())
-                .unwrap_or(0);
+            let uri = p.uri();
+            let port_counts = p.port_counts();
+            let control_input_ports: Vec<livi::Port> =
+                p.ports_with_type(livi::PortType::ControlInput).collect();
+            let patch_params = if port_counts.atom_sequence_inputs > 0 {
+                discover_patch_params(&world, &uri, &features)
+            } else {
+                Vec::new()
+            };
+            let parameters = parameter_infos(&control_input_ports, &patch_params);
+            let (presets, _) = discover_presets(&world, &uri, &control_input_ports, &patch_params);
             let path = p
                 .raw()
                 .bundle_uri()
@@ -249,16 +867,69 @@ pub fn enumerate_plugins() -> Vec<PluginInfo> {
                 .to_string();
             PluginInfo {
                 name: p.name(),
-                id: p.uri(),
+                id: uri,
                 is_instrument: p.is_instrument(),
-                param_count: p.ports_with_type(livi::PortType::ControlInput).count(),
-                preset_count,
+                param_count: parameters.len(),
+                preset_count: presets.len(),
                 path,
+                audio_input_count: port_counts.audio_inputs,
+                audio_output_count: port_counts.audio_outputs,
+                parameters,
+                presets,
             }
         })
         .collect()
 }
 
+/// Control input ports followed by patch parameters, as exposed by
+/// [`Plugin::parameters`].
+fn parameter_infos(ports: &[livi::Port], patch_params: &[PatchParam]) -> Vec<ParameterInfo> {
+    let ports = ports.iter().map(|port| ParameterInfo {
+        index: port.index.0 as u32,
+        name: port.name.clone(),
+        min: port.min_value.unwrap_or(0.0),
+        max: port.max_value.unwrap_or(1.0),
+        default: port.default_value,
+    });
+    let patch = patch_params.iter().enumerate().map(|(i, p)| ParameterInfo {
+        index: PATCH_INDEX_BASE + i as u32,
+        name: p.name.clone(),
+        min: p.min,
+        max: p.max,
+        default: p.default,
+    });
+    ports.chain(patch).collect()
+}
+
+impl Lv2Plugin {
+    /// Publish control output values to the meters and pick up patch
+    /// parameter changes reported on the atom outputs.
+    fn read_outputs(&mut self) {
+        for (port, meter) in self.control_output_ports.iter().zip(&self.meters) {
+            if let Some(value) = self.instance.control_output(port.index) {
+                meter.value.set(value);
+            }
+        }
+
+        if self.patch_params.is_empty() {
+            return;
+        }
+        for seq in &self.atom_seq_outputs {
+            for event in seq.iter() {
+                if event.event.body.mytype != self.patch_urids.object {
+                    continue;
+                }
+                let Some((property, value)) = parse_patch_set(&self.patch_urids, event.data) else {
+                    continue;
+                };
+                if let Some(param) = self.patch_params.iter_mut().find(|p| p.urid == property) {
+                    param.value = value;
+                }
+            }
+        }
+    }
+}
+
 impl Plugin for Lv2Plugin {
     fn name(&self) -> &str {
         &self.name
@@ -287,6 +958,24 @@ impl Plugin for Lv2Plugin {
         audio_out: &mut [&mut [f32]],
     ) -> anyhow::Result<()> {
         self.event_buf.clear();
+
+        // Patch messages go first: atom sequence events must be time-ordered.
+        let mut msg = [0u8; PATCH_MSG_SIZE];
+        if self.patch_get_pending {
+            let len = encode_patch_get(&self.patch_urids, &mut msg);
+            if let Err(e) = self.event_buf.push_midi_event::<PATCH_MSG_SIZE>(0, self.patch_urids.object, &msg[..len]) {
+                log::debug!("LV2: failed to push patch:Get: {e:?}");
+            }
+            self.patch_get_pending = false;
+        }
+        for param in self.patch_params.iter_mut().filter(|p| p.pending) {
+            let len = encode_patch_set(&self.patch_urids, param.urid, param.value_type, param.value, &mut msg);
+            if let Err(e) = self.event_buf.push_midi_event::<PATCH_MSG_SIZE>(0, self.patch_urids.object, &msg[..len]) {
+                log::debug!("LV2: failed to push patch:Set for {}: {e:?}", param.name);
+            }
+            param.pending = false;
+        }
+
         for (timestamp, bytes) in midi_events {
             match self
                 .event_buf
@@ -346,27 +1035,31 @@ impl Plugin for Lv2Plugin {
             }
         }
 
+        self.read_outputs();
         Ok(())
     }
 
     fn parameters(&self) -> Vec<ParameterInfo> {
-        self.control_input_ports
-            .iter()
-            .map(|port| ParameterInfo {
-                index: port.index.0 as u32,
-                name: port.name.clone(),
-                min: port.min_value.unwrap_or(0.0),
-                max: port.max_value.unwrap_or(1.0),
-                default: port.default_value,
-            })
-            .collect()
+        parameter_infos(&self.control_input_ports, &self.patch_params)
     }
 
     fn get_parameter(&mut self, index: u32) -> Option<f32> {
+        if let Some(i) = index.checked_sub(PATCH_INDEX_BASE) {
+            return self.patch_params.get(i as usize).map(|p| p.value);
+        }
         self.instance.control_input(livi::PortIndex(index as usize))
     }
 
     fn set_parameter(&mut self, index: u32, value: f32) -> anyhow::Result<()> {
+        if let Some(i) = index.checked_sub(PATCH_INDEX_BASE) {
+            let param = self
+                .patch_params
+                .get_mut(i as usize)
+                .ok_or_else(|| anyhow::anyhow!("Invalid parameter index: {index}"))?;
+            param.value = value.clamp(param.min, param.max);
+            param.pending = true;
+            return Ok(());
+        }
         self.instance
             .set_control_input(livi::PortIndex(index as usize), value)
             .ok_or_else(|| anyhow::anyhow!("Invalid parameter index: {index}"))?;
@@ -377,6 +1070,10 @@ impl Plugin for Lv2Plugin {
         self.preset_cache.clone()
     }
 
+    fn meters(&self) -> Vec<MeterInfo> {
+        self.meters.clone()
+    }
+
     fn load_preset(&mut self, id: &str) -> anyhow::Result<()> {
         let data = self
             .preset_data
@@ -389,8 +1086,167 @@ impl Plugin for Lv2Plugin {
         for &(port_index, value) in &data.port_values {
             self.instance.set_control_input(port_index, value);
         }
+        for &(i, value) in &data.patch_values {
+            if let Some(param) = self.patch_params.get_mut(i) {
+                param.value = value.clamp(param.min, param.max);
+                param.pending = true;
+            }
+        }
 
         log::info!("LV2: loaded preset {id}");
         Ok(())
     }
+
+    fn save_preset(&mut self, name: &str) -> anyhow::Result<Preset> {
+        // Control ports and patch parameters are stored. Opaque state
+        // (state:interface) needs the LV2 state extension, which livi doesn't
+        // expose, so refuse rather than save a preset that sounds different.
+        if self.has_state {
+            anyhow::bail!(
+                "{} keeps internal state that tang can't save as an LV2 preset; \
+                 save a tang preset instead",
+                self.name
+            );
+        }
+        let mut port_values = Vec::new();
+        let mut symbol_values = Vec::new();
+        for port in &self.control_input_ports {
+            if let Some(value) = self.instance.control_input(port.index) {
+                port_values.push((port.index, value));
+                symbol_values.push((port.symbol.clone(), value));
+            }
+        }
+
+        let root = user_bundle_dir()
+            .ok_or_else(|| anyhow::anyhow!("Cannot determine the user LV2 directory"))?;
+        let bundle = root.join(format!(
+            "{}_{}.preset.lv2",
+            symbolify(&self.name),
+            symbolify(name)
+        ));
+        let patch_values: Vec<(usize, f32)> =
+            self.patch_params.iter().map(|p| p.value).enumerate().collect();
+        let properties: Vec<(String, String)> = self
+            .patch_params
+            .iter()
+            .map(|p| (p.uri.clone(), patch_literal(p.value_type, p.value)))
+            .collect();
+
+        let file_name = format!("{}.ttl", symbolify(name));
+        let (manifest, preset_ttl) =
+            preset_bundle_ttl(&self.uri, name, &file_name, &symbol_values, &properties);
+
+        std::fs::create_dir_all(&bundle)
+            .map_err(|e| anyhow::anyhow!("Failed to create {}: {e}", bundle.display()))?;
+        std::fs::write(bundle.join("manifest.ttl"), manifest)?;
+        std::fs::write(bundle.join(&file_name), preset_ttl)?;
+
+        let preset = Preset {
+            name: name.to_string(),
+            id: format!("file://{}", bundle.join(&file_name).display()),
+        };
+        let data = Lv2PresetData {
+            port_values,
+            patch_values,
+        };
+        match self.preset_cache.iter().position(|p| p.id == preset.id) {
+            Some(i) => self.preset_data[i] = data,
+            None => {
+                self.preset_cache.push(preset.clone());
+                self.preset_data.push(data);
+            }
+        }
+        log::info!("LV2: saved preset to {}", bundle.display());
+        Ok(preset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn preset_bundle_turtle() {
+        let (manifest, preset) = preset_bundle_ttl(
+            "urn:example:synth",
+            "Warm \"Pad\"",
+            "Warm__Pad_.ttl",
+            &[("cutoff".into(), 440.0), ("gain".into(), 1.0), ("bad".into(), f32::NAN)],
+            &[],
+        );
+        assert!(manifest.contains("<Warm__Pad_.ttl>\n    a pset:Preset ;"));
+        assert!(manifest.contains("lv2:appliesTo <urn:example:synth> ;"));
+        assert!(manifest.contains("rdfs:seeAlso <Warm__Pad_.ttl> ."));
+        assert!(preset.contains("rdfs:label \"Warm \\\"Pad\\\"\""));
+        assert!(preset.contains("lv2:symbol \"cutoff\" ;\n        pset:value 440.0"));
+        assert!(preset.contains("pset:value 1.0"));
+        assert!(!preset.contains("bad"));
+        assert!(preset.ends_with("pset:value 1.0\n    ] .\n"));
+    }
+
+    #[test]
+    fn preset_bundle_stores_patch_parameters() {
+        let properties = [
+            ("urn:example:synth#level".into(), patch_literal(PatchValueType::Float, 0.5)),
+            ("urn:example:synth#steps".into(), patch_literal(PatchValueType::Int, 3.2)),
+            ("urn:example:synth#on".into(), patch_literal(PatchValueType::Bool, 1.0)),
+        ];
+        let (_, preset) = preset_bundle_ttl("urn:example:synth", "p", "p.ttl", &[], &properties);
+        assert!(preset.contains("@prefix state: <http://lv2plug.in/ns/ext/state#> ."));
+        assert!(preset.contains(
+            "    state:state [\n        <urn:example:synth#level> \"0.5\"^^xsd:float ;\n        \
+             <urn:example:synth#steps> \"3\"^^xsd:int ;\n        <urn:example:synth#on> true\n    ] .\n"
+        ));
+    }
+
+    #[test]
+    fn symbolify_labels() {
+        assert_eq!(symbolify("Warm Pad #2"), "Warm_Pad__2");
+        assert_eq!(symbolify(""), "preset");
+    }
+
+    fn test_urids() -> PatchUrids {
+        PatchUrids {
+            object: 1,
+            urid: 2,
+            float: 3,
+            double: 4,
+            int: 5,
+            long: 6,
+            bool: 7,
+            set: 8,
+            get: 9,
+            property: 10,
+            value: 11,
+        }
+    }
+
+    #[test]
+    fn patch_set_round_trip() {
+        let urids = test_urids();
+        let mut buf = [0u8; PATCH_MSG_SIZE];
+        for (ty, value, expected) in [
+            (PatchValueType::Float, 0.25, 0.25),
+            (PatchValueType::Double, -3.5, -3.5),
+            (PatchValueType::Int, 6.6, 7.0),
+            (PatchValueType::Long, 12.0, 12.0),
+            (PatchValueType::Bool, 0.9, 1.0),
+        ] {
+            let len = encode_patch_set(&urids, 42, ty, value, &mut buf);
+            assert_eq!(len % 8, 0);
+            assert_eq!(parse_patch_set(&urids, &buf[..len]), Some((42, expected)));
+        }
+    }
+
+    #[test]
+    fn patch_parse_ignores_other_messages() {
+        let urids = test_urids();
+        let mut buf = [0u8; PATCH_MSG_SIZE];
+        let len = encode_patch_get(&urids, &mut buf);
+        assert_eq!(len, 8);
+        assert_eq!(parse_patch_set(&urids, &buf[..len]), None);
+        // Truncated set message.
+        let len = encode_patch_set(&urids, 42, PatchValueType::Float, 1.0, &mut buf);
+        assert_eq!(parse_patch_set(&urids, &buf[..len - 8]), None);
+    }
 }
@@ -0,0 +1,297 @@
+//! Master output stage: a sanitiser that keeps NaN/Inf and DC offset away
+//! from the audio device, and an optional look-ahead brickwall limiter.
+//!
+//! Runs on the audio thread after all splits are summed. All buffers are
+//! allocated up front in [`MasterBus::new`].
+
+use std::collections::VecDeque;
+
+use super::MeterValue;
+use crate::session::MasterConfig;
+
+/// DC blocker cutoff in Hz.
+const DC_CUTOFF: f32 = 5.0;
+
+/// How long the sanitiser indicator stays lit after a bad sample, in seconds.
+const SANITISE_HOLD_SECS: f32 = 1.0;
+
+/// Master stage state shared with the UI.
+#[derive(Clone, Default)]
+pub struct MasterMeters {
+    /// Current limiter gain reduction in dB (0 when idle).
+    pub gain_reduction_db: MeterValue,
+    /// 1.0 while the sanitiser has recently replaced NaN/Inf samples.
+    pub sanitised: MeterValue,
+}
+
+pub struct MasterBus {
+    config: MasterConfig,
+    meters: MasterMeters,
+    sample_rate: f32,
+    /// DC blocker pole and per-channel (previous input, previous output).
+    dc_coef: f32,
+    dc_state: Vec<(f32, f32)>,
+    /// Samples left before the sanitiser indicator is cleared.
+    sanitise_hold: usize,
+    limiter: Option<Limiter>,
+}
+
+impl MasterBus {
+    pub fn new(config: &MasterConfig, sample_rate: f32, num_channels: usize) -> Self {
+        let limiter = config
+            .limiter
+            .then(|| Limiter::new(config, sample_rate, num_channels));
+        MasterBus {
+            config: config.clone(),
+            meters: MasterMeters::default(),
+            sample_rate,
+            dc_coef: (-std::f32::consts::TAU * DC_CUTOFF / sample_rate).exp(),
+            dc_state: vec![(0.0, 0.0); num_channels],
+            sanitise_hold: 0,
+            limiter,
+        }
+    }
+
+    /// Handle for the UI to watch the limiter and sanitiser.
+    pub fn meters(&self) -> MasterMeters {
+        self.meters.clone()
+    }
+
+    /// Latency added by the limiter's look-ahead, in samples.
+    pub fn latency(&self) -> usize {
+        self.limiter.as_ref().map_or(0, |l| l.lookahead)
+    }
+
+    /// Process the first `frames` samples of each channel in place.
+    pub fn process(&mut self, channels: &mut [Vec<f32>], frames: usize) {
+        if self.config.sanitise {
+            self.sanitise(channels, frames);
+        }
+        if let Some(limiter) = &mut self.limiter {
+            let reduction = limiter.process(channels, frames);
+            self.meters.gain_reduction_db.set(reduction);
+        }
+    }
+
+    fn sanitise(&mut self, channels: &mut [Vec<f32>], frames: usize) {
+        let mut bad = 0;
+        for (buf, (x1, y1)) in channels.iter_mut().zip(&mut self.dc_state) {
+            for sample in &mut buf[..frames] {
+                let x = if sample.is_finite() {
+                    *sample
+                } else {
+                    bad += 1;
+                    0.0
+                };
+                let y = x - *x1 + self.dc_coef * *y1;
+                *x1 = x;
+                // Flush denormals so a silent master doesn't slow to a crawl.
+                *y1 = if y.abs() < 1e-15 { 0.0 } else { y };
+                *sample = *y1;
+            }
+        }
+
+        if bad > 0 {
+            if self.sanitise_hold == 0 {
+                log::warn!("Master: replaced {bad} non-finite samples with silence");
+            }
+            self.sanitise_hold = (SANITISE_HOLD_SECS * self.sample_rate) as usize;
+            self.meters.sanitised.set(1.0);
+        } else if self.sanitise_hold > 0 {
+            self.sanitise_hold = self.sanitise_hold.saturating_sub(frames);
+            if self.sanitise_hold == 0 {
+                self.meters.sanitised.set(0.0);
+            }
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Limiter
+// ---------------------------------------------------------------------------
+
+/// Look-ahead brickwall limiter.
+///
+/// The audio is delayed by `lookahead` samples. The gain each sample needs
+/// to stay under the ceiling is held at its minimum over the look-ahead
+/// window, released slowly, then smoothed with a moving average of the same
+/// length. Every value in the average is at most the gain needed by the
+/// sample leaving the delay line, so the output never exceeds the ceiling
+/// while gain changes stay ramped rather than stepped.
+struct Limiter {
+    ceiling: f32,
+    lookahead: usize,
+    release_coef: f32,
+    /// Per-channel delay lines.
+    delay: Vec<Vec<f32>>,
+    pos: usize,
+    /// Sliding-window minimum: (sample index, target gain), increasing gains.
+    window: VecDeque<(usize, f32)>,
+    index: usize,
+    /// Held gain after release.
+    held: f32,
+    /// Moving average of `held` over the last `lookahead` samples.
+    smooth: Vec<f32>,
+    smooth_sum: f64,
+}
+
+impl Limiter {
+    fn new(config: &MasterConfig, sample_rate: f32, num_channels: usize) -> Self {
+        let lookahead = ((config.lookahead_ms / 1000.0 * sample_rate) as usize).max(1);
+        let release_samples = (config.release_ms / 1000.0 * sample_rate).max(1.0);
+        Limiter {
+            ceiling: 10.0_f32.powf(config.ceiling_db.min(0.0) / 20.0),
+            lookahead,
+            release_coef: 1.0 - (-1.0 / release_samples).exp(),
+            delay: vec![vec![0.0; lookahead]; num_channels],
+            pos: 0,
+            window: VecDeque::with_capacity(lookahead + 1),
+            index: 0,
+            held: 1.0,
+            smooth: vec![1.0; lookahead],
+            smooth_sum: lookahead as f64,
+        }
+    }
+
+    /// Limit one block in place and return the largest gain reduction
+    /// applied, in dB.
+    fn process(&mut self, channels: &mut [Vec<f32>], frames: usize) -> f32 {
+        let mut min_gain = 1.0_f32;
+        for i in 0..frames {
+            let peak = channels
+                .iter()
+                .map(|buf| buf[i].abs())
+                .fold(0.0_f32, f32::max);
+            let target = if peak > self.ceiling {
+                self.ceiling / peak
+            } else {
+                1.0
+            };
+
+            // Minimum target gain over the last `lookahead + 1` samples.
+            while self.window.back().is_some_and(|&(_, g)| g >= target) {
+                self.window.pop_back();
+            }
+            self.window.push_back((self.index, target));
+            while self
+                .window
+                .front()
+                .is_some_and(|&(idx, _)| idx + self.lookahead < self.index)
+            {
+                self.window.pop_front();
+            }
+            self.index += 1;
+            let window_min = self.window.front().map_or(1.0, |&(_, g)| g);
+
+            // Attack is instant here (the average below ramps it); release
+            // is exponential.
+            if window_min < self.held {
+                self.held = window_min;
+            } else {
+                self.held += (window_min - self.held) * self.release_coef;
+            }
+
+            let slot = self.pos;
+            self.smooth_sum += (self.held - self.smooth[slot]) as f64;
+            self.smooth[slot] = self.held;
+            let gain = (self.smooth_sum / self.lookahead as f64) as f32;
+            min_gain = min_gain.min(gain);
+
+            for (buf, line) in channels.iter_mut().zip(&mut self.delay) {
+                let delayed = std::mem::replace(&mut line[slot], buf[i]);
+                // The clamp only catches float rounding in the average.
+                buf[i] = (delayed * gain).clamp(-self.ceiling, self.ceiling);
+            }
+            self.pos = (self.pos + 1) % self.lookahead;
+        }
+        -20.0 * min_gain.max(1e-6).log10()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SR: f32 = 48000.0;
+
+    fn limiter_config() -> MasterConfig {
+        MasterConfig {
+            limiter: true,
+            ..MasterConfig::default()
+        }
+    }
+
+    #[test]
+    fn limiter_holds_ceiling() {
+        let config = limiter_config();
+        let mut master = MasterBus::new(&config, SR, 2);
+        let ceiling = 10.0_f32.powf(config.ceiling_db / 20.0);
+
+        // A quiet tone with a loud burst in the middle.
+        let frames = 4800;
+        let tone: Vec<f32> = (0..frames)
+            .map(|i| {
+                let amp = if (2000..2400).contains(&i) { 4.0 } else { 0.25 };
+                amp * (i as f32 * 440.0 / SR * std::f32::consts::TAU).sin()
+            })
+            .collect();
+        let mut bufs = [tone.clone(), tone];
+        for block in 0..frames / 480 {
+            let mut chunk: Vec<Vec<f32>> = bufs
+                .iter()
+                .map(|b| b[block * 480..(block + 1) * 480].to_vec())
+                .collect();
+            master.process(&mut chunk, 480);
+            for (b, c) in bufs.iter_mut().zip(&chunk) {
+                b[block * 480..(block + 1) * 480].copy_from_slice(c);
+            }
+        }
+
+        let peak = bufs[0].iter().fold(0.0_f32, |m, s| m.max(s.abs()));
+        assert!(peak <= ceiling + 1e-6, "peak {peak} over ceiling {ceiling}");
+        assert!(peak > ceiling * 0.9, "limiter too aggressive: {peak}");
+        // The quiet part before the burst is untouched (apart from the DC
+        // blocker and look-ahead delay).
+        let lookahead = master.latency();
+        let before = bufs[0][lookahead + 500..1500]
+            .iter()
+            .fold(0.0_f32, |m, s| m.max(s.abs()));
+        assert!((before - 0.25).abs() < 0.01, "quiet part changed: {before}");
+    }
+
+    #[test]
+    fn limiter_reports_gain_reduction() {
+        let mut master = MasterBus::new(&limiter_config(), SR, 2);
+        let meters = master.meters();
+        let mut bufs = vec![vec![0.1; 512], vec![0.1; 512]];
+        master.process(&mut bufs, 512);
+        assert_eq!(meters.gain_reduction_db.get(), 0.0);
+
+        let mut bufs = vec![vec![2.0; 512], vec![-2.0; 512]];
+        master.process(&mut bufs, 512);
+        assert!(meters.gain_reduction_db.get() > 6.0);
+    }
+
+    #[test]
+    fn sanitiser_removes_nan_inf_and_dc() {
+        let mut master = MasterBus::new(&MasterConfig::default(), SR, 2);
+        let meters = master.meters();
+        assert_eq!(master.latency(), 0);
+
+        let mut bufs = vec![vec![0.5; 512], vec![0.5; 512]];
+        bufs[0][10] = f32::NAN;
+        bufs[1][20] = f32::INFINITY;
+        master.process(&mut bufs, 512);
+        assert!(bufs.iter().flatten().all(|s| s.is_finite()));
+        assert_eq!(meters.sanitised.get(), 1.0);
+
+        // A constant offset decays away, and the indicator clears once the
+        // hold time has passed.
+        for _ in 0..200 {
+            bufs = vec![vec![0.5; 512], vec![0.5; 512]];
+            master.process(&mut bufs, 512);
+        }
+        assert!(bufs[0][511].abs() < 1e-3);
+        assert_eq!(meters.sanitised.get(), 0.0);
+    }
+}
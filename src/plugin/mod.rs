@@ -2,12 +2,25 @@ pub mod autodetect;
 pub mod builtin;
 pub mod chain;
 pub mod clap;
+pub mod library;
 #[cfg(feature = "lv2")]
 pub mod lv2;
+pub mod master;
+pub mod quarantine;
+#[cfg(unix)]
+pub mod sandbox;
+pub mod scan_cache;
 #[cfg(feature = "vst3")]
 pub mod vst3;
+#[cfg(feature = "vst3")]
+pub mod vstpreset;
 
-#[derive(Clone)]
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct ParameterInfo {
     pub index: u32,
     pub name: String,
@@ -16,12 +29,36 @@ pub struct ParameterInfo {
     pub default: f32,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Preset {
     pub name: String,
     pub id: String,
 }
 
+/// A read-only plugin output (e.g. an LV2 control output port), shown as a
+/// meter. The plugin publishes the latest value after each process call.
+#[derive(Clone)]
+pub struct MeterInfo {
+    pub name: String,
+    pub min: f32,
+    pub max: f32,
+    pub value: MeterValue,
+}
+
+/// Lock-free f32 shared between the audio thread and the UI.
+#[derive(Clone, Default)]
+pub struct MeterValue(Arc<AtomicU32>);
+
+impl MeterValue {
+    pub fn get(&self) -> f32 {
+        f32::from_bits(self.0.load(Ordering::Relaxed))
+    }
+
+    pub fn set(&self, value: f32) {
+        self.0.store(value.to_bits(), Ordering::Relaxed);
+    }
+}
+
 /// A loaded plugin instance ready to process audio.
 pub trait Plugin: Send {
     fn name(&self) -> &str;
@@ -38,15 +75,81 @@ pub trait Plugin: Send {
     ) -> anyhow::Result<()>;
 
     fn parameters(&self) -> Vec<ParameterInfo>;
-    #[expect(dead_code)]
     fn get_parameter(&mut self, index: u32) -> Option<f32>;
     fn set_parameter(&mut self, index: u32, value: f32) -> anyhow::Result<()>;
 
     fn presets(&self) -> Vec<Preset>;
     fn load_preset(&mut self, id: &str) -> anyhow::Result<()>;
+
+    /// Save the plugin's current state as a user preset called `name` in the
+    /// format's native preset location, and return the new preset entry.
+    fn save_preset(&mut self, _name: &str) -> anyhow::Result<Preset> {
+        anyhow::bail!("{} does not support saving presets", self.name())
+    }
+
+    /// Opaque plugin state beyond its parameter values (e.g. loaded samples
+    /// or wavetables), for host-neutral presets. `None` if the plugin has no
+    /// state API or saving failed.
+    fn save_state(&mut self) -> Option<Vec<u8>> {
+        None
+    }
+
+    /// Restore state captured by [`Plugin::save_state`].
+    fn load_state(&mut self, _data: &[u8]) -> anyhow::Result<()> {
+        anyhow::bail!("{} does not support state restore", self.name())
+    }
+
+    /// Read-only outputs to display as meters. The returned handles stay
+    /// live after the plugin moves to the audio thread.
+    fn meters(&self) -> Vec<MeterInfo> {
+        Vec::new()
+    }
+
+    /// Whether parameter `index` accepts non-destructive modulation offsets
+    /// via [`Plugin::set_modulation`].
+    fn supports_modulation(&self, _index: u32) -> bool {
+        false
+    }
+
+    /// Set the modulation offset (in parameter units) for parameter `index`
+    /// for the next block, on top of its current value. Called every block
+    /// while modulated; a parameter that is not updated for a block returns
+    /// to its unmodulated value.
+    fn set_modulation(&mut self, index: u32, _offset: f32) -> anyhow::Result<()> {
+        anyhow::bail!("parameter {index} does not support modulation")
+    }
+
+    /// Whether parameter `index` can be modulated per voice via
+    /// [`Plugin::set_voice_modulation`].
+    fn supports_voice_modulation(&self, _index: u32) -> bool {
+        false
+    }
+
+    /// Set the modulation offset (in parameter units) applied to parameter
+    /// `index` for the voice playing `key` on MIDI `channel`. The plugin's own
+    /// parameter value is left untouched.
+    fn set_voice_modulation(
+        &mut self,
+        index: u32,
+        _channel: u8,
+        _key: u8,
+        _offset: f32,
+    ) -> anyhow::Result<()> {
+        anyhow::bail!("parameter {index} does not support per-voice modulation")
+    }
+
+    /// Host tempo in beats per minute, for tempo-synced features. Called when
+    /// the plugin joins the audio graph and whenever the global BPM changes.
+    fn set_tempo(&mut self, _bpm: f32) {}
+
+    /// How many of the instrument's output channels the host routes
+    /// anywhere; the rest are dropped. Called when an instrument joins the
+    /// audio graph.
+    fn set_routed_outputs(&mut self, _channels: usize) {}
 }
 
 /// Summary info returned by plugin enumeration.
+#[derive(Clone, Default, Serialize, Deserialize)]
 pub struct PluginInfo {
     pub name: String,
     pub id: String,
@@ -54,6 +157,35 @@ pub struct PluginInfo {
     pub param_count: usize,
     pub preset_count: usize,
     pub path: String,
+    #[serde(default)]
+    pub audio_input_count: usize,
+    #[serde(default)]
+    pub audio_output_count: usize,
+    /// Empty when the scan couldn't load the plugin.
+    #[serde(default)]
+    pub parameters: Vec<ParameterInfo>,
+    #[serde(default)]
+    pub presets: Vec<Preset>,
+}
+
+impl PluginInfo {
+    /// Info for a loaded instance. `id` is how sessions refer to the plugin.
+    pub fn of(plugin: &dyn Plugin, id: String, path: String) -> Self {
+        let parameters = plugin.parameters();
+        let presets = plugin.presets();
+        Self {
+            name: plugin.name().to_string(),
+            id,
+            is_instrument: plugin.is_instrument(),
+            param_count: parameters.len(),
+            preset_count: presets.len(),
+            path,
+            audio_input_count: plugin.audio_input_count(),
+            audio_output_count: plugin.audio_output_count(),
+            parameters,
+            presets,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -69,6 +201,8 @@ pub enum PluginType {
 pub struct Runtime {
     #[cfg(feature = "lv2")]
     pub lv2: Option<lv2::Lv2Runtime>,
+    /// Run external plugins in child processes (see [`sandbox`]).
+    pub sandbox: bool,
 }
 
 impl Runtime {
@@ -76,12 +210,50 @@ impl Runtime {
     pub fn with_lv2(max_block_size: usize) -> Self {
         Self {
             lv2: Some(lv2::Lv2Runtime::new(max_block_size)),
+            ..Self::default()
         }
     }
 }
 
+/// Record the calling thread as the one that services plugin main-thread
+/// callbacks. Call once at startup, before loading any plugin.
+pub fn set_main_thread() {
+    clap::set_main_thread();
+}
+
+/// Service main-thread callbacks requested by loaded plugins (CLAP
+/// `request_callback` and timers). Call regularly from the main loop.
+pub fn pump_main_thread() {
+    clap::pump_main_thread();
+}
+
 /// Load a plugin from the given source, returning a boxed Plugin trait object.
+/// Presets from tang's own preset library are merged into its native ones.
+/// With [`Runtime::sandbox`] set, external plugins are loaded in a child
+/// process so a crash can't take tang down.
 pub fn load(
+    source: &str,
+    sample_rate: f32,
+    max_block_size: usize,
+    runtime: &Runtime,
+) -> anyhow::Result<Box<dyn Plugin>> {
+    #[cfg(unix)]
+    let plugin = if runtime.sandbox && !source.starts_with("builtin:") {
+        sandbox::load(source, sample_rate, max_block_size)?
+    } else {
+        load_native(source, sample_rate, max_block_size, runtime)?
+    };
+    #[cfg(not(unix))]
+    let plugin = {
+        if runtime.sandbox {
+            log::warn!("Plugin sandboxing is not supported on this platform; loading {source} in-process");
+        }
+        load_native(source, sample_rate, max_block_size, runtime)?
+    };
+    Ok(library::LibraryPresets::wrap(plugin, source))
+}
+
+fn load_native(
     source: &str,
     sample_rate: f32,
     max_block_size: usize,
@@ -0,0 +1,247 @@
+//! Plugin bundles that crashed tang while being scanned, and the user
+//! blocklist.
+//!
+//! Before a bundle is scanned, its path is written to a per-process marker
+//! file that is removed once the scan returns. If tang dies mid-scan, the next
+//! scan finds the stale marker and quarantines that bundle, so one broken
+//! plugin can't take down every `enumerate plugins`. Quarantined bundles are
+//! skipped until cleared with `tang quarantine clear`. Entries under
+//! `blocklist` in config.toml (bundle paths, bundle file names or plugin IDs)
+//! are always skipped.
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+const QUARANTINE_FILE: &str = "quarantine.toml";
+const MARKER_PREFIX: &str = "scan-in-progress-";
+
+#[derive(Default, Serialize, Deserialize)]
+struct QuarantineFile {
+    #[serde(default)]
+    bundles: Vec<String>,
+}
+
+pub struct Quarantine {
+    /// Config dir holding the quarantine list and markers; `None` disables
+    /// persistence (no config dir).
+    dir: Option<PathBuf>,
+    bundles: Vec<String>,
+}
+
+impl Quarantine {
+    /// Load the quarantine list, first quarantining any bundle whose scan
+    /// was interrupted by a crash.
+    pub fn open() -> Self {
+        match crate::dirs_config() {
+            Ok(dir) => Self::open_in(&dir),
+            Err(_) => Self {
+                dir: None,
+                bundles: Vec::new(),
+            },
+        }
+    }
+
+    fn open_in(dir: &Path) -> Self {
+        let bundles = std::fs::read_to_string(dir.join(QUARANTINE_FILE))
+            .ok()
+            .and_then(|text| toml::from_str::<QuarantineFile>(&text).ok())
+            .map(|f| f.bundles)
+            .unwrap_or_default();
+        let mut quarantine = Self {
+            dir: Some(dir.to_path_buf()),
+            bundles,
+        };
+        quarantine.recover();
+        quarantine
+    }
+
+    /// Quarantine the bundles named in markers left by processes that are
+    /// no longer running.
+    fn recover(&mut self) {
+        let Some(dir) = &self.dir else { return };
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+        let mut crashed = Vec::new();
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let Some(pid) = name.strip_prefix(MARKER_PREFIX) else {
+                continue;
+            };
+            let Ok(pid) = pid.parse::<u32>() else {
+                continue;
+            };
+            if pid == std::process::id() || process_alive(pid) {
+                continue;
+            }
+            if let Ok(bundle) = std::fs::read_to_string(entry.path()) {
+                crashed.push(bundle);
+            }
+            let _ = std::fs::remove_file(entry.path());
+        }
+        if crashed.is_empty() {
+            return;
+        }
+        for bundle in crashed {
+            log::warn!("Quarantining {bundle}: tang crashed while scanning it");
+            if !self.bundles.contains(&bundle) {
+                self.bundles.push(bundle);
+            }
+        }
+        if let Err(e) = self.save() {
+            log::warn!("Failed to save plugin quarantine: {e}");
+        }
+    }
+
+    fn save(&self) -> anyhow::Result<()> {
+        let Some(dir) = &self.dir else { return Ok(()) };
+        std::fs::create_dir_all(dir)?;
+        let file = QuarantineFile {
+            bundles: self.bundles.clone(),
+        };
+        std::fs::write(dir.join(QUARANTINE_FILE), toml::to_string(&file)?)?;
+        Ok(())
+    }
+
+    fn marker_path(&self) -> Option<PathBuf> {
+        let dir = self.dir.as_ref()?;
+        Some(dir.join(format!("{MARKER_PREFIX}{}", std::process::id())))
+    }
+
+    /// Quarantined bundle paths.
+    pub fn bundles(&self) -> &[String] {
+        &self.bundles
+    }
+
+    /// Whether the bundle at `path` should be skipped, either quarantined or
+    /// blocklisted.
+    pub fn skips(&self, path: &Path) -> bool {
+        let key = path.to_string_lossy();
+        self.bundles.iter().any(|b| *b == key) || is_blocklisted(path, None)
+    }
+
+    /// Run `scan` on the bundle at `path` with a marker in place, so a crash
+    /// during the scan quarantines the bundle.
+    pub fn guard<T>(&self, path: &Path, scan: impl FnOnce() -> T) -> T {
+        let marker = self.marker_path();
+        if let Some(marker) = &marker {
+            if let Err(e) = std::fs::write(marker, path.to_string_lossy().as_bytes()) {
+                log::warn!("Failed to write scan marker {}: {e}", marker.display());
+            }
+        }
+        let result = scan();
+        if let Some(marker) = &marker {
+            let _ = std::fs::remove_file(marker);
+        }
+        result
+    }
+
+    /// Remove `bundle` (or everything, if `None`) from quarantine. Returns
+    /// the number of entries removed.
+    pub fn clear(&mut self, bundle: Option<&str>) -> anyhow::Result<usize> {
+        let before = self.bundles.len();
+        match bundle {
+            Some(bundle) => {
+                let bundle = bundle.trim_end_matches('/');
+                self.bundles.retain(|b| b.trim_end_matches('/') != bundle);
+            }
+            None => self.bundles.clear(),
+        }
+        let removed = before - self.bundles.len();
+        if removed > 0 {
+            self.save()?;
+        }
+        Ok(removed)
+    }
+}
+
+/// Whether a bundle path or plugin ID matches an entry of the config
+/// blocklist. Entries match a full bundle path, a bundle file name (e.g.
+/// `Foo.vst3`) or a plugin ID.
+pub fn is_blocklisted(path: &Path, id: Option<&str>) -> bool {
+    let blocklist = crate::config::blocklist();
+    if blocklist.is_empty() {
+        return false;
+    }
+    let full = path.to_string_lossy();
+    let full = full.trim_end_matches('/');
+    let file_name = path.file_name().map(|n| n.to_string_lossy());
+    blocklist.iter().any(|entry| {
+        let entry = entry.trim_end_matches('/');
+        entry == full || file_name.as_deref() == Some(entry) || id == Some(entry)
+    })
+}
+
+#[cfg(unix)]
+fn process_alive(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    // Safety: signal 0 only checks whether the process exists.
+    if unsafe { libc::kill(pid, 0) } == 0 {
+        return true;
+    }
+    std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(not(unix))]
+fn process_alive(_pid: u32) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A PID above Linux's maximum, so never a running process.
+    const DEAD_PID: u32 = 0x3fff_fff0;
+
+    #[test]
+    fn interrupted_scan_quarantines_bundle() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join(format!("{MARKER_PREFIX}{DEAD_PID}")),
+            "/plugins/Broken.clap",
+        )
+        .unwrap();
+
+        let quarantine = Quarantine::open_in(dir.path());
+        assert_eq!(quarantine.bundles(), ["/plugins/Broken.clap"]);
+        assert!(quarantine.skips(Path::new("/plugins/Broken.clap")));
+        assert!(!quarantine.skips(Path::new("/plugins/Fine.clap")));
+        assert!(
+            !dir.path()
+                .join(format!("{MARKER_PREFIX}{DEAD_PID}"))
+                .exists()
+        );
+
+        // The quarantine persists.
+        let reopened = Quarantine::open_in(dir.path());
+        assert_eq!(reopened.bundles(), ["/plugins/Broken.clap"]);
+    }
+
+    #[test]
+    fn guard_removes_marker_after_scan() {
+        let dir = tempfile::tempdir().unwrap();
+        let quarantine = Quarantine::open_in(dir.path());
+        let marker = quarantine.marker_path().unwrap();
+        let seen = quarantine.guard(Path::new("/plugins/Fine.clap"), || {
+            std::fs::read_to_string(&marker).unwrap()
+        });
+        assert_eq!(seen, "/plugins/Fine.clap");
+        assert!(!marker.exists());
+        assert!(Quarantine::open_in(dir.path()).bundles().is_empty());
+    }
+
+    #[test]
+    fn clear_removes_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut quarantine = Quarantine::open_in(dir.path());
+        quarantine.bundles = vec!["/a.clap".into(), "/b.vst3".into()];
+        assert_eq!(quarantine.clear(Some("/a.clap")).unwrap(), 1);
+        assert_eq!(Quarantine::open_in(dir.path()).bundles(), ["/b.vst3"]);
+        assert_eq!(quarantine.clear(None).unwrap(), 1);
+        assert!(Quarantine::open_in(dir.path()).bundles().is_empty());
+    }
+}
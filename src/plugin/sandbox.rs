@@ -0,0 +1,1326 @@
+//! Out-of-process plugin hosting.
+//!
+//! In sandboxed mode each plugin runs in a child `tang sandbox-host` process.
+//! Audio and MIDI for a block are exchanged through a shared memory file;
+//! requests and replies travel over the child's stdin/stdout as small framed
+//! messages. When the child crashes or stops answering, the plugin outputs
+//! silence while a supervisor thread reaps it, starts a replacement and
+//! brings that back to the last preset, state and parameter values.
+//!
+//! The audio thread never blocks on the child for longer than part of a
+//! block: a block the child hasn't finished in time is output as silence and
+//! its reply is skipped once it arrives.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::{Receiver, Sender};
+use serde::{Deserialize, Serialize};
+
+use super::{MeterInfo, MeterValue, ParameterInfo, Plugin, Preset, Runtime};
+
+/// Most audio channels per direction carried over shared memory.
+const MAX_CHANNELS: usize = 16;
+/// Most MIDI events per block; extra events in a block are dropped.
+const MAX_MIDI_EVENTS: usize = 1024;
+/// Shared memory bytes per MIDI event: frame (u64 LE) + 3 data bytes, padded.
+const MIDI_EVENT_SIZE: usize = 16;
+/// Most meters whose values are carried over shared memory.
+const MAX_METERS: usize = 64;
+
+/// How long the child may go without answering before it is treated as
+/// hung. Requests from the main thread wait this long; the audio thread only
+/// checks how long a late block has been outstanding.
+const REPLY_TIMEOUT: Duration = Duration::from_millis(250);
+/// Share of a block's duration the audio thread waits for the child to
+/// process it, leaving the rest for the remainder of the graph.
+const BLOCK_BUDGET: f32 = 0.5;
+/// How often an idle child services its plugin's main-thread callbacks.
+const PUMP_INTERVAL: Duration = Duration::from_millis(10);
+/// How long a new child may take to load its plugin.
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
+/// Restarts attempted before a crashing plugin is left silent.
+const MAX_RESTARTS: u32 = 3;
+
+// Requests (parent → child).
+const OP_PROCESS: u8 = 1;
+const OP_SET_PARAM: u8 = 2;
+const OP_GET_PARAM: u8 = 3;
+const OP_LOAD_PRESET: u8 = 4;
+const OP_SAVE_PRESET: u8 = 5;
+const OP_SAVE_STATE: u8 = 6;
+const OP_LOAD_STATE: u8 = 7;
+const OP_SET_MODULATION: u8 = 8;
+const OP_SET_VOICE_MODULATION: u8 = 9;
+const OP_SET_TEMPO: u8 = 10;
+const OP_SET_ROUTED_OUTPUTS: u8 = 11;
+
+// Replies (child → parent). SET_PARAM, SET_MODULATION, SET_VOICE_MODULATION,
+// SET_TEMPO and SET_ROUTED_OUTPUTS get none.
+const REPLY_OK: u8 = 0;
+const REPLY_ERR: u8 = 255;
+
+static SHM_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+// ---------------------------------------------------------------------------
+// Framing
+// ---------------------------------------------------------------------------
+
+/// Write one message: `[op: u8][len: u32 LE][payload]`.
+fn write_msg(w: &mut impl Write, op: u8, payload: &[u8]) -> io::Result<()> {
+    let mut header = [0u8; 5];
+    header[0] = op;
+    header[1..].copy_from_slice(&(payload.len() as u32).to_le_bytes());
+    w.write_all(&header)?;
+    w.write_all(payload)?;
+    w.flush()
+}
+
+/// Read one message into `payload`, returning its op.
+fn read_msg(r: &mut impl Read, payload: &mut Vec<u8>) -> io::Result<u8> {
+    let mut header = [0u8; 5];
+    r.read_exact(&mut header)?;
+    let len = u32::from_le_bytes([header[1], header[2], header[3], header[4]]) as usize;
+    payload.resize(len, 0);
+    r.read_exact(payload)?;
+    Ok(header[0])
+}
+
+/// Block until `file` is readable (data or hang-up) or `timeout` passes.
+fn wait_readable(file: &impl AsRawFd, timeout: Duration) -> io::Result<()> {
+    let mut pfd = libc::pollfd {
+        fd: file.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    let ms = timeout.as_millis().min(i32::MAX as u128) as i32;
+    loop {
+        // Safety: pfd is a valid pollfd for the duration of the call.
+        let n = unsafe { libc::poll(&mut pfd, 1, ms) };
+        if n > 0 {
+            return Ok(());
+        }
+        if n == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "plugin process not responding",
+            ));
+        }
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+}
+
+fn read_u32(data: &[u8], at: usize) -> Option<u32> {
+    data.get(at..at + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+fn index_value(index: u32, value: f32) -> [u8; 8] {
+    let mut buf = [0u8; 8];
+    buf[..4].copy_from_slice(&index.to_le_bytes());
+    buf[4..].copy_from_slice(&value.to_le_bytes());
+    buf
+}
+
+// ---------------------------------------------------------------------------
+// Shared memory
+// ---------------------------------------------------------------------------
+
+/// A file mapped into both processes. Layout, for a max block size `B`:
+/// `MAX_CHANNELS` input channels of `B` f32s, then `MAX_CHANNELS` output
+/// channels, then `MAX_MIDI_EVENTS` MIDI event slots, then `MAX_METERS` f32
+/// meter values.
+struct SharedMem {
+    ptr: *mut u8,
+    len: usize,
+    max_block_size: usize,
+}
+
+// Safety: the mapping is plain memory, accessed only by the owning thread on
+// each side, and only while the other side waits for a message.
+unsafe impl Send for SharedMem {}
+
+impl SharedMem {
+    fn audio_len(max_block_size: usize) -> usize {
+        2 * MAX_CHANNELS * max_block_size
+    }
+
+    fn byte_len(max_block_size: usize) -> usize {
+        Self::audio_len(max_block_size) * 4 + MAX_MIDI_EVENTS * MIDI_EVENT_SIZE + MAX_METERS * 4
+    }
+
+    fn create(path: &Path, max_block_size: usize) -> anyhow::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(path)?;
+        file.set_len(Self::byte_len(max_block_size) as u64)?;
+        Self::map(&file, max_block_size)
+    }
+
+    fn open(path: &Path, max_block_size: usize) -> anyhow::Result<Self> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        let len = file.metadata()?.len();
+        if len < Self::byte_len(max_block_size) as u64 {
+            anyhow::bail!("shared memory file {} is too small", path.display());
+        }
+        Self::map(&file, max_block_size)
+    }
+
+    fn map(file: &File, max_block_size: usize) -> anyhow::Result<Self> {
+        let len = Self::byte_len(max_block_size);
+        // Safety: mapping a regular file we hold open; the mapping outlives
+        // the descriptor, which is fine for MAP_SHARED.
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error().into());
+        }
+        Ok(Self {
+            ptr: ptr.cast(),
+            len,
+            max_block_size,
+        })
+    }
+
+    /// Input and output channels (`MAX_CHANNELS` × max block size each), the
+    /// MIDI event area and the meter values.
+    fn regions(&mut self) -> (&mut [f32], &mut [f32], &mut [u8], &mut [f32]) {
+        let audio_len = Self::audio_len(self.max_block_size);
+        let midi_len = MAX_MIDI_EVENTS * MIDI_EVENT_SIZE;
+        // Safety: the mapping is page-aligned and at least byte_len() long;
+        // the regions don't overlap, and the meter region starts at a
+        // multiple of 4 bytes.
+        let (audio, midi, meters) = unsafe {
+            (
+                std::slice::from_raw_parts_mut(self.ptr.cast::<f32>(), audio_len),
+                std::slice::from_raw_parts_mut(self.ptr.add(audio_len * 4), midi_len),
+                std::slice::from_raw_parts_mut(
+                    self.ptr.add(audio_len * 4 + midi_len).cast::<f32>(),
+                    MAX_METERS,
+                ),
+            )
+        };
+        let (inputs, outputs) = audio.split_at_mut(audio_len / 2);
+        (inputs, outputs, midi, meters)
+    }
+}
+
+impl Drop for SharedMem {
+    fn drop(&mut self) {
+        // Safety: ptr/len come from a successful mmap.
+        unsafe {
+            libc::munmap(self.ptr.cast(), self.len);
+        }
+    }
+}
+
+fn write_midi(area: &mut [u8], events: &[(u64, [u8; 3])]) -> usize {
+    let count = events.len().min(MAX_MIDI_EVENTS);
+    for (slot, (frame, data)) in area.chunks_exact_mut(MIDI_EVENT_SIZE).zip(&events[..count]) {
+        slot[..8].copy_from_slice(&frame.to_le_bytes());
+        slot[8..11].copy_from_slice(data);
+    }
+    count
+}
+
+fn read_midi(area: &[u8], count: usize, out: &mut Vec<(u64, [u8; 3])>) {
+    out.clear();
+    for slot in area
+        .chunks_exact(MIDI_EVENT_SIZE)
+        .take(count.min(MAX_MIDI_EVENTS))
+    {
+        let mut frame = [0u8; 8];
+        frame.copy_from_slice(&slot[..8]);
+        out.push((u64::from_le_bytes(frame), [slot[8], slot[9], slot[10]]));
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Child side
+// ---------------------------------------------------------------------------
+
+/// A meter reported by the child; its value travels over shared memory.
+#[derive(Serialize, Deserialize)]
+struct MeterSpec {
+    name: String,
+    min: f32,
+    max: f32,
+}
+
+/// What the child reports about its plugin after loading it.
+#[derive(Serialize, Deserialize)]
+struct HostInfo {
+    name: String,
+    is_instrument: bool,
+    sample_rate: f32,
+    audio_inputs: usize,
+    audio_outputs: usize,
+    #[serde(default)]
+    modulatable: Vec<u32>,
+    #[serde(default)]
+    voice_modulatable: Vec<u32>,
+    #[serde(default)]
+    meters: Vec<MeterSpec>,
+    #[serde(default)]
+    params: Vec<ParameterInfo>,
+    #[serde(default)]
+    presets: Vec<Preset>,
+}
+
+impl HostInfo {
+    fn of(plugin: &dyn Plugin) -> Self {
+        let params = plugin.parameters();
+        Self {
+            name: plugin.name().to_string(),
+            is_instrument: plugin.is_instrument(),
+            sample_rate: plugin.sample_rate(),
+            audio_inputs: plugin.audio_input_count().min(MAX_CHANNELS),
+            audio_outputs: plugin.audio_output_count().min(MAX_CHANNELS),
+            modulatable: params
+                .iter()
+                .map(|p| p.index)
+                .filter(|&i| plugin.supports_modulation(i))
+                .collect(),
+            voice_modulatable: params
+                .iter()
+                .map(|p| p.index)
+                .filter(|&i| plugin.supports_voice_modulation(i))
+                .collect(),
+            meters: plugin
+                .meters()
+                .into_iter()
+                .take(MAX_METERS)
+                .map(|m| MeterSpec {
+                    name: m.name,
+                    min: m.min,
+                    max: m.max,
+                })
+                .collect(),
+            params,
+            presets: plugin.presets(),
+        }
+    }
+}
+
+/// Entry point of `tang sandbox-host`: load `source` and serve requests on
+/// stdin/stdout until the parent goes away.
+pub fn host_main(
+    source: &str,
+    shm_path: &Path,
+    sample_rate: f32,
+    max_block_size: usize,
+) -> anyhow::Result<()> {
+    // Keep the real stdout for the protocol and point fd 1 at stderr, so
+    // anything the plugin prints can't corrupt the message stream.
+    // Safety: plain descriptor duplication before any plugin code runs.
+    let mut writer = unsafe {
+        let fd = libc::dup(1);
+        if fd < 0 || libc::dup2(2, 1) < 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        File::from_raw_fd(fd)
+    };
+    // Read requests unbuffered, so polling the descriptor sees every one.
+    // Safety: as above.
+    let reader = unsafe {
+        let fd = libc::dup(0);
+        if fd < 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        File::from_raw_fd(fd)
+    };
+
+    let shm = SharedMem::open(shm_path, max_block_size)?;
+
+    #[cfg(feature = "lv2")]
+    let runtime = Runtime::with_lv2(max_block_size);
+    #[cfg(not(feature = "lv2"))]
+    let runtime = Runtime::default();
+
+    let plugin = match super::load_native(source, sample_rate, max_block_size, &runtime) {
+        Ok(plugin) => plugin,
+        Err(e) => {
+            write_msg(&mut writer, REPLY_ERR, e.to_string().as_bytes())?;
+            return Err(e);
+        }
+    };
+    serve(plugin, shm, reader, writer)
+}
+
+/// Announce `plugin` and answer requests until the request stream closes.
+fn serve(
+    mut plugin: Box<dyn Plugin>,
+    mut shm: SharedMem,
+    mut reader: File,
+    mut writer: impl Write,
+) -> anyhow::Result<()> {
+    let info = HostInfo::of(plugin.as_ref());
+    let meters = plugin.meters();
+    write_msg(&mut writer, REPLY_OK, toml::to_string(&info)?.as_bytes())?;
+
+    let block = shm.max_block_size;
+    let mut payload = Vec::new();
+    let mut midi = Vec::with_capacity(MAX_MIDI_EVENTS);
+    loop {
+        // This is the child's main thread: keep servicing plugin callbacks
+        // and timers while waiting for the parent.
+        super::pump_main_thread();
+        match wait_readable(&reader, PUMP_INTERVAL) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::TimedOut => continue,
+            Err(e) => return Err(e.into()),
+        }
+        let op = match read_msg(&mut reader, &mut payload) {
+            Ok(op) => op,
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let reply: anyhow::Result<Vec<u8>> = match op {
+            OP_PROCESS => {
+                let frames = (read_u32(&payload, 0).unwrap_or(0) as usize).min(block);
+                let n_midi = read_u32(&payload, 4).unwrap_or(0) as usize;
+                let (inputs, outputs, area, meter_values) = shm.regions();
+                read_midi(area, n_midi, &mut midi);
+                let ins: Vec<&[f32]> = inputs
+                    .chunks(block)
+                    .take(info.audio_inputs)
+                    .map(|c| &c[..frames])
+                    .collect();
+                let mut outs: Vec<&mut [f32]> = outputs
+                    .chunks_mut(block)
+                    .take(info.audio_outputs)
+                    .map(|c| &mut c[..frames])
+                    .collect();
+                let result = plugin.process(&midi, &ins, &mut outs);
+                for (dst, meter) in meter_values.iter_mut().zip(&meters) {
+                    *dst = meter.value.get();
+                }
+                result.map(|()| Vec::new())
+            }
+            OP_SET_PARAM | OP_SET_MODULATION => {
+                let (Some(index), Some(bits)) = (read_u32(&payload, 0), read_u32(&payload, 4))
+                else {
+                    continue;
+                };
+                let value = f32::from_bits(bits);
+                let result = if op == OP_SET_PARAM {
+                    plugin.set_parameter(index, value)
+                } else {
+                    plugin.set_modulation(index, value)
+                };
+                if let Err(e) = result {
+                    log::warn!("{}: {e}", info.name);
+                }
+                continue;
+            }
+            OP_SET_VOICE_MODULATION => {
+                let (Some(index), Some(bits), Some(&[channel, key])) = (
+                    read_u32(&payload, 0),
+                    read_u32(&payload, 4),
+                    payload.get(8..10),
+                ) else {
+                    continue;
+                };
+                let offset = f32::from_bits(bits);
+                if let Err(e) = plugin.set_voice_modulation(index, channel, key, offset) {
+                    log::warn!("{}: {e}", info.name);
+                }
+                continue;
+            }
+            OP_SET_TEMPO => {
+                if let Some(bits) = read_u32(&payload, 0) {
+                    plugin.set_tempo(f32::from_bits(bits));
+                }
+                continue;
+            }
+            OP_SET_ROUTED_OUTPUTS => {
+                if let Some(channels) = read_u32(&payload, 0) {
+                    plugin.set_routed_outputs(channels as usize);
+                }
+                continue;
+            }
+            OP_GET_PARAM => {
+                let index = read_u32(&payload, 0).unwrap_or(u32::MAX);
+                match plugin.get_parameter(index) {
+                    Some(v) => Ok(v.to_le_bytes().to_vec()),
+                    None => Err(anyhow::anyhow!("no parameter {index}")),
+                }
+            }
+            OP_LOAD_PRESET => plugin
+                .load_preset(&String::from_utf8_lossy(&payload))
+                .map(|()| Vec::new()),
+            OP_SAVE_PRESET => plugin
+                .save_preset(&String::from_utf8_lossy(&payload))
+                .and_then(|p| Ok(toml::to_string(&p)?.into_bytes())),
+            OP_SAVE_STATE => Ok(plugin.save_state().unwrap_or_default()),
+            OP_LOAD_STATE => plugin.load_state(&payload).map(|()| Vec::new()),
+            _ => Err(anyhow::anyhow!("unknown request {op}")),
+        };
+
+        match reply {
+            Ok(data) => write_msg(&mut writer, REPLY_OK, &data)?,
+            Err(e) => write_msg(&mut writer, REPLY_ERR, e.to_string().as_bytes())?,
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Parent side
+// ---------------------------------------------------------------------------
+
+/// How to start (and restart) a sandbox child.
+#[derive(Clone)]
+struct SpawnArgs {
+    source: String,
+    sample_rate: f32,
+    max_block_size: usize,
+}
+
+/// Plugin-level failure reported by the child, as opposed to the child
+/// itself failing (an `io::Error`).
+type Reply<'a> = Result<&'a [u8], String>;
+
+/// Outcome of running one block in the child.
+enum Block {
+    /// Outputs and meters are filled in.
+    Done,
+    /// The child is still working on this (or an earlier) block.
+    Late,
+    /// The plugin reported an error.
+    Failed(String),
+}
+
+/// Pipes and shared memory to one child process.
+struct Connection {
+    child: Option<Child>,
+    writer: File,
+    reader: File,
+    shm: SharedMem,
+    payload: Vec<u8>,
+    /// When the block the child is still processing was sent, if its reply
+    /// hasn't been read yet.
+    late_since: Option<Instant>,
+}
+
+impl Connection {
+    fn spawn(args: &SpawnArgs) -> anyhow::Result<(Self, HostInfo)> {
+        let shm_path = std::env::temp_dir().join(format!(
+            "tang-sandbox-{}-{}",
+            std::process::id(),
+            SHM_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let result = Self::spawn_with(args, &shm_path);
+        // Both sides have it mapped (or gave up) by now.
+        let _ = std::fs::remove_file(&shm_path);
+        result
+    }
+
+    fn spawn_with(args: &SpawnArgs, shm_path: &Path) -> anyhow::Result<(Self, HostInfo)> {
+        let shm = SharedMem::create(shm_path, args.max_block_size)?;
+        let mut child = Command::new(std::env::current_exe()?)
+            .arg("sandbox-host")
+            .arg(&args.source)
+            .arg("--shm")
+            .arg(shm_path)
+            .arg("--sample-rate")
+            .arg(args.sample_rate.to_string())
+            .arg("--block-size")
+            .arg(args.max_block_size.to_string())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| anyhow::anyhow!("Failed to start sandbox process: {e}"))?;
+        let writer = File::from(OwnedFd::from(child.stdin.take().expect("piped stdin")));
+        let reader = File::from(OwnedFd::from(child.stdout.take().expect("piped stdout")));
+        let mut conn = Self::new(Some(child), writer, reader, shm);
+        let info = conn.handshake()?;
+        Ok((conn, info))
+    }
+
+    fn new(child: Option<Child>, writer: File, reader: File, shm: SharedMem) -> Self {
+        Self {
+            child,
+            writer,
+            reader,
+            shm,
+            payload: Vec::new(),
+            late_since: None,
+        }
+    }
+
+    /// Wait for the child to report its loaded plugin.
+    fn handshake(&mut self) -> anyhow::Result<HostInfo> {
+        match self.read_reply(STARTUP_TIMEOUT)? {
+            Ok(data) => Ok(toml::from_str(std::str::from_utf8(data)?)?),
+            Err(msg) => anyhow::bail!("{msg}"),
+        }
+    }
+
+    fn send(&mut self, op: u8, payload: &[u8]) -> io::Result<()> {
+        write_msg(&mut self.writer, op, payload)
+    }
+
+    fn read_reply(&mut self, timeout: Duration) -> io::Result<Reply<'_>> {
+        wait_readable(&self.reader, timeout)?;
+        match read_msg(&mut self.reader, &mut self.payload)? {
+            REPLY_OK => Ok(Ok(&self.payload)),
+            _ => Ok(Err(String::from_utf8_lossy(&self.payload).into_owned())),
+        }
+    }
+
+    /// Whether a reply arrives by `deadline`. Polls in whole milliseconds
+    /// while that many remain, then spins, so blocks shorter than a
+    /// millisecond still get their share of the wait.
+    fn reply_by(&self, deadline: Instant) -> io::Result<bool> {
+        loop {
+            let left = deadline.saturating_duration_since(Instant::now());
+            match wait_readable(&self.reader, left) {
+                Ok(()) => return Ok(true),
+                Err(e) if e.kind() == io::ErrorKind::TimedOut => {}
+                Err(e) => return Err(e),
+            }
+            if Instant::now() >= deadline {
+                return Ok(false);
+            }
+            std::hint::spin_loop();
+        }
+    }
+
+    /// Read and drop the reply to a late block, waiting up to `timeout`.
+    fn settle(&mut self, timeout: Duration) -> io::Result<()> {
+        if self.late_since.is_some() {
+            let _ = self.read_reply(timeout)?;
+            self.late_since = None;
+        }
+        Ok(())
+    }
+
+    fn request(&mut self, op: u8, payload: &[u8]) -> io::Result<Reply<'_>> {
+        self.settle(REPLY_TIMEOUT)?;
+        self.send(op, payload)?;
+        self.read_reply(REPLY_TIMEOUT)
+    }
+
+    /// Run one block in the child, waiting at most `budget` for it. Output
+    /// channels the child doesn't fill are cleared. A block that misses the
+    /// budget is left running; its reply is skipped on a later call, and the
+    /// child counts as hung once that has taken longer than `REPLY_TIMEOUT`.
+    fn process(
+        &mut self,
+        midi_events: &[(u64, [u8; 3])],
+        audio_in: &[&[f32]],
+        audio_out: &mut [&mut [f32]],
+        meters: &[MeterInfo],
+        budget: Duration,
+    ) -> io::Result<Block> {
+        if let Some(since) = self.late_since {
+            if !self.reply_by(Instant::now())? {
+                if since.elapsed() > REPLY_TIMEOUT {
+                    return Err(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        "plugin process not responding",
+                    ));
+                }
+                return Ok(Block::Late);
+            }
+            self.settle(Duration::ZERO)?;
+        }
+
+        let block = self.shm.max_block_size;
+        let frames = audio_out.first().map_or(0, |b| b.len()).min(block);
+        let (inputs, _, area, _) = self.shm.regions();
+        for (dst, src) in inputs.chunks_mut(block).zip(audio_in) {
+            let n = frames.min(src.len());
+            dst[..n].copy_from_slice(&src[..n]);
+            dst[n..frames].fill(0.0);
+        }
+        let n_midi = write_midi(area, midi_events);
+
+        let mut header = [0u8; 8];
+        header[..4].copy_from_slice(&(frames as u32).to_le_bytes());
+        header[4..].copy_from_slice(&(n_midi as u32).to_le_bytes());
+        let sent = Instant::now();
+        self.send(OP_PROCESS, &header)?;
+        if !self.reply_by(sent + budget)? {
+            self.late_since = Some(sent);
+            return Ok(Block::Late);
+        }
+        if let Err(msg) = self.read_reply(Duration::ZERO)? {
+            return Ok(Block::Failed(msg));
+        }
+
+        let (_, outputs, _, meter_values) = self.shm.regions();
+        let mut sources = outputs.chunks(block);
+        for dst in audio_out.iter_mut() {
+            match sources.next() {
+                Some(src) => dst[..frames].copy_from_slice(&src[..frames]),
+                None => dst.fill(0.0),
+            }
+        }
+        for (meter, &value) in meters.iter().zip(meter_values.iter()) {
+            meter.value.set(value);
+        }
+        Ok(Block::Done)
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+/// Settings replayed into a restarted child.
+#[derive(Clone, Default)]
+struct Restore {
+    preset: Option<String>,
+    state: Option<Vec<u8>>,
+    /// Values set since the last preset or state load.
+    params: Vec<(u32, f32)>,
+    tempo: Option<f32>,
+    routed_outputs: Option<u32>,
+}
+
+impl Restore {
+    fn apply(&self, conn: &mut Connection) -> io::Result<()> {
+        if let Some(id) = &self.preset {
+            let _ = conn.request(OP_LOAD_PRESET, id.as_bytes())?;
+        }
+        if let Some(state) = &self.state {
+            let _ = conn.request(OP_LOAD_STATE, state)?;
+        }
+        for &(index, value) in &self.params {
+            conn.send(OP_SET_PARAM, &index_value(index, value))?;
+        }
+        if let Some(bpm) = self.tempo {
+            conn.send(OP_SET_TEMPO, &bpm.to_le_bytes())?;
+        }
+        if let Some(channels) = self.routed_outputs {
+            conn.send(OP_SET_ROUTED_OUTPUTS, &channels.to_le_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Supervisor
+// ---------------------------------------------------------------------------
+
+/// A child that stopped, handed from the audio thread to the supervisor.
+struct Crash {
+    conn: Connection,
+    error: io::Error,
+    restore: Restore,
+}
+
+/// Start the thread that reaps crashed children and starts replacements,
+/// so neither happens on the audio thread. It exits when `crashes` closes.
+fn spawn_supervisor(
+    name: String,
+    spawn: Option<SpawnArgs>,
+    crashes: Receiver<Crash>,
+    respawned: Sender<Connection>,
+) {
+    let spawned = std::thread::Builder::new()
+        .name("sandbox-supervisor".into())
+        .spawn(move || {
+            let mut restarts = 0;
+            for crash in crashes {
+                // Kills and reaps the child.
+                drop(crash.conn);
+                let error = crash.error;
+                let Some(args) = &spawn else {
+                    log::error!("{name}: plugin process stopped ({error})");
+                    continue;
+                };
+                if restarts >= MAX_RESTARTS {
+                    log::error!(
+                        "{name}: plugin process stopped ({error}); giving up after {MAX_RESTARTS} restarts"
+                    );
+                    continue;
+                }
+                restarts += 1;
+                log::error!("{name}: plugin process stopped ({error}); restarting");
+
+                let result = Connection::spawn(args).and_then(|(mut conn, _)| {
+                    crash.restore.apply(&mut conn)?;
+                    Ok(conn)
+                });
+                match result {
+                    Ok(conn) => {
+                        log::info!("{name}: plugin process restarted");
+                        if respawned.send(conn).is_err() {
+                            break;
+                        }
+                    }
+                    Err(e) => log::error!("{name}: failed to restart plugin process: {e}"),
+                }
+            }
+        });
+    if let Err(e) = spawned {
+        log::error!("Failed to start sandbox supervisor: {e}");
+    }
+}
+
+/// A plugin hosted in a child process. Implements [`Plugin`] by forwarding
+/// every call; if the child dies, it outputs silence until a restarted child
+/// is ready.
+pub struct SandboxedPlugin {
+    info: HostInfo,
+    conn: Option<Connection>,
+    meters: Vec<MeterInfo>,
+    crash_tx: Sender<Crash>,
+    respawn_rx: Receiver<Connection>,
+    restore: Restore,
+    /// Share of each block's duration to wait for the child.
+    block_budget: f32,
+}
+
+/// Load `source` in a new sandbox child process.
+pub fn load(
+    source: &str,
+    sample_rate: f32,
+    max_block_size: usize,
+) -> anyhow::Result<Box<dyn Plugin>> {
+    let args = SpawnArgs {
+        source: source.to_string(),
+        sample_rate,
+        max_block_size,
+    };
+    let (conn, info) = Connection::spawn(&args)?;
+    Ok(Box::new(SandboxedPlugin::new(conn, info, Some(args))))
+}
+
+impl SandboxedPlugin {
+    fn new(conn: Connection, info: HostInfo, spawn: Option<SpawnArgs>) -> Self {
+        let meters = info
+            .meters
+            .iter()
+            .map(|m| MeterInfo {
+                name: m.name.clone(),
+                min: m.min,
+                max: m.max,
+                value: MeterValue::default(),
+            })
+            .collect();
+        // One crash per running child, and at most MAX_RESTARTS restarts, so
+        // sending a crash never blocks or fails while the supervisor lives.
+        let (crash_tx, crash_rx) = crossbeam_channel::bounded(MAX_RESTARTS as usize + 1);
+        let (respawn_tx, respawn_rx) = crossbeam_channel::bounded(1);
+        spawn_supervisor(info.name.clone(), spawn, crash_rx, respawn_tx);
+        Self {
+            info,
+            conn: Some(conn),
+            meters,
+            crash_tx,
+            respawn_rx,
+            restore: Restore::default(),
+            block_budget: BLOCK_BUDGET,
+        }
+    }
+
+    /// Hand the dead child to the supervisor, which reaps it and, unless it
+    /// keeps crashing, starts a replacement.
+    fn crashed(&mut self, error: io::Error) {
+        let Some(conn) = self.conn.take() else { return };
+        let _ = self.crash_tx.try_send(Crash {
+            conn,
+            error,
+            restore: self.restore.clone(),
+        });
+    }
+
+    /// Pick up a restarted child, if one is ready.
+    fn poll_respawn(&mut self) {
+        if let Ok(conn) = self.respawn_rx.try_recv() {
+            self.conn = Some(conn);
+        }
+    }
+
+    /// Send a request and wait for its reply.
+    fn call(&mut self, op: u8, payload: &[u8]) -> anyhow::Result<Vec<u8>> {
+        self.poll_respawn();
+        let Some(conn) = self.conn.as_mut() else {
+            anyhow::bail!("{} is not running (plugin process crashed)", self.info.name);
+        };
+        match conn.request(op, payload).map(|r| r.map(<[u8]>::to_vec)) {
+            Ok(Ok(data)) => Ok(data),
+            Ok(Err(msg)) => Err(anyhow::anyhow!(msg)),
+            Err(e) => {
+                let msg = format!("{}: plugin process stopped ({e})", self.info.name);
+                self.crashed(e);
+                anyhow::bail!(msg)
+            }
+        }
+    }
+
+    /// Send a request that has no reply.
+    fn notify(&mut self, op: u8, payload: &[u8]) {
+        let Some(conn) = self.conn.as_mut() else {
+            return;
+        };
+        if let Err(e) = conn.send(op, payload) {
+            self.crashed(e);
+        }
+    }
+}
+
+impl Plugin for SandboxedPlugin {
+    fn name(&self) -> &str {
+        &self.info.name
+    }
+
+    fn is_instrument(&self) -> bool {
+        self.info.is_instrument
+    }
+
+    fn sample_rate(&self) -> f32 {
+        self.info.sample_rate
+    }
+
+    fn audio_output_count(&self) -> usize {
+        self.info.audio_outputs
+    }
+
+    fn audio_input_count(&self) -> usize {
+        self.info.audio_inputs
+    }
+
+    fn process(
+        &mut self,
+        midi_events: &[(u64, [u8; 3])],
+        audio_in: &[&[f32]],
+        audio_out: &mut [&mut [f32]],
+    ) -> anyhow::Result<()> {
+        self.poll_respawn();
+        let frames = audio_out.first().map_or(0, |b| b.len());
+        let budget =
+            Duration::from_secs_f32(frames as f32 / self.info.sample_rate * self.block_budget);
+        let result = match self.conn.as_mut() {
+            Some(conn) => conn.process(midi_events, audio_in, audio_out, &self.meters, budget),
+            None => Ok(Block::Late),
+        };
+        match result {
+            Ok(Block::Done) => Ok(()),
+            Ok(Block::Late) => {
+                audio_out.iter_mut().for_each(|ch| ch.fill(0.0));
+                Ok(())
+            }
+            Ok(Block::Failed(msg)) => Err(anyhow::anyhow!(msg)),
+            Err(e) => {
+                self.crashed(e);
+                audio_out.iter_mut().for_each(|ch| ch.fill(0.0));
+                Ok(())
+            }
+        }
+    }
+
+    fn parameters(&self) -> Vec<ParameterInfo> {
+        self.info.params.clone()
+    }
+
+    fn get_parameter(&mut self, index: u32) -> Option<f32> {
+        if self.conn.is_none() {
+            // Only known if set since the last load, or never loaded over.
+            let restore = &self.restore;
+            if let Some(p) = restore.params.iter().find(|p| p.0 == index) {
+                return Some(p.1);
+            }
+            if restore.preset.is_some() || restore.state.is_some() {
+                return None;
+            }
+            let info = self.info.params.iter().find(|p| p.index == index)?;
+            return Some(info.default);
+        }
+        let data = self.call(OP_GET_PARAM, &index.to_le_bytes()).ok()?;
+        read_u32(&data, 0).map(f32::from_bits)
+    }
+
+    fn set_parameter(&mut self, index: u32, value: f32) -> anyhow::Result<()> {
+        match self.restore.params.iter_mut().find(|p| p.0 == index) {
+            Some(p) => p.1 = value,
+            None => self.restore.params.push((index, value)),
+        }
+        self.notify(OP_SET_PARAM, &index_value(index, value));
+        Ok(())
+    }
+
+    fn presets(&self) -> Vec<Preset> {
+        self.info.presets.clone()
+    }
+
+    fn load_preset(&mut self, id: &str) -> anyhow::Result<()> {
+        self.call(OP_LOAD_PRESET, id.as_bytes())?;
+        self.restore.preset = Some(id.to_string());
+        self.restore.state = None;
+        self.restore.params.clear();
+        Ok(())
+    }
+
+    fn save_preset(&mut self, name: &str) -> anyhow::Result<Preset> {
+        let data = self.call(OP_SAVE_PRESET, name.as_bytes())?;
+        let preset: Preset = toml::from_str(std::str::from_utf8(&data)?)?;
+        self.info.presets.push(preset.clone());
+        Ok(preset)
+    }
+
+    fn save_state(&mut self) -> Option<Vec<u8>> {
+        self.call(OP_SAVE_STATE, &[]).ok().filter(|d| !d.is_empty())
+    }
+
+    fn load_state(&mut self, data: &[u8]) -> anyhow::Result<()> {
+        self.call(OP_LOAD_STATE, data)?;
+        self.restore.state = Some(data.to_vec());
+        self.restore.params.clear();
+        Ok(())
+    }
+
+    fn meters(&self) -> Vec<MeterInfo> {
+        self.meters.clone()
+    }
+
+    fn supports_modulation(&self, index: u32) -> bool {
+        self.info.modulatable.contains(&index)
+    }
+
+    fn set_modulation(&mut self, index: u32, offset: f32) -> anyhow::Result<()> {
+        self.notify(OP_SET_MODULATION, &index_value(index, offset));
+        Ok(())
+    }
+
+    fn supports_voice_modulation(&self, index: u32) -> bool {
+        self.info.voice_modulatable.contains(&index)
+    }
+
+    fn set_voice_modulation(
+        &mut self,
+        index: u32,
+        channel: u8,
+        key: u8,
+        offset: f32,
+    ) -> anyhow::Result<()> {
+        let mut payload = [0u8; 10];
+        payload[..8].copy_from_slice(&index_value(index, offset));
+        payload[8] = channel;
+        payload[9] = key;
+        self.notify(OP_SET_VOICE_MODULATION, &payload);
+        Ok(())
+    }
+
+    fn set_tempo(&mut self, bpm: f32) {
+        self.restore.tempo = Some(bpm);
+        self.notify(OP_SET_TEMPO, &bpm.to_le_bytes());
+    }
+
+    fn set_routed_outputs(&mut self, channels: usize) {
+        let channels = channels as u32;
+        self.restore.routed_outputs = Some(channels);
+        self.notify(OP_SET_ROUTED_OUTPUTS, &channels.to_le_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixStream;
+    use std::sync::{Arc, Mutex};
+
+    const BLOCK: usize = 64;
+
+    /// Connect a parent-side plugin to `plugin` served on a thread, over a
+    /// socket pair instead of a child process.
+    fn serve_in_thread(
+        plugin: Box<dyn Plugin>,
+        dir: &Path,
+    ) -> (SandboxedPlugin, std::thread::JoinHandle<()>) {
+        let shm_path = dir.join("shm");
+        let shm = SharedMem::create(&shm_path, BLOCK).unwrap();
+        let child_shm = SharedMem::open(&shm_path, BLOCK).unwrap();
+        let (ours, theirs) = UnixStream::pair().unwrap();
+        let handle = std::thread::spawn(move || {
+            let reader = File::from(OwnedFd::from(theirs.try_clone().unwrap()));
+            let _ = serve(plugin, child_shm, reader, theirs);
+        });
+        let mut conn = Connection::new(
+            None,
+            File::from(OwnedFd::from(ours.try_clone().unwrap())),
+            File::from(OwnedFd::from(ours)),
+            shm,
+        );
+        let info = conn.handshake().unwrap();
+        let mut plugin = SandboxedPlugin::new(conn, info, None);
+        // Generous, so a busy test machine doesn't make blocks late.
+        plugin.block_budget = 50.0;
+        (plugin, handle)
+    }
+
+    fn sine() -> Box<dyn Plugin> {
+        super::super::builtin::load("builtin:sine", 48000.0, BLOCK).unwrap()
+    }
+
+    fn run_block(plugin: &mut SandboxedPlugin, midi: &[(u64, [u8; 3])]) -> Vec<f32> {
+        let mut left = vec![1.0; BLOCK];
+        let mut right = vec![1.0; BLOCK];
+        plugin
+            .process(midi, &[], &mut [&mut left, &mut right])
+            .unwrap();
+        left
+    }
+
+    #[test]
+    fn sandboxed_plugin_forwards_audio_and_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut plugin, _handle) = serve_in_thread(sine(), dir.path());
+        assert_eq!(plugin.name(), sine().name());
+        assert!(plugin.is_instrument());
+
+        assert!(run_block(&mut plugin, &[]).iter().all(|&s| s == 0.0));
+        let out = run_block(&mut plugin, &[(0, [0x90, 69, 100])]);
+        assert!(out.iter().any(|&s| s.abs() > 0.01));
+
+        // Plugin errors come back as errors without killing the connection.
+        let err = plugin.load_preset("nope").unwrap_err();
+        assert!(err.to_string().contains("no preset"));
+        assert_eq!(plugin.get_parameter(999), None);
+        assert!(plugin.conn.is_some());
+    }
+
+    struct Crashing;
+
+    impl Plugin for Crashing {
+        fn name(&self) -> &str {
+            "crashing"
+        }
+        fn is_instrument(&self) -> bool {
+            true
+        }
+        fn sample_rate(&self) -> f32 {
+            48000.0
+        }
+        fn audio_output_count(&self) -> usize {
+            2
+        }
+        fn audio_input_count(&self) -> usize {
+            0
+        }
+        fn process(
+            &mut self,
+            _midi_events: &[(u64, [u8; 3])],
+            _audio_in: &[&[f32]],
+            _audio_out: &mut [&mut [f32]],
+        ) -> anyhow::Result<()> {
+            panic!("plugin crashed");
+        }
+        fn parameters(&self) -> Vec<ParameterInfo> {
+            Vec::new()
+        }
+        fn get_parameter(&mut self, _index: u32) -> Option<f32> {
+            None
+        }
+        fn set_parameter(&mut self, _index: u32, _value: f32) -> anyhow::Result<()> {
+            Ok(())
+        }
+        fn presets(&self) -> Vec<Preset> {
+            Vec::new()
+        }
+        fn load_preset(&mut self, _id: &str) -> anyhow::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn restart_replays_preset_then_later_parameters() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut plugin, _handle) = serve_in_thread(sine(), dir.path());
+        plugin.set_parameter(1, 0.9).unwrap();
+        plugin.load_preset("Square Lead").unwrap();
+        plugin.set_parameter(3, 0.4).unwrap();
+
+        // What a restarted child ends up with.
+        let fresh = tempfile::tempdir().unwrap();
+        let (mut restarted, _handle) = serve_in_thread(sine(), fresh.path());
+        plugin
+            .restore
+            .apply(restarted.conn.as_mut().unwrap())
+            .unwrap();
+        assert_eq!(restarted.get_parameter(0), Some(3.0));
+        assert_eq!(restarted.get_parameter(1), Some(0.3));
+        assert_eq!(restarted.get_parameter(3), Some(0.4));
+    }
+
+    #[test]
+    fn crashed_plugin_outputs_silence() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut plugin, handle) = serve_in_thread(Box::new(Crashing), dir.path());
+        assert!(run_block(&mut plugin, &[]).iter().all(|&s| s == 0.0));
+        assert!(handle.join().is_err());
+        // The hang-up is noticed by the next block, if not the first.
+        assert!(run_block(&mut plugin, &[]).iter().all(|&s| s == 0.0));
+        assert!(plugin.conn.is_none());
+        assert!(run_block(&mut plugin, &[]).iter().all(|&s| s == 0.0));
+        assert!(plugin.load_preset("x").is_err());
+    }
+
+    /// Records what it is sent and sleeps in `process` when asked to.
+    #[derive(Default)]
+    struct Probe {
+        log: Arc<Mutex<Vec<String>>>,
+        meter: MeterValue,
+        delay: Arc<Mutex<Duration>>,
+    }
+
+    impl Plugin for Probe {
+        fn name(&self) -> &str {
+            "probe"
+        }
+        fn is_instrument(&self) -> bool {
+            true
+        }
+        fn sample_rate(&self) -> f32 {
+            48000.0
+        }
+        fn audio_output_count(&self) -> usize {
+            1
+        }
+        fn audio_input_count(&self) -> usize {
+            0
+        }
+        fn process(
+            &mut self,
+            _midi_events: &[(u64, [u8; 3])],
+            _audio_in: &[&[f32]],
+            audio_out: &mut [&mut [f32]],
+        ) -> anyhow::Result<()> {
+            std::thread::sleep(*self.delay.lock().unwrap());
+            self.meter.set(0.75);
+            audio_out.iter_mut().for_each(|ch| ch.fill(0.5));
+            Ok(())
+        }
+        fn parameters(&self) -> Vec<ParameterInfo> {
+            vec![ParameterInfo {
+                index: 0,
+                name: "cutoff".into(),
+                min: 0.0,
+                max: 1.0,
+                default: 0.5,
+            }]
+        }
+        fn get_parameter(&mut self, _index: u32) -> Option<f32> {
+            Some(0.5)
+        }
+        fn set_parameter(&mut self, _index: u32, _value: f32) -> anyhow::Result<()> {
+            Ok(())
+        }
+        fn presets(&self) -> Vec<Preset> {
+            Vec::new()
+        }
+        fn load_preset(&mut self, _id: &str) -> anyhow::Result<()> {
+            Ok(())
+        }
+        fn meters(&self) -> Vec<MeterInfo> {
+            vec![MeterInfo {
+                name: "level".into(),
+                min: 0.0,
+                max: 1.0,
+                value: self.meter.clone(),
+            }]
+        }
+        fn supports_voice_modulation(&self, index: u32) -> bool {
+            index == 0
+        }
+        fn set_voice_modulation(
+            &mut self,
+            index: u32,
+            channel: u8,
+            key: u8,
+            offset: f32,
+        ) -> anyhow::Result<()> {
+            let entry = format!("voice {index} {channel} {key} {offset}");
+            self.log.lock().unwrap().push(entry);
+            Ok(())
+        }
+        fn set_tempo(&mut self, bpm: f32) {
+            self.log.lock().unwrap().push(format!("tempo {bpm}"));
+        }
+        fn set_routed_outputs(&mut self, channels: usize) {
+            self.log.lock().unwrap().push(format!("routed {channels}"));
+        }
+    }
+
+    #[test]
+    fn sandboxed_plugin_forwards_meters_tempo_and_voice_modulation() {
+        let dir = tempfile::tempdir().unwrap();
+        let probe = Probe::default();
+        let log = probe.log.clone();
+        let (mut plugin, _handle) = serve_in_thread(Box::new(probe), dir.path());
+        assert!(plugin.supports_voice_modulation(0));
+        assert!(!plugin.supports_voice_modulation(1));
+        let meters = plugin.meters();
+        assert_eq!(meters.len(), 1);
+        assert_eq!(meters[0].name, "level");
+
+        plugin.set_tempo(128.0);
+        plugin.set_voice_modulation(0, 2, 60, 0.25).unwrap();
+        plugin.set_routed_outputs(4);
+        run_block(&mut plugin, &[]);
+        assert_eq!(meters[0].value.get(), 0.75);
+        assert_eq!(
+            *log.lock().unwrap(),
+            ["tempo 128", "voice 0 2 60 0.25", "routed 4"]
+        );
+    }
+
+    #[test]
+    fn late_block_outputs_silence_without_waiting() {
+        let dir = tempfile::tempdir().unwrap();
+        let probe = Probe::default();
+        let delay = probe.delay.clone();
+        let (mut plugin, _handle) = serve_in_thread(Box::new(probe), dir.path());
+        assert!(run_block(&mut plugin, &[]).iter().all(|&s| s == 0.5));
+
+        *delay.lock().unwrap() = Duration::from_millis(200);
+        let start = Instant::now();
+        assert!(run_block(&mut plugin, &[]).iter().all(|&s| s == 0.0));
+        assert!(start.elapsed() < Duration::from_millis(150));
+        assert!(plugin.conn.as_ref().unwrap().late_since.is_some());
+
+        // Once the child catches up, its stale reply is skipped.
+        *delay.lock().unwrap() = Duration::ZERO;
+        std::thread::sleep(Duration::from_millis(250));
+        assert!(run_block(&mut plugin, &[]).iter().all(|&s| s == 0.5));
+        assert!(plugin.conn.as_ref().unwrap().late_since.is_none());
+    }
+
+    #[test]
+    fn midi_round_trips_through_shared_memory() {
+        let mut area = vec![0u8; MAX_MIDI_EVENTS * MIDI_EVENT_SIZE];
+        let events = [(0, [0x90, 60, 100]), (1000, [0x80, 60, 0])];
+        assert_eq!(write_midi(&mut area, &events), 2);
+        let mut out = Vec::new();
+        read_midi(&area, 2, &mut out);
+        assert_eq!(out, events);
+    }
+}
@@ -0,0 +1,360 @@
+//! On-disk cache of plugin scan results.
+//!
+//! Scanning loads every plugin binary (and briefly instantiates CLAP and VST3
+//! plugins), which is slow with large plugin folders. Results are cached per
+//! bundle in `<config dir>/plugin-cache.toml`, keyed by bundle path and
+//! modification time, so a refresh only rescans bundles that changed.
+
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use serde::{Deserialize, Serialize};
+
+use super::quarantine::{self, Quarantine};
+use super::PluginInfo;
+
+const CACHE_FILE: &str = "plugin-cache.toml";
+
+/// How deep to look inside a bundle for modified files (e.g.
+/// `Foo.vst3/Contents/x86_64-linux/Foo.so`).
+const MTIME_DEPTH: usize = 3;
+
+/// Bumped when [`PluginInfo`] gains data that needs a rescan to fill in.
+const CACHE_VERSION: u32 = 1;
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct ScanCache {
+    #[serde(default)]
+    version: u32,
+    #[serde(default, rename = "bundle")]
+    bundles: Vec<CachedBundle>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedBundle {
+    format: String,
+    path: String,
+    mtime: u64,
+    #[serde(default, rename = "plugin")]
+    plugins: Vec<PluginInfo>,
+}
+
+/// Scan results grouped by plugin format.
+#[derive(Default)]
+pub struct Catalog {
+    pub lv2: Vec<PluginInfo>,
+    pub clap: Vec<PluginInfo>,
+    pub vst3: Vec<PluginInfo>,
+}
+
+impl Catalog {
+    /// All plugins, in format order.
+    pub fn into_all(self) -> Vec<PluginInfo> {
+        let mut all = self.lv2;
+        all.extend(self.clap);
+        all.extend(self.vst3);
+        all
+    }
+}
+
+fn cache_path() -> Option<PathBuf> {
+    crate::dirs_config().ok().map(|dir| dir.join(CACHE_FILE))
+}
+
+/// Latest modification time (seconds since the epoch) of `path` and, for
+/// bundle directories, the files inside it. 0 if the path is unreadable.
+fn bundle_mtime(path: &Path) -> u64 {
+    fn walk(path: &Path, depth: usize) -> u64 {
+        let Ok(meta) = std::fs::metadata(path) else { return 0 };
+        let own = meta
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |d| d.as_secs());
+        if !meta.is_dir() || depth == 0 {
+            return own;
+        }
+        let Ok(entries) = std::fs::read_dir(path) else { return own };
+        entries
+            .flatten()
+            .map(|e| walk(&e.path(), depth - 1))
+            .fold(own, u64::max)
+    }
+    walk(path, MTIME_DEPTH)
+}
+
+impl ScanCache {
+    /// Load the cache from the config dir; empty if missing or unreadable.
+    pub fn load() -> Self {
+        cache_path().map(|p| Self::load_from(&p)).unwrap_or_default()
+    }
+
+    fn load_from(path: &Path) -> Self {
+        let Ok(content) = std::fs::read_to_string(path) else {
+            return Self::default();
+        };
+        toml::from_str(&content).unwrap_or_else(|e| {
+            log::warn!("Ignoring invalid plugin cache {}: {e}", path.display());
+            Self::default()
+        })
+    }
+
+    pub fn save(&self) -> anyhow::Result<()> {
+        let path =
+            cache_path().ok_or_else(|| anyhow::anyhow!("could not determine config directory"))?;
+        self.save_to(&path)
+    }
+
+    fn save_to(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, toml::to_string(self)?)
+            .map_err(|e| anyhow::anyhow!("Failed to write {}: {e}", path.display()))
+    }
+
+    /// Cached plugins, without scanning anything. Blocklisted plugins are
+    /// left out.
+    pub fn catalog(&self) -> Catalog {
+        let of = |format: &str| -> Vec<PluginInfo> {
+            self.bundles
+                .iter()
+                .filter(|b| b.format == format)
+                .flat_map(|b| b.plugins.iter())
+                .filter(|p| !quarantine::is_blocklisted(Path::new(&p.path), Some(&p.id)))
+                .cloned()
+                .collect()
+        };
+        Catalog {
+            lv2: of("lv2"),
+            clap: of("clap"),
+            vst3: of("vst3"),
+        }
+    }
+
+    /// Rescan the bundles of `format` that are new or modified since they
+    /// were cached, and forget bundles that no longer exist. Returns the
+    /// number of bundles scanned.
+    fn refresh_bundles(
+        &mut self,
+        format: &str,
+        bundles: &[PathBuf],
+        mut scan: impl FnMut(&Path) -> Option<Vec<PluginInfo>>,
+    ) -> usize {
+        let (mut old, keep): (Vec<CachedBundle>, Vec<CachedBundle>) =
+            std::mem::take(&mut self.bundles)
+                .into_iter()
+                .partition(|b| b.format == format);
+        self.bundles = keep;
+
+        let mut scanned = 0;
+        for path in bundles {
+            let key = path.to_string_lossy().to_string();
+            let mtime = bundle_mtime(path);
+            if let Some(pos) = old.iter().position(|b| b.path == key && b.mtime == mtime) {
+                self.bundles.push(old.swap_remove(pos));
+                continue;
+            }
+            scanned += 1;
+            let plugins = scan(path).unwrap_or_else(|| {
+                log::warn!("Failed to scan {format} bundle: {}", path.display());
+                Vec::new()
+            });
+            self.bundles.push(CachedBundle {
+                format: format.to_string(),
+                path: key,
+                mtime,
+                plugins,
+            });
+        }
+        scanned
+    }
+
+    /// Like [`ScanCache::refresh_bundles`] for formats that can only be
+    /// scanned as a whole (LV2 worlds): if any bundle changed, rescan
+    /// everything and file each plugin under its bundle path. Plugins from
+    /// bundles outside `bundles` (found via other search paths) are kept too.
+    #[cfg_attr(not(feature = "lv2"), allow(dead_code))]
+    fn refresh_whole(
+        &mut self,
+        format: &str,
+        bundles: &[PathBuf],
+        scan_all: impl FnOnce() -> Vec<PluginInfo>,
+    ) -> usize {
+        let cached: Vec<&CachedBundle> =
+            self.bundles.iter().filter(|b| b.format == format).collect();
+        let unchanged = bundles.iter().all(|path| {
+            let key = path.to_string_lossy();
+            cached.iter().any(|b| b.path == key && b.mtime == bundle_mtime(path))
+        }) && cached.iter().all(|b| {
+            bundles.iter().any(|path| path.to_string_lossy() == b.path)
+                || bundle_mtime(Path::new(&b.path)) == b.mtime
+        });
+        if unchanged {
+            return 0;
+        }
+
+        let mut plugins = scan_all();
+        let scanned = self.refresh_bundles(format, bundles, |path| {
+            let key = path.to_string_lossy();
+            let key = key.trim_end_matches('/');
+            let (mine, rest) = std::mem::take(&mut plugins)
+                .into_iter()
+                .partition(|p| p.path.trim_end_matches('/') == key);
+            plugins = rest;
+            Some(mine)
+        });
+
+        // Whatever is left came from unchanged bundles (already cached) or
+        // from bundles outside the search paths we list.
+        plugins.retain(|p| {
+            let path = p.path.trim_end_matches('/');
+            !bundles.iter().any(|b| b.to_string_lossy().trim_end_matches('/') == path)
+        });
+        while let Some(first) = plugins.first() {
+            let path = first.path.clone();
+            let (mine, rest) = plugins.into_iter().partition(|p| p.path == path);
+            plugins = rest;
+            self.bundles.push(CachedBundle {
+                format: format.to_string(),
+                mtime: bundle_mtime(Path::new(&path)),
+                path,
+                plugins: mine,
+            });
+        }
+        scanned
+    }
+
+    /// Bring the cache up to date with the installed plugins. With `rescan`,
+    /// every bundle is scanned again regardless of its modification time.
+    /// Quarantined and blocklisted bundles are skipped, and CLAP/VST3 scans
+    /// run under a crash marker (see [`quarantine`]).
+    pub fn refresh(&mut self, rescan: bool) {
+        if rescan || self.version != CACHE_VERSION {
+            self.bundles.clear();
+            self.version = CACHE_VERSION;
+        }
+        let quarantine = Quarantine::open();
+        let allowed = |paths: Vec<PathBuf>| -> Vec<PathBuf> {
+            paths.into_iter().filter(|p| !quarantine.skips(p)).collect()
+        };
+
+        #[cfg(feature = "lv2")]
+        {
+            let n = self.refresh_whole("lv2", &allowed(super::lv2::bundle_paths()), super::lv2::enumerate_plugins);
+            log::info!("Plugin scan: {n} LV2 bundle(s) rescanned");
+        }
+
+        let n = self.refresh_bundles("clap", &allowed(super::clap::bundle_paths()), |p| {
+            quarantine.guard(p, || super::clap::scan_bundle(p))
+        });
+        log::info!("Plugin scan: {n} CLAP bundle(s) rescanned");
+
+        #[cfg(feature = "vst3")]
+        {
+            let n = self.refresh_bundles("vst3", &allowed(super::vst3::bundle_paths()), |p| {
+                quarantine.guard(p, || super::vst3::scan_bundle(p))
+            });
+            log::info!("Plugin scan: {n} VST3 bundle(s) rescanned");
+        }
+    }
+}
+
+/// Load the cache, refresh it and write it back. Returns the up-to-date
+/// catalogue.
+pub fn scan(rescan: bool) -> Catalog {
+    let mut cache = ScanCache::load();
+    cache.refresh(rescan);
+    if let Err(e) = cache.save() {
+        log::warn!("Failed to save plugin cache: {e}");
+    }
+    cache.catalog()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugin::ParameterInfo;
+
+    fn info(name: &str, path: &Path) -> PluginInfo {
+        PluginInfo {
+            name: name.to_string(),
+            id: format!("test:{name}"),
+            is_instrument: true,
+            param_count: 2,
+            preset_count: 0,
+            path: path.to_string_lossy().to_string(),
+            audio_output_count: 2,
+            parameters: vec![ParameterInfo {
+                index: 0,
+                name: "gain".into(),
+                min: 0.0,
+                max: 2.0,
+                default: 1.0,
+            }],
+            ..PluginInfo::default()
+        }
+    }
+
+    #[test]
+    fn refresh_only_rescans_changed_bundles() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a.clap");
+        let b = dir.path().join("b.clap");
+        std::fs::write(&a, "a").unwrap();
+        std::fs::write(&b, "b").unwrap();
+
+        let mut cache = ScanCache::default();
+        let mut scans = Vec::new();
+        let mut scan = |p: &Path| {
+            scans.push(p.to_path_buf());
+            Some(vec![info(&p.file_stem().unwrap().to_string_lossy(), p)])
+        };
+        assert_eq!(cache.refresh_bundles("clap", &[a.clone(), b.clone()], &mut scan), 2);
+        assert_eq!(cache.refresh_bundles("clap", &[a.clone(), b.clone()], &mut scan), 0);
+
+        // A removed bundle is forgotten; a modified one is rescanned.
+        cache.bundles.iter_mut().find(|x| x.path.ends_with("a.clap")).unwrap().mtime = 1;
+        assert_eq!(cache.refresh_bundles("clap", std::slice::from_ref(&a), &mut scan), 1);
+        assert_eq!(scans.last(), Some(&a));
+        let names: Vec<String> = cache.catalog().clap.into_iter().map(|p| p.name).collect();
+        assert_eq!(names, ["a"]);
+    }
+
+    #[test]
+    fn whole_format_refresh_files_plugins_by_bundle() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a.lv2");
+        let b = dir.path().join("b.lv2");
+        std::fs::create_dir(&a).unwrap();
+        std::fs::create_dir(&b).unwrap();
+        let bundles = [a.clone(), b.clone()];
+
+        let mut cache = ScanCache::default();
+        let all = || vec![info("a1", &a), info("a2", &a), info("b1", &b)];
+        assert_eq!(cache.refresh_whole("lv2", &bundles, all), 2);
+        assert_eq!(cache.bundles.iter().find(|x| x.path.ends_with("a.lv2")).unwrap().plugins.len(), 2);
+        assert_eq!(cache.refresh_whole("lv2", &bundles, || panic!("unchanged")), 0);
+        assert_eq!(cache.catalog().lv2.len(), 3);
+    }
+
+    #[test]
+    fn cache_round_trips_through_toml() {
+        let dir = tempfile::tempdir().unwrap();
+        let bundle = dir.path().join("x.vst3");
+        let mut cache = ScanCache::default();
+        cache.refresh_bundles("vst3", std::slice::from_ref(&bundle), |p| Some(vec![info("x", p)]));
+
+        let file = dir.path().join(CACHE_FILE);
+        cache.save_to(&file).unwrap();
+        let loaded = ScanCache::load_from(&file);
+        let vst3 = loaded.catalog().vst3;
+        assert_eq!(vst3.len(), 1);
+        assert_eq!(vst3[0].id, "test:x");
+        assert_eq!(vst3[0].param_count, 2);
+        assert_eq!(vst3[0].audio_output_count, 2);
+        assert_eq!(vst3[0].parameters[0].name, "gain");
+        assert_eq!(vst3[0].parameters[0].max, 2.0);
+        assert!(loaded.catalog().clap.is_empty());
+    }
+}
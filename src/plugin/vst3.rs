@@ -25,13 +25,15 @@ use vst3::Steinberg::Vst::{
     ParameterInfo as Vst3ParameterInfo, ProcessContext, ProcessData, ProcessSetup,
     ProgramListInfo, String128,
 };
+use vst3::Steinberg::IBStream_::IStreamSeekMode_::{kIBSeekCur, kIBSeekEnd, kIBSeekSet};
 use vst3::Steinberg::{
-    self, FUnknown, IPluginBaseTrait as _, IPluginFactory, IPluginFactory2,
-    IPluginFactory2Trait as _, IPluginFactoryTrait as _, PClassInfo, PClassInfo2, kResultOk,
+    self, FUnknown, IBStream, IBStreamTrait, IPluginBaseTrait as _, IPluginFactory,
+    IPluginFactory2, IPluginFactory2Trait as _, IPluginFactoryTrait as _, PClassInfo,
+    PClassInfo2, PFactoryInfo, kResultOk,
 };
 use vst3::{Class, ComPtr, ComWrapper, Interface};
 
-use super::{ParameterInfo, Plugin, PluginInfo, Preset};
+use super::{ParameterInfo, Plugin, PluginInfo, Preset, vstpreset};
 
 // ---------------------------------------------------------------------------
 // String helpers
@@ -55,6 +57,11 @@ fn char_array_to_string(s: &[c_char]) -> String {
     s[..end].iter().map(|&c| c as u8 as char).collect()
 }
 
+/// The raw bytes of a TUID ([c_char; 16]), e.g. a class ID from the factory.
+fn tuid_bytes(tuid: &Steinberg::TUID) -> [u8; 16] {
+    tuid.map(|b| b as u8)
+}
+
 /// Convert a `Guid` ([u8; 16]) to a TUID ([c_char; 16]) for passing to createInstance.
 fn guid_to_tuid(guid: &vst3::com_scrape_types::Guid) -> Steinberg::TUID {
     let mut tuid: Steinberg::TUID = [0; 16];
@@ -270,6 +277,17 @@ impl Vst3Module {
     fn factory(&self) -> &ComPtr<IPluginFactory> {
         self.factory.as_ref().expect("factory already dropped")
     }
+
+    /// Vendor name from the factory info, used for preset folder names.
+    fn vendor(&self) -> String {
+        let mut info: PFactoryInfo = unsafe { std::mem::zeroed() };
+        let result = unsafe { self.factory().getFactoryInfo(&mut info) };
+        if result == kResultOk {
+            char_array_to_string(&info.vendor)
+        } else {
+            String::new()
+        }
+    }
 }
 
 impl Drop for Vst3Module {
@@ -480,6 +498,123 @@ impl IParameterChangesTrait for TangParameterChanges {
     }
 }
 
+// ---------------------------------------------------------------------------
+// State streams
+// ---------------------------------------------------------------------------
+
+/// In-memory IBStream for moving component/controller state in and out of
+/// `.vstpreset` files.
+struct TangMemoryStream {
+    data: UnsafeCell<Vec<u8>>,
+    pos: UnsafeCell<usize>,
+}
+
+impl TangMemoryStream {
+    fn new(data: Vec<u8>) -> ComWrapper<Self> {
+        ComWrapper::new(TangMemoryStream {
+            data: UnsafeCell::new(data),
+            pos: UnsafeCell::new(0),
+        })
+    }
+
+    fn rewind(&self) {
+        unsafe { *self.pos.get() = 0 };
+    }
+
+    fn take_data(&self) -> Vec<u8> {
+        unsafe { std::mem::take(&mut *self.data.get()) }
+    }
+}
+
+impl Class for TangMemoryStream {
+    type Interfaces = (IBStream,);
+}
+
+impl IBStreamTrait for TangMemoryStream {
+    unsafe fn read(
+        &self,
+        buffer: *mut c_void,
+        num_bytes: Steinberg::int32,
+        num_bytes_read: *mut Steinberg::int32,
+    ) -> Steinberg::tresult {
+        unsafe {
+            let data = &*self.data.get();
+            let pos = &mut *self.pos.get();
+            let count = (num_bytes.max(0) as usize).min(data.len().saturating_sub(*pos));
+            if count > 0 {
+                std::ptr::copy_nonoverlapping(data.as_ptr().add(*pos), buffer as *mut u8, count);
+            }
+            *pos += count;
+            if !num_bytes_read.is_null() {
+                *num_bytes_read = count as Steinberg::int32;
+            }
+        }
+        kResultOk
+    }
+
+    unsafe fn write(
+        &self,
+        buffer: *mut c_void,
+        num_bytes: Steinberg::int32,
+        num_bytes_written: *mut Steinberg::int32,
+    ) -> Steinberg::tresult {
+        unsafe {
+            let data = &mut *self.data.get();
+            let pos = &mut *self.pos.get();
+            let count = num_bytes.max(0) as usize;
+            if data.len() < *pos + count {
+                data.resize(*pos + count, 0);
+            }
+            if count > 0 {
+                std::ptr::copy_nonoverlapping(
+                    buffer as *const u8,
+                    data.as_mut_ptr().add(*pos),
+                    count,
+                );
+            }
+            *pos += count;
+            if !num_bytes_written.is_null() {
+                *num_bytes_written = count as Steinberg::int32;
+            }
+        }
+        kResultOk
+    }
+
+    unsafe fn seek(
+        &self,
+        pos: Steinberg::int64,
+        mode: Steinberg::int32,
+        result: *mut Steinberg::int64,
+    ) -> Steinberg::tresult {
+        unsafe {
+            let len = (*self.data.get()).len() as i64;
+            let current = *self.pos.get() as i64;
+            let target = match mode {
+                m if m == kIBSeekSet as i32 => pos,
+                m if m == kIBSeekCur as i32 => current + pos,
+                m if m == kIBSeekEnd as i32 => len + pos,
+                _ => return Steinberg::kInvalidArgument,
+            };
+            if target < 0 {
+                return Steinberg::kInvalidArgument;
+            }
+            *self.pos.get() = target as usize;
+            if !result.is_null() {
+                *result = target;
+            }
+        }
+        kResultOk
+    }
+
+    unsafe fn tell(&self, pos: *mut Steinberg::int64) -> Steinberg::tresult {
+        if pos.is_null() {
+            return Steinberg::kInvalidArgument;
+        }
+        unsafe { *pos = *self.pos.get() as Steinberg::int64 };
+        kResultOk
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Vst3Plugin
 // ---------------------------------------------------------------------------
@@ -494,9 +629,14 @@ pub struct Vst3Plugin {
     params_cache: Vec<ParameterInfo>,
     param_ids: Vec<u32>,
     pending_param_changes: Vec<(u32, f64)>,
+    // Program-list presets (id = program index) followed by `.vstpreset`
+    // files (id = file path)
     preset_cache: Vec<Preset>,
     preset_param_id: Option<u32>,
     preset_count: usize,
+    // Class ID and vendor, for matching and writing `.vstpreset` files
+    class_cid: [u8; 16],
+    vendor: String,
     // Pre-allocated audio buffers
     output_bufs: Vec<Vec<f32>>,
     input_bufs: Vec<Vec<f32>>,
@@ -886,9 +1026,60 @@ impl Plugin for Vst3Plugin {
     }
 
     fn load_preset(&mut self, id: &str) -> anyhow::Result<()> {
-        let index: usize = id
-            .parse()
-            .map_err(|_| anyhow::anyhow!("Invalid preset ID: {id}"))?;
+        match id.parse::<usize>() {
+            Ok(index) => self.load_program(index)?,
+            Err(_) => self.load_preset_file(Path::new(id))?,
+        }
+        log::info!("VST3: loaded preset {id}");
+        Ok(())
+    }
+
+    fn save_preset(&mut self, name: &str) -> anyhow::Result<Preset> {
+        let (component_state, controller_state) = self.capture_state()?;
+        let path = vstpreset::user_preset_path(&self.vendor, &self.name, name)
+            .ok_or_else(|| anyhow::anyhow!("No user preset folder on this platform"))?;
+        vstpreset::write(
+            &path,
+            &vstpreset::native_class_id(&self.class_cid),
+            &component_state,
+            controller_state.as_deref(),
+        )?;
+
+        let preset = Preset {
+            name: vstpreset::sanitize(name),
+            id: path.to_string_lossy().to_string(),
+        };
+        match self.preset_cache.iter_mut().find(|p| p.id == preset.id) {
+            Some(existing) => *existing = preset.clone(),
+            None => self.preset_cache.push(preset.clone()),
+        }
+        log::info!("VST3: saved preset to {}", path.display());
+        Ok(preset)
+    }
+
+    fn save_state(&mut self) -> Option<Vec<u8>> {
+        // Packed in the .vstpreset chunk layout so both state halves survive
+        match self.capture_state() {
+            Ok((component_state, controller_state)) => Some(vstpreset::to_bytes(
+                &vstpreset::native_class_id(&self.class_cid),
+                &component_state,
+                controller_state.as_deref(),
+            )),
+            Err(e) => {
+                log::warn!("VST3: failed to save state: {e}");
+                None
+            }
+        }
+    }
+
+    fn load_state(&mut self, data: &[u8]) -> anyhow::Result<()> {
+        self.restore_state(vstpreset::from_bytes(data)?)
+    }
+}
+
+impl Vst3Plugin {
+    /// Select a program-list preset via the program-change parameter.
+    fn load_program(&mut self, index: usize) -> anyhow::Result<()> {
         let preset_param_id = self
             .preset_param_id
             .ok_or_else(|| anyhow::anyhow!("Plugin does not support program changes"))?;
@@ -910,8 +1101,75 @@ impl Plugin for Vst3Plugin {
         }
         self.pending_param_changes
             .push((preset_param_id, normalized));
+        Ok(())
+    }
 
-        log::info!("VST3: loaded preset {id}");
+    /// Restore component and controller state from a `.vstpreset` file.
+    fn load_preset_file(&mut self, path: &Path) -> anyhow::Result<()> {
+        self.restore_state(vstpreset::read(path)?)
+            .map_err(|e| anyhow::anyhow!("{}: {e}", path.display()))
+    }
+
+    /// Read the component state and, for separate controllers, the
+    /// controller state.
+    fn capture_state(&mut self) -> anyhow::Result<(Vec<u8>, Option<Vec<u8>>)> {
+        let comp_stream = TangMemoryStream::new(Vec::new());
+        let comp_ptr = comp_stream
+            .to_com_ptr::<IBStream>()
+            .ok_or_else(|| anyhow::anyhow!("Failed to get IBStream from memory stream"))?;
+        let result = unsafe { self.component.getState(comp_ptr.as_ptr()) };
+        if result != kResultOk {
+            anyhow::bail!("IComponent::getState failed (result={result})");
+        }
+        let component_state = comp_stream.take_data();
+
+        // Single-component plugins share one getState for both interfaces
+        let controller_state = if self.separate_controller {
+            let ctrl_stream = TangMemoryStream::new(Vec::new());
+            let ctrl_ptr = ctrl_stream
+                .to_com_ptr::<IBStream>()
+                .ok_or_else(|| anyhow::anyhow!("Failed to get IBStream from memory stream"))?;
+            let result = unsafe { self.controller.getState(ctrl_ptr.as_ptr()) };
+            let data = ctrl_stream.take_data();
+            (result == kResultOk && !data.is_empty()).then_some(data)
+        } else {
+            None
+        };
+
+        Ok((component_state, controller_state))
+    }
+
+    /// Apply component state, sync the controller with it, then apply the
+    /// controller's own state if present.
+    fn restore_state(&mut self, preset: vstpreset::VstPreset) -> anyhow::Result<()> {
+        if !vstpreset::class_id_strings(&self.class_cid)
+            .iter()
+            .any(|id| id.eq_ignore_ascii_case(&preset.class_id))
+        {
+            anyhow::bail!("state belongs to a different plugin (class {})", preset.class_id);
+        }
+
+        let comp_stream = TangMemoryStream::new(preset.component);
+        let comp_ptr = comp_stream
+            .to_com_ptr::<IBStream>()
+            .ok_or_else(|| anyhow::anyhow!("Failed to get IBStream from memory stream"))?;
+        let result = unsafe { self.component.setState(comp_ptr.as_ptr()) };
+        if result != kResultOk {
+            anyhow::bail!("IComponent::setState failed (result={result})");
+        }
+
+        comp_stream.rewind();
+        unsafe { self.controller.setComponentState(comp_ptr.as_ptr()) };
+        if let Some(controller_state) = preset.controller {
+            let ctrl_stream = TangMemoryStream::new(controller_state);
+            let ctrl_ptr = ctrl_stream
+                .to_com_ptr::<IBStream>()
+                .ok_or_else(|| anyhow::anyhow!("Failed to get IBStream from memory stream"))?;
+            let result = unsafe { self.controller.setState(ctrl_ptr.as_ptr()) };
+            if result != kResultOk {
+                log::warn!("VST3 IEditController::setState returned {result}");
+            }
+        }
         Ok(())
     }
 }
@@ -925,8 +1183,20 @@ pub fn load(
     sample_rate: f32,
     max_block_size: usize,
 ) -> anyhow::Result<Box<dyn Plugin>> {
-    let (module, class_cid, name, is_instrument) = find_plugin(source)?;
+    let (module, class_cid, name, is_instrument, bundle_path) = find_plugin(source)?;
+    load_class(module, class_cid, name, is_instrument, bundle_path, sample_rate, max_block_size)
+}
 
+/// Load class `class_cid` of an already loaded module.
+fn load_class(
+    module: Vst3Module,
+    class_cid: Steinberg::TUID,
+    name: String,
+    is_instrument: bool,
+    bundle_path: PathBuf,
+    sample_rate: f32,
+    max_block_size: usize,
+) -> anyhow::Result<Box<dyn Plugin>> {
     let host_app = ComWrapper::new(TangHostApp);
     let handler = ComWrapper::new(TangComponentHandler);
 
@@ -1172,7 +1442,23 @@ pub fn load(
         }
         preset_count = preset_cache.len();
     }
-    log::info!("VST3 plugin has {} presets", preset_count);
+
+    // Add factory and user presets stored as .vstpreset files
+    let class_cid_bytes = tuid_bytes(&class_cid);
+    let vendor = module.vendor();
+    let preset_files = vstpreset::find_presets(&class_cid_bytes, &vendor, &name, Some(&bundle_path));
+    for (preset_name, path) in preset_files {
+        preset_cache.push(Preset {
+            name: preset_name,
+            id: path.to_string_lossy().to_string(),
+        });
+    }
+    log::info!(
+        "VST3 plugin has {} presets ({} programs, {} preset files)",
+        preset_cache.len(),
+        preset_count,
+        preset_cache.len() - preset_count,
+    );
 
     // Query MIDI CC → parameter mapping
     let mut cc_param_map: Vec<Option<u32>> = vec![None; 130]; // 0-127 CC + 128 pitch bend + 129 aftertouch
@@ -1191,7 +1477,7 @@ pub fn load(
         "Loaded VST3 plugin: {name} (instrument={is_instrument}, \
          output_channels={audio_out_channel_count}, params={}, presets={})",
         params_cache.len(),
-        preset_count,
+        preset_cache.len(),
     );
 
     // Setup processing
@@ -1266,6 +1552,8 @@ pub fn load(
         preset_cache,
         preset_param_id,
         preset_count,
+        class_cid: class_cid_bytes,
+        vendor,
         output_bufs,
         input_bufs,
         param_changes,
@@ -1280,8 +1568,10 @@ pub fn load(
 }
 
 /// Find a VST3 plugin by name or bundle path.
-/// Returns (module, class_cid, name, is_instrument).
-fn find_plugin(source: &str) -> anyhow::Result<(Vst3Module, Steinberg::TUID, String, bool)> {
+/// Returns (module, class_cid, name, is_instrument, bundle_path).
+fn find_plugin(
+    source: &str,
+) -> anyhow::Result<(Vst3Module, Steinberg::TUID, String, bool, PathBuf)> {
     // Try stripping "vst3:" prefix for name-based lookup
     if let Some(plugin_name) = source.strip_prefix("vst3:") {
         let search_name = plugin_name.to_lowercase();
@@ -1293,7 +1583,7 @@ fn find_plugin(source: &str) -> anyhow::Result<(Vst3Module, Steinberg::TUID, Str
                 if let Ok((module, cid, name, is_instrument)) =
                     scan_bundle_for_name(&bundle_path, &search_name)
                 {
-                    return Ok((module, cid, name, is_instrument));
+                    return Ok((module, cid, name, is_instrument, bundle_path));
                 }
             }
         }
@@ -1329,7 +1619,7 @@ fn find_plugin(source: &str) -> anyhow::Result<(Vst3Module, Steinberg::TUID, Str
         let name = char_array_to_string(&info.name);
         let is_instrument = is_class_instrument(factory, i);
 
-        return Ok((module, info.cid, name, is_instrument));
+        return Ok((module, info.cid, name, is_instrument, path.to_path_buf()));
     }
 
     anyhow::bail!(
@@ -1385,151 +1675,66 @@ fn is_class_instrument(factory: &ComPtr<IPluginFactory>, index: Steinberg::int32
 // Enumeration
 // ---------------------------------------------------------------------------
 
-pub fn enumerate_plugins() -> Vec<PluginInfo> {
-    let mut plugins = Vec::new();
-
-    for search_dir in vst3_search_paths() {
-        if !search_dir.exists() {
-            continue;
-        }
-        for bundle_path in find_vst3_bundles(&search_dir) {
-            match scan_bundle_for_enum(&bundle_path) {
-                Some(found) => plugins.extend(found),
-                None => {
-                    log::warn!("Failed to scan VST3 bundle: {}", bundle_path.display());
-                }
-            }
-        }
-    }
-
-    plugins
+/// All .vst3 bundles under the search paths.
+pub fn bundle_paths() -> Vec<PathBuf> {
+    vst3_search_paths()
+        .iter()
+        .filter(|dir| dir.exists())
+        .flat_map(|dir| find_vst3_bundles(dir))
+        .collect()
 }
 
-fn scan_bundle_for_enum(bundle_path: &Path) -> Option<Vec<PluginInfo>> {
-    let module = Vst3Module::load(bundle_path).ok()?;
-    let factory = module.factory();
-    let count = unsafe { factory.countClasses() };
-
-    let mut found = Vec::new();
-    for i in 0..count {
-        let mut info: PClassInfo = unsafe { std::mem::zeroed() };
-        let result = unsafe { factory.getClassInfo(i, &mut info) };
-        if result != kResultOk {
-            continue;
-        }
-
-        let category = char_array_to_string(&info.category);
-        if category != "Audio Module Class" {
-            continue;
-        }
-
-        let name = char_array_to_string(&info.name);
-        let is_instrument = is_class_instrument(factory, i);
-
-        // Briefly instantiate to query param count and preset count
-        let (param_count, preset_count) =
-            brief_instantiate(factory, &info.cid, &module).unwrap_or((0, 0));
-
-        found.push(PluginInfo {
-            name: name.clone(),
-            id: format!("vst3:{name}"),
-            is_instrument,
-            param_count,
-            preset_count,
-            path: bundle_path.to_string_lossy().to_string(),
-        });
-    }
-
-    Some(found)
-}
-
-/// Briefly instantiate a VST3 plugin to query parameter and preset counts.
-fn brief_instantiate(
-    factory: &ComPtr<IPluginFactory>,
-    class_cid: &Steinberg::TUID,
-    _module: &Vst3Module,
-) -> Option<(usize, usize)> {
-    let host_app = ComWrapper::new(TangHostApp);
-    let host_unknown: ComPtr<FUnknown> = host_app.to_com_ptr::<FUnknown>()?;
-
-    let iid = guid_to_tuid(&<IComponent as Interface>::IID);
-    let mut obj: *mut c_void = std::ptr::null_mut();
-    let result = unsafe { factory.createInstance(class_cid.as_ptr(), iid.as_ptr(), &mut obj) };
-    if result != kResultOk || obj.is_null() {
-        return None;
-    }
-    let component = unsafe { ComPtr::from_raw(obj as *mut IComponent) }?;
-    let init_result = unsafe { component.initialize(host_unknown.as_ptr()) };
-    if init_result != kResultOk {
-        return None;
-    }
-
-    // Get controller
-    let (controller, separate) = if let Some(ec) = component.cast::<IEditController>() {
-        (ec, false)
-    } else {
-        let mut ctrl_cid: Steinberg::TUID = [0; 16];
-        unsafe { component.getControllerClassId(&mut ctrl_cid) };
-        let ctrl_iid = guid_to_tuid(&<IEditController as Interface>::IID);
-        let mut ctrl_obj: *mut c_void = std::ptr::null_mut();
-        let r =
-            unsafe { factory.createInstance(ctrl_cid.as_ptr(), ctrl_iid.as_ptr(), &mut ctrl_obj) };
-        if r != kResultOk || ctrl_obj.is_null() {
-            unsafe { component.terminate() };
-            return None;
-        }
-        let ec = unsafe { ComPtr::from_raw(ctrl_obj as *mut IEditController) }?;
-        unsafe { ec.initialize(host_unknown.as_ptr()) };
-        (ec, true)
-    };
-
-    // Connect component and controller for separate-controller plugins
-    let (comp_conn, ctrl_conn) = if separate {
-        let cc = component.cast::<IConnectionPoint>();
-        let tc = controller.cast::<IConnectionPoint>();
-        if let (Some(cc), Some(tc)) = (&cc, &tc) {
-            unsafe {
-                cc.connect(tc.as_ptr());
-                tc.connect(cc.as_ptr());
+/// Scan one bundle for its audio module classes, briefly loading each one to
+/// record its audio buses, parameters and presets.
+pub fn scan_bundle(bundle_path: &Path) -> Option<Vec<PluginInfo>> {
+    let mut classes = Vec::new();
+    {
+        let module = Vst3Module::load(bundle_path).ok()?;
+        let factory = module.factory();
+        let count = unsafe { factory.countClasses() };
+        for i in 0..count {
+            let mut info: PClassInfo = unsafe { std::mem::zeroed() };
+            let result = unsafe { factory.getClassInfo(i, &mut info) };
+            if result != kResultOk {
+                continue;
             }
-        }
-        (cc, tc)
-    } else {
-        (None, None)
-    };
 
-    let param_count = unsafe { controller.getParameterCount() } as usize;
-
-    // Count presets via IUnitInfo
-    let preset_count = if let Some(unit_info) = controller.cast::<IUnitInfo>() {
-        let list_count = unsafe { unit_info.getProgramListCount() };
-        let mut total = 0usize;
-        for list_idx in 0..list_count {
-            let mut list_info: ProgramListInfo = unsafe { std::mem::zeroed() };
-            let r = unsafe { unit_info.getProgramListInfo(list_idx, &mut list_info) };
-            if r == kResultOk {
-                total += list_info.programCount as usize;
+            let category = char_array_to_string(&info.category);
+            if category != "Audio Module Class" {
+                continue;
             }
-        }
-        total
-    } else {
-        0
-    };
 
-    // Clean up — disconnect before terminate, drop connection points before controller
-    if let (Some(cc), Some(tc)) = (&comp_conn, &ctrl_conn) {
-        unsafe {
-            cc.disconnect(tc.as_ptr());
-            tc.disconnect(cc.as_ptr());
+            let name = char_array_to_string(&info.name);
+            let is_instrument = is_class_instrument(factory, i);
+            classes.push((info.cid, name, is_instrument));
         }
     }
-    drop(comp_conn);
-    drop(ctrl_conn);
-    if separate {
-        unsafe { controller.terminate() };
-    }
-    drop(controller);
-    unsafe { component.terminate() };
 
-    Some((param_count, preset_count))
+    // Each class gets its own module handle, opened after the previous one
+    // is released, as the loaded plugin takes ownership of it.
+    let path = bundle_path.to_string_lossy().to_string();
+    let found = classes
+        .into_iter()
+        .map(|(cid, name, is_instrument)| {
+            let id = format!("vst3:{name}");
+            let loaded = Vst3Module::load(bundle_path).and_then(|module| {
+                let bundle = bundle_path.to_path_buf();
+                load_class(module, cid, name.clone(), is_instrument, bundle, 48000.0, 512)
+            });
+            match loaded {
+                Ok(plugin) => PluginInfo::of(plugin.as_ref(), id, path.clone()),
+                Err(e) => {
+                    log::warn!("VST3 plugin {name} failed to load during scan: {e}");
+                    PluginInfo {
+                        name,
+                        id,
+                        is_instrument,
+                        path: path.clone(),
+                        ..PluginInfo::default()
+                    }
+                }
+            }
+        })
+        .collect();
+    Some(found)
 }
@@ -0,0 +1,377 @@
+//! Reading and writing Steinberg `.vstpreset` files.
+//!
+//! Layout: a 48-byte header (`"VST3"`, version, 32-char ASCII class ID,
+//! offset of the chunk list), the raw chunk data, then the chunk list
+//! (`"List"`, entry count, and `{id, offset, size}` per entry). Tang reads
+//! and writes the component (`Comp`) and controller (`Cont`) state chunks.
+
+use std::path::{Path, PathBuf};
+
+const HEADER_SIZE: usize = 48;
+const FORMAT_VERSION: i32 = 1;
+const EXTENSION: &str = "vstpreset";
+
+/// The state chunks stored in a preset file.
+pub struct VstPreset {
+    pub class_id: String,
+    pub component: Vec<u8>,
+    pub controller: Option<Vec<u8>>,
+}
+
+/// Both textual forms of a class ID that may appear in a preset header:
+/// plain byte order, and the COM GUID order written by Windows hosts.
+pub fn class_id_strings(cid: &[u8; 16]) -> [String; 2] {
+    let hex = |bytes: &[u8]| bytes.iter().map(|b| format!("{b:02X}")).collect::<String>();
+    let plain = hex(cid);
+    let com_order = [
+        cid[3], cid[2], cid[1], cid[0], cid[5], cid[4], cid[7], cid[6], cid[8], cid[9], cid[10],
+        cid[11], cid[12], cid[13], cid[14], cid[15],
+    ];
+    [plain, hex(&com_order)]
+}
+
+/// The class ID string a native host writes on this platform.
+pub fn native_class_id(cid: &[u8; 16]) -> String {
+    let [plain, com_order] = class_id_strings(cid);
+    if cfg!(target_os = "windows") {
+        com_order
+    } else {
+        plain
+    }
+}
+
+/// Read only the class ID from a preset file header.
+pub fn read_class_id(path: &Path) -> Option<String> {
+    use std::io::Read;
+    let mut header = [0u8; HEADER_SIZE];
+    std::fs::File::open(path).ok()?.read_exact(&mut header).ok()?;
+    if &header[0..4] != b"VST3" {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&header[8..40]).to_string())
+}
+
+/// Parse a preset file.
+pub fn read(path: &Path) -> anyhow::Result<VstPreset> {
+    let data = std::fs::read(path)
+        .map_err(|e| anyhow::anyhow!("Failed to read {}: {e}", path.display()))?;
+    from_bytes(&data).map_err(|e| anyhow::anyhow!("Invalid VST3 preset {}: {e}", path.display()))
+}
+
+pub fn from_bytes(data: &[u8]) -> anyhow::Result<VstPreset> {
+    if data.len() < HEADER_SIZE || &data[0..4] != b"VST3" {
+        anyhow::bail!("missing VST3 header");
+    }
+    let class_id = String::from_utf8_lossy(&data[8..40]).to_string();
+    let list_offset = read_i64(data, 40)? as usize;
+
+    let list = data
+        .get(list_offset..)
+        .ok_or_else(|| anyhow::anyhow!("chunk list offset out of range"))?;
+    if list.len() < 8 || &list[0..4] != b"List" {
+        anyhow::bail!("missing chunk list");
+    }
+    let entry_count = read_i32(list, 4)?.max(0) as usize;
+
+    let mut component = None;
+    let mut controller = None;
+    for i in 0..entry_count {
+        let entry = 8 + i * 20;
+        let id = list
+            .get(entry..entry + 4)
+            .ok_or_else(|| anyhow::anyhow!("truncated chunk list"))?;
+        let offset = read_i64(list, entry + 4)? as usize;
+        let size = read_i64(list, entry + 12)? as usize;
+        let chunk = data
+            .get(offset..offset.saturating_add(size))
+            .ok_or_else(|| anyhow::anyhow!("chunk out of range"))?;
+        match id {
+            b"Comp" => component = Some(chunk.to_vec()),
+            b"Cont" => controller = Some(chunk.to_vec()),
+            _ => {}
+        }
+    }
+
+    Ok(VstPreset {
+        class_id,
+        component: component.ok_or_else(|| anyhow::anyhow!("no component state chunk"))?,
+        controller,
+    })
+}
+
+/// Write a preset file, creating parent directories as needed.
+pub fn write(
+    path: &Path,
+    class_id: &str,
+    component: &[u8],
+    controller: Option<&[u8]>,
+) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, to_bytes(class_id, component, controller))
+        .map_err(|e| anyhow::anyhow!("Failed to write {}: {e}", path.display()))
+}
+
+pub fn to_bytes(class_id: &str, component: &[u8], controller: Option<&[u8]>) -> Vec<u8> {
+    let mut chunks: Vec<(&[u8; 4], &[u8])> = vec![(b"Comp", component)];
+    if let Some(controller) = controller {
+        chunks.push((b"Cont", controller));
+    }
+
+    let mut out = Vec::with_capacity(HEADER_SIZE + component.len() + 64);
+    out.extend_from_slice(b"VST3");
+    out.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    let mut id = [b'0'; 32];
+    for (dst, src) in id.iter_mut().zip(class_id.bytes()) {
+        *dst = src;
+    }
+    out.extend_from_slice(&id);
+    out.extend_from_slice(&0i64.to_le_bytes()); // patched below
+
+    let mut entries = Vec::new();
+    for (chunk_id, chunk) in &chunks {
+        entries.push((*chunk_id, out.len() as i64, chunk.len() as i64));
+        out.extend_from_slice(chunk);
+    }
+
+    let list_offset = out.len() as i64;
+    out[40..48].copy_from_slice(&list_offset.to_le_bytes());
+    out.extend_from_slice(b"List");
+    out.extend_from_slice(&(entries.len() as i32).to_le_bytes());
+    for (chunk_id, offset, size) in entries {
+        out.extend_from_slice(chunk_id);
+        out.extend_from_slice(&offset.to_le_bytes());
+        out.extend_from_slice(&size.to_le_bytes());
+    }
+    out
+}
+
+fn read_i32(data: &[u8], at: usize) -> anyhow::Result<i32> {
+    let bytes = data
+        .get(at..at + 4)
+        .ok_or_else(|| anyhow::anyhow!("unexpected end of file"))?;
+    Ok(i32::from_le_bytes(bytes.try_into()?))
+}
+
+fn read_i64(data: &[u8], at: usize) -> anyhow::Result<i64> {
+    let bytes = data
+        .get(at..at + 8)
+        .ok_or_else(|| anyhow::anyhow!("unexpected end of file"))?;
+    Ok(i64::from_le_bytes(bytes.try_into()?))
+}
+
+// ---------------------------------------------------------------------------
+// Preset locations
+// ---------------------------------------------------------------------------
+
+/// Standard preset roots, user location first.
+fn preset_roots() -> Vec<PathBuf> {
+    let mut roots = Vec::new();
+
+    #[cfg(target_os = "linux")]
+    {
+        if let Some(home) = std::env::var_os("HOME") {
+            roots.push(PathBuf::from(home).join(".vst3/presets"));
+        }
+        roots.push(PathBuf::from("/usr/share/vst3/presets"));
+        roots.push(PathBuf::from("/usr/local/share/vst3/presets"));
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        if let Some(home) = std::env::var_os("HOME") {
+            roots.push(PathBuf::from(home).join("Library/Audio/Presets"));
+        }
+        roots.push(PathBuf::from("/Library/Audio/Presets"));
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        if let Some(profile) = std::env::var_os("USERPROFILE") {
+            roots.push(PathBuf::from(profile).join("Documents").join("VST3 Presets"));
+        }
+        if let Some(appdata) = std::env::var_os("APPDATA") {
+            roots.push(PathBuf::from(appdata).join("VST3 Presets"));
+        }
+        if let Some(programdata) = std::env::var_os("PROGRAMDATA") {
+            roots.push(PathBuf::from(programdata).join("VST3 Presets"));
+        }
+    }
+
+    roots
+}
+
+/// Directory where tang saves user presets for a plugin.
+pub fn user_preset_dir(vendor: &str, plugin: &str) -> Option<PathBuf> {
+    preset_roots()
+        .into_iter()
+        .next()
+        .map(|root| root.join(sanitize(vendor)).join(sanitize(plugin)))
+}
+
+/// Replace characters that are not valid in file names.
+pub fn sanitize(name: &str) -> String {
+    let cleaned: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c => c,
+        })
+        .collect();
+    let trimmed = cleaned.trim().trim_matches('.');
+    if trimmed.is_empty() {
+        "Untitled".into()
+    } else {
+        trimmed.to_string()
+    }
+}
+
+/// Find all preset files for a plugin class: factory presets inside the
+/// bundle plus the standard user/shared folders. The vendor folder is scanned
+/// when present (plugins don't always use their display name as folder name),
+/// otherwise only the plugin's own folder. Returns `(name, path)` sorted by
+/// name.
+pub fn find_presets(
+    cid: &[u8; 16],
+    vendor: &str,
+    plugin: &str,
+    bundle: Option<&Path>,
+) -> Vec<(String, PathBuf)> {
+    let mut dirs: Vec<PathBuf> = Vec::new();
+    if let Some(bundle) = bundle {
+        dirs.push(bundle.join("Contents/Resources/Presets"));
+    }
+    for root in preset_roots() {
+        let vendor_dir = root.join(sanitize(vendor));
+        dirs.push(if vendor_dir.is_dir() { vendor_dir } else { root.join(sanitize(plugin)) });
+    }
+    scan_presets(&class_id_strings(cid), &dirs)
+}
+
+/// Preset files under `dirs` whose class ID is one of `ids`, each listed
+/// once even if the folders overlap.
+fn scan_presets(ids: &[String], dirs: &[PathBuf]) -> Vec<(String, PathBuf)> {
+    let mut paths: Vec<PathBuf> = dirs
+        .iter()
+        .flat_map(|dir| collect_files(dir))
+        .filter(|path| {
+            read_class_id(path).is_some_and(|id| ids.iter().any(|c| c.eq_ignore_ascii_case(&id)))
+        })
+        .collect();
+    paths.sort();
+    paths.dedup();
+
+    let mut found: Vec<(String, PathBuf)> = paths
+        .into_iter()
+        .map(|path| {
+            let name = path
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_default();
+            (name, path)
+        })
+        .collect();
+    found.sort_by_key(|a| a.0.to_lowercase());
+    found
+}
+
+fn collect_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut stack = vec![dir.to_path_buf()];
+    while let Some(current) = stack.pop() {
+        let entries = match std::fs::read_dir(&current) {
+            Ok(e) => e,
+            Err(_) => continue,
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                stack.push(path);
+            } else if path
+                .extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case(EXTENSION))
+            {
+                files.push(path);
+            }
+        }
+    }
+    files
+}
+
+/// Path for a new user preset file.
+pub fn user_preset_path(vendor: &str, plugin: &str, name: &str) -> Option<PathBuf> {
+    user_preset_dir(vendor, plugin).map(|dir| dir.join(format!("{}.{EXTENSION}", sanitize(name))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CID: [u8; 16] = [
+        0x01, 0x23, 0x45, 0x67, 0x89, 0xAB, 0xCD, 0xEF, 0x10, 0x32, 0x54, 0x76, 0x98, 0xBA, 0xDC,
+        0xFE,
+    ];
+
+    #[test]
+    fn class_id_string_orders() {
+        let [plain, com] = class_id_strings(&CID);
+        assert_eq!(plain, "0123456789ABCDEF1032547698BADCFE");
+        assert_eq!(com, "67452301AB89EFCD1032547698BADCFE");
+    }
+
+    #[test]
+    fn roundtrip_with_controller_state() {
+        let [id, _] = class_id_strings(&CID);
+        let data = to_bytes(&id, b"component-state", Some(b"ctrl"));
+        let preset = from_bytes(&data).unwrap();
+        assert_eq!(preset.class_id, id);
+        assert_eq!(preset.component, b"component-state");
+        assert_eq!(preset.controller.as_deref(), Some(&b"ctrl"[..]));
+    }
+
+    #[test]
+    fn roundtrip_file_and_header() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sub/My Preset.vstpreset");
+        let [id, _] = class_id_strings(&CID);
+        write(&path, &id, &[1, 2, 3], None).unwrap();
+
+        assert_eq!(read_class_id(&path).as_deref(), Some(id.as_str()));
+        let preset = read(&path).unwrap();
+        assert_eq!(preset.component, vec![1, 2, 3]);
+        assert!(preset.controller.is_none());
+    }
+
+    #[test]
+    fn rejects_truncated_data() {
+        let [id, _] = class_id_strings(&CID);
+        let data = to_bytes(&id, b"state", None);
+        assert!(from_bytes(&data[..HEADER_SIZE + 2]).is_err());
+        assert!(from_bytes(b"VST2").is_err());
+    }
+
+    #[test]
+    fn scan_lists_each_preset_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let [id, _] = class_id_strings(&CID);
+        write(&dir.path().join("Pad.vstpreset"), &id, &[], None).unwrap();
+        write(&dir.path().join("sub/Pad.vstpreset"), &id, &[], None).unwrap();
+        write(&dir.path().join("sub/Other.vstpreset"), &"0".repeat(32), &[], None).unwrap();
+
+        // The subfolder is reached twice, once directly and once from its parent.
+        let dirs = [dir.path().join("sub"), dir.path().to_path_buf()];
+        let found = scan_presets(&class_id_strings(&CID), &dirs);
+        let paths: Vec<_> = found.iter().map(|(_, path)| path.clone()).collect();
+        assert_eq!(
+            paths,
+            [dir.path().join("Pad.vstpreset"), dir.path().join("sub/Pad.vstpreset")]
+        );
+    }
+
+    #[test]
+    fn sanitize_file_names() {
+        assert_eq!(sanitize("Pads/Warm: 1"), "Pads_Warm_ 1");
+        assert_eq!(sanitize("  "), "Untitled");
+    }
+}
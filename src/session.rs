@@ -18,6 +18,36 @@ pub struct RemapTarget {
 /// Top-level session config: one or more keyboards, each with splits.
 pub struct SessionConfig {
     pub keyboards: Vec<KeyboardConfig>,
+    pub master: MasterConfig,
+}
+
+/// Master output processing (`[master]` table), applied after all splits
+/// are summed.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct MasterConfig {
+    /// Replace NaN/Inf samples with silence and remove DC offset.
+    pub sanitise: bool,
+    /// Enable the look-ahead brickwall limiter.
+    pub limiter: bool,
+    /// Limiter ceiling in dBFS.
+    pub ceiling_db: f32,
+    /// Limiter look-ahead in milliseconds (adds this much output latency).
+    pub lookahead_ms: f32,
+    /// Limiter release time in milliseconds.
+    pub release_ms: f32,
+}
+
+impl Default for MasterConfig {
+    fn default() -> Self {
+        MasterConfig {
+            sanitise: true,
+            limiter: false,
+            ceiling_db: -1.0,
+            lookahead_ms: 5.0,
+            release_ms: 100.0,
+        }
+    }
 }
 
 pub struct KeyboardConfig {
@@ -139,6 +169,8 @@ fn default_mix() -> f64 {
 struct NewSessionRaw {
     #[serde(default, rename = "keyboard")]
     keyboards: Vec<KeyboardRaw>,
+    #[serde(default)]
+    master: MasterConfig,
 }
 
 #[derive(Deserialize)]
@@ -232,6 +264,8 @@ struct LegacySessionRaw {
     instrument: PluginConfig,
     #[serde(default, rename = "effect")]
     effects: Vec<EffectConfig>,
+    #[serde(default)]
+    master: MasterConfig,
 }
 
 // ---------------------------------------------------------------------------
@@ -263,7 +297,10 @@ pub fn load(path: &str) -> anyhow::Result<SessionConfig> {
                     splits,
                 });
             }
-            return Ok(SessionConfig { keyboards });
+            return Ok(SessionConfig {
+                keyboards,
+                master: raw.master,
+            });
         }
     }
 
@@ -280,6 +317,7 @@ pub fn load(path: &str) -> anyhow::Result<SessionConfig> {
                 pattern: None,
             }],
         }],
+        master: legacy.master,
     })
 }
 
@@ -326,6 +364,15 @@ fn parse_pattern_raw(raw: PatternRaw) -> anyhow::Result<PatternConfig> {
 
 /// Resolve a plugin path relative to the session file's directory.
 pub fn resolve_plugin_path(plugin_source: &str, session_dir: &Path) -> String {
+    // Built-ins that take a file (builtin:sfz:piano/piano.sfz) resolve it
+    // like a plugin path
+    if let Some((name, file)) = plugin_source
+        .strip_prefix("builtin:")
+        .and_then(|rest| rest.split_once(':'))
+    {
+        let file = resolve_plugin_path(file, session_dir);
+        return format!("builtin:{name}:{file}");
+    }
     // URI-style references (lv2:..., clap:...) pass through as-is
     if plugin_source.contains(':') {
         return plugin_source.to_string();
@@ -477,6 +524,8 @@ pub struct SaveEffect {
 
 #[derive(Serialize)]
 struct SessionOut {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    master: Option<MasterConfig>,
     #[serde(rename = "keyboard")]
     keyboards: Vec<KeyboardOut>,
 }
@@ -528,7 +577,7 @@ struct PatternEventOut {
 }
 
 #[derive(Serialize)]
-struct ModulatorOut {
+pub struct ModulatorOut {
     #[serde(rename = "type", skip_serializing_if = "is_lfo_type")]
     mod_type: String,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -643,9 +692,42 @@ fn note_name(note: u8) -> String {
     crate::note_name(note)
 }
 
+/// Convert modulators to their serializable form (shared with tang presets).
+pub fn modulators_to_out(mods: &[SaveModulator]) -> Vec<ModulatorOut> {
+    mods.iter()
+        .map(|m| {
+            let targets: Vec<ModTargetOut> = m.targets.iter().map(save_mod_target_to_out).collect();
+            match &m.source {
+                SaveModSource::Lfo { waveform, rate } => ModulatorOut {
+                    mod_type: "lfo".into(),
+                    waveform: Some(waveform.clone()),
+                    rate: Some(*rate as f64),
+                    attack: None,
+                    decay: None,
+                    sustain: None,
+                    release: None,
+                    targets,
+                },
+                SaveModSource::Envelope { attack, decay, sustain, release } => ModulatorOut {
+                    mod_type: "envelope".into(),
+                    waveform: None,
+                    rate: None,
+                    attack: Some(*attack as f64),
+                    decay: Some(*decay as f64),
+                    sustain: Some(*sustain as f64),
+                    release: Some(*release as f64),
+                    targets,
+                },
+            }
+        })
+        .collect()
+}
+
 /// Save the current session state to a TOML file.
-pub fn save(path: &Path, keyboards: &[SaveKeyboard]) -> anyhow::Result<()> {
+pub fn save(path: &Path, keyboards: &[SaveKeyboard], master: &MasterConfig) -> anyhow::Result<()> {
     let session = SessionOut {
+        // Only write [master] when it differs from the defaults.
+        master: (*master != MasterConfig::default()).then(|| master.clone()),
         keyboards: keyboards
             .iter()
             .map(|kb| KeyboardOut {
@@ -654,39 +736,6 @@ pub fn save(path: &Path, keyboards: &[SaveKeyboard]) -> anyhow::Result<()> {
                     .splits
                     .iter()
                     .map(|sp| {
-                        let mods_to_out = |mods: &[SaveModulator]| -> Vec<ModulatorOut> {
-                            mods.iter()
-                                .map(|m| {
-                                    let targets: Vec<ModTargetOut> = m
-                                        .targets
-                                        .iter()
-                                        .map(save_mod_target_to_out)
-                                        .collect();
-                                    match &m.source {
-                                        SaveModSource::Lfo { waveform, rate } => ModulatorOut {
-                                            mod_type: "lfo".into(),
-                                            waveform: Some(waveform.clone()),
-                                            rate: Some(*rate as f64),
-                                            attack: None,
-                                            decay: None,
-                                            sustain: None,
-                                            release: None,
-                                            targets,
-                                        },
-                                        SaveModSource::Envelope { attack, decay, sustain, release } => ModulatorOut {
-                                            mod_type: "envelope".into(),
-                                            waveform: None,
-                                            rate: None,
-                                            attack: Some(*attack as f64),
-                                            decay: Some(*decay as f64),
-                                            sustain: Some(*sustain as f64),
-                                            release: Some(*release as f64),
-                                            targets,
-                                        },
-                                    }
-                                })
-                                .collect()
-                        };
                         SplitOut {
                             range: sp
                                 .range
@@ -702,7 +751,7 @@ pub fn save(path: &Path, keyboards: &[SaveKeyboard]) -> anyhow::Result<()> {
                                     plugin: inst.plugin.clone(),
                                     volume: inst.volume,
                                     params,
-                                    modulators: mods_to_out(&inst.modulators),
+                                    modulators: modulators_to_out(&inst.modulators),
                                 }
                             }),
                             effects: sp
@@ -718,7 +767,7 @@ pub fn save(path: &Path, keyboards: &[SaveKeyboard]) -> anyhow::Result<()> {
                                         plugin: fx.plugin.clone(),
                                         mix: fx.mix,
                                         params,
-                                        modulators: mods_to_out(&fx.modulators),
+                                        modulators: modulators_to_out(&fx.modulators),
                                     }
                                 })
                                 .collect(),
@@ -878,7 +927,7 @@ plugin = "builtin:sine"
             ],
         }];
 
-        save(&path, &keyboards).unwrap();
+        save(&path, &keyboards, &MasterConfig::default()).unwrap();
 
         // Reload and verify
         let config = load(path.to_str().unwrap()).unwrap();
@@ -925,7 +974,7 @@ plugin = "builtin:sine"
             }],
         }];
 
-        save(&path, &keyboards).unwrap();
+        save(&path, &keyboards, &MasterConfig::default()).unwrap();
 
         let config = load(path.to_str().unwrap()).unwrap();
         assert_eq!(config.keyboards.len(), 1);
@@ -972,4 +1021,81 @@ depth = 0.3
         assert_eq!(m.targets[0].param.as_deref(), Some("frequency"));
         assert!((m.targets[0].depth - 0.3).abs() < 0.01);
     }
+
+    #[test]
+    fn resolve_builtin_file_argument() {
+        let dir = Path::new("/sessions");
+        assert_eq!(resolve_plugin_path("builtin:sine", dir), "builtin:sine");
+        assert_eq!(
+            resolve_plugin_path("builtin:sfz:piano/piano.sfz", dir),
+            "builtin:sfz:/sessions/piano/piano.sfz"
+        );
+        assert_eq!(
+            resolve_plugin_path("builtin:sfz:/abs/drums.sfz", dir),
+            "builtin:sfz:/abs/drums.sfz"
+        );
+        assert_eq!(resolve_plugin_path("synth.clap", dir), "/sessions/synth.clap");
+    }
+
+    #[test]
+    fn master_config_load_and_save() {
+        let toml = r#"
+[master]
+limiter = true
+ceiling_db = -0.3
+
+[[keyboard]]
+
+[[keyboard.split]]
+
+[keyboard.split.instrument]
+plugin = "builtin:sine"
+"#;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("master.toml");
+        std::fs::write(&path, toml).unwrap();
+
+        let config = load(path.to_str().unwrap()).unwrap();
+        assert!(config.master.limiter);
+        assert!(config.master.sanitise);
+        assert!((config.master.ceiling_db + 0.3).abs() < 1e-6);
+        assert!((config.master.release_ms - 100.0).abs() < 1e-6);
+
+        // Round-trips through save; defaults are left out of the file.
+        let keyboards = vec![SaveKeyboard {
+            name: "Main".into(),
+            splits: vec![SaveSplit {
+                range: None,
+                transpose: 0,
+                instrument: Some(SaveInstrument {
+                    plugin: "builtin:sine".into(),
+                    volume: 1.0,
+                    params: vec![],
+                    modulators: vec![],
+                }),
+                effects: vec![],
+                pattern: None,
+            }],
+        }];
+        let out = dir.path().join("out.toml");
+        save(&out, &keyboards, &config.master).unwrap();
+        assert_eq!(load(out.to_str().unwrap()).unwrap().master, config.master);
+        save(&out, &keyboards, &MasterConfig::default()).unwrap();
+        assert!(!std::fs::read_to_string(&out).unwrap().contains("[master]"));
+    }
+
+    #[test]
+    fn apply_preset_by_name_on_builtin() {
+        let mut plugin = crate::plugin::builtin::load("builtin:fm", 48000.0, 512).unwrap();
+        let algorithm = plugin
+            .parameters()
+            .iter()
+            .position(|p| p.name == "Algorithm")
+            .unwrap() as u32;
+        apply_preset(&mut plugin, "Bell");
+        assert_eq!(plugin.get_parameter(algorithm), Some(2.0));
+        // Unknown names leave the plugin as it was.
+        apply_preset(&mut plugin, "No Such Preset");
+        assert_eq!(plugin.get_parameter(algorithm), Some(2.0));
+    }
 }
@@ -12,6 +12,7 @@ use crossterm::terminal::{EnterAlternateScreen, LeaveAlternateScreen};
 use ratatui::backend::CrosstermBackend;
 use ratatui::layout::{Constraint, Layout, Rect};
 use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
 use ratatui::widgets::{Block, Borders, Clear, Paragraph};
 use ratatui::Terminal;
 
@@ -36,12 +37,14 @@ const TAB_SEP: &str = " │ ";
 struct PluginSlot {
     name: String,
     format: String,
-    #[allow(dead_code)]
     id: String,
-    #[allow(dead_code)]
     is_instrument: bool,
     params: Vec<ParamSlot>,
     modulators: Vec<ModulatorSlot>,
+    /// Native and tang library presets, as listed when the plugin was loaded.
+    presets: Vec<plugin::Preset>,
+    /// Read-only outputs, updated live by the audio thread.
+    meters: Vec<plugin::MeterInfo>,
 }
 
 enum ParamKind {
@@ -186,12 +189,14 @@ fn actions_for(addr: Option<&TreeAddress>) -> Vec<(&'static str, &'static str)>
             ("m", "modulate"),
             ("d", "delete"),
             ("p", "presets"),
+            ("P", "save preset"),
         ],
         Some(TreeAddress::Effect { .. }) => vec![
             ("a", "add effect"),
             ("m", "modulate"),
             ("d", "delete"),
             ("p", "presets"),
+            ("P", "save preset"),
         ],
         Some(TreeAddress::Pattern { .. }) => vec![
             ("r", "record"),
@@ -252,6 +257,35 @@ struct RangeEditState {
     kb: usize,
 }
 
+struct PresetSelectorState {
+    filter: FilterListState,
+    items: Vec<FilterListItem>,
+    addr: TreeAddress,
+}
+
+struct PresetSaveState {
+    input: TextInputState,
+    addr: TreeAddress,
+    /// Save in the plugin's own preset format instead of as a tang preset.
+    native: bool,
+}
+
+/// Preset or state work that must run on the main thread, on a plugin lent
+/// by the audio thread.
+enum PluginTask {
+    Load(plugin::Preset),
+    ApplyTang(plugin::library::TangPreset),
+    SaveTang(String),
+    SaveNative(String),
+}
+
+/// A [`PluginTask`] waiting for its plugin to arrive from the audio thread.
+struct PluginJob {
+    addr: TreeAddress,
+    rx: crossbeam_channel::Receiver<Box<dyn plugin::Plugin>>,
+    task: PluginTask,
+}
+
 #[derive(Default, Clone)]
 struct Areas {
     tab: Rect,
@@ -281,6 +315,9 @@ struct State {
     range_edit: Option<RangeEditState>,
     selector: Option<SelectorState>,
     target_selector: Option<TargetSelectorState>,
+    preset_selector: Option<PresetSelectorState>,
+    preset_save: Option<PresetSaveState>,
+    plugin_jobs: Vec<PluginJob>,
     catalog: Vec<PluginInfo>,
     areas: Areas,
     quit: bool,
@@ -301,6 +338,11 @@ struct State {
     global_bpm: f32,
     bpm_editing: Option<EditState>,
     pattern_rx: crossbeam_channel::Receiver<crate::plugin::chain::PatternNotification>,
+    /// Background plugin scan results.
+    catalog_rx: crossbeam_channel::Receiver<Vec<PluginInfo>>,
+    // Master output stage: session settings and live indicators.
+    master: crate::session::MasterConfig,
+    master_meters: crate::plugin::master::MasterMeters,
 }
 
 impl State {
@@ -510,6 +552,18 @@ impl State {
         }
     }
 
+    /// Swap in the refreshed catalogue once the background scan is done.
+    fn poll_catalog_refresh(&mut self) {
+        // The open selector's items index into the current catalogue.
+        if self.selector.is_some() {
+            return;
+        }
+        if let Ok(scanned) = self.catalog_rx.try_recv() {
+            self.catalog = finish_catalog(scanned);
+            log::info!("Plugin catalogue refreshed ({} plugins)", self.catalog.len());
+        }
+    }
+
     fn selected_address(&self) -> Option<&TreeAddress> {
         self.tree_entries.get(self.chain_state.selected).map(|e| &e.address)
     }
@@ -549,6 +603,9 @@ impl State {
     }
 
     fn confirm_selector(&mut self) {
+        if self.chain_busy() {
+            return;
+        }
         let sel = match self.selector.take() {
             Some(s) => s,
             None => return,
@@ -598,6 +655,8 @@ impl State {
             is_instrument: loaded.is_instrument(),
             params,
             modulators: vec![],
+            presets: loaded.presets(),
+            meters: loaded.meters(),
         };
 
         match sel.mode {
@@ -1179,40 +1238,6 @@ impl State {
             }
         }
 
-        let mods_to_save = |mods: &[ModulatorSlot]| -> Vec<crate::session::SaveModulator> {
-            mods.iter()
-                .map(|m| {
-                    let source = match &m.source {
-                        ModSourceSlot::Lfo { waveform, rate } => {
-                            crate::session::SaveModSource::Lfo {
-                                waveform: waveform.name().to_string(),
-                                rate: *rate,
-                            }
-                        }
-                        ModSourceSlot::Envelope { attack, decay, sustain, release } => {
-                            crate::session::SaveModSource::Envelope {
-                                attack: *attack,
-                                decay: *decay,
-                                sustain: *sustain,
-                                release: *release,
-                            }
-                        }
-                    };
-                    crate::session::SaveModulator {
-                        source,
-                        targets: m
-                            .targets
-                            .iter()
-                            .map(|t| crate::session::SaveModTarget {
-                                kind: t.kind.clone(),
-                                label: t.param_name.clone(),
-                                depth: t.depth,
-                            })
-                            .collect(),
-                    }
-                })
-                .collect()
-        };
         let save_keyboards: Vec<crate::session::SaveKeyboard> = self
             .keyboards
             .iter()
@@ -1234,7 +1259,7 @@ impl State {
                                     .filter(|p| (p.value - p.default).abs() > f32::EPSILON)
                                     .map(|p| (p.name.clone(), p.value))
                                     .collect(),
-                                modulators: mods_to_save(&inst.modulators),
+                                modulators: modulators_to_save(&inst.modulators),
                             }
                         }),
                         effects: sp
@@ -1249,7 +1274,7 @@ impl State {
                                     .filter(|p| (p.value - p.default).abs() > f32::EPSILON)
                                     .map(|p| (p.name.clone(), p.value))
                                     .collect(),
-                                modulators: mods_to_save(&fx.modulators),
+                                modulators: modulators_to_save(&fx.modulators),
                             })
                             .collect(),
                         pattern: sp.pattern.as_ref().map(|p| crate::session::SavePattern {
@@ -1265,7 +1290,7 @@ impl State {
             })
             .collect();
 
-        match crate::session::save(&path, &save_keyboards) {
+        match crate::session::save(&path, &save_keyboards, &self.master) {
             Ok(()) => {
                 self.dirty = false;
                 log::info!("Session saved to {}", path.display());
@@ -1275,6 +1300,252 @@ impl State {
             }
         }
     }
+
+    fn open_preset_selector(&mut self, addr: TreeAddress) {
+        let Some(pslot) = self.plugin_at(&addr) else { return };
+        let items: Vec<FilterListItem> = pslot
+            .presets
+            .iter()
+            .enumerate()
+            .map(|(i, p)| {
+                let source = if p.id.starts_with(plugin::library::PRESET_ID_PREFIX) {
+                    "tang".to_string()
+                } else {
+                    pslot.format.clone()
+                };
+                FilterListItem {
+                    cells: vec![p.name.clone(), source],
+                    index: i,
+                }
+            })
+            .collect();
+        if items.is_empty() {
+            log::info!("'{}' has no presets", pslot.name);
+            return;
+        }
+
+        let mut filter = FilterListState::new();
+        filter.apply_filter(&items);
+        self.preset_selector = Some(PresetSelectorState { filter, items, addr });
+    }
+
+    fn confirm_preset_selector(&mut self) {
+        let Some(ps) = self.preset_selector.take() else { return };
+        let Some(chosen) = ps.filter.selected_item(&ps.items).map(|item| item.index) else {
+            return;
+        };
+        let Some(preset) = self.plugin_at(&ps.addr).and_then(|p| p.presets.get(chosen)).cloned() else {
+            return;
+        };
+
+        let task = match preset.id.strip_prefix(plugin::library::PRESET_ID_PREFIX) {
+            Some(path) => match plugin::library::load(std::path::Path::new(path)) {
+                Ok(tang) => PluginTask::ApplyTang(tang),
+                Err(e) => {
+                    log::error!("Failed to load preset '{}': {e}", preset.name);
+                    return;
+                }
+            },
+            None => PluginTask::Load(preset),
+        };
+        self.start_plugin_job(ps.addr, task);
+    }
+
+    /// Borrow the plugin at `addr` from the audio thread, leaving a
+    /// placeholder in its slot, and run `task` on it once it arrives (see
+    /// [`State::poll_plugin_jobs`]). Preset and state IO never runs on the
+    /// audio thread.
+    fn start_plugin_job(&mut self, addr: TreeAddress, task: PluginTask) {
+        let Some((kb, split)) = addr.kb_split() else { return };
+        let Some(pslot) = self.plugin_at(&addr) else { return };
+        if self.plugin_jobs.iter().any(|job| job.addr == addr) {
+            log::warn!("'{}' is busy, try again", pslot.name);
+            return;
+        }
+        let placeholder = plugin::chain::Placeholder::new(&pslot.name, pslot.is_instrument, self.sample_rate);
+        let (reply, rx) = crossbeam_channel::bounded(1);
+        let _ = self.cmd_tx.send(GraphCommand::ExchangePlugin {
+            kb,
+            split,
+            slot: addr.slot(),
+            plugin: Box::new(placeholder),
+            reply: Some(reply),
+        });
+        self.plugin_jobs.push(PluginJob { addr, rx, task });
+    }
+
+    /// Whether chain edits must wait: plugins are returned to their slot by
+    /// position, so adding, removing or moving plugins while one is lent
+    /// would put it back in the wrong place.
+    fn chain_busy(&self) -> bool {
+        if self.plugin_jobs.is_empty() {
+            return false;
+        }
+        log::warn!("Preset operation in progress, try again");
+        true
+    }
+
+    /// Run the tasks whose plugins have arrived, then hand each plugin back
+    /// to the audio thread.
+    fn poll_plugin_jobs(&mut self) {
+        let mut i = 0;
+        while i < self.plugin_jobs.len() {
+            match self.plugin_jobs[i].rx.try_recv() {
+                Ok(plugin) => {
+                    let job = self.plugin_jobs.remove(i);
+                    self.run_plugin_job(job, plugin);
+                }
+                Err(crossbeam_channel::TryRecvError::Empty) => i += 1,
+                Err(crossbeam_channel::TryRecvError::Disconnected) => {
+                    // The slot went away before the audio thread got to it.
+                    self.plugin_jobs.remove(i);
+                }
+            }
+        }
+    }
+
+    fn run_plugin_job(&mut self, job: PluginJob, mut plugin: Box<dyn plugin::Plugin>) {
+        let addr = job.addr;
+        match job.task {
+            PluginTask::Load(preset) => match plugin.load_preset(&preset.id) {
+                Ok(()) => {
+                    self.read_back_params(&addr, plugin.as_mut());
+                    log::info!("Loaded preset '{}'", preset.name);
+                    self.dirty = true;
+                }
+                Err(e) => log::error!("Failed to load preset '{}': {e}", preset.name),
+            },
+            PluginTask::ApplyTang(preset) => {
+                let name = preset.name.clone();
+                self.apply_tang_preset(addr, plugin.as_mut(), preset);
+                log::info!("Loaded preset '{name}'");
+                self.dirty = true;
+            }
+            PluginTask::SaveTang(name) => self.save_tang_preset(addr, plugin.as_mut(), &name),
+            PluginTask::SaveNative(name) => match plugin.save_preset(&name) {
+                Ok(preset) => {
+                    log::info!("Preset '{name}' saved");
+                    if let Some(pslot) = self.plugin_at_mut(&addr) {
+                        pslot.presets.retain(|p| p.id != preset.id);
+                        pslot.presets.push(preset);
+                    }
+                }
+                Err(e) => log::error!("Failed to save preset '{name}': {e}"),
+            },
+        }
+
+        if let Some((kb, split)) = addr.kb_split() {
+            let _ = self.cmd_tx.send(GraphCommand::ExchangePlugin {
+                kb,
+                split,
+                slot: addr.slot(),
+                plugin,
+                reply: None,
+            });
+        }
+        self.rebuild_tree();
+    }
+
+    /// Refresh the mirrored parameter values of `addr` from the plugin.
+    fn read_back_params(&mut self, addr: &TreeAddress, plugin: &mut dyn plugin::Plugin) {
+        let Some(pslot) = self.plugin_at_mut(addr) else { return };
+        for p in pslot.params.iter_mut().filter(|p| !matches!(p.kind, ParamKind::Separator)) {
+            if let Some(value) = plugin.get_parameter(p.index) {
+                p.value = value;
+            }
+        }
+    }
+
+    /// Apply a tang preset to `plugin` (lent from `addr`): state first, then
+    /// named parameter values, then replace the slot's modulators.
+    fn apply_tang_preset(
+        &mut self,
+        addr: TreeAddress,
+        plugin: &mut dyn plugin::Plugin,
+        preset: plugin::library::TangPreset,
+    ) {
+        let Some((kb, split)) = addr.kb_split() else { return };
+        let slot = addr.slot();
+        let cmd_tx = self.cmd_tx.clone();
+
+        if let Some(data) = &preset.state {
+            match plugin.load_state(data) {
+                Ok(()) => self.read_back_params(&addr, plugin),
+                Err(e) => log::warn!("Preset '{}': {e}", preset.name),
+            }
+        }
+
+        let Some(pslot) = self.plugin_at_mut(&addr) else { return };
+        for (name, value) in &preset.params {
+            match pslot.params.iter_mut().find(|p| p.name == *name) {
+                Some(p) => {
+                    p.value = value.clamp(p.min, p.max);
+                    if let Err(e) = plugin.set_parameter(p.index, p.value) {
+                        log::warn!("Preset '{}': {name}: {e}", preset.name);
+                    }
+                }
+                None => log::warn!("Preset '{}': unknown parameter '{name}'", preset.name),
+            }
+        }
+
+        for index in (0..pslot.modulators.len()).rev() {
+            let _ = cmd_tx.send(GraphCommand::RemoveModulator {
+                kb,
+                split,
+                parent_slot: slot,
+                index,
+            });
+        }
+        pslot.modulators.clear();
+
+        let param_infos: Vec<plugin::ParameterInfo> = pslot
+            .params
+            .iter()
+            .map(|p| plugin::ParameterInfo {
+                index: p.index,
+                name: p.name.clone(),
+                min: p.min,
+                max: p.max,
+                default: p.default,
+            })
+            .collect();
+        match crate::load_modulators(&preset.modulators, slot, &param_infos, kb, split, &cmd_tx) {
+            Ok(mods) => pslot.modulators = mods.into_iter().map(to_modulator_slot).collect(),
+            Err(e) => log::error!("Preset '{}': failed to load modulators: {e}", preset.name),
+        }
+    }
+
+    /// Save `plugin` (lent from `addr`) as a tang preset called `name` in the
+    /// user preset library.
+    fn save_tang_preset(&mut self, addr: TreeAddress, plugin: &mut dyn plugin::Plugin, name: &str) {
+        let state = plugin.save_state();
+
+        let Some(pslot) = self.plugin_at_mut(&addr) else { return };
+        let params: Vec<(String, f32)> = pslot
+            .params
+            .iter()
+            .filter(|p| !matches!(p.kind, ParamKind::Separator))
+            .map(|p| (p.name.clone(), p.value))
+            .collect();
+        let modulators = modulators_to_save(&pslot.modulators);
+
+        let path = match plugin::library::preset_path(&pslot.id, name) {
+            Ok(p) => p,
+            Err(e) => {
+                log::error!("Failed to save preset '{name}': {e}");
+                return;
+            }
+        };
+        if let Err(e) = plugin::library::save(&path, name, &pslot.id, &params, state.as_deref(), &modulators) {
+            log::error!("Failed to save preset '{name}': {e}");
+            return;
+        }
+
+        let id = format!("{}{}", plugin::library::PRESET_ID_PREFIX, path.display());
+        pslot.presets.retain(|p| p.id != id);
+        pslot.presets.push(plugin::Preset { name: name.to_string(), id });
+        log::info!("Preset '{name}' saved to {}", path.display());
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -1339,6 +1610,8 @@ pub struct LoadedPlugin {
     pub params: Vec<plugin::ParameterInfo>,
     pub param_values: Vec<f32>,
     pub modulators: Vec<LoadedModulator>,
+    pub presets: Vec<plugin::Preset>,
+    pub meters: Vec<plugin::MeterInfo>,
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -1351,9 +1624,12 @@ pub fn run(
     max_block_size: usize,
     session_path: Option<PathBuf>,
     pattern_rx: crossbeam_channel::Receiver<crate::plugin::chain::PatternNotification>,
+    master: crate::session::MasterConfig,
+    master_meters: crate::plugin::master::MasterMeters,
 ) -> anyhow::Result<()> {
-    // Build catalog from enumerate.
+    // Start from the scan cache and refresh it in the background.
     let catalog = build_catalog();
+    let catalog_rx = spawn_catalog_refresh();
 
     // Convert loaded keyboards into KeyboardNodes.
     let keyboards: Vec<KeyboardNode> = loaded_keyboards
@@ -1410,6 +1686,7 @@ pub fn run(
         .map(|p| p.bpm)
         .next()
         .unwrap_or(120.0);
+    let _ = cmd_tx.send(GraphCommand::SetGlobalBpm { bpm: initial_bpm });
 
     // Send transpose and pattern data to audio graph for loaded splits.
     for (kb_idx, kb) in keyboards.iter().enumerate() {
@@ -1473,6 +1750,9 @@ pub fn run(
         range_edit: None,
         selector: None,
         target_selector: None,
+        preset_selector: None,
+        preset_save: None,
+        plugin_jobs: Vec::new(),
         catalog,
         areas: Areas::default(),
         quit: false,
@@ -1489,6 +1769,9 @@ pub fn run(
         global_bpm: initial_bpm,
         bpm_editing: None,
         pattern_rx,
+        catalog_rx,
+        master,
+        master_meters,
     };
 
     // Set up terminal.
@@ -1541,14 +1824,22 @@ fn event_loop(
             }
         }
 
+        s.poll_catalog_refresh();
+        s.poll_plugin_jobs();
+
+        // Service plugin main-thread callbacks and timers.
+        plugin::pump_main_thread();
+
         render(terminal, s)?;
         if s.quit {
             break;
         }
 
         // Poll with timeout so we wake up to drain pattern notifications
-        // even when there's no user input.
-        if !event::poll(Duration::from_millis(100))? {
+        // and pump plugin timers even when there's no user input.
+        // Wake sooner while a plugin is away from the audio thread.
+        let timeout = if s.plugin_jobs.is_empty() { 100 } else { 5 };
+        if !event::poll(Duration::from_millis(timeout))? {
             continue;
         }
         let ev = event::read()?;
@@ -1571,6 +1862,10 @@ fn process_event(s: &mut State, ev: Event) {
                 handle_selector_key(s, key.code);
             } else if s.target_selector.is_some() {
                 handle_target_selector_key(s, key.code);
+            } else if s.preset_selector.is_some() {
+                handle_preset_selector_key(s, key.code);
+            } else if s.preset_save.is_some() {
+                handle_preset_save_key(s, key.code);
             } else if s.bpm_editing.is_some() {
                 handle_bpm_edit_key(s, key.code);
             } else if s.editing.is_some() {
@@ -1584,10 +1879,12 @@ fn process_event(s: &mut State, ev: Event) {
             }
         }
         Event::Mouse(mouse) => {
-            if s.selector.is_some() || s.target_selector.is_some() || s.editing.is_some() || s.range_edit.is_some() || s.bpm_editing.is_some() {
+            if s.selector.is_some() || s.target_selector.is_some() || s.preset_selector.is_some() || s.preset_save.is_some() || s.editing.is_some() || s.range_edit.is_some() || s.bpm_editing.is_some() {
                 if let MouseEventKind::Down(MouseButton::Left) = mouse.kind {
                     s.selector = None;
                     s.target_selector = None;
+                    s.preset_selector = None;
+                    s.preset_save = None;
                     s.editing = None;
                     s.range_edit = None;
                     s.bpm_editing = None;
@@ -1650,6 +1947,61 @@ fn handle_target_selector_key(s: &mut State, code: KeyCode) {
     }
 }
 
+fn handle_preset_selector_key(s: &mut State, code: KeyCode) {
+    let ps = s.preset_selector.as_mut().unwrap();
+    match code {
+        KeyCode::Esc => s.preset_selector = None,
+        KeyCode::Enter => s.confirm_preset_selector(),
+        KeyCode::Up => {
+            ps.filter.list.up();
+            ps.filter.list.ensure_visible(20);
+        }
+        KeyCode::Down => {
+            ps.filter.list.down();
+            ps.filter.list.ensure_visible(20);
+        }
+        KeyCode::Backspace => {
+            ps.filter.input.backspace();
+            ps.filter.apply_filter(&ps.items);
+        }
+        KeyCode::Char(ch) => {
+            ps.filter.input.insert(ch);
+            ps.filter.apply_filter(&ps.items);
+        }
+        _ => {}
+    }
+}
+
+fn handle_preset_save_key(s: &mut State, code: KeyCode) {
+    let ps = s.preset_save.as_mut().unwrap();
+    match code {
+        KeyCode::Esc => s.preset_save = None,
+        KeyCode::Enter => {
+            let name = ps.input.value.trim().to_string();
+            if name.is_empty() {
+                return; // keep popup open until a name is given
+            }
+            let task = if ps.native {
+                PluginTask::SaveNative(name)
+            } else {
+                PluginTask::SaveTang(name)
+            };
+            let addr = ps.addr;
+            s.preset_save = None;
+            s.start_plugin_job(addr, task);
+        }
+        KeyCode::Tab => ps.native = !ps.native,
+        KeyCode::Backspace => ps.input.backspace(),
+        KeyCode::Delete => ps.input.delete(),
+        KeyCode::Left => ps.input.move_left(),
+        KeyCode::Right => ps.input.move_right(),
+        KeyCode::Home => ps.input.home(),
+        KeyCode::End => ps.input.end(),
+        KeyCode::Char(ch) => ps.input.insert(ch),
+        _ => {}
+    }
+}
+
 fn handle_edit_key(s: &mut State, code: KeyCode) {
     let edit = s.editing.as_mut().unwrap();
     match code {
@@ -1815,6 +2167,28 @@ fn handle_key(s: &mut State, code: KeyCode, modifiers: KeyModifiers) {
             }
         }
 
+        // 'p' — browse and load presets for the selected plugin.
+        KeyCode::Char('p') if s.active_tab == 0 && !s.focus_params => {
+            if let Some(addr @ (TreeAddress::Instrument { .. } | TreeAddress::Effect { .. })) =
+                s.selected_address().copied()
+            {
+                s.open_preset_selector(addr);
+            }
+        }
+
+        // 'P' — save the selected plugin as a tang or native preset.
+        KeyCode::Char('P') if s.active_tab == 0 && !s.focus_params => {
+            if let Some(addr @ (TreeAddress::Instrument { .. } | TreeAddress::Effect { .. })) =
+                s.selected_address().copied()
+            {
+                s.preset_save = Some(PresetSaveState {
+                    input: TextInputState::new(""),
+                    addr,
+                    native: false,
+                });
+            }
+        }
+
         // 'm' — add LFO modulator to the selected plugin (instrument or effect).
         KeyCode::Char('m') if s.active_tab == 0 && !s.focus_params => {
             if let Some(addr) = s.selected_address().copied() {
@@ -1917,6 +2291,9 @@ fn handle_key(s: &mut State, code: KeyCode, modifiers: KeyModifiers) {
         }
 
         KeyCode::Char('d') if s.active_tab == 0 && !s.focus_params => {
+            if s.chain_busy() {
+                return;
+            }
             let sel = s.chain_state.selected;
             if sel < s.tree_entries.len() {
                 let addr = s.tree_entries[sel].address;
@@ -2134,6 +2511,9 @@ fn handle_key(s: &mut State, code: KeyCode, modifiers: KeyModifiers) {
                 && !s.focus_params
                 && modifiers.contains(KeyModifiers::SHIFT) =>
         {
+            if s.chain_busy() {
+                return;
+            }
             let sel = s.chain_state.selected;
             if sel < s.tree_entries.len() {
                 match s.tree_entries[sel].address {
@@ -2211,6 +2591,9 @@ fn handle_key(s: &mut State, code: KeyCode, modifiers: KeyModifiers) {
                 && !s.focus_params
                 && modifiers.contains(KeyModifiers::SHIFT) =>
         {
+            if s.chain_busy() {
+                return;
+            }
             let sel = s.chain_state.selected;
             if sel < s.tree_entries.len() {
                 match s.tree_entries[sel].address {
@@ -2468,20 +2851,33 @@ fn render(
         let tab_names: &[&str] = &[session_label, TAB_NAMES[1], TAB_NAMES[2], TAB_NAMES[3]];
         frame.render_widget(TabBar::new(tab_names, s.active_tab), tab_area);
 
-        // BPM display on the right side of the tab bar.
+        // BPM display on the right side of the tab bar, preceded by the
+        // master limiter/sanitiser indicators while they are engaged.
         let bpm_text = format!("{:.0} BPM", s.global_bpm);
-        let bpm_width = bpm_text.len() as u16;
-        if tab_area.width > bpm_width + 2 {
-            let bpm_area = Rect {
-                x: tab_area.right() - bpm_width - 1,
+        let mut status = vec![Span::styled(bpm_text, Style::default().fg(Color::DarkGray))];
+        let reduction = s.master_meters.gain_reduction_db.get();
+        if reduction >= 0.1 {
+            status.insert(0, Span::styled(
+                format!("LIMIT -{reduction:.1} dB  "),
+                Style::default().fg(Color::Yellow),
+            ));
+        }
+        if s.master_meters.sanitised.get() > 0.0 {
+            status.insert(0, Span::styled(
+                "NaN/Inf  ",
+                Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+            ));
+        }
+        let status = Line::from(status);
+        let status_width = status.width() as u16;
+        if tab_area.width > status_width + 2 {
+            let status_area = Rect {
+                x: tab_area.right() - status_width - 1,
                 y: tab_area.y,
-                width: bpm_width + 1,
+                width: status_width + 1,
                 height: 1,
             };
-            frame.render_widget(
-                Paragraph::new(bpm_text).style(Style::default().fg(Color::DarkGray)),
-                bpm_area,
-            );
+            frame.render_widget(Paragraph::new(status), status_area);
         }
 
         match s.active_tab {
@@ -2521,6 +2917,12 @@ fn render(
                 if let Some(ts) = &s.target_selector {
                     render_target_selector_popup(frame, area, ts);
                 }
+                if let Some(ps) = &s.preset_selector {
+                    render_preset_selector_popup(frame, area, ps);
+                }
+                if let Some(ps) = &s.preset_save {
+                    render_preset_save_popup(frame, area, ps);
+                }
                 if let Some(edit) = &s.bpm_editing {
                     render_edit_popup(frame, area, edit);
                 }
@@ -2855,6 +3257,37 @@ fn render_session(
         (None, right_inner)
     };
 
+    // Plugin control outputs are shown as meters below the parameter list.
+    let meters: &[plugin::MeterInfo] = if is_plugin_node {
+        let slot = match &tree_entries[selected].address {
+            TreeAddress::Instrument { kb, split } => keyboards
+                .get(*kb)
+                .and_then(|k| k.splits.get(*split))
+                .and_then(|s| s.instrument.as_ref()),
+            TreeAddress::Effect { kb, split, index } => keyboards
+                .get(*kb)
+                .and_then(|k| k.splits.get(*split))
+                .and_then(|s| s.effects.get(*index)),
+            _ => None,
+        };
+        slot.map_or(&[], |p| p.meters.as_slice())
+    } else {
+        &[]
+    };
+    let (list_area, meter_area) = if !meters.is_empty() && list_area.height > 6 {
+        // Meters get at least a third of the pane, more if the parameter
+        // list leaves room (e.g. the analyzer's spectrum bands).
+        let spare = list_area.height.saturating_sub(plugin_params.len() as u16);
+        let meter_h = (meters.len() as u16 + 1).min(spare.max(list_area.height / 3));
+        let [la, ma] = Layout::vertical([
+            Constraint::Fill(1),
+            Constraint::Length(meter_h),
+        ]).areas(list_area);
+        (la, Some(ma))
+    } else {
+        (list_area, None)
+    };
+
     // Render filter bar.
     if let Some(fa) = filter_area {
         let prompt = "/ ";
@@ -2952,9 +3385,38 @@ fn render_session(
     };
     frame.render_widget(param_list, list_area);
 
+    if let Some(ma) = meter_area {
+        render_meters(frame, ma, meters, name_width);
+    }
+
     (left_inner, right_inner)
 }
 
+fn render_meters(frame: &mut ratatui::Frame, area: Rect, meters: &[plugin::MeterInfo], name_width: usize) {
+    let dim = Style::default().fg(Color::DarkGray);
+    let bar_width = area.width.saturating_sub(name_width as u16 + 12) as usize;
+    let mut lines = vec![Line::from(Span::styled(
+        format!("  {:<width$} ──────", "Outputs", width = name_width),
+        dim,
+    ))];
+    for m in meters.iter().take(area.height.saturating_sub(1) as usize) {
+        let value = m.value.get();
+        let normalized = if (m.max - m.min).abs() > f32::EPSILON {
+            ((value - m.min) / (m.max - m.min)).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        let filled = (normalized * bar_width as f32).round() as usize;
+        lines.push(Line::from(vec![
+            Span::styled(format!("  {:<width$} ", truncate(&m.name, name_width), width = name_width), dim),
+            Span::styled("▮".repeat(filled), Style::default().fg(Color::Cyan)),
+            Span::styled("·".repeat(bar_width.saturating_sub(filled)), dim),
+            Span::styled(format!(" {:>8.2}", value), dim),
+        ]));
+    }
+    frame.render_widget(Paragraph::new(lines), area);
+}
+
 fn render_action_bar(
     frame: &mut ratatui::Frame,
     area: Rect,
@@ -3097,6 +3559,63 @@ fn render_target_selector_popup(frame: &mut ratatui::Frame, area: Rect, ts: &Tar
     frame.render_widget(FilterList::new(&ts.filter, &ts.items, columns), inner);
 }
 
+fn render_preset_selector_popup(frame: &mut ratatui::Frame, area: Rect, ps: &PresetSelectorState) {
+    let w = (area.width * 60 / 100).max(36).min(area.width);
+    let h = (area.height * 60 / 100).max(10).min(area.height);
+    let popup = centered_rect(w, h, area);
+    frame.render_widget(Clear, popup);
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Cyan))
+        .title(" Select Preset ");
+    let inner = block.inner(popup);
+    frame.render_widget(block, popup);
+
+    let columns: &[(&str, u16)] = &[
+        ("Name", inner.width.saturating_sub(10)),
+        ("Source", 8),
+    ];
+    frame.render_widget(FilterList::new(&ps.filter, &ps.items, columns), inner);
+}
+
+fn render_preset_save_popup(frame: &mut ratatui::Frame, area: Rect, ps: &PresetSaveState) {
+    let popup = centered_rect(34, 6, area);
+    frame.render_widget(Clear, popup);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Yellow))
+        .title(" Save Preset ");
+    let inner = block.inner(popup);
+    frame.render_widget(block, popup);
+
+    if inner.height >= 3 {
+        frame.render_widget(
+            Paragraph::new("Name").style(Style::default().fg(Color::DarkGray)),
+            Rect::new(inner.x, inner.y, inner.width, 1),
+        );
+        frame.render_widget(
+            TextInput::new(&ps.input),
+            Rect::new(inner.x, inner.y + 1, inner.width, 1),
+        );
+        let choice = |label: &'static str, selected: bool| {
+            let style = if selected {
+                Style::default().fg(Color::Black).bg(Color::Yellow)
+            } else {
+                Style::default().fg(Color::DarkGray)
+            };
+            Span::styled(label, style)
+        };
+        let line = Line::from(vec![
+            Span::styled("Tab: ", Style::default().fg(Color::DarkGray)),
+            choice(" tang ", !ps.native),
+            Span::raw(" "),
+            choice(" native ", ps.native),
+        ]);
+        frame.render_widget(Paragraph::new(line), Rect::new(inner.x, inner.y + 2, inner.width, 1));
+    }
+}
+
 fn render_help(frame: &mut ratatui::Frame, area: Rect, lines: &[String], offset: usize) {
     let scroll_lines: Vec<ScrollLine> = lines
         .iter()
@@ -3192,26 +3711,7 @@ fn to_plugin_slot(lp: LoadedPlugin) -> PluginSlot {
             kind: ParamKind::Float,
         })
         .collect();
-    let modulators = lp.modulators.into_iter().map(|lm| {
-        let source = match lm.source {
-            LoadedModSource::Lfo { waveform, rate } => ModSourceSlot::Lfo { waveform, rate },
-            LoadedModSource::Envelope { attack, decay, sustain, release } => {
-                ModSourceSlot::Envelope { attack, decay, sustain, release }
-            }
-        };
-        ModulatorSlot {
-            source,
-            targets: lm.targets.into_iter().map(|lt| {
-                ModTargetSlot {
-                    param_name: lt.param_name.clone(),
-                    kind: crate::plugin::chain::ModTargetKind::PluginParam { param_index: lt.param_index },
-                    depth: lt.depth,
-                    param_min: lt.param_min,
-                    param_max: lt.param_max,
-                }
-            }).collect(),
-        }
-    }).collect();
+    let modulators = lp.modulators.into_iter().map(to_modulator_slot).collect();
     PluginSlot {
         name: lp.name,
         format: format_from_id(&lp.id),
@@ -3219,9 +3719,68 @@ fn to_plugin_slot(lp: LoadedPlugin) -> PluginSlot {
         is_instrument: lp.is_instrument,
         params,
         modulators,
+        presets: lp.presets,
+        meters: lp.meters,
     }
 }
 
+fn to_modulator_slot(lm: LoadedModulator) -> ModulatorSlot {
+    let source = match lm.source {
+        LoadedModSource::Lfo { waveform, rate } => ModSourceSlot::Lfo { waveform, rate },
+        LoadedModSource::Envelope { attack, decay, sustain, release } => {
+            ModSourceSlot::Envelope { attack, decay, sustain, release }
+        }
+    };
+    ModulatorSlot {
+        source,
+        targets: lm.targets.into_iter().map(|lt| {
+            ModTargetSlot {
+                param_name: lt.param_name.clone(),
+                kind: crate::plugin::chain::ModTargetKind::PluginParam { param_index: lt.param_index },
+                depth: lt.depth,
+                param_min: lt.param_min,
+                param_max: lt.param_max,
+            }
+        }).collect(),
+    }
+}
+
+/// Convert the TUI's modulators into their session/preset representation.
+fn modulators_to_save(mods: &[ModulatorSlot]) -> Vec<crate::session::SaveModulator> {
+    mods.iter()
+        .map(|m| {
+            let source = match &m.source {
+                ModSourceSlot::Lfo { waveform, rate } => {
+                    crate::session::SaveModSource::Lfo {
+                        waveform: waveform.name().to_string(),
+                        rate: *rate,
+                    }
+                }
+                ModSourceSlot::Envelope { attack, decay, sustain, release } => {
+                    crate::session::SaveModSource::Envelope {
+                        attack: *attack,
+                        decay: *decay,
+                        sustain: *sustain,
+                        release: *release,
+                    }
+                }
+            };
+            crate::session::SaveModulator {
+                source,
+                targets: m
+                    .targets
+                    .iter()
+                    .map(|t| crate::session::SaveModTarget {
+                        kind: t.kind.clone(),
+                        label: t.param_name.clone(),
+                        depth: t.depth,
+                    })
+                    .collect(),
+            }
+        })
+        .collect()
+}
+
 fn param_step(s: &State, modifiers: KeyModifiers) -> f32 {
     let pa = s.real_param_index().unwrap_or(s.param_state.selected);
     let sel = s.chain_state.selected;
@@ -3436,23 +3995,57 @@ fn action_bar_hit(x: u16, y: u16, area: Rect, actions: &[(&str, &str)]) -> Optio
     None
 }
 
+/// Initial catalogue from the plugin scan cache, without loading any
+/// plugin binaries. A full refresh runs in the background.
 fn build_catalog() -> Vec<PluginInfo> {
-    let mut catalog = Vec::new();
-
-    catalog.extend(plugin::builtin::enumerate_plugins());
-
-    #[cfg(feature = "lv2")]
-    catalog.extend(plugin::lv2::enumerate_plugins());
+    finish_catalog(plugin::scan_cache::ScanCache::load().catalog().into_all())
+}
 
-    catalog.extend(plugin::clap::enumerate_plugins());
+/// Add built-ins and tang library preset counts to scanned plugins.
+fn finish_catalog(scanned: Vec<PluginInfo>) -> Vec<PluginInfo> {
+    let mut catalog = plugin::builtin::enumerate_plugins();
+    catalog.extend(scanned);
 
-    #[cfg(feature = "vst3")]
-    catalog.extend(plugin::vst3::enumerate_plugins());
+    // Count tang library presets alongside each plugin's native ones.
+    for entry in &mut catalog {
+        entry.preset_count += plugin::library::presets_for(&entry.id).len();
+    }
 
     catalog.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
     catalog
 }
 
+/// Refresh the plugin scan cache in a child `tang enumerate plugins`
+/// process, so scanning never loads plugin binaries into the live session.
+/// Once the child has written the cache, it is read back and the up-to-date
+/// scan results arrive on the returned channel.
+fn spawn_catalog_refresh() -> crossbeam_channel::Receiver<Vec<PluginInfo>> {
+    let (tx, rx) = crossbeam_channel::bounded(1);
+    let spawned = std::thread::Builder::new()
+        .name("plugin-scan".into())
+        .spawn(move || {
+            let status = std::env::current_exe().and_then(|exe| {
+                std::process::Command::new(exe)
+                    .args(["enumerate", "plugins"])
+                    .stdin(std::process::Stdio::null())
+                    .stdout(std::process::Stdio::null())
+                    .stderr(std::process::Stdio::null())
+                    .status()
+            });
+            match status {
+                Ok(s) if s.success() => {
+                    let _ = tx.send(plugin::scan_cache::ScanCache::load().catalog().into_all());
+                }
+                Ok(s) => log::warn!("Plugin scan process failed: {s}"),
+                Err(e) => log::error!("Failed to run plugin scan process: {e}"),
+            }
+        });
+    if let Err(e) = spawned {
+        log::error!("Failed to start plugin scan: {e}");
+    }
+    rx
+}
+
 fn build_help_lines() -> Vec<String> {
     vec![
         "Tang — Terminal Audio Plugin Host".into(),
@@ -3472,6 +4065,8 @@ fn build_help_lines() -> Vec<String> {
         "  a          Add effect after selected".into(),
         "  d          Delete selected".into(),
         "  m          Add modulator".into(),
+        "  p          Load preset (native or tang)".into(),
+        "  P          Save preset (Tab: tang or native format)".into(),
         "  r          Record/stop pattern".into(),
         "  Ctrl+R     Clear pattern".into(),
         "  b          Set BPM".into(),
@@ -0,0 +1,396 @@
+//! `tang validate`: load a plugin through [`plugin::load`] and exercise it
+//! with a fixed set of conformance checks, reporting pass/warn/fail per check.
+//!
+//! Every check loads a fresh instance, so one misbehaving check doesn't skew
+//! the others. Output is checked after every block: NaN or infinite samples
+//! fail the check, denormal samples only warn. Panics inside a plugin are
+//! caught and reported as failures; a native crash still takes the process
+//! down, which is itself the answer for that plugin.
+
+use std::panic::{self, AssertUnwindSafe};
+
+use serde::Serialize;
+
+use crate::cli::OutputFormat;
+use crate::plugin::{self, Plugin};
+
+const DEFAULT_SAMPLE_RATE: f32 = 48000.0;
+const DEFAULT_BLOCK_SIZE: usize = 512;
+const SAMPLE_RATES: [f32; 4] = [22050.0, 44100.0, 48000.0, 96000.0];
+const BLOCK_SIZES: [usize; 4] = [1, 64, 512, 4096];
+/// Buffer lengths processed by a plugin set up for [`DEFAULT_BLOCK_SIZE`].
+const ODD_LENGTHS: [usize; 6] = [0, 1, 7, 333, 511, 512];
+/// Blocks run after each change before the output is judged.
+const SETTLE_BLOCKS: usize = 4;
+/// Load/process/unload cycles in the activation check.
+const ACTIVATION_CYCLES: usize = 5;
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Pass,
+    Warn,
+    Fail,
+}
+
+#[derive(Serialize)]
+pub struct Check {
+    pub name: String,
+    pub outcome: Outcome,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub detail: String,
+}
+
+#[derive(Serialize)]
+pub struct Report {
+    pub source: String,
+    pub name: String,
+    pub checks: Vec<Check>,
+}
+
+impl Report {
+    fn count(&self, outcome: Outcome) -> usize {
+        self.checks.iter().filter(|c| c.outcome == outcome).count()
+    }
+
+    /// Run `check`, recording a failure for errors and panics and a warning
+    /// for output containing denormals.
+    fn run(&mut self, name: impl Into<String>, check: impl FnOnce() -> anyhow::Result<Stats>) {
+        let (outcome, detail) = match panic::catch_unwind(AssertUnwindSafe(check)) {
+            Ok(Ok(stats)) if stats.denormals > 0 => (
+                Outcome::Warn,
+                format!("{} denormal output sample(s)", stats.denormals),
+            ),
+            Ok(Ok(_)) => (Outcome::Pass, String::new()),
+            Ok(Err(e)) => (Outcome::Fail, format!("{e:#}")),
+            Err(payload) => (
+                Outcome::Fail,
+                format!("panicked: {}", panic_message(&payload)),
+            ),
+        };
+        self.checks.push(Check {
+            name: name.into(),
+            outcome,
+            detail,
+        });
+    }
+}
+
+fn panic_message(payload: &Box<dyn std::any::Any + Send>) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".into())
+}
+
+// ---------------------------------------------------------------------------
+// Block processing
+// ---------------------------------------------------------------------------
+
+/// What was seen in the output so far.
+#[derive(Debug, Default)]
+struct Stats {
+    denormals: usize,
+}
+
+impl Stats {
+    fn merge(mut self, other: Stats) -> Self {
+        self.denormals += other.denormals;
+        self
+    }
+}
+
+/// Scan output buffers: non-finite samples are an error, denormals are
+/// counted.
+fn inspect(audio_out: &[impl AsRef<[f32]>]) -> anyhow::Result<Stats> {
+    let mut stats = Stats::default();
+    for (ch, buf) in audio_out.iter().map(AsRef::as_ref).enumerate() {
+        if let Some(i) = buf.iter().position(|s| !s.is_finite()) {
+            anyhow::bail!(
+                "non-finite output ({}) on channel {ch} at frame {i}",
+                buf[i]
+            );
+        }
+        stats.denormals += buf.iter().filter(|s| s.is_subnormal()).count();
+    }
+    Ok(stats)
+}
+
+/// Drives a plugin with a test signal on its inputs and checks every block.
+struct Harness {
+    plugin: Box<dyn Plugin>,
+    inputs: Vec<Vec<f32>>,
+    outputs: Vec<Vec<f32>>,
+    phase: f32,
+}
+
+impl Harness {
+    fn load(source: &str, sample_rate: f32, max_block_size: usize) -> anyhow::Result<Self> {
+        let plugin = plugin::load(
+            source,
+            sample_rate,
+            max_block_size,
+            &plugin::Runtime::default(),
+        )?;
+        let inputs = vec![vec![0.0; max_block_size]; plugin.audio_input_count()];
+        let outputs = vec![vec![0.0; max_block_size]; plugin.audio_output_count()];
+        Ok(Self {
+            plugin,
+            inputs,
+            outputs,
+            phase: 0.0,
+        })
+    }
+
+    /// Process `frames` frames with `midi` and check the output.
+    fn block(&mut self, frames: usize, midi: &[(u64, [u8; 3])]) -> anyhow::Result<Stats> {
+        let step = 220.0 / self.plugin.sample_rate();
+        for i in 0..frames {
+            let s = 0.5 * (self.phase * std::f32::consts::TAU).sin();
+            self.phase = (self.phase + step).fract();
+            for ch in &mut self.inputs {
+                ch[i] = s;
+            }
+        }
+        let audio_in: Vec<&[f32]> = self.inputs.iter().map(|c| &c[..frames]).collect();
+        let mut audio_out: Vec<&mut [f32]> =
+            self.outputs.iter_mut().map(|c| &mut c[..frames]).collect();
+        self.plugin.process(midi, &audio_in, &mut audio_out)?;
+        inspect(&audio_out)
+    }
+
+    /// Run `blocks` full blocks, with `midi` in the first.
+    fn run(
+        &mut self,
+        blocks: usize,
+        frames: usize,
+        midi: &[(u64, [u8; 3])],
+    ) -> anyhow::Result<Stats> {
+        let mut stats = self.block(frames, midi)?;
+        for _ in 1..blocks {
+            stats = stats.merge(self.block(frames, &[])?);
+        }
+        Ok(stats)
+    }
+}
+
+fn note_on(frame: u64, key: u8) -> (u64, [u8; 3]) {
+    (frame, [0x90, key, 100])
+}
+
+fn note_off(frame: u64, key: u8) -> (u64, [u8; 3]) {
+    (frame, [0x80, key, 0])
+}
+
+// ---------------------------------------------------------------------------
+// Checks
+// ---------------------------------------------------------------------------
+
+fn check_rates_and_sizes(report: &mut Report, source: &str) {
+    for &rate in &SAMPLE_RATES {
+        for &size in &BLOCK_SIZES {
+            report.run(format!("process at {rate} Hz, {size}-frame blocks"), || {
+                let mut h = Harness::load(source, rate, size)?;
+                h.run(SETTLE_BLOCKS, size, &[note_on(0, 60)])
+            });
+        }
+    }
+}
+
+fn check_odd_lengths(report: &mut Report, source: &str) {
+    report.run("zero-length and odd-length buffers", || {
+        let mut h = Harness::load(source, DEFAULT_SAMPLE_RATE, DEFAULT_BLOCK_SIZE)?;
+        let mut stats = Stats::default();
+        let mut note_held = false;
+        for &frames in &ODD_LENGTHS {
+            // Start a note in the first non-empty buffer.
+            let midi = if frames > 0 && !note_held {
+                note_held = true;
+                vec![note_on(0, 60)]
+            } else {
+                Vec::new()
+            };
+            let s = h
+                .block(frames, &midi)
+                .map_err(|e| anyhow::anyhow!("{frames} frames: {e}"))?;
+            stats = stats.merge(s);
+        }
+        Ok(stats)
+    });
+}
+
+fn check_presets(report: &mut Report, source: &str, presets: &[plugin::Preset]) {
+    if presets.is_empty() {
+        return;
+    }
+    report.run(format!("load all {} preset(s)", presets.len()), || {
+        let mut h = Harness::load(source, DEFAULT_SAMPLE_RATE, DEFAULT_BLOCK_SIZE)?;
+        let mut stats = Stats::default();
+        for preset in presets {
+            h.plugin
+                .load_preset(&preset.id)
+                .map_err(|e| anyhow::anyhow!("preset {:?}: {e}", preset.name))?;
+            let s = h
+                .run(SETTLE_BLOCKS, DEFAULT_BLOCK_SIZE, &[note_on(0, 60)])
+                .map_err(|e| anyhow::anyhow!("preset {:?}: {e}", preset.name))?;
+            stats = stats.merge(s);
+            h.block(DEFAULT_BLOCK_SIZE, &[note_off(0, 60)])?;
+        }
+        Ok(stats)
+    });
+}
+
+fn check_parameter_sweeps(report: &mut Report, source: &str, params: &[plugin::ParameterInfo]) {
+    if params.is_empty() {
+        return;
+    }
+    report.run(format!("sweep all {} parameter(s)", params.len()), || {
+        let mut h = Harness::load(source, DEFAULT_SAMPLE_RATE, DEFAULT_BLOCK_SIZE)?;
+        h.block(DEFAULT_BLOCK_SIZE, &[note_on(0, 60)])?;
+        let mut stats = Stats::default();
+        for param in params {
+            let mid = param.min + (param.max - param.min) / 2.0;
+            for value in [param.min, param.max, mid, param.default] {
+                h.plugin
+                    .set_parameter(param.index, value)
+                    .map_err(|e| anyhow::anyhow!("set {:?} to {value}: {e}", param.name))?;
+                let s = h
+                    .block(DEFAULT_BLOCK_SIZE, &[])
+                    .map_err(|e| anyhow::anyhow!("{:?} = {value}: {e}", param.name))?;
+                stats = stats.merge(s);
+            }
+        }
+        Ok(stats)
+    });
+}
+
+fn check_note_storm(report: &mut Report, source: &str) {
+    report.run("note storm (128 notes on, then off)", || {
+        let mut h = Harness::load(source, DEFAULT_SAMPLE_RATE, DEFAULT_BLOCK_SIZE)?;
+        let frames = DEFAULT_BLOCK_SIZE as u64;
+        let ons: Vec<_> = (0..128u8).map(|k| note_on(k as u64 % frames, k)).collect();
+        let offs: Vec<_> = (0..128u8).map(|k| note_off(k as u64 % frames, k)).collect();
+        let mut stats = h.run(SETTLE_BLOCKS, DEFAULT_BLOCK_SIZE, &ons)?;
+        stats = stats.merge(h.run(SETTLE_BLOCKS, DEFAULT_BLOCK_SIZE, &offs)?);
+        // All Notes Off on every channel.
+        let panic: Vec<_> = (0..16u8).map(|ch| (0, [0xB0 | ch, 123, 0])).collect();
+        Ok(stats.merge(h.run(SETTLE_BLOCKS, DEFAULT_BLOCK_SIZE, &panic)?))
+    });
+}
+
+fn check_activation_cycles(report: &mut Report, source: &str) {
+    report.run(
+        format!("load/process/unload {ACTIVATION_CYCLES} times"),
+        || {
+            let mut stats = Stats::default();
+            for _ in 0..ACTIVATION_CYCLES {
+                let mut h = Harness::load(source, DEFAULT_SAMPLE_RATE, DEFAULT_BLOCK_SIZE)?;
+                stats = stats.merge(h.run(2, DEFAULT_BLOCK_SIZE, &[note_on(0, 60)])?);
+            }
+            Ok(stats)
+        },
+    );
+}
+
+/// Run every check against `source`. Fails only if the plugin can't be
+/// loaded at all.
+pub fn validate(source: &str) -> anyhow::Result<Report> {
+    let probe = plugin::load(
+        source,
+        DEFAULT_SAMPLE_RATE,
+        DEFAULT_BLOCK_SIZE,
+        &plugin::Runtime::default(),
+    )?;
+    let mut report = Report {
+        source: source.to_string(),
+        name: probe.name().to_string(),
+        checks: Vec::new(),
+    };
+    let params = probe.parameters();
+    let presets = probe.presets();
+    drop(probe);
+
+    check_rates_and_sizes(&mut report, source);
+    check_odd_lengths(&mut report, source);
+    check_presets(&mut report, source, &presets);
+    check_parameter_sweeps(&mut report, source, &params);
+    check_note_storm(&mut report, source);
+    check_activation_cycles(&mut report, source);
+    Ok(report)
+}
+
+/// `tang validate`: print the report; an error if any check failed.
+pub fn run(source: &str, format: OutputFormat) -> anyhow::Result<()> {
+    let report = validate(source)?;
+    let (passed, warned, failed) = (
+        report.count(Outcome::Pass),
+        report.count(Outcome::Warn),
+        report.count(Outcome::Fail),
+    );
+
+    if format == OutputFormat::Json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        println!("Validating {} ({})", report.source, report.name);
+        for check in &report.checks {
+            let label = match check.outcome {
+                Outcome::Pass => "PASS",
+                Outcome::Warn => "WARN",
+                Outcome::Fail => "FAIL",
+            };
+            if check.detail.is_empty() {
+                println!("  {label}  {}", check.name);
+            } else {
+                println!("  {label}  {}: {}", check.name, check.detail);
+            }
+        }
+        println!("{passed} passed, {warned} warning(s), {failed} failed");
+    }
+
+    if failed > 0 {
+        anyhow::bail!("{failed} check(s) failed for {source}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_sine_passes_validation() {
+        let report = validate("builtin:sine").unwrap();
+        assert_eq!(report.name, "Sine Oscillator");
+        for check in &report.checks {
+            assert!(
+                check.outcome == Outcome::Pass,
+                "{}: {}",
+                check.name,
+                check.detail
+            );
+        }
+        assert!(report.checks.len() >= SAMPLE_RATES.len() * BLOCK_SIZES.len() + 3);
+    }
+
+    #[test]
+    fn inspect_flags_nan_and_counts_denormals() {
+        let ok = vec![vec![0.0, 0.5, f32::MIN_POSITIVE / 2.0]];
+        assert_eq!(inspect(&ok).unwrap().denormals, 1);
+        let bad = vec![vec![0.0], vec![0.1, f32::NAN]];
+        let err = inspect(&bad).unwrap_err().to_string();
+        assert!(err.contains("channel 1 at frame 1"), "{err}");
+    }
+
+    #[test]
+    fn panicking_check_is_reported_as_failure() {
+        let mut report = Report {
+            source: "test".into(),
+            name: "test".into(),
+            checks: Vec::new(),
+        };
+        report.run("boom", || panic!("plugin exploded"));
+        assert!(report.checks[0].outcome == Outcome::Fail);
+        assert!(report.checks[0].detail.contains("plugin exploded"));
+    }
+}